@@ -0,0 +1,144 @@
+use serde_json::{Map, Value, json};
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+/// Timed runs per benchmark; the median is reported.
+const SAMPLES: usize = 7;
+
+/// Slowdown against the saved baseline reported as a regression.
+const REGRESSION_THRESHOLD: f64 = 0.10;
+
+struct Benchmark {
+    name: &'static str,
+    program: Vec<String>,
+    handler: Option<Vec<String>>,
+}
+
+fn benchmarks() -> Vec<Benchmark> {
+    vec![
+        // A long program, just below the exception vector, that stops at
+        // its first instruction: loading and decoding dominate.
+        Benchmark {
+            name: "decode",
+            program: std::iter::once("ebreak".to_string())
+                .chain((1..60_000).map(|i| format!("addi x{}, x0, {}", i % 31 + 1, i % 2048)))
+                .collect(),
+            handler: None,
+        },
+        // Every instruction depends on the one before.
+        Benchmark {
+            name: "arithmetic-chain",
+            program: (0..4_000)
+                .map(|i| match i % 3 {
+                    0 => "addi x1, x1, 3".to_string(),
+                    1 => "mulu x1, x1, x1".to_string(),
+                    _ => "xor x1, x1, x2".to_string(),
+                })
+                .collect(),
+            handler: None,
+        },
+        // Independent instructions over all registers.
+        Benchmark {
+            name: "wide-ilp",
+            program: (0..4_000)
+                .map(|i| format!("add x{}, x{}, x{}", i % 31 + 1, (i + 7) % 32, (i + 13) % 32))
+                .collect(),
+            handler: None,
+        },
+        // Every fourth instruction divides by zero and runs the handler.
+        Benchmark {
+            name: "exceptions",
+            program: (0..2_000)
+                .map(|i| match i % 4 {
+                    3 => "divu x3, x1, x0".to_string(),
+                    _ => format!("addi x{}, x{}, 1", i % 4 + 1, i % 4 + 1),
+                })
+                .collect(),
+            handler: Some(vec!["addi x5, x5, 1".to_string(), "mret".to_string()]),
+        },
+    ]
+}
+
+fn scratch(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("ooo470-bench-{}-{}", std::process::id(), name))
+}
+
+/// Results of the previous run, kept next to the build output.
+fn baseline_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target/ooo470-bench.json")
+}
+
+fn run(benchmark: &Benchmark) -> Duration {
+    let program_path = scratch(&format!("{}-program.json", benchmark.name));
+    let handler_path = scratch(&format!("{}-handler.json", benchmark.name));
+    let log_path = scratch(&format!("{}-log.json", benchmark.name));
+    fs::write(&program_path, json!(benchmark.program).to_string()).unwrap();
+    let mut samples: Vec<Duration> = (0..SAMPLES)
+        .map(|_| {
+            let mut command = Command::new(env!("CARGO_BIN_EXE_ooo470"));
+            if let Some(handler) = &benchmark.handler {
+                fs::write(&handler_path, json!(handler).to_string()).unwrap();
+                command.arg("--handler").arg(&handler_path);
+            }
+            command.arg(&program_path).arg(&log_path);
+            let start = Instant::now();
+            let output = command.output().unwrap();
+            let elapsed = start.elapsed();
+            assert!(
+                output.status.success(),
+                "{}: {}",
+                benchmark.name,
+                String::from_utf8_lossy(&output.stderr)
+            );
+            elapsed
+        })
+        .collect();
+    for path in [program_path, handler_path, log_path] {
+        fs::remove_file(path).ok();
+    }
+    samples.sort();
+    samples[SAMPLES / 2]
+}
+
+/// Runs every benchmark, or those whose name contains the first argument,
+/// and compares each median with the one saved by the previous run.
+fn main() {
+    let filter = std::env::args().skip(1).find(|arg| !arg.starts_with("--"));
+    let mut baseline: Map<String, Value> = fs::read_to_string(baseline_path())
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default();
+    let mut regressions = Vec::new();
+    for benchmark in benchmarks() {
+        if filter
+            .as_ref()
+            .is_some_and(|f| !benchmark.name.contains(f.as_str()))
+        {
+            continue;
+        }
+        let median = run(&benchmark).as_secs_f64() * 1000.0;
+        let change = match baseline.get(benchmark.name).and_then(Value::as_f64) {
+            Some(previous) => {
+                let change = median / previous - 1.0;
+                if change > REGRESSION_THRESHOLD {
+                    regressions.push(benchmark.name);
+                }
+                format!("{:+.1}%", change * 100.0)
+            }
+            None => "no baseline".to_string(),
+        };
+        println!("{:<20} {:>10.2} ms   {}", benchmark.name, median, change);
+        baseline.insert(benchmark.name.to_string(), json!(median));
+    }
+    fs::create_dir_all(baseline_path().parent().unwrap()).unwrap();
+    fs::write(baseline_path(), Value::Object(baseline).to_string()).unwrap();
+    if !regressions.is_empty() {
+        println!(
+            "Regressed by more than {:.0}%: {}",
+            REGRESSION_THRESHOLD * 100.0,
+            regressions.join(", ")
+        );
+    }
+}
@@ -0,0 +1,183 @@
+use serde_json::Value;
+use std::fs;
+
+use crate::cli::AnimateArgs;
+use crate::gif::Gif;
+use crate::json_io::{load_log, logged_cycle, state_from_cycle};
+
+const BACKGROUND: u8 = 0;
+const EMPTY: u8 = 1;
+const WAITING: u8 = 2;
+const READY: u8 = 3;
+const EXCEPTION: u8 = 4;
+const SQUASHED: u8 = 5;
+const PROGRESS: u8 = 6;
+const DECODED: u8 = 7;
+
+const PALETTE: [[u8; 3]; 8] = [
+    [255, 255, 255],
+    [225, 225, 225],
+    [66, 110, 200],
+    [60, 170, 90],
+    [210, 50, 50],
+    [240, 150, 30],
+    [90, 90, 90],
+    [140, 90, 190],
+];
+
+const CELL: usize = 10;
+const GAP: usize = 2;
+const MARGIN: usize = 4;
+const SLOTS: usize = 32;
+const WIDTH: usize = 2 * MARGIN + SLOTS * (CELL + GAP) - GAP;
+/// Exception strip, three bands (active list, integer queue, decoded
+/// instructions) and the progress bar, each followed by a margin.
+const HEIGHT: usize = MARGIN + 4 + MARGIN + 3 * (CELL + MARGIN) + 4 + MARGIN;
+
+struct Frame {
+    pixels: Vec<u8>,
+}
+
+impl Frame {
+    fn new() -> Frame {
+        Frame {
+            pixels: vec![BACKGROUND; WIDTH * HEIGHT],
+        }
+    }
+    fn fill(&mut self, x: usize, y: usize, w: usize, h: usize, color: u8) {
+        for row in y..y + h {
+            self.pixels[row * WIDTH + x..row * WIDTH + x + w].fill(color);
+        }
+    }
+    /// Draws one structure as a row of slots colored by `colors`.
+    fn band(&mut self, y: usize, slots: usize, colors: &[u8]) {
+        for slot in 0..slots {
+            let color = colors.get(slot).copied().unwrap_or(EMPTY);
+            self.fill(MARGIN + slot * (CELL + GAP), y, CELL, CELL, color);
+        }
+    }
+}
+
+fn entries<'a>(state: &'a Value, key: &str) -> &'a [Value] {
+    state
+        .get(key)
+        .and_then(Value::as_array)
+        .map_or(&[], Vec::as_slice)
+}
+
+fn flag(entry: &Value, key: &str) -> bool {
+    entry.get(key).and_then(Value::as_bool).unwrap_or(false)
+}
+
+/// Colors the entries of one structure and, while an exception is being
+/// handled, marks the slots the previous state held but this one lost.
+fn colors(
+    state: &Value,
+    previous: Option<&Value>,
+    key: &str,
+    color: impl Fn(&Value) -> u8,
+) -> Vec<u8> {
+    let mut colors: Vec<u8> = entries(state, key).iter().map(color).collect();
+    if flag(state, "Exception")
+        && let Some(previous) = previous
+    {
+        let lost = entries(previous, key).len().saturating_sub(colors.len());
+        colors.extend(std::iter::repeat_n(SQUASHED, lost));
+    }
+    colors
+}
+
+fn render(state: &Value, previous: Option<&Value>, progress: f64) -> Frame {
+    let mut frame = Frame::new();
+    let strip = if flag(state, "Exception") {
+        EXCEPTION
+    } else {
+        EMPTY
+    };
+    frame.fill(MARGIN, MARGIN, WIDTH - 2 * MARGIN, 4, strip);
+
+    let mut y = 2 * MARGIN + 4;
+    let active = colors(state, previous, "ActiveList", |e| {
+        match (flag(e, "Exception"), flag(e, "Done")) {
+            (true, _) => EXCEPTION,
+            (false, true) => READY,
+            (false, false) => WAITING,
+        }
+    });
+    frame.band(y, SLOTS, &active);
+    y += CELL + MARGIN;
+    let queue = colors(state, previous, "IntegerQueue", |e| {
+        if flag(e, "OpAIsReady") && flag(e, "OpBIsReady") {
+            READY
+        } else {
+            WAITING
+        }
+    });
+    frame.band(y, SLOTS, &queue);
+    y += CELL + MARGIN;
+    let decoded = colors(state, previous, "DecodedPCs", |_| DECODED);
+    frame.band(y, 4, &decoded);
+    y += CELL + MARGIN;
+
+    let bar = ((WIDTH - 2 * MARGIN) as f64 * progress).round() as usize;
+    frame.fill(MARGIN, y, bar.max(1), 4, PROGRESS);
+    frame
+}
+
+/// Renders the active list, integer queue and decoded instructions of each
+/// state in a cycle window as one GIF frame. Slots emptied by exception
+/// rollback are shown as squashed in the cycle they are removed.
+pub fn run(args: &AnimateArgs) -> Result<(), String> {
+    let log = load_log(&args.log_path);
+    if log.is_empty() {
+        return Err("log contains no states".to_string());
+    }
+    // Positions in the log, which are the cycles only if it starts at reset.
+    let from = args
+        .from
+        .map_or(Some(0), |cycle| state_from_cycle(&log, cycle));
+    let to = match args.to {
+        Some(cycle) => (0..log.len())
+            .rev()
+            .find(|&index| logged_cycle(&log, index) <= cycle),
+        None => Some(log.len() - 1),
+    };
+    let (Some(from), Some(to)) = (from, to) else {
+        return Err(empty_window(args, &log));
+    };
+    if from > to {
+        return Err(empty_window(args, &log));
+    }
+
+    let mut gif = Gif::new(WIDTH as u16, HEIGHT as u16, &PALETTE);
+    for index in from..=to {
+        let previous = index.checked_sub(1).map(|i| &log[i]);
+        let progress = if to == from {
+            1.0
+        } else {
+            (index - from) as f64 / (to - from) as f64
+        };
+        let frame = render(&log[index], previous, progress);
+        gif.add_frame(&frame.pixels, args.delay);
+    }
+    fs::write(&args.output_path, gif.finish())
+        .map_err(|err| format!("Failed to write animation: {}", err))?;
+    println!(
+        "Animation of cycles {}..={} saved to {}",
+        logged_cycle(&log, from),
+        logged_cycle(&log, to),
+        args.output_path
+    );
+    Ok(())
+}
+
+fn empty_window(args: &AnimateArgs, log: &[Value]) -> String {
+    format!(
+        "empty cycle window {}..={}; the log has {} states (cycles {}..={})",
+        args.from.unwrap_or(0),
+        args.to.map_or("end".to_string(), |to| to.to_string()),
+        log.len(),
+        logged_cycle(log, 0),
+        logged_cycle(log, log.len() - 1)
+    )
+}
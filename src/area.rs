@@ -0,0 +1,107 @@
+use serde_json::{Value, json};
+
+use crate::config::{AreaConfig, Config, Renaming};
+use crate::fp::NUM_FP_ARCH_REGS;
+use crate::simulator::{COMMIT_WIDTH, DECODE_WIDTH, NUM_ARCH_REGS};
+
+/// Bits of a value held in a register or queue entry.
+const VALUE_BITS: usize = 64;
+
+/// Bits to number `n` entries.
+fn index_bits(n: usize) -> usize {
+    n.max(2).next_power_of_two().trailing_zeros() as usize
+}
+
+/// A storage array of `entries` rows of `bits` bits with `ports` ports.
+fn array(area: &AreaConfig, entries: usize, bits: usize, ports: usize) -> f64 {
+    (entries * bits) as f64 * (area.bit + area.port * ports as f64)
+}
+
+/// Approximate area of each structure of the out-of-order pipeline in
+/// square micrometres: storage per bit and port, wakeup comparators per
+/// tag bit, and a fixed area per functional unit.
+pub fn estimate(config: &Config) -> Vec<(&'static str, f64)> {
+    let area = &config.area;
+    let alus = config.num_alus;
+    let registers = match config.renaming {
+        Renaming::MergedRegisterFile => config.physical_registers,
+        Renaming::ReorderBuffer => NUM_ARCH_REGS + config.active_list_capacity,
+    };
+    let tag = index_bits(registers);
+    let write_ports = config.writeback.ports.unwrap_or(alus);
+    let read_ports = config.register_file.read_ports.unwrap_or(2 * DECODE_WIDTH);
+    // Two source operands and a destination per renamed micro-op; the
+    // register file is read at rename and written at writeback.
+    let rename_ports = 3 * DECODE_WIDTH;
+    let queue_entry = 2 * (VALUE_BITS + tag + 1) + tag + 8;
+    let active_entry = 2 * tag + index_bits(NUM_ARCH_REGS) + VALUE_BITS + 4;
+    let queue = config.integer_queue_capacity;
+    let mut structures = vec![
+        (
+            "PhysicalRegisterFile",
+            array(area, registers, VALUE_BITS, read_ports + write_ports),
+        ),
+        ("MapTable", array(area, NUM_ARCH_REGS, tag, rename_ports)),
+        (
+            "FreeList",
+            array(area, registers, tag, DECODE_WIDTH + COMMIT_WIDTH),
+        ),
+        (
+            "ActiveList",
+            array(
+                area,
+                config.active_list_capacity,
+                active_entry,
+                DECODE_WIDTH + COMMIT_WIDTH + write_ports,
+            ),
+        ),
+        (
+            "IntegerQueue",
+            array(area, queue, queue_entry, DECODE_WIDTH + alus)
+                + (queue * 2 * write_ports * tag) as f64 * area.comparator_bit,
+        ),
+        ("Alus", alus as f64 * area.alu),
+    ];
+    if config.fp.enabled {
+        let fp = &config.fp;
+        let fp_tag = index_bits(fp.physical_registers);
+        structures.push((
+            "FpRegisterFile",
+            array(
+                area,
+                fp.physical_registers,
+                VALUE_BITS,
+                2 * DECODE_WIDTH + fp.units,
+            ),
+        ));
+        structures.push((
+            "FpMapTable",
+            array(area, NUM_FP_ARCH_REGS, fp_tag, rename_ports),
+        ));
+        structures.push((
+            "FpQueue",
+            array(
+                area,
+                fp.queue_capacity,
+                2 * (VALUE_BITS + fp_tag + 1) + fp_tag + 8,
+                DECODE_WIDTH + fp.units,
+            ) + (fp.queue_capacity * 2 * fp.units * fp_tag) as f64 * area.comparator_bit,
+        ));
+        structures.push(("FpUnits", fp.units as f64 * area.fp_unit));
+    }
+    structures
+}
+
+/// Total and per-structure area, in square micrometres.
+pub fn to_json(config: &Config) -> Value {
+    let structures = estimate(config);
+    let total: f64 = structures.iter().map(|(_, area)| area).sum();
+    let per_structure: serde_json::Map<String, Value> = structures
+        .iter()
+        .map(|&(name, area)| (name.to_string(), json!(area)))
+        .collect();
+    json!({
+        "Total": total,
+        "Structures": per_structure,
+    })
+}
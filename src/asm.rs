@@ -0,0 +1,608 @@
+use std::collections::HashMap;
+
+use crate::opcode::Opcode;
+use crate::validate::Diagnostic;
+
+/// Operand layout of an instruction mnemonic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// `op rd, rs1, rs2`
+    Register,
+    /// `op rd, rs1, imm`
+    Immediate,
+    /// `op rd, imm`
+    UpperImmediate,
+    /// `op fd, fs1, fs2` on FP registers
+    FloatRegister,
+    /// `op rd, csr, rs1`
+    Csr,
+}
+
+/// Kind of an operand written after a mnemonic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    Register,
+    FpRegister,
+    Immediate,
+    /// A CSR, by name or number.
+    Csr,
+}
+
+/// Where a field of the micro-op an instruction decodes to comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    /// The operand at this position.
+    Operand(usize),
+    /// A fixed integer register.
+    Register(u32),
+    /// A source read as a ready zero.
+    Zero,
+    /// A fixed immediate.
+    Immediate(i64),
+}
+
+/// The operands an instruction takes and the micro-op fields they fill, so
+/// that formats of any arity decode without slicing by position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Descriptor {
+    pub operands: &'static [Operand],
+    /// The operands as written, such as `rd, rs1, imm`.
+    pub syntax: &'static str,
+    pub dest: Field,
+    pub src1: Field,
+    pub src2: Field,
+    /// The second source is an immediate.
+    pub is_imm: bool,
+}
+
+const fn descriptor(
+    operands: &'static [Operand],
+    syntax: &'static str,
+    [dest, src1, src2]: [Field; 3],
+    is_imm: bool,
+) -> Descriptor {
+    Descriptor {
+        operands,
+        syntax,
+        dest,
+        src1,
+        src2,
+        is_imm,
+    }
+}
+
+impl Format {
+    pub const fn descriptor(self) -> Descriptor {
+        use Field::Operand as Nth;
+        use Operand::*;
+        match self {
+            Format::Register => descriptor(
+                &[Register, Register, Register],
+                "rd, rs1, rs2",
+                [Nth(0), Nth(1), Nth(2)],
+                false,
+            ),
+            Format::Immediate => descriptor(
+                &[Register, Register, Immediate],
+                "rd, rs1, imm",
+                [Nth(0), Nth(1), Nth(2)],
+                true,
+            ),
+            Format::UpperImmediate => descriptor(
+                &[Register, Immediate],
+                "rd, imm",
+                [Nth(0), Field::Zero, Nth(1)],
+                true,
+            ),
+            Format::FloatRegister => descriptor(
+                &[FpRegister, FpRegister, FpRegister],
+                "fd, fs1, fs2",
+                [Nth(0), Nth(1), Nth(2)],
+                false,
+            ),
+            // The CSR number takes the place of the immediate.
+            Format::Csr => descriptor(
+                &[Register, Csr, Register],
+                "rd, csr, rs1",
+                [Nth(0), Nth(2), Nth(1)],
+                true,
+            ),
+        }
+    }
+}
+
+/// Maps each accepted mnemonic to the ALU opcode it executes as and its
+/// operand format. Immediate forms share the opcode of their register form.
+const MNEMONICS: &[(&str, Opcode, Format)] = &[
+    ("add", Opcode::Add, Format::Register),
+    ("addi", Opcode::Add, Format::Immediate),
+    ("sub", Opcode::Sub, Format::Register),
+    ("mulu", Opcode::Mulu, Format::Register),
+    ("divu", Opcode::Divu, Format::Register),
+    ("remu", Opcode::Remu, Format::Register),
+    ("mul", Opcode::Mul, Format::Register),
+    ("div", Opcode::Div, Format::Register),
+    ("rem", Opcode::Rem, Format::Register),
+    ("slt", Opcode::Slt, Format::Register),
+    ("slti", Opcode::Slt, Format::Immediate),
+    ("sltu", Opcode::Sltu, Format::Register),
+    ("sltiu", Opcode::Sltu, Format::Immediate),
+    ("and", Opcode::And, Format::Register),
+    ("andi", Opcode::And, Format::Immediate),
+    ("or", Opcode::Or, Format::Register),
+    ("ori", Opcode::Or, Format::Immediate),
+    ("xor", Opcode::Xor, Format::Register),
+    ("xori", Opcode::Xor, Format::Immediate),
+    ("sll", Opcode::Sll, Format::Register),
+    ("slli", Opcode::Sll, Format::Immediate),
+    ("srl", Opcode::Srl, Format::Register),
+    ("srli", Opcode::Srl, Format::Immediate),
+    ("sra", Opcode::Sra, Format::Register),
+    ("srai", Opcode::Sra, Format::Immediate),
+    ("lui", Opcode::Lui, Format::UpperImmediate),
+    ("auipc", Opcode::Auipc, Format::UpperImmediate),
+    ("fadd", Opcode::Fadd, Format::FloatRegister),
+    ("fadd.d", Opcode::Fadd, Format::FloatRegister),
+    ("fsub", Opcode::Fsub, Format::FloatRegister),
+    ("fsub.d", Opcode::Fsub, Format::FloatRegister),
+    ("fmul", Opcode::Fmul, Format::FloatRegister),
+    ("fmul.d", Opcode::Fmul, Format::FloatRegister),
+    ("fdiv", Opcode::Fdiv, Format::FloatRegister),
+    ("fdiv.d", Opcode::Fdiv, Format::FloatRegister),
+    ("csrrw", Opcode::Csrrw, Format::Csr),
+    ("csrrs", Opcode::Csrrs, Format::Csr),
+    ("csrrc", Opcode::Csrrc, Format::Csr),
+];
+
+/// Pseudo-instructions that decode to a single micro-op, with the opcode
+/// they execute as. `li` may instead expand to two, as `li_expansion`
+/// chooses, and `mret` also redirects fetch.
+const PSEUDO_INSTRUCTIONS: &[(&str, Opcode, Descriptor)] = {
+    use Field::{Operand as Nth, Register as Reg, Zero};
+    use Operand::{Csr, Immediate as Imm, Register};
+    const X0: Field = Reg(0);
+    &[
+        (
+            "nop",
+            Opcode::Add,
+            descriptor(&[], "", [X0, X0, Field::Immediate(0)], true),
+        ),
+        (
+            "mret",
+            Opcode::Add,
+            descriptor(&[], "", [X0, X0, Field::Immediate(0)], true),
+        ),
+        (
+            "unimp",
+            Opcode::Unimp,
+            descriptor(&[], "", [X0, Zero, Field::Immediate(0)], true),
+        ),
+        // The syscall number is in `a7`, the argument and result in `a0`.
+        (
+            "ecall",
+            Opcode::Ecall,
+            descriptor(&[], "", [Reg(10), Reg(17), Reg(10)], false),
+        ),
+        (
+            "ebreak",
+            Opcode::Ebreak,
+            descriptor(&[], "", [X0, Zero, X0], false),
+        ),
+        (
+            "li",
+            Opcode::Add,
+            descriptor(&[Register, Imm], "rd, imm", [Nth(0), Zero, Nth(1)], true),
+        ),
+        (
+            "mv",
+            Opcode::Add,
+            descriptor(
+                &[Register, Register],
+                "rd, rs",
+                [Nth(0), Nth(1), Field::Immediate(0)],
+                true,
+            ),
+        ),
+        (
+            "neg",
+            Opcode::Sub,
+            descriptor(
+                &[Register, Register],
+                "rd, rs",
+                [Nth(0), Zero, Nth(1)],
+                false,
+            ),
+        ),
+        (
+            "not",
+            Opcode::Xor,
+            descriptor(
+                &[Register, Register],
+                "rd, rs",
+                [Nth(0), Nth(1), Field::Immediate(-1)],
+                true,
+            ),
+        ),
+        (
+            "seqz",
+            Opcode::Sltu,
+            descriptor(
+                &[Register, Register],
+                "rd, rs",
+                [Nth(0), Nth(1), Field::Immediate(1)],
+                true,
+            ),
+        ),
+        (
+            "snez",
+            Opcode::Sltu,
+            descriptor(
+                &[Register, Register],
+                "rd, rs",
+                [Nth(0), Zero, Nth(1)],
+                false,
+            ),
+        ),
+        (
+            "csrr",
+            Opcode::Csrrs,
+            descriptor(&[Register, Csr], "rd, csr", [Nth(0), Zero, Nth(1)], true),
+        ),
+        (
+            "rdcycle",
+            Opcode::Csrrs,
+            descriptor(
+                &[Register],
+                "rd",
+                [Nth(0), Zero, Field::Immediate(crate::csr::CYCLE as i64)],
+                true,
+            ),
+        ),
+        (
+            "rdinstret",
+            Opcode::Csrrs,
+            descriptor(
+                &[Register],
+                "rd",
+                [Nth(0), Zero, Field::Immediate(crate::csr::INSTRET as i64)],
+                true,
+            ),
+        ),
+    ]
+};
+
+/// RISC-V ABI register names, indexed by architectural register number.
+pub const ABI_NAMES: [&str; 32] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3", "a4",
+    "a5", "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4",
+    "t5", "t6",
+];
+
+/// RISC-V ABI names of the FP registers, indexed by register number.
+const FP_ABI_NAMES: [&str; 32] = [
+    "ft0", "ft1", "ft2", "ft3", "ft4", "ft5", "ft6", "ft7", "fs0", "fs1", "fa0", "fa1", "fa2",
+    "fa3", "fa4", "fa5", "fa6", "fa7", "fs2", "fs3", "fs4", "fs5", "fs6", "fs7", "fs8", "fs9",
+    "fs10", "fs11", "ft8", "ft9", "ft10", "ft11",
+];
+
+/// Looks up a mnemonic, returning its ALU opcode and operand format.
+pub fn lookup(mnemonic: &str) -> Option<(Opcode, Format)> {
+    MNEMONICS
+        .iter()
+        .find(|(name, _, _)| *name == mnemonic)
+        .map(|&(_, op, format)| (op, format))
+        .or_else(|| custom_opcode(mnemonic).map(|op| (op, Format::Register)))
+}
+
+/// A registered opcode without a mnemonic of its own, which takes the
+/// register form.
+fn custom_opcode(mnemonic: &str) -> Option<Opcode> {
+    match Opcode::parse(mnemonic) {
+        Some(op @ Opcode::Custom(_)) => Some(op),
+        _ => None,
+    }
+}
+
+/// Looks up a mnemonic or pseudo-instruction, returning the opcode it
+/// executes as and its operand layout.
+pub fn descriptor_of(mnemonic: &str) -> Option<(Opcode, Descriptor)> {
+    lookup(mnemonic)
+        .map(|(op, format)| (op, format.descriptor()))
+        .or_else(|| {
+            PSEUDO_INSTRUCTIONS
+                .iter()
+                .find(|(name, _, _)| *name == mnemonic)
+                .map(|&(_, op, descriptor)| (op, descriptor))
+        })
+}
+
+/// Every accepted mnemonic, pseudo-instructions included.
+pub fn mnemonics() -> impl Iterator<Item = &'static str> {
+    MNEMONICS
+        .iter()
+        .map(|&(name, _, _)| name)
+        .chain(PSEUDO_INSTRUCTIONS.iter().map(|&(name, _, _)| name))
+}
+
+/// Splits an instruction into its mnemonic and operands, which are
+/// separated by commas, whitespace or both.
+pub fn split_instruction(line: &str) -> Option<(&str, Vec<&str>)> {
+    let line = line.trim();
+    let (mnemonic, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let mnemonic = mnemonic.trim_end_matches(',');
+    if mnemonic.is_empty() {
+        return None;
+    }
+    let operands = rest
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|operand| !operand.is_empty())
+        .collect();
+    Some((mnemonic, operands))
+}
+
+/// The mnemonic executing as ALU opcode `op` in `format`, such as `addi`
+/// for `add` with an immediate.
+pub fn mnemonic_for(op: Opcode, format: Format) -> Option<&'static str> {
+    MNEMONICS
+        .iter()
+        .find(|&&(_, o, f)| o == op && f == format)
+        .map(|&(name, _, _)| name)
+}
+
+/// Resolves `x0`..`x31` or an ABI name (`fp` is an alias of `s0`) to an
+/// architectural register number.
+pub fn register_index(name: &str) -> Option<u32> {
+    if name == "fp" {
+        return Some(8);
+    }
+    if let Some(i) = ABI_NAMES.iter().position(|&abi| abi == name) {
+        return Some(i as u32);
+    }
+    let index: u32 = name.strip_prefix('x')?.parse().ok()?;
+    (index < 32).then_some(index)
+}
+
+/// Resolves `f0`..`f31` or an FP ABI name to an FP register number.
+pub fn fp_register_index(name: &str) -> Option<u32> {
+    if let Some(i) = FP_ABI_NAMES.iter().position(|&abi| abi == name) {
+        return Some(i as u32);
+    }
+    let index: u32 = name.strip_prefix('f')?.parse().ok()?;
+    (index < 32).then_some(index)
+}
+
+/// Parses an immediate operand: decimal, hexadecimal (`0x1f`) or binary
+/// (`0b101`), optionally negative. Negative values wrap to their
+/// two's-complement bit pattern.
+pub fn parse_immediate(src: &str) -> Option<u64> {
+    let (negative, magnitude) = match src.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, src),
+    };
+    let radix = |prefix: [&str; 2]| prefix.iter().find_map(|p| magnitude.strip_prefix(p));
+    let value = if let Some(digits) = radix(["0x", "0X"]) {
+        u64::from_str_radix(digits, 16).ok()?
+    } else if let Some(digits) = radix(["0b", "0B"]) {
+        u64::from_str_radix(digits, 2).ok()?
+    } else if magnitude.starts_with(|c: char| c.is_ascii_digit()) {
+        magnitude.parse().ok()?
+    } else {
+        return None;
+    };
+    match negative {
+        // Magnitudes beyond 2^63 have no negative counterpart.
+        true => (value <= 1 << 63).then(|| value.wrapping_neg()),
+        false => Some(value),
+    }
+}
+
+/// `line` without its comment, which starts at a `#` or `;`, and without
+/// surrounding whitespace.
+pub fn strip_comment(line: &str) -> &str {
+    line.split(['#', ';']).next().unwrap().trim()
+}
+
+/// Splits plain assembly source into instructions: one per line, with `#`
+/// or `;` starting a comment and blank lines skipped.
+pub fn parse_source(text: &str) -> Vec<String> {
+    text.lines()
+        .map(strip_comment)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Resolves labels in a pre-pass: a definition (`loop:`, alone or before an
+/// instruction) names the index of the next instruction, and every operand
+/// naming a label is replaced by that index. The ISA has no branches, so
+/// labels stand wherever an immediate is accepted. Comments and entries left
+/// blank by them are dropped first, so they take no PC.
+pub fn resolve_labels(program: &[String]) -> Result<Vec<String>, Vec<Diagnostic>> {
+    let lines = resolve_labels_indexed(program)?;
+    Ok(lines.into_iter().map(|(_, line)| line).collect())
+}
+
+/// `resolve_labels`, keeping next to every instruction its index in
+/// `program`, which diagnostics report: dropped comments and blank entries
+/// shift the PCs of the instructions after them but not their indices.
+pub fn resolve_labels_indexed(program: &[String]) -> Result<Vec<(usize, String)>, Vec<Diagnostic>> {
+    let (lines, diagnostics) = resolve_labels_partially(program);
+    match diagnostics.is_empty() {
+        true => Ok(lines),
+        false => Err(diagnostics),
+    }
+}
+
+/// `resolve_labels_indexed`, resolving the labels that are well defined
+/// even if others are not, so that the instructions can be checked too. A
+/// label defined twice names its last definition.
+pub fn resolve_labels_partially(program: &[String]) -> (Vec<(usize, String)>, Vec<Diagnostic>) {
+    let mut labels: HashMap<&str, usize> = HashMap::new();
+    let mut lines: Vec<(usize, &str)> = Vec::new();
+    let mut diagnostics = Vec::new();
+    for (index, line) in program.iter().enumerate() {
+        let mut rest = strip_comment(line);
+        while let Some((name, after)) = rest.split_once(':')
+            && is_label(name)
+        {
+            if register_index(name).is_some() || fp_register_index(name).is_some() {
+                diagnostics.push(Diagnostic {
+                    index,
+                    token: name.to_string(),
+                    expected: "a label that is not a register name".to_string(),
+                    suggestion: Some(format!("_{}", name)),
+                });
+            } else if labels.insert(name, lines.len()).is_some() {
+                diagnostics.push(Diagnostic {
+                    index,
+                    token: name.to_string(),
+                    expected: "a label defined only once".to_string(),
+                    suggestion: None,
+                });
+            }
+            rest = after.trim_start();
+        }
+        if !rest.is_empty() {
+            lines.push((index, rest));
+        }
+    }
+    let lines = lines
+        .into_iter()
+        .map(|(index, line)| {
+            let Some((mnemonic, operands)) = split_instruction(line)
+                .filter(|(_, operands)| operands.iter().any(|o| labels.contains_key(o)))
+            else {
+                return (index, line.to_string());
+            };
+            let operands: Vec<String> = operands
+                .iter()
+                .map(|o| labels.get(o).map_or(o.to_string(), usize::to_string))
+                .collect();
+            (index, format!("{} {}", mnemonic, operands.join(", ")))
+        })
+        .collect();
+    (lines, diagnostics)
+}
+
+/// A label name: a letter, `_` or `.` followed by letters, digits, `_` or `.`.
+fn is_label(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '.')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn program(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn resolved_instructions_keep_their_input_index() {
+        let input = program(&["add x1, x1, x1", "", "# c", "ad x1, x2, x3"]);
+        let lines = resolve_labels_indexed(&input).unwrap();
+        let indices: Vec<usize> = lines.iter().map(|&(index, _)| index).collect();
+        assert_eq!(indices, [0, 3]);
+    }
+
+    #[test]
+    fn label_diagnostics_report_the_input_index() {
+        let input = program(&["", "x1: add x1, x1, x1", "a:", "a: nop"]);
+        let diagnostics = resolve_labels(&input).unwrap_err();
+        let indices: Vec<usize> = diagnostics.iter().map(|d| d.index).collect();
+        assert_eq!(indices, [1, 3]);
+    }
+
+    #[test]
+    fn labels_resolve_to_instruction_indices() {
+        let input = program(&[
+            "start: addi x1, x0, end",
+            "# a comment takes no index",
+            "loop:",
+            "again: addi x2, x2, loop",
+            "addi x3, x0, again ; trailing comment",
+            "end: addi x4, x0, start",
+        ]);
+        assert_eq!(
+            resolve_labels(&input).unwrap(),
+            [
+                "addi x1, x0, 3",
+                "addi x2, x2, 1",
+                "addi x3, x0, 1",
+                "addi x4, x0, 0"
+            ]
+        );
+    }
+
+    #[test]
+    fn operands_that_are_not_labels_are_kept() {
+        let input = program(&["a: addi x1, x1, -0x10", "addi x2, x1, b"]);
+        assert_eq!(
+            resolve_labels(&input).unwrap(),
+            ["addi x1, x1, -0x10", "addi x2, x1, b"]
+        );
+    }
+
+    #[test]
+    fn register_names_are_not_labels() {
+        let diagnostics = resolve_labels(&program(&["a0: nop", "f3: nop"])).unwrap_err();
+        let suggestions: Vec<_> = diagnostics.iter().map(|d| d.suggestion.clone()).collect();
+        assert_eq!(
+            suggestions,
+            [Some("_a0".to_string()), Some("_f3".to_string())]
+        );
+    }
+
+    #[test]
+    fn immediates_accept_decimal_hex_and_binary() {
+        assert_eq!(parse_immediate("42"), Some(42));
+        assert_eq!(parse_immediate("0x1f"), Some(31));
+        assert_eq!(parse_immediate("0XFF"), Some(255));
+        assert_eq!(parse_immediate("0b101"), Some(5));
+        assert_eq!(parse_immediate("18446744073709551615"), Some(u64::MAX));
+    }
+
+    #[test]
+    fn negative_immediates_wrap_to_twos_complement() {
+        assert_eq!(parse_immediate("-1"), Some(u64::MAX));
+        assert_eq!(parse_immediate("-0x10"), Some(-16i64 as u64));
+        assert_eq!(parse_immediate("-9223372036854775808"), Some(1 << 63));
+        assert_eq!(parse_immediate("-9223372036854775809"), None);
+    }
+
+    #[test]
+    fn malformed_immediates_are_rejected() {
+        for text in [
+            "",
+            "-",
+            "0x",
+            "0b2",
+            "x1",
+            "1_000",
+            "--1",
+            "+1",
+            "18446744073709551616",
+        ] {
+            assert_eq!(parse_immediate(text), None, "{}", text);
+        }
+    }
+
+    #[test]
+    fn label_errors_leave_the_other_instructions_resolved() {
+        let input = program(&["a: nop", "a: addi x1, x0, a", "x2: addi x2, x0, 1"]);
+        let (lines, diagnostics) = resolve_labels_partially(&input);
+        assert_eq!(
+            lines,
+            [
+                (0, "nop".to_string()),
+                (1, "addi x1, x0, 1".to_string()),
+                (2, "addi x2, x0, 1".to_string())
+            ]
+        );
+        assert_eq!(diagnostics.len(), 2);
+    }
+}
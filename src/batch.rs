@@ -0,0 +1,237 @@
+use std::any::Any;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crate::builder::SimulatorBuilder;
+use crate::cli::BatchArgs;
+use crate::config::Config;
+use crate::json_io::{self, parse_config, save_log_value};
+use crate::{asm, warnings};
+
+/// A program found under the input directory.
+struct Job {
+    /// Path relative to the input directory; the log is saved under the same
+    /// relative path, with a `.json` extension, in the output directory.
+    relative: PathBuf,
+    program: Vec<String>,
+}
+
+/// Outcome of one simulation, a row of the summary table.
+struct Row {
+    cycles: usize,
+    committed: u64,
+    /// The last state has the exception flag or PC of the handler.
+    exception: bool,
+}
+
+/// Simulates every program under `input_dir` on a pool of worker threads and
+/// writes one log per program plus `summary.csv` to `output_dir`. A program
+/// that panics is reported in the summary and does not stop the others.
+pub fn run(args: &BatchArgs) -> Result<(), String> {
+    let config = match &args.config_path {
+        Some(path) => parse_config(path),
+        None => Config::default(),
+    };
+    let input_dir = Path::new(&args.input_dir);
+    let output_dir = Path::new(&args.output_dir);
+    let mut jobs = Vec::new();
+    discover(input_dir, input_dir, output_dir, &mut jobs)?;
+    if jobs.is_empty() {
+        return Err(format!("No input programs found in {}", args.input_dir));
+    }
+    jobs.sort_by(|a, b| a.relative.cmp(&b.relative));
+    fs::create_dir_all(output_dir)
+        .map_err(|err| format!("Failed to create {}: {}", output_dir.display(), err))?;
+
+    let results = parallel_map(&jobs, args.jobs, |job| simulate(job, &config, output_dir));
+
+    let failed = results.iter().filter(|r| r.is_err()).count();
+    let summary = summary_csv(&jobs, &results);
+    print_summary(&jobs, &results);
+    let summary_path = output_dir.join("summary.csv");
+    fs::write(&summary_path, summary)
+        .map_err(|err| format!("Failed to write {}: {}", summary_path.display(), err))?;
+    println!("Summary saved to {}", summary_path.display());
+    if failed > 0 {
+        return Err(format!("{} of {} simulations failed", failed, jobs.len()));
+    }
+    Ok(())
+}
+
+/// Collects every assembly file under `dir` and every JSON file that holds
+/// an array of instruction strings. Logs and configs are JSON too but fail
+/// that check.
+fn discover(root: &Path, dir: &Path, skip: &Path, jobs: &mut Vec<Job>) -> Result<(), String> {
+    let entries =
+        fs::read_dir(dir).map_err(|err| format!("Failed to read {}: {}", dir.display(), err))?;
+    for entry in entries {
+        let path = entry
+            .map_err(|err| format!("Failed to read {}: {}", dir.display(), err))?
+            .path();
+        if path.is_dir() {
+            if !same_path(&path, skip) {
+                discover(root, &path, skip, jobs)?;
+            }
+            continue;
+        }
+        let assembly = json_io::is_assembly(&path.to_string_lossy());
+        if !assembly && path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        let Ok(text) = fs::read_to_string(&path) else {
+            continue;
+        };
+        let program = match assembly {
+            true => Ok(asm::parse_source(&text)),
+            false => serde_json::from_str::<Vec<String>>(&text),
+        };
+        if let Ok(program) = program {
+            jobs.push(Job {
+                relative: path.strip_prefix(root).unwrap().to_path_buf(),
+                program,
+            });
+        }
+    }
+    Ok(())
+}
+
+fn same_path(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+fn simulate(job: &Job, config: &Config, output_dir: &Path) -> Result<Row, String> {
+    let program = asm::resolve_labels(&job.program).map_err(|d| d[0].to_string())?;
+    let sim = SimulatorBuilder::new()
+        .program(program.clone())
+        .config(config)
+        .build()?;
+    let warnings = warnings::check_program(&program, config);
+    let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+        let sim = crate::run(sim, None, false, None, None);
+        let output_path = output_dir.join(job.relative.with_extension("json"));
+        if let Some(parent) = output_path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|err| format!("Failed to create {}: {}", parent.display(), err))?;
+        }
+        // The jobs already run in parallel.
+        save_log_value(
+            &output_path.to_string_lossy(),
+            &crate::log_output(&sim, config, &warnings, None, Some(1)),
+            Some(1),
+        );
+        let last = &sim.state;
+        Ok(Row {
+            cycles: sim.cycles(),
+            committed: sim.committed,
+            exception: last.exception || last.pc >= config.vector_index(),
+        })
+    }));
+    outcome.unwrap_or_else(|payload| Err(panic_message(payload)))
+}
+
+/// Applies `f` to every item on a pool of `jobs` worker threads, the
+/// available parallelism by default, and returns the results in order.
+pub(crate) fn parallel_map<T: Sync, R: Send>(
+    items: &[T],
+    jobs: Option<usize>,
+    f: impl Fn(&T) -> R + Sync,
+) -> Vec<R> {
+    let workers = jobs
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()))
+        .min(items.len());
+    if workers <= 1 {
+        return items.iter().map(f).collect();
+    }
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<R>>> = Mutex::new((0..items.len()).map(|_| None).collect());
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(item) = items.get(i) else {
+                        break;
+                    };
+                    let result = f(item);
+                    results.lock().unwrap()[i] = Some(result);
+                }
+            });
+        }
+    });
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(Option::unwrap)
+        .collect()
+}
+
+/// The message a panic was raised with.
+pub(crate) fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "panicked".to_string())
+}
+
+fn ipc(row: &Row) -> f64 {
+    if row.cycles > 0 {
+        row.committed as f64 / row.cycles as f64
+    } else {
+        0.0
+    }
+}
+
+fn summary_csv(jobs: &[Job], results: &[Result<Row, String>]) -> String {
+    let mut csv = String::from("input,cycles,committed,ipc,exception,error\n");
+    for (job, result) in jobs.iter().zip(results) {
+        let input = job.relative.display();
+        csv += &match result {
+            Ok(row) => format!(
+                "{},{},{},{:.3},{},\n",
+                input,
+                row.cycles,
+                row.committed,
+                ipc(row),
+                row.exception
+            ),
+            Err(err) => format!("{},,,,,\"{}\"\n", input, err.replace('"', "\"\"")),
+        };
+    }
+    csv
+}
+
+fn print_summary(jobs: &[Job], results: &[Result<Row, String>]) {
+    let width = jobs
+        .iter()
+        .map(|j| j.relative.display().to_string().len())
+        .max()
+        .unwrap_or(0)
+        .max("Input".len());
+    println!(
+        "{:<width$}  {:>7}  {:>9}  {:>6}  Exception",
+        "Input", "Cycles", "Committed", "IPC"
+    );
+    for (job, result) in jobs.iter().zip(results) {
+        let input = job.relative.display().to_string();
+        match result {
+            Ok(row) => println!(
+                "{:<width$}  {:>7}  {:>9}  {:>6.3}  {}",
+                input,
+                row.cycles,
+                row.committed,
+                ipc(row),
+                if row.exception { "yes" } else { "no" }
+            ),
+            Err(err) => println!("{:<width$}  error: {}", input, err),
+        }
+    }
+}
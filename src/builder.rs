@@ -0,0 +1,171 @@
+use std::fmt;
+
+use crate::config::Config;
+use crate::simulator::Simulator;
+use crate::validate;
+
+/// Builds a `Simulator` once the config is consistent and the programs
+/// decode under it, so that bad parameters or instructions are reported up
+/// front instead of panicking in the middle of a cycle:
+///
+/// `SimulatorBuilder::new().program(p).config(&config).alus(2).iq_capacity(16).build()?`
+///
+/// Without a config the default config is used. The typed setters change
+/// the config set so far, so they come after `config`.
+#[derive(Debug, Clone, Default)]
+pub struct SimulatorBuilder {
+    program: Vec<String>,
+    threads: Vec<Vec<String>>,
+    config: Config,
+}
+
+/// Everything that kept a simulator from being built: a config error, or
+/// every instruction that does not decode under the config.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuildError {
+    pub errors: Vec<String>,
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.errors.join("\n"))
+    }
+}
+
+impl From<BuildError> for String {
+    fn from(err: BuildError) -> String {
+        err.to_string()
+    }
+}
+
+impl SimulatorBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn program(mut self, program: Vec<String>) -> Self {
+        self.program = program;
+        self
+    }
+
+    /// Runs `program` on one more hardware thread.
+    pub fn thread(mut self, program: Vec<String>) -> Self {
+        self.threads.push(program);
+        self
+    }
+
+    pub fn config(mut self, config: &Config) -> Self {
+        self.config = config.clone();
+        self
+    }
+
+    /// The config the simulator is built with, the setters applied.
+    pub fn configuration(&self) -> &Config {
+        &self.config
+    }
+
+    /// The simulator, or the inconsistency in the config, or else every
+    /// instruction of the programs that does not decode under it.
+    pub fn build(self) -> Result<Simulator, BuildError> {
+        let invalid = |err: String| BuildError {
+            errors: vec![format!("Invalid config: {}", err)],
+        };
+        self.config.validate().map_err(invalid)?;
+        self.config
+            .validate_threads(1 + self.threads.len())
+            .map_err(invalid)?;
+        let mut errors: Vec<String> = validate::check_program(&self.program, &self.config)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        for (t, thread) in self.threads.iter().enumerate() {
+            for diagnostic in validate::check_program(thread, &self.config) {
+                errors.push(format!("thread {} {}", t + 1, diagnostic));
+            }
+        }
+        if !errors.is_empty() {
+            return Err(BuildError { errors });
+        }
+        let mut sim = Simulator::new(self.program, &self.config);
+        for thread in self.threads {
+            sim.add_thread(thread, &self.config);
+        }
+        Ok(sim)
+    }
+}
+
+/// Typed setters for the parameters varied most often, which `sweep` and
+/// `explore` vary through them.
+impl SimulatorBuilder {
+    pub fn alus(mut self, alus: usize) -> Self {
+        self.config.num_alus = alus;
+        self
+    }
+
+    pub fn physical_registers(mut self, registers: usize) -> Self {
+        self.config.physical_registers = registers;
+        self
+    }
+
+    pub fn active_list_capacity(mut self, capacity: usize) -> Self {
+        self.config.active_list_capacity = capacity;
+        self
+    }
+
+    pub fn iq_capacity(mut self, capacity: usize) -> Self {
+        self.config.integer_queue_capacity = capacity;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn program(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn setters_change_the_config() {
+        let sim = SimulatorBuilder::new()
+            .program(program(&["addi x1, x0, 1"]))
+            .alus(2)
+            .iq_capacity(8)
+            .build()
+            .unwrap();
+        assert_eq!(sim.alus.len(), 2);
+    }
+
+    #[test]
+    fn build_reports_every_bad_instruction() {
+        let err = SimulatorBuilder::new()
+            .program(program(&["addi x1, x0, 1", "ad x1, x2, x3", "add x1, x2"]))
+            .thread(program(&["sub x1, x2, y3"]))
+            .physical_registers(128)
+            .build()
+            .err()
+            .unwrap();
+        let indices: Vec<&str> = err
+            .errors
+            .iter()
+            .map(|error| error.split(':').next().unwrap())
+            .collect();
+        assert_eq!(
+            indices,
+            ["instruction 1", "instruction 2", "thread 1 instruction 0"]
+        );
+    }
+
+    #[test]
+    fn build_reports_an_invalid_config() {
+        let err = SimulatorBuilder::new()
+            .program(program(&["addi x1, x0, 1"]))
+            .alus(0)
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(err.errors.len(), 1);
+        assert!(err.errors[0].starts_with("Invalid config: "), "{}", err);
+    }
+}
@@ -0,0 +1,177 @@
+use serde::{Deserialize, Serialize};
+
+use crate::config::BypassConfig;
+use crate::simulator::Simulator;
+
+/// A result broadcast to the integer queue entries that were waiting on it
+/// when it left the ALU, delivered once `cycles` more cycles have passed.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Wakeup {
+    reg: u32,
+    value: u64,
+    poison: Option<u32>,
+    /// Sequence numbers of the entries to wake.
+    consumers: Vec<u64>,
+    cycles: u32,
+}
+
+/// The path from the ALU outputs back to the integer queue.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Bypass {
+    /// Cycles between a result leaving the ALU and its consumers waking.
+    delay: u32,
+    pending: Vec<Wakeup>,
+    back_to_back: bool,
+    /// Entries woken in the current cycle, recorded without back-to-back
+    /// issue.
+    pub(crate) woken: Vec<u64>,
+    /// For each tag, the positions of the integer queue entries waiting on
+    /// it, rebuilt as the cycle's wakeups begin so a result only visits its
+    /// own consumers.
+    #[serde(skip)]
+    waiting: Vec<Vec<usize>>,
+}
+
+impl Bypass {
+    pub fn new(config: &BypassConfig) -> Self {
+        Self {
+            // Without forwarding, consumers read the register file in the
+            // cycle after writeback.
+            delay: config.wakeup_delay + !config.forwarding as u32,
+            pending: Vec::new(),
+            back_to_back: config.back_to_back,
+            woken: Vec::new(),
+            waiting: Vec::new(),
+        }
+    }
+}
+
+impl Simulator {
+    /// Wakes the integer queue entries waiting on physical register `reg`,
+    /// written by an ALU of `cluster`, now or after the configured wakeup
+    /// delay. Entries in other clusters also wait for the inter-cluster
+    /// bypass.
+    pub(crate) fn broadcast(&mut self, reg: u32, value: u64, poison: Option<u32>, cluster: usize) {
+        let crossing = match self.clusters.is_clustered() {
+            true => self.clusters.delay,
+            false => 0,
+        };
+        if self.bypass.delay == 0 && crossing == 0 {
+            self.wake_up(reg, value, poison, None);
+            return;
+        }
+        let mut consumers: Vec<(u32, u64)> = self
+            .waiting_on(reg)
+            .iter()
+            .map(|&i| &self.state.integer_queue[i])
+            .filter(|e| {
+                (!e.op_a_is_ready && e.op_a_reg_tag == reg)
+                    || (!e.op_b_is_ready && e.op_b_reg_tag == reg)
+            })
+            .map(|e| {
+                let delay = self.bypass.delay + if e.cluster == cluster { 0 } else { crossing };
+                (delay, e.seq)
+            })
+            .collect();
+        consumers.sort();
+        for group in consumers.chunk_by(|a, b| a.0 == b.0) {
+            let cycles = group[0].0;
+            let consumers: Vec<u64> = group.iter().map(|&(_, seq)| seq).collect();
+            match cycles {
+                0 => self.wake_up(reg, value, poison, Some(&consumers)),
+                _ => self.bypass.pending.push(Wakeup {
+                    reg,
+                    value,
+                    poison,
+                    consumers,
+                    cycles,
+                }),
+            }
+        }
+    }
+
+    /// Delivers the delayed broadcasts that are due this cycle. Consumers
+    /// squashed in the meantime are no longer in the queue to be woken.
+    pub(crate) fn deliver_wakeups(&mut self) {
+        self.index_waiting();
+        for wakeup in self.bypass.pending.iter_mut() {
+            wakeup.cycles -= 1;
+        }
+        let (due, pending) = std::mem::take(&mut self.bypass.pending)
+            .into_iter()
+            .partition(|w| w.cycles == 0);
+        self.bypass.pending = pending;
+        for wakeup in due {
+            self.wake_up(
+                wakeup.reg,
+                wakeup.value,
+                wakeup.poison,
+                Some(&wakeup.consumers),
+            );
+        }
+    }
+
+    /// Whether the ready entry `seq` can be selected for issue this cycle.
+    pub(crate) fn selectable(&self, seq: u64) -> bool {
+        self.bypass.back_to_back || !self.bypass.woken.contains(&seq)
+    }
+
+    /// Marks the operands waiting on `reg` ready with `value`, in every
+    /// entry or only in the `consumers` given.
+    fn wake_up(&mut self, reg: u32, value: u64, poison: Option<u32>, consumers: Option<&[u64]>) {
+        let waiting = std::mem::take(&mut self.bypass.waiting);
+        let positions = waiting.get(reg as usize).map_or(&[][..], Vec::as_slice);
+        for &i in positions {
+            let entry = &mut self.state.integer_queue[i];
+            if consumers.is_some_and(|c| !c.contains(&entry.seq)) {
+                continue;
+            }
+            let waiting = (!entry.op_a_is_ready && entry.op_a_reg_tag == reg)
+                || (!entry.op_b_is_ready && entry.op_b_reg_tag == reg);
+            if waiting && !self.bypass.back_to_back {
+                self.bypass.woken.push(entry.seq);
+            }
+            if !entry.op_a_is_ready && entry.op_a_reg_tag == reg {
+                entry.op_a_is_ready = true;
+                entry.op_a_value = value;
+                entry.op_a_reg_tag = 0;
+                entry.op_a_poison = poison;
+            }
+            if !entry.op_b_is_ready && entry.op_b_reg_tag == reg {
+                entry.op_b_is_ready = true;
+                entry.op_b_value = value;
+                entry.op_b_reg_tag = 0;
+                entry.op_b_poison = poison;
+            }
+        }
+        self.bypass.waiting = waiting;
+    }
+
+    fn waiting_on(&self, reg: u32) -> &[usize] {
+        self.bypass
+            .waiting
+            .get(reg as usize)
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// Indexes the integer queue entries by the tags their operands wait on.
+    /// Entries only leave the queue at issue and only wait on new tags once
+    /// renamed, so the positions hold until the cycle's writebacks are done.
+    fn index_waiting(&mut self) {
+        let waiting = &mut self.bypass.waiting;
+        for positions in waiting.iter_mut() {
+            positions.clear();
+        }
+        for (i, entry) in self.state.integer_queue.iter().enumerate() {
+            let a = (!entry.op_a_is_ready).then_some(entry.op_a_reg_tag);
+            let b = (!entry.op_b_is_ready).then_some(entry.op_b_reg_tag);
+            for tag in [a, b.filter(|&b| a != Some(b))].into_iter().flatten() {
+                let tag = tag as usize;
+                if waiting.len() <= tag {
+                    waiting.resize_with(tag + 1, Vec::new);
+                }
+                waiting[tag].push(i);
+            }
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+use crate::config::Config;
+use crate::opcode::Opcode;
+use crate::simulator::{
+    ActiveEntry, DecodedInstructionEntry, ExceptionCause, NUM_ARCH_REGS, Simulator, compute,
+};
+
+/// A sequential interpreter of the program that `--check` runs alongside the
+/// out-of-order pipeline. Each committed micro-op is executed here too, and
+/// the first disagreement in PC, destination, value or exception is kept as
+/// a report.
+#[derive(Serialize, Deserialize)]
+pub struct Checker {
+    registers: [u64; NUM_ARCH_REGS],
+    /// Micro-ops of the current instruction not yet committed.
+    pending: VecDeque<DecodedInstructionEntry>,
+    next_pc: u64,
+    pc_step: u64,
+    trap_on_overflow: bool,
+    /// The program took its exception; nothing further commits.
+    finished: bool,
+    pub failure: Option<String>,
+}
+
+impl Checker {
+    /// Starts from the architectural registers and the PC of the current
+    /// state, the reset state unless the run was fast-forwarded.
+    pub fn new(sim: &Simulator, config: &Config) -> Checker {
+        Checker {
+            registers: sim.architectural_registers().try_into().unwrap(),
+            pending: VecDeque::new(),
+            next_pc: sim.state.pc,
+            pc_step: config.pc_step(),
+            trap_on_overflow: config.trap_on_overflow,
+            finished: false,
+            failure: None,
+        }
+    }
+
+    fn operand(&self, src: Option<u32>) -> u64 {
+        src.map_or(0, |r| self.registers[r as usize])
+    }
+
+    fn execute(&self, micro_op: &DecodedInstructionEntry) -> Result<u64, ExceptionCause> {
+        let a = match micro_op.op {
+            Opcode::Auipc => micro_op.pc * self.pc_step,
+            _ => self.operand(micro_op.src1),
+        };
+        let b = match micro_op.is_imm {
+            true => micro_op.imm,
+            false => self.operand(micro_op.src2),
+        };
+        compute(micro_op.op, a, b, self.trap_on_overflow)
+    }
+}
+
+impl Simulator {
+    /// Executes up to `k` instructions of the program with the reference
+    /// interpreter, without timing, and starts the pipeline after them with
+    /// the registers they left. Stops early at the end of the program and
+    /// before an `ecall`, an `ebreak`, a CSR instruction or an instruction
+    /// that raises an exception, which the pipeline then executes. Returns the number of
+    /// instructions executed.
+    pub fn fast_forward(&mut self, config: &Config, k: u64) -> u64 {
+        let mut interpreter = Checker::new(self, config);
+        while interpreter.next_pc < k.min(self.program.len() as u64) {
+            let micro_ops = self.decode(interpreter.next_pc);
+            if micro_ops
+                .iter()
+                .any(|m| matches!(m.op, Opcode::Ecall | Opcode::Ebreak) || m.op.is_csr())
+            {
+                break;
+            }
+            let before = interpreter.registers;
+            let raises = micro_ops.iter().any(|micro_op| {
+                let Ok(mut value) = interpreter.execute(micro_op) else {
+                    return true;
+                };
+                let dest = micro_op.dest as usize;
+                if self.hardwired_zero && dest == 0 {
+                    value = 0;
+                }
+                interpreter.registers[dest] = value;
+                false
+            });
+            if raises {
+                interpreter.registers = before;
+                break;
+            }
+            interpreter.next_pc += 1;
+        }
+        self.set_architectural_state(&interpreter.registers, interpreter.next_pc);
+        interpreter.next_pc
+    }
+
+    /// Gives a pipeline with nothing in flight the architectural registers
+    /// and the PC to fetch from next.
+    pub(crate) fn set_architectural_state(&mut self, registers: &[u64], pc: u64) {
+        let state = &mut self.state;
+        for (r, &value) in registers.iter().enumerate() {
+            let tag = match state.rob {
+                Some(_) => r,
+                None => state.register_map_table[r] as usize,
+            };
+            state.physical_register_file[tag] = value;
+        }
+        state.pc = pc;
+    }
+
+    /// Checks a micro-op leaving the active list: `result` is the value
+    /// written to its destination, or `None` if it raised the exception.
+    pub(crate) fn check_commit(&mut self, entry: &ActiveEntry, result: Option<u64>) {
+        let Some(mut checker) = self.checker.take() else {
+            return;
+        };
+        if !checker.finished && checker.failure.is_none() {
+            if checker.pending.is_empty() {
+                checker.pending.extend(self.decode(checker.next_pc));
+                checker.next_pc += 1;
+            }
+            let micro_op = checker.pending.pop_front().unwrap();
+            let dest = micro_op.dest as usize;
+            let mut expected = checker.execute(&micro_op);
+            // The counters depend on timing the reference does not model,
+            // so it takes the old CSR value the pipeline read.
+            if micro_op.op.is_csr()
+                && let Some(actual) = result
+            {
+                expected = Ok(actual);
+            }
+            if self.hardwired_zero && dest == 0 {
+                expected = expected.map(|_| 0);
+            }
+            let failure = if micro_op.pc != entry.pc {
+                Some(format!(
+                    "committed instruction {} where the reference executes instruction {}",
+                    entry.pc, micro_op.pc
+                ))
+            } else if dest != entry.logical_destination as usize {
+                Some(format!(
+                    "wrote x{} where the reference writes x{}",
+                    entry.logical_destination, dest
+                ))
+            } else {
+                match (expected, result) {
+                    (Ok(value), Some(actual)) if value != actual => Some(format!(
+                        "wrote x{} = {}, the reference computes {}",
+                        dest, actual, value
+                    )),
+                    (Ok(_), None) => {
+                        Some("raised an exception the reference does not raise".to_string())
+                    }
+                    (Err(cause), Some(actual)) => Some(format!(
+                        "wrote x{} = {} where the reference raises {:?}",
+                        dest, actual, cause
+                    )),
+                    _ => None,
+                }
+            };
+            match failure {
+                Some(failure) => {
+                    checker.failure = Some(format!(
+                        "cycle {}, instruction {} ({}): {}",
+                        self.cycle(),
+                        entry.pc,
+                        self.instruction(entry.pc).unwrap(),
+                        failure
+                    ));
+                }
+                None => match expected {
+                    Ok(value) => checker.registers[dest] = value,
+                    Err(_) => checker.finished = true,
+                },
+            }
+        }
+        self.checker = Some(checker);
+    }
+}
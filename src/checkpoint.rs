@@ -0,0 +1,213 @@
+use std::cell::Cell;
+use std::fs;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::config::Config;
+use crate::simulator::Simulator;
+use crate::units;
+
+thread_local! {
+    /// Set while a checkpoint is being written.
+    static WRITING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// `skip_serializing_if` filter for fields kept out of the log but needed to
+/// resume a run: they are only written into checkpoints.
+pub fn omit<T>(_: &T) -> bool {
+    !WRITING.with(Cell::get)
+}
+
+pub fn is_writing() -> bool {
+    WRITING.with(Cell::get)
+}
+
+/// A paused run: the complete simulator together with the config it was
+/// built from.
+#[derive(Deserialize)]
+pub struct Checkpoint {
+    pub config: Config,
+    pub simulator: Simulator,
+}
+
+#[derive(Deserialize)]
+struct CheckpointConfig {
+    config: Config,
+}
+
+#[derive(Serialize)]
+struct CheckpointRef<'a> {
+    config: &'a Config,
+    simulator: &'a Simulator,
+}
+
+/// Writes the complete simulator, in-flight ALU and FP unit work included,
+/// so the run can be resumed with `restore`.
+pub fn save(path: &str, config: &Config, simulator: &Simulator) -> Result<(), String> {
+    let json = to_json(&CheckpointRef { config, simulator })
+        .map_err(|err| format!("Failed to serialize checkpoint: {}", err))?;
+    fs::write(path, json).map_err(|err| format!("Failed to write checkpoint: {}", err))
+}
+
+/// Serializes `value` with the fields only checkpoints carry.
+fn to_json<T: Serialize>(value: &T) -> serde_json::Result<String> {
+    let _writing = Writing::start();
+    serde_json::to_string(value)
+}
+
+/// Keeps `WRITING` set while alive. It is cleared on drop, so a
+/// serialization that fails or panics leaves later logs without the
+/// checkpoint fields.
+struct Writing;
+
+impl Writing {
+    fn start() -> Writing {
+        WRITING.with(|writing| writing.set(true));
+        Writing
+    }
+}
+
+impl Drop for Writing {
+    fn drop(&mut self) {
+        WRITING.with(|writing| writing.set(false));
+    }
+}
+
+/// Reads a checkpoint written by `save`.
+pub fn restore(path: &str) -> Result<Checkpoint, String> {
+    let json =
+        fs::read_to_string(path).map_err(|err| format!("Failed to read checkpoint: {}", err))?;
+    let parse = |err| format!("Failed to parse checkpoint: {}", err);
+    // The custom opcodes of the config are registered first, since the
+    // micro-ops of the simulator may name them.
+    let CheckpointConfig { config } = serde_json::from_str(&json).map_err(parse)?;
+    units::register_custom(&config.custom_opcodes)?;
+    let mut checkpoint: Checkpoint = serde_json::from_str(&json).map_err(parse)?;
+    checkpoint.simulator.predecode();
+    Ok(checkpoint)
+}
+
+/// Snapshots of one run taken every `interval` cycles. Any earlier cycle
+/// is reached by restoring the nearest snapshot before it and simulating
+/// forward, so stepping backwards needs no per-cycle history.
+pub struct Snapshots {
+    interval: usize,
+    taken: Vec<String>,
+}
+
+impl Snapshots {
+    pub fn new(interval: usize) -> Snapshots {
+        Snapshots {
+            interval,
+            taken: Vec::new(),
+        }
+    }
+
+    /// Takes a snapshot if `sim` is at the next multiple of the interval.
+    pub fn record(&mut self, sim: &Simulator) {
+        let cycles = sim.cycles();
+        if cycles == self.taken.len() * self.interval {
+            self.taken
+                .push(to_json(sim).expect("simulator state serializes"));
+        }
+    }
+
+    /// The simulator after `cycle` cycles, or after its last cycle if it
+    /// finishes earlier.
+    pub fn seek(&self, cycle: usize) -> Simulator {
+        let nearest = (cycle / self.interval).min(self.taken.len() - 1);
+        let mut sim: Simulator =
+            serde_json::from_str(&self.taken[nearest]).expect("snapshots deserialize");
+        sim.predecode();
+        while sim.cycles() < cycle && !sim.done() {
+            sim.simulate_cycle();
+        }
+        sim
+    }
+}
+
+/// FP values are logged as numbers but checkpointed as their bit patterns,
+/// which survive the round trip exactly, NaNs and infinities included.
+/// JSON has no NaN or infinities, so the log spells them `"nan"`, `"inf"`
+/// and `"-inf"`.
+pub mod float {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(value: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+        match is_writing() {
+            true => value.to_bits().serialize(serializer),
+            false => Logged(*value).serialize(serializer),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+        u64::deserialize(deserializer).map(f64::from_bits)
+    }
+
+    /// An FP value as it appears in the log.
+    pub(super) struct Logged(pub f64);
+
+    impl Serialize for Logged {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            match self.0 {
+                value if value.is_nan() => serializer.serialize_str("nan"),
+                f64::INFINITY => serializer.serialize_str("inf"),
+                f64::NEG_INFINITY => serializer.serialize_str("-inf"),
+                value => serializer.serialize_f64(value),
+            }
+        }
+    }
+}
+
+/// `float` for a whole register file.
+pub mod floats {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(values: &[f64], serializer: S) -> Result<S::Ok, S::Error> {
+        match is_writing() {
+            true => serializer.collect_seq(values.iter().map(|v| v.to_bits())),
+            false => serializer.collect_seq(values.iter().map(|&v| float::Logged(v))),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, T: FromIterator<f64>>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        Vec::<u64>::deserialize(deserializer)
+            .map(|bits| bits.into_iter().map(f64::from_bits).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::SimulatorBuilder;
+    use std::panic;
+
+    /// A value whose serialization fails, or panics.
+    struct Failing {
+        panics: bool,
+    }
+
+    impl Serialize for Failing {
+        fn serialize<S: Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+            match self.panics {
+                true => panic!("serialization panicked"),
+                false => Err(serde::ser::Error::custom("serialization failed")),
+            }
+        }
+    }
+
+    #[test]
+    fn logs_after_a_failed_checkpoint_keep_their_fields() {
+        let sim = SimulatorBuilder::new()
+            .program(vec!["addi x1, x0, 1".to_string()])
+            .build()
+            .unwrap();
+        let logged = sim.state.to_log_value();
+        assert!(to_json(&Failing { panics: false }).is_err());
+        assert!(panic::catch_unwind(|| to_json(&Failing { panics: true })).is_err());
+        assert!(!is_writing());
+        assert_eq!(sim.state.to_log_value(), logged);
+    }
+}
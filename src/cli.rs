@@ -0,0 +1,1089 @@
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+use std::process;
+
+use crate::asm;
+use crate::config::{CompatMode, Pipeline};
+
+/// Options for a simulation run.
+pub struct SimulateArgs {
+    /// The program, read as JSON from standard input for `-`.
+    pub input_path: String,
+    /// Programs of further hardware threads sharing the pipeline.
+    pub thread_paths: Vec<String>,
+    /// The log, streamed as NDJSON to standard output for `-`.
+    pub output_path: String,
+    pub config_path: Option<String>,
+    /// Number of runs, each with its own seed. Only the first run's log is
+    /// saved.
+    pub repeat: usize,
+    /// CSV file receiving per-cycle fetch, dispatch, issue and commit counts.
+    pub bandwidth_path: Option<String>,
+    /// CSV file receiving per-cycle occupancies, PC, committed count and
+    /// exception flag.
+    pub metrics_path: Option<String>,
+    /// CSV file receiving the stage cycles of every dynamic micro-op.
+    pub timeline_path: Option<String>,
+    /// Chrome `trace_event` file of the micro-op stage lifetimes.
+    pub trace_path: Option<String>,
+    /// gem5 O3PipeView file of the micro-op stage lifetimes.
+    pub pipeview_path: Option<String>,
+    /// Standalone HTML page with charts of the run and its committed
+    /// instructions.
+    pub report_path: Option<String>,
+    /// NDJSON file receiving every fetch, dispatch, issue, writeback,
+    /// commit and exception of the out-of-order pipeline.
+    pub events_path: Option<String>,
+    /// NDJSON trace of every retired micro-op with the register it wrote.
+    pub commits_path: Option<String>,
+    /// NDJSON trace of every rename with the registers it allocated and
+    /// freed.
+    pub renames_path: Option<String>,
+    /// Cycles after which the rename trace records the map table.
+    pub rename_snapshots: Vec<u64>,
+    /// PCs whose micro-ops are drawn as a cycle-by-stage grid after the run.
+    pub diagram: Option<RangeInclusive<u64>>,
+    /// Hottest PCs listed with their share of the run after it.
+    pub profile: Option<usize>,
+    /// Write `stats.json` with pipeline counters next to the log.
+    pub stats: bool,
+    /// Script of per-cycle counters and stop conditions.
+    pub hooks_path: Option<String>,
+    /// Reference behavior the config must not deviate from.
+    pub compat: Option<CompatMode>,
+    /// Cycles whose states are recorded and written; the reset state is
+    /// cycle 0.
+    pub log_range: RangeInclusive<usize>,
+    /// Overrides the pipeline selected by the config.
+    pub pipeline: Option<Pipeline>,
+    /// Overrides the fields the config leaves out of the log.
+    pub log_omit: Option<Vec<String>>,
+    /// Write only the fields that changed since the previous cycle, with a
+    /// full keyframe every this many cycles.
+    pub delta: Option<usize>,
+    pub format: LogFormat,
+    /// Verify every commit against a sequential reference interpreter.
+    pub check: bool,
+    /// Validate the renaming bookkeeping and queue sizes after every cycle.
+    pub assert_invariants: bool,
+    /// Stop the run after this many cycles.
+    pub max_cycles: Option<usize>,
+    /// Stop the run once this many instructions committed.
+    pub max_instructions: Option<u64>,
+    /// Instructions executed by the reference interpreter before the
+    /// pipeline starts.
+    pub fast_forward: Option<u64>,
+    /// Exception handler program mapped at the exception vector.
+    pub handler_path: Option<String>,
+    /// External interrupt schedule, a JSON object from cycle to interrupt id.
+    pub interrupts_path: Option<String>,
+    /// Where the complete simulator is saved when the run stops.
+    pub save_path: Option<String>,
+    /// Checkpoint to resume instead of starting from the input program.
+    pub restore_path: Option<String>,
+    /// Where the inputs of the run are recorded for `--replay`.
+    pub record_path: Option<String>,
+    /// Replay file to take the config, programs and interrupts from.
+    pub replay_path: Option<String>,
+    /// Client driving the run over TCP.
+    pub remote: Option<Remote>,
+    /// Show no progress line on stderr during long runs.
+    pub quiet: bool,
+    /// Print the config the run uses, after defaults and overrides, as JSON.
+    pub print_config: bool,
+    /// Where the run starts logging, simulated up to without recording.
+    pub until: Option<Until>,
+}
+
+/// A client that controls a run over TCP instead of letting it run to
+/// completion, with the address to listen on, such as `:1234`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Remote {
+    /// gdb over the remote serial protocol.
+    Gdb(String),
+    /// A testbench advancing the run in lock-step.
+    Cosim(String),
+}
+
+impl Remote {
+    /// The option selecting this client.
+    pub fn option(&self) -> &'static str {
+        match self {
+            Remote::Gdb(_) => "--gdb",
+            Remote::Cosim(_) => "--cosim",
+        }
+    }
+}
+
+/// The point `--until-cycle` or `--until-pc` runs to before logging.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Until {
+    Cycle(usize),
+    /// The commit of the instruction at this address.
+    Pc(u64),
+}
+
+impl Until {
+    /// The option selecting this target.
+    pub fn option(&self) -> &'static str {
+        match self {
+            Until::Cycle(_) => "--until-cycle",
+            Until::Pc(_) => "--until-pc",
+        }
+    }
+}
+
+/// Encoding of the saved log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Json,
+    /// MessagePack, smaller and faster to write than JSON.
+    Msgpack,
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(LogFormat::Json),
+            "msgpack" => Ok(LogFormat::Msgpack),
+            _ => Err(format!("unknown log format '{}'", s)),
+        }
+    }
+}
+
+/// Options for point queries over a saved log.
+pub struct InspectArgs {
+    pub log_path: String,
+    pub cycle: Option<usize>,
+    pub query: Option<String>,
+    pub when: Option<String>,
+}
+
+/// Options for rendering a cycle window of a saved log as an animated GIF.
+pub struct AnimateArgs {
+    pub log_path: String,
+    pub output_path: String,
+    pub from: Option<usize>,
+    pub to: Option<usize>,
+    /// Frame duration in hundredths of a second.
+    pub delay: u16,
+}
+
+/// Options for simulating every program found under a directory.
+pub struct BatchArgs {
+    pub input_dir: String,
+    pub output_dir: String,
+    pub config_path: Option<String>,
+    /// Worker threads; defaults to the available parallelism.
+    pub jobs: Option<usize>,
+}
+
+/// Options for simulating one program under every combination of a set of
+/// config parameters.
+pub struct SweepArgs {
+    pub input_path: String,
+    /// Config keys with the values each takes, in the order given.
+    pub params: Vec<(String, Vec<String>)>,
+    /// Base config the parameters are applied to.
+    pub config_path: Option<String>,
+    /// Worker threads; defaults to the available parallelism.
+    pub jobs: Option<usize>,
+    /// Where to save the CSV; printed if absent.
+    pub output_path: Option<String>,
+}
+
+/// Options for estimating the IPC of a long program from detailed windows
+/// between functionally executed stretches.
+pub struct SampleArgs {
+    pub input_path: String,
+    pub config_path: Option<String>,
+    /// Instructions from the start of one window to the start of the next.
+    pub period: u64,
+    /// Instructions simulated in detail per window.
+    pub window: u64,
+    /// Where to save the windows as CSV.
+    pub output_path: Option<String>,
+}
+
+/// Options for running a program under two configs in lock-step.
+pub struct LockstepArgs {
+    pub input_path: String,
+    pub config_paths: [String; 2],
+    /// Micro-ops per region of the cycle comparison.
+    pub region: u64,
+}
+
+/// Options for re-simulating a program whenever it or its config changes.
+pub struct WatchArgs {
+    pub input_path: String,
+    pub output_path: String,
+    pub config_path: Option<String>,
+    /// Log each run is compared against.
+    pub golden_path: Option<String>,
+    /// Milliseconds between checks of the watched files.
+    pub interval: u64,
+}
+
+/// Cost weighed against IPC when exploring configurations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cost {
+    Area,
+    /// Energy per committed instruction.
+    Energy,
+}
+
+impl std::str::FromStr for Cost {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "area" => Ok(Cost::Area),
+            "energy" => Ok(Cost::Energy),
+            _ => Err(format!("unknown cost '{}'", s)),
+        }
+    }
+}
+
+/// Options for searching the configurations spanned by a set of parameters
+/// for the best trade-offs of IPC against cost.
+pub struct ExploreArgs {
+    pub input_path: String,
+    pub params: Vec<(String, Vec<String>)>,
+    pub config_path: Option<String>,
+    /// Configurations drawn at random; every one is simulated if absent.
+    pub samples: Option<usize>,
+    pub seed: u64,
+    /// The cost traded off against IPC; both area and energy if absent.
+    pub cost: Option<Cost>,
+    pub jobs: Option<usize>,
+    /// Where to save every configuration explored as CSV.
+    pub output_path: Option<String>,
+}
+
+/// Options for checking the committed instructions against a Spike or QEMU
+/// log of the same program.
+pub struct VerifyTraceArgs {
+    pub input_path: String,
+    pub reference_path: String,
+    pub config_path: Option<String>,
+    /// Address of the first instruction in the reference.
+    pub base: u64,
+}
+
+/// Options for stepping through a simulation interactively.
+pub struct TuiArgs {
+    pub input_path: String,
+    pub config_path: Option<String>,
+}
+
+/// Kind of JSON document checked by `validate`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Document {
+    Program,
+    Log,
+}
+
+impl Document {
+    pub fn name(self) -> &'static str {
+        match self {
+            Document::Program => "program",
+            Document::Log => "log",
+        }
+    }
+}
+
+impl std::str::FromStr for Document {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "program" => Ok(Document::Program),
+            "log" => Ok(Document::Log),
+            _ => Err(format!("unknown schema '{}'", s)),
+        }
+    }
+}
+
+/// Options for checking a program or a log against its JSON Schema.
+pub struct ValidateArgs {
+    pub path: String,
+    /// Detected from the contents if absent.
+    pub document: Option<Document>,
+}
+
+/// Options for reconstructing the full states of a delta-encoded log.
+pub struct ExpandArgs {
+    pub log_path: String,
+    pub output_path: String,
+}
+
+/// Options for turning a MessagePack log back into JSON.
+pub struct ConvertArgs {
+    pub log_path: String,
+    pub output_path: String,
+}
+
+/// Options for comparing a log against a golden reference.
+pub struct DiffArgs {
+    pub golden_path: String,
+    pub log_path: String,
+}
+
+/// Options for checking the simulator against the checked-in test cases.
+pub struct SelftestArgs {
+    pub dir: String,
+}
+
+/// Options for generating a random program.
+pub struct GenArgs {
+    pub instructions: usize,
+    pub seed: u64,
+    /// Probability that a division or remainder has a zero divisor.
+    pub div_zero: f64,
+    /// Registers `x0` up to this count are used; fewer registers mean more
+    /// dependences.
+    pub registers: usize,
+    /// Where to save the program; printed if absent.
+    pub output_path: Option<String>,
+}
+
+pub enum Command {
+    Simulate(Box<SimulateArgs>),
+    Inspect(InspectArgs),
+    Animate(AnimateArgs),
+    Batch(BatchArgs),
+    Sweep(SweepArgs),
+    Sample(SampleArgs),
+    Lockstep(LockstepArgs),
+    Watch(WatchArgs),
+    Explore(ExploreArgs),
+    VerifyTrace(VerifyTraceArgs),
+    Tui(TuiArgs),
+    Validate(ValidateArgs),
+    Expand(ExpandArgs),
+    Convert(ConvertArgs),
+    Diff(DiffArgs),
+    Selftest(SelftestArgs),
+    Gen(GenArgs),
+}
+
+const USAGE: &str = "Usage:
+  {0} [--config <config.json>] [--repeat <k>] [--bandwidth <out.csv>] [--stats]
+      [--metrics <out.csv>] [--timeline <out.csv>] [--trace <trace.json>]
+      [--o3pipeview <o3pipeview.out>] [--report <report.html>]
+      [--events <events.ndjson>] [--commits <commits.ndjson>] [--hooks <hooks.txt>]
+      [--renames <renames.ndjson>] [--rename-snapshots <cycle>[,<cycle>...]]
+      [--diagram <from-pc>..<to-pc>] [--profile <n>]
+      [--compat cs470-2024]
+      [--pipeline out-of-order|in-order|scoreboard|tomasulo]
+      [--log-from <n>] [--log-to <n>] [--delta <keyframe-interval>]
+      [--log-omit <field>[,<field>...]]
+      [--format json|msgpack] [--check] [--assert-invariants]
+      [--max-cycles <n>] [--max-instructions <n>] [--fast-forward <k>]
+      [--until-cycle <n> | --until-pc <address>]
+      [--handler <handler.json|handler.s>]
+      [--interrupts <schedule.json>] [--save-checkpoint <checkpoint.json>]
+      [--record <replay.json>] [--gdb [<host>]:<port>]
+      [--cosim [<host>]:<port>] [--quiet] [--print-config]
+      <input.json|input.s|trace.ndjson|-> [<thread.json>...]
+      <output.json|output.ndjson>[.gz|.zst]|-
+  {0} --restore-checkpoint <checkpoint.json> [--save-checkpoint <checkpoint.json>]
+      [--max-cycles <n>] [<options>] <output.json>[.gz|.zst]
+  {0} --replay <replay.json> [<options>] <output.json|output.ndjson>[.gz|.zst]
+  {0} inspect <log.json> [--cycle <n>] [--query <path>] [--when <predicate>]
+  {0} animate <log.json> <output.gif> [--from <n>] [--to <n>] [--delay <cs>]
+  {0} batch <dir> <outdir> [--config <config.json>] [--jobs <n>]
+  {0} sweep <input.json|input.s> --param <key>=<values> [--param ...]
+      [--config <config.json>] [--jobs <n>] [--output <sweep.csv>]
+  {0} explore <input.json|input.s> --param <key>=<values> [--param ...]
+      [--samples <n>] [--seed <s>] [--cost area|energy] [--config <config.json>]
+      [--jobs <n>] [--output <explore.csv>]
+  {0} sample <input.json|input.s> --period <n> --window <n> [--config <config.json>]
+      [--output <windows.csv>]
+  {0} lockstep <input.json|input.s> <config-a.json> <config-b.json> [--region <n>]
+  {0} watch <input.json|input.s> <output.json> [--config <config.json>]
+      [--golden <golden.json>] [--interval <ms>]
+  {0} verify-trace <input.json|input.s|input.elf> <spike.log|qemu.log>
+      [--config <config.json>] [--base <address>]
+  {0} tui <input.json|input.s> [--config <config.json>]
+  {0} validate <program.json|log.json> [--schema program|log]
+  {0} expand <delta.json> <output.json>
+  {0} convert <log.msgpack> <output.json>
+  {0} diff <golden.json> <log.json>
+  {0} selftest [<testcases-dir>]
+  {0} gen [--instructions <n>] [--seed <s>] [--div-zero <p>] [--registers <n>]
+      [--output <program.json>]";
+
+fn usage(program: &str) -> ! {
+    eprintln!("{}", USAGE.replace("{0}", program));
+    process::exit(1);
+}
+
+/// Positional arguments and option values of one command line. Options not
+/// taking a value map to an empty string.
+struct Parsed {
+    positional: Vec<String>,
+    options: HashMap<String, String>,
+}
+
+impl Parsed {
+    fn take(&mut self, name: &str) -> Option<String> {
+        self.options.remove(name)
+    }
+}
+
+/// Splits `args` into positionals and the options in `spec`, given as
+/// `(name, takes_value)` pairs. Unknown options are a usage error.
+fn split_args(program: &str, args: &[String], spec: &[(&str, bool)]) -> Parsed {
+    let mut parsed = Parsed {
+        positional: Vec::new(),
+        options: HashMap::new(),
+    };
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if !arg.starts_with("--") {
+            parsed.positional.push(arg.clone());
+            continue;
+        }
+        let Some(&(name, takes_value)) = spec.iter().find(|(name, _)| name == arg) else {
+            eprintln!("Unknown option: {}", arg);
+            usage(program);
+        };
+        let value = if takes_value {
+            iter.next().cloned().unwrap_or_else(|| {
+                eprintln!("Option {} expects a value", name);
+                usage(program);
+            })
+        } else {
+            String::new()
+        };
+        parsed.options.insert(name.to_string(), value);
+    }
+    parsed
+}
+
+/// Parses an option value, exiting with a usage message if it is malformed.
+fn parse_value<T: std::str::FromStr>(program: &str, name: &str, value: &str) -> T {
+    value.parse().unwrap_or_else(|_| {
+        eprintln!("Invalid value for {}: {}", name, value);
+        usage(program);
+    })
+}
+
+/// Parses the process arguments, exiting with a usage message if they are
+/// malformed.
+pub fn parse_args(args: &[String]) -> Command {
+    let program = args.first().map(String::as_str).unwrap_or("ooo470");
+    match args.get(1).map(String::as_str) {
+        Some("inspect") => parse_inspect(program, &args[2..]),
+        Some("animate") => parse_animate(program, &args[2..]),
+        Some("batch") => parse_batch(program, &args[2..]),
+        Some("sweep") => parse_sweep(program, &args[2..]),
+        Some("explore") => parse_explore(program, &args[2..]),
+        Some("sample") => parse_sample(program, &args[2..]),
+        Some("lockstep") => parse_lockstep(program, &args[2..]),
+        Some("watch") => parse_watch(program, &args[2..]),
+        Some("verify-trace") => parse_verify_trace(program, &args[2..]),
+        Some("tui") => parse_tui(program, &args[2..]),
+        Some("validate") => parse_validate(program, &args[2..]),
+        Some("expand") => parse_expand(program, &args[2..]),
+        Some("convert") => parse_convert(program, &args[2..]),
+        Some("diff") => parse_diff(program, &args[2..]),
+        Some("selftest") => parse_selftest(program, &args[2..]),
+        Some("gen") => parse_gen(program, &args[2..]),
+        _ => parse_simulate(program, &args[1..]),
+    }
+}
+
+fn parse_simulate(program: &str, args: &[String]) -> Command {
+    let spec = [
+        ("--config", true),
+        ("--repeat", true),
+        ("--bandwidth", true),
+        ("--metrics", true),
+        ("--stats", false),
+        ("--timeline", true),
+        ("--trace", true),
+        ("--o3pipeview", true),
+        ("--report", true),
+        ("--events", true),
+        ("--commits", true),
+        ("--renames", true),
+        ("--rename-snapshots", true),
+        ("--diagram", true),
+        ("--profile", true),
+        ("--hooks", true),
+        ("--compat", true),
+        ("--pipeline", true),
+        ("--log-from", true),
+        ("--log-to", true),
+        ("--log-omit", true),
+        ("--delta", true),
+        ("--format", true),
+        ("--check", false),
+        ("--assert-invariants", false),
+        ("--max-cycles", true),
+        ("--max-instructions", true),
+        ("--fast-forward", true),
+        ("--until-cycle", true),
+        ("--until-pc", true),
+        ("--handler", true),
+        ("--interrupts", true),
+        ("--save-checkpoint", true),
+        ("--restore-checkpoint", true),
+        ("--record", true),
+        ("--replay", true),
+        ("--gdb", true),
+        ("--cosim", true),
+        ("--quiet", false),
+        ("--print-config", false),
+    ];
+    let mut parsed = split_args(program, args, &spec);
+    // A restored or replayed run takes its programs from the checkpoint or
+    // the replay file.
+    let restore_path = parsed.take("--restore-checkpoint");
+    let replay_path = parsed.take("--replay");
+    let without_input = restore_path.is_some() || replay_path.is_some();
+    if restore_path.is_some() && replay_path.is_some() {
+        eprintln!("--restore-checkpoint and --replay are mutually exclusive");
+        usage(program);
+    }
+    let remote = match (parsed.take("--gdb"), parsed.take("--cosim")) {
+        (Some(_), Some(_)) => {
+            eprintln!("--gdb and --cosim are mutually exclusive");
+            usage(program);
+        }
+        (Some(address), None) => Some(Remote::Gdb(address)),
+        (None, Some(address)) => Some(Remote::Cosim(address)),
+        (None, None) => None,
+    };
+    match without_input {
+        true if parsed.positional.len() != 1 => usage(program),
+        false if parsed.positional.len() < 2 => usage(program),
+        _ => {}
+    }
+    let mut cycle = |name: &str| parsed.take(name).map(|v| parse_value(program, name, &v));
+    let log_from = cycle("--log-from").unwrap_or(0);
+    let log_to = cycle("--log-to").unwrap_or(usize::MAX);
+    if log_from > log_to {
+        eprintln!("--log-from must not exceed --log-to");
+        usage(program);
+    }
+    let repeat = parsed
+        .take("--repeat")
+        .map_or(1, |v| parse_value(program, "--repeat", &v));
+    if repeat == 0 {
+        eprintln!("--repeat must be at least 1");
+        usage(program);
+    }
+    let delta = parsed
+        .take("--delta")
+        .map(|v| parse_value(program, "--delta", &v));
+    if delta == Some(0) {
+        eprintln!("--delta must be at least 1");
+        usage(program);
+    }
+    let max_cycles = parsed
+        .take("--max-cycles")
+        .map(|v| parse_value(program, "--max-cycles", &v));
+    if max_cycles == Some(0) {
+        eprintln!("--max-cycles must be at least 1");
+        usage(program);
+    }
+    let max_instructions = parsed
+        .take("--max-instructions")
+        .map(|v| parse_value(program, "--max-instructions", &v));
+    if max_instructions == Some(0) {
+        eprintln!("--max-instructions must be at least 1");
+        usage(program);
+    }
+    let fast_forward = parsed
+        .take("--fast-forward")
+        .map(|v| parse_value(program, "--fast-forward", &v));
+    let until = match (parsed.take("--until-cycle"), parsed.take("--until-pc")) {
+        (Some(_), Some(_)) => {
+            eprintln!("--until-cycle and --until-pc are mutually exclusive");
+            usage(program);
+        }
+        (Some(cycle), None) => Some(Until::Cycle(parse_value(program, "--until-cycle", &cycle))),
+        (None, Some(pc)) => match asm::parse_immediate(&pc) {
+            Some(address) => Some(Until::Pc(address)),
+            None => {
+                eprintln!("Invalid value for --until-pc: {}", pc);
+                usage(program);
+            }
+        },
+        (None, None) => None,
+    };
+    let profile = parsed
+        .take("--profile")
+        .map(|v| parse_value(program, "--profile", &v));
+    if profile == Some(0) {
+        eprintln!("--profile must be at least 1");
+        usage(program);
+    }
+    let diagram = parsed.take("--diagram").map(|v| {
+        parse_pc_range(&v).unwrap_or_else(|| {
+            eprintln!("Invalid value for --diagram: {}", v);
+            usage(program);
+        })
+    });
+    let renames_path = parsed.take("--renames");
+    let rename_snapshots: Vec<u64> = parsed
+        .take("--rename-snapshots")
+        .map(|v| {
+            v.split(',')
+                .map(|cycle| parse_value(program, "--rename-snapshots", cycle))
+                .collect()
+        })
+        .unwrap_or_default();
+    if !rename_snapshots.is_empty() && renames_path.is_none() {
+        eprintln!("--rename-snapshots requires --renames");
+        usage(program);
+    }
+    let output_path = parsed.positional.pop().unwrap();
+    let input_path = match without_input {
+        true => String::new(),
+        false => parsed.positional.remove(0),
+    };
+    Command::Simulate(Box::new(SimulateArgs {
+        input_path,
+        thread_paths: std::mem::take(&mut parsed.positional),
+        output_path,
+        config_path: parsed.take("--config"),
+        repeat,
+        bandwidth_path: parsed.take("--bandwidth"),
+        metrics_path: parsed.take("--metrics"),
+        stats: parsed.take("--stats").is_some(),
+        timeline_path: parsed.take("--timeline"),
+        events_path: parsed.take("--events"),
+        commits_path: parsed.take("--commits"),
+        renames_path,
+        rename_snapshots,
+        diagram,
+        profile,
+        trace_path: parsed.take("--trace"),
+        pipeview_path: parsed.take("--o3pipeview"),
+        report_path: parsed.take("--report"),
+        hooks_path: parsed.take("--hooks"),
+        compat: parsed
+            .take("--compat")
+            .map(|v| parse_value(program, "--compat", &v)),
+        log_range: log_from..=log_to,
+        pipeline: parsed
+            .take("--pipeline")
+            .map(|v| parse_value(program, "--pipeline", &v)),
+        log_omit: parsed
+            .take("--log-omit")
+            .map(|v| v.split(',').map(str::to_string).collect()),
+        delta,
+        format: parsed
+            .take("--format")
+            .map_or(LogFormat::Json, |v| parse_value(program, "--format", &v)),
+        check: parsed.take("--check").is_some(),
+        assert_invariants: parsed.take("--assert-invariants").is_some(),
+        max_cycles,
+        max_instructions,
+        fast_forward,
+        handler_path: parsed.take("--handler"),
+        interrupts_path: parsed.take("--interrupts"),
+        save_path: parsed.take("--save-checkpoint"),
+        restore_path,
+        record_path: parsed.take("--record"),
+        replay_path,
+        remote,
+        quiet: parsed.take("--quiet").is_some(),
+        print_config: parsed.take("--print-config").is_some(),
+        until,
+    }))
+}
+
+/// Parses an inclusive range of PCs such as `8..20` or `0x80..0x90`.
+fn parse_pc_range(range: &str) -> Option<RangeInclusive<u64>> {
+    let (from, to) = range.split_once("..")?;
+    let (from, to) = (asm::parse_immediate(from)?, asm::parse_immediate(to)?);
+    (from <= to).then_some(from..=to)
+}
+
+fn parse_inspect(program: &str, args: &[String]) -> Command {
+    let spec = [("--cycle", true), ("--query", true), ("--when", true)];
+    let mut parsed = split_args(program, args, &spec);
+    if parsed.positional.len() != 1 {
+        usage(program);
+    }
+    let cycle = parsed
+        .take("--cycle")
+        .map(|v| parse_value(program, "--cycle", &v));
+    Command::Inspect(InspectArgs {
+        log_path: parsed.positional.pop().unwrap(),
+        cycle,
+        query: parsed.take("--query"),
+        when: parsed.take("--when"),
+    })
+}
+
+fn parse_animate(program: &str, args: &[String]) -> Command {
+    let spec = [("--from", true), ("--to", true), ("--delay", true)];
+    let mut parsed = split_args(program, args, &spec);
+    if parsed.positional.len() != 2 {
+        usage(program);
+    }
+    let mut cycle = |name: &str| parsed.take(name).map(|v| parse_value(program, name, &v));
+    let (from, to) = (cycle("--from"), cycle("--to"));
+    let delay = parsed
+        .take("--delay")
+        .map_or(50, |v| parse_value(program, "--delay", &v));
+    let output_path = parsed.positional.pop().unwrap();
+    Command::Animate(AnimateArgs {
+        log_path: parsed.positional.pop().unwrap(),
+        output_path,
+        from,
+        to,
+        delay,
+    })
+}
+
+fn parse_batch(program: &str, args: &[String]) -> Command {
+    let spec = [("--config", true), ("--jobs", true)];
+    let mut parsed = split_args(program, args, &spec);
+    if parsed.positional.len() != 2 {
+        usage(program);
+    }
+    let output_dir = parsed.positional.pop().unwrap();
+    Command::Batch(BatchArgs {
+        input_dir: parsed.positional.pop().unwrap(),
+        output_dir,
+        config_path: parsed.take("--config"),
+        jobs: parse_jobs(program, &mut parsed),
+    })
+}
+
+fn parse_sweep(program: &str, args: &[String]) -> Command {
+    let (params, rest) = take_params(program, args);
+    let spec = [("--config", true), ("--jobs", true), ("--output", true)];
+    let mut parsed = split_args(program, &rest, &spec);
+    if parsed.positional.len() != 1 || params.is_empty() {
+        usage(program);
+    }
+    Command::Sweep(SweepArgs {
+        input_path: parsed.positional.pop().unwrap(),
+        params,
+        config_path: parsed.take("--config"),
+        jobs: parse_jobs(program, &mut parsed),
+        output_path: parsed.take("--output"),
+    })
+}
+
+fn parse_explore(program: &str, args: &[String]) -> Command {
+    let (params, rest) = take_params(program, args);
+    let spec = [
+        ("--config", true),
+        ("--samples", true),
+        ("--seed", true),
+        ("--cost", true),
+        ("--jobs", true),
+        ("--output", true),
+    ];
+    let mut parsed = split_args(program, &rest, &spec);
+    if parsed.positional.len() != 1 || params.is_empty() {
+        usage(program);
+    }
+    let samples = parsed
+        .take("--samples")
+        .map(|v| parse_value(program, "--samples", &v));
+    if samples == Some(0) {
+        eprintln!("--samples must be at least 1");
+        usage(program);
+    }
+    Command::Explore(ExploreArgs {
+        input_path: parsed.positional.pop().unwrap(),
+        params,
+        config_path: parsed.take("--config"),
+        samples,
+        seed: parsed
+            .take("--seed")
+            .map_or(0, |v| parse_value(program, "--seed", &v)),
+        cost: parsed
+            .take("--cost")
+            .map(|v| parse_value(program, "--cost", &v)),
+        jobs: parse_jobs(program, &mut parsed),
+        output_path: parsed.take("--output"),
+    })
+}
+
+fn parse_sample(program: &str, args: &[String]) -> Command {
+    let spec = [
+        ("--config", true),
+        ("--period", true),
+        ("--window", true),
+        ("--output", true),
+    ];
+    let mut parsed = split_args(program, args, &spec);
+    if parsed.positional.len() != 1 {
+        usage(program);
+    }
+    let mut count = |name: &str| -> u64 {
+        match parsed.take(name) {
+            Some(v) => parse_value(program, name, &v),
+            None => {
+                eprintln!("{} is required", name);
+                usage(program);
+            }
+        }
+    };
+    let (period, window) = (count("--period"), count("--window"));
+    if window == 0 || period < window {
+        eprintln!("--window must be at least 1 and at most --period");
+        usage(program);
+    }
+    Command::Sample(SampleArgs {
+        input_path: parsed.positional.pop().unwrap(),
+        config_path: parsed.take("--config"),
+        period,
+        window,
+        output_path: parsed.take("--output"),
+    })
+}
+
+fn parse_lockstep(program: &str, args: &[String]) -> Command {
+    let spec = [("--region", true)];
+    let mut parsed = split_args(program, args, &spec);
+    if parsed.positional.len() != 3 {
+        usage(program);
+    }
+    let region = parsed
+        .take("--region")
+        .map_or(1000, |v| parse_value(program, "--region", &v));
+    if region == 0 {
+        eprintln!("--region must be at least 1");
+        usage(program);
+    }
+    let config_b = parsed.positional.pop().unwrap();
+    let config_a = parsed.positional.pop().unwrap();
+    Command::Lockstep(LockstepArgs {
+        input_path: parsed.positional.pop().unwrap(),
+        config_paths: [config_a, config_b],
+        region,
+    })
+}
+
+fn parse_watch(program: &str, args: &[String]) -> Command {
+    let spec = [("--config", true), ("--golden", true), ("--interval", true)];
+    let mut parsed = split_args(program, args, &spec);
+    if parsed.positional.len() != 2 {
+        usage(program);
+    }
+    let interval = parsed
+        .take("--interval")
+        .map_or(500, |v| parse_value(program, "--interval", &v));
+    if interval == 0 {
+        eprintln!("--interval must be at least 1");
+        usage(program);
+    }
+    let output_path = parsed.positional.pop().unwrap();
+    Command::Watch(WatchArgs {
+        input_path: parsed.positional.pop().unwrap(),
+        output_path,
+        config_path: parsed.take("--config"),
+        golden_path: parsed.take("--golden"),
+        interval,
+    })
+}
+
+fn parse_verify_trace(program: &str, args: &[String]) -> Command {
+    let spec = [("--config", true), ("--base", true)];
+    let mut parsed = split_args(program, args, &spec);
+    if parsed.positional.len() != 2 {
+        usage(program);
+    }
+    // Spike and QEMU load programs at the start of DRAM by default.
+    let base = parsed.take("--base").map_or(0x8000_0000, |v| {
+        asm::parse_immediate(&v).unwrap_or_else(|| {
+            eprintln!("Invalid value for --base: {}", v);
+            usage(program);
+        })
+    });
+    let reference_path = parsed.positional.pop().unwrap();
+    Command::VerifyTrace(VerifyTraceArgs {
+        input_path: parsed.positional.pop().unwrap(),
+        reference_path,
+        config_path: parsed.take("--config"),
+        base,
+    })
+}
+
+/// Takes every `--param` out of `args`, as the option may be repeated, and
+/// returns the parameters with the remaining arguments.
+fn take_params(program: &str, args: &[String]) -> (Vec<(String, Vec<String>)>, Vec<String>) {
+    let mut params = Vec::new();
+    let mut rest = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg != "--param" {
+            rest.push(arg.clone());
+            continue;
+        }
+        let Some(param) = iter.next() else {
+            eprintln!("Option --param expects a value");
+            usage(program);
+        };
+        params.push(parse_param(param).unwrap_or_else(|err| {
+            eprintln!("Invalid value for --param: {}", err);
+            usage(program);
+        }));
+    }
+    (params, rest)
+}
+
+fn parse_jobs(program: &str, parsed: &mut Parsed) -> Option<usize> {
+    let jobs = parsed
+        .take("--jobs")
+        .map(|v| parse_value(program, "--jobs", &v));
+    if jobs == Some(0) {
+        eprintln!("--jobs must be at least 1");
+        usage(program);
+    }
+    jobs
+}
+
+/// Splits `key=values` into the key and its values, given as an inclusive
+/// integer range `lo..hi` or a comma-separated list.
+fn parse_param(param: &str) -> Result<(String, Vec<String>), String> {
+    let Some((key, values)) = param.split_once('=') else {
+        return Err(format!("{}: expected <key>=<values>", param));
+    };
+    if key.is_empty() || values.is_empty() {
+        return Err(format!("{}: expected <key>=<values>", param));
+    }
+    let values = match values.split_once("..") {
+        Some((lo, hi)) => {
+            let bound = |s: &str| {
+                s.parse::<u64>()
+                    .map_err(|_| format!("{}: invalid range bound '{}'", param, s))
+            };
+            let (lo, hi) = (bound(lo)?, bound(hi)?);
+            if lo > hi {
+                return Err(format!("{}: empty range", param));
+            }
+            (lo..=hi).map(|v| v.to_string()).collect()
+        }
+        None => values.split(',').map(str::to_string).collect(),
+    };
+    Ok((key.to_string(), values))
+}
+
+fn parse_tui(program: &str, args: &[String]) -> Command {
+    let mut parsed = split_args(program, args, &[("--config", true)]);
+    if parsed.positional.len() != 1 {
+        usage(program);
+    }
+    Command::Tui(TuiArgs {
+        input_path: parsed.positional.pop().unwrap(),
+        config_path: parsed.take("--config"),
+    })
+}
+
+fn parse_expand(program: &str, args: &[String]) -> Command {
+    let mut parsed = split_args(program, args, &[]);
+    if parsed.positional.len() != 2 {
+        usage(program);
+    }
+    let output_path = parsed.positional.pop().unwrap();
+    Command::Expand(ExpandArgs {
+        log_path: parsed.positional.pop().unwrap(),
+        output_path,
+    })
+}
+
+fn parse_validate(program: &str, args: &[String]) -> Command {
+    let mut parsed = split_args(program, args, &[("--schema", true)]);
+    if parsed.positional.len() != 1 {
+        usage(program);
+    }
+    Command::Validate(ValidateArgs {
+        path: parsed.positional.pop().unwrap(),
+        document: parsed
+            .take("--schema")
+            .map(|v| parse_value(program, "--schema", &v)),
+    })
+}
+
+fn parse_convert(program: &str, args: &[String]) -> Command {
+    let mut parsed = split_args(program, args, &[]);
+    if parsed.positional.len() != 2 {
+        usage(program);
+    }
+    let output_path = parsed.positional.pop().unwrap();
+    Command::Convert(ConvertArgs {
+        log_path: parsed.positional.pop().unwrap(),
+        output_path,
+    })
+}
+
+fn parse_diff(program: &str, args: &[String]) -> Command {
+    let mut parsed = split_args(program, args, &[]);
+    if parsed.positional.len() != 2 {
+        usage(program);
+    }
+    let log_path = parsed.positional.pop().unwrap();
+    Command::Diff(DiffArgs {
+        golden_path: parsed.positional.pop().unwrap(),
+        log_path,
+    })
+}
+
+fn parse_selftest(program: &str, args: &[String]) -> Command {
+    let mut parsed = split_args(program, args, &[]);
+    if parsed.positional.len() > 1 {
+        usage(program);
+    }
+    Command::Selftest(SelftestArgs {
+        dir: parsed
+            .positional
+            .pop()
+            .unwrap_or_else(|| "testcases".to_string()),
+    })
+}
+
+fn parse_gen(program: &str, args: &[String]) -> Command {
+    let spec = [
+        ("--instructions", true),
+        ("--seed", true),
+        ("--div-zero", true),
+        ("--registers", true),
+        ("--output", true),
+    ];
+    let mut parsed = split_args(program, args, &spec);
+    if !parsed.positional.is_empty() {
+        usage(program);
+    }
+    let instructions = parsed
+        .take("--instructions")
+        .map_or(32, |v| parse_value(program, "--instructions", &v));
+    let seed = parsed
+        .take("--seed")
+        .map_or(0, |v| parse_value(program, "--seed", &v));
+    let div_zero: f64 = parsed
+        .take("--div-zero")
+        .map_or(0.05, |v| parse_value(program, "--div-zero", &v));
+    if !(0.0..=1.0).contains(&div_zero) {
+        eprintln!("--div-zero must be between 0 and 1");
+        usage(program);
+    }
+    let registers = parsed
+        .take("--registers")
+        .map_or(32, |v| parse_value(program, "--registers", &v));
+    if !(1..=32).contains(&registers) {
+        eprintln!("--registers must be between 1 and 32");
+        usage(program);
+    }
+    Command::Gen(GenArgs {
+        instructions,
+        seed,
+        div_zero,
+        registers,
+        output_path: parsed.take("--output"),
+    })
+}
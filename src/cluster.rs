@@ -0,0 +1,161 @@
+use serde::{Deserialize, Serialize};
+
+use crate::config::{ClusterConfig, ClusterSteering};
+use crate::simulator::Simulator;
+
+/// A clustered back end: the integer queue is split into equal parts, each
+/// issuing only to its own group of ALUs. Entries record their cluster.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Clusters {
+    count: usize,
+    /// Entries each cluster's queue holds.
+    capacity: usize,
+    steering: ClusterSteering,
+    /// Cycles a result takes to wake an entry in another cluster.
+    pub(crate) delay: u32,
+    /// Cluster round-robin steering tries first.
+    next: usize,
+    /// Cluster of the last micro-op renamed to each physical register.
+    producer: Vec<usize>,
+}
+
+impl Clusters {
+    pub fn new(config: &ClusterConfig, queue_capacity: usize, registers: usize) -> Self {
+        Self {
+            count: config.count,
+            capacity: queue_capacity / config.count,
+            steering: config.steering,
+            delay: config.bypass_delay,
+            next: 0,
+            producer: vec![0; registers],
+        }
+    }
+
+    pub fn is_clustered(&self) -> bool {
+        self.count > 1
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+impl Simulator {
+    /// Cluster ALU `alu` belongs to.
+    pub(crate) fn alu_cluster(&self, alu: usize) -> usize {
+        alu * self.clusters.count / self.alus.len()
+    }
+
+    /// Entries of the integer queue in `cluster`.
+    pub(crate) fn cluster_occupancy(&self, cluster: usize) -> usize {
+        self.state
+            .integer_queue
+            .iter()
+            .filter(|e| e.cluster == cluster)
+            .count()
+    }
+
+    /// Picks the cluster of a micro-op writing physical register `dest`,
+    /// given the tags of its sources still waiting on a producer. Rename has
+    /// checked that the queues have room in total, so some cluster does.
+    pub(crate) fn steer(&mut self, waiting_on: &[u32], dest: u32) -> usize {
+        let count = self.clusters.count;
+        if count == 1 {
+            return 0;
+        }
+        let has_room = |sim: &Self, c: usize| sim.cluster_occupancy(c) < sim.clusters.capacity;
+        let cluster = match self.clusters.steering {
+            ClusterSteering::RoundRobin => {
+                let start = self.clusters.next;
+                let cluster = (0..count)
+                    .map(|i| (start + i) % count)
+                    .find(|&c| has_room(self, c))
+                    .unwrap();
+                self.clusters.next = (cluster + 1) % count;
+                cluster
+            }
+            // With the producer of the first waiting source if it has room,
+            // so the result reaches it without crossing clusters; else the
+            // emptiest cluster.
+            ClusterSteering::Dependence => waiting_on
+                .iter()
+                .map(|&tag| self.clusters.producer[tag as usize])
+                .find(|&c| has_room(self, c))
+                .unwrap_or_else(|| {
+                    (0..count)
+                        .min_by_key(|&c| self.cluster_occupancy(c))
+                        .unwrap()
+                }),
+        };
+        self.clusters.producer[dest as usize] = cluster;
+        cluster
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::SimulatorBuilder;
+    use crate::config::Config;
+
+    fn simulator(lines: &[&str], steering: ClusterSteering, bypass_delay: u32) -> Simulator {
+        let config = Config {
+            clusters: ClusterConfig {
+                count: 2,
+                steering,
+                bypass_delay,
+            },
+            ..Config::default()
+        };
+        SimulatorBuilder::new()
+            .program(lines.iter().map(|line| line.to_string()).collect())
+            .config(&config)
+            .build()
+            .unwrap()
+    }
+
+    const CHAIN: [&str; 4] = [
+        "addi x1, x0, 1",
+        "add x2, x1, x1",
+        "add x3, x2, x2",
+        "add x4, x3, x3",
+    ];
+
+    /// Clusters of the micro-ops dispatched by the first rename.
+    fn steered(steering: ClusterSteering) -> Vec<usize> {
+        let mut sim = simulator(&CHAIN, steering, 1);
+        sim.simulate_cycle();
+        sim.simulate_cycle();
+        sim.state.integer_queue.iter().map(|e| e.cluster).collect()
+    }
+
+    fn cycles(steering: ClusterSteering) -> usize {
+        let mut sim = simulator(&CHAIN, steering, 3);
+        while !sim.done() {
+            sim.simulate_cycle();
+        }
+        assert_eq!(sim.architectural_registers()[1..5], [1, 2, 4, 8]);
+        sim.cycles()
+    }
+
+    #[test]
+    fn round_robin_steering_alternates_clusters() {
+        assert_eq!(steered(ClusterSteering::RoundRobin), [0, 1, 0, 1]);
+    }
+
+    #[test]
+    fn dependence_steering_follows_the_producer() {
+        assert_eq!(steered(ClusterSteering::Dependence), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn crossing_clusters_delays_the_wakeup() {
+        let crossing = cycles(ClusterSteering::RoundRobin);
+        let local = cycles(ClusterSteering::Dependence);
+        assert_eq!(crossing, local + 3 * 3);
+    }
+}
@@ -0,0 +1,1165 @@
+use serde::{Deserialize, Serialize};
+
+use crate::fp::NUM_FP_ARCH_REGS;
+use crate::opcode::{OPCODES, Opcode};
+use crate::simulator::{DECODE_WIDTH, EXCEPTION_VECTOR, NUM_ARCH_REGS};
+use crate::units;
+
+/// Size of an encoded instruction.
+pub const INSTRUCTION_BYTES: u64 = 4;
+
+/// Number of ALUs in the reference design, the default of `num_alus`.
+pub const NUM_ALUS: usize = 4;
+
+/// Machine configuration. Every field defaults to the reference design, so an
+/// empty config file reproduces the baseline behavior.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Microarchitecture the program runs on.
+    pub pipeline: Pipeline,
+    /// Register renaming scheme of the out-of-order pipeline.
+    pub renaming: Renaming,
+    /// Number of ALUs, which is also the integer issue width.
+    pub num_alus: usize,
+    /// Integer physical registers, including the 32 architectural ones; the
+    /// rest form the free list.
+    pub physical_registers: usize,
+    pub active_list_capacity: usize,
+    pub integer_queue_capacity: usize,
+    /// Opcodes each ALU may execute, indexed by ALU number. ALUs without an
+    /// entry, or with an empty list, accept every opcode. Immediate forms share
+    /// the register form's name (`add` also covers `addi`). An entry may name
+    /// the class `simple` or `mul-div` instead of single opcodes, so that
+    /// `[["simple"], ["simple"], ["simple"], ["simple", "mul-div"]]` leaves
+    /// multiplies and divides to the last of four ALUs.
+    pub alu_capabilities: Vec<Vec<String>>,
+    /// Opcodes beyond those of the reference design, which the ALUs execute
+    /// like any other.
+    pub custom_opcodes: Vec<CustomOpcode>,
+    /// Execute stages of each ALU. A result reaches the forwarding path at
+    /// the end of the last one, so every stage past the first delays
+    /// dependents by a cycle; the reference ALUs have two.
+    pub alu_pipeline_depth: usize,
+    /// How `li` of a constant too wide for a 12-bit immediate is executed.
+    pub li_expansion: LiExpansion,
+    /// Raise an overflow exception when signed `add`, `sub` or `mul` overflow
+    /// instead of wrapping.
+    pub trap_on_overflow: bool,
+    /// Width of the I-type immediate field; wider immediates draw a warning.
+    pub immediate_bits: u32,
+    /// Wrap the output log in an object carrying a header (warnings found in
+    /// the program and this config) alongside the per-cycle states.
+    pub log_header: bool,
+    /// Log the cause of the last exception taken (`ExceptionCause`) and of
+    /// every faulting active list entry (`Cause`).
+    pub log_exception_cause: bool,
+    /// Log the cycle of every state and the micro-ops committed up to it
+    /// (`Cycle` and `RetiredInstructions`), so the states can be matched
+    /// without counting them.
+    pub log_cycle: bool,
+    /// Log the PCs of the micro-ops fetched, dispatched, issued, completed
+    /// and committed in each cycle (`Fetched`, `Dispatched`, `Issued`,
+    /// `Completed` and `Committed`).
+    pub log_events: bool,
+    /// Log which structures stalled rename in each cycle (`Backpressure`):
+    /// the integer queue, active list, free list and, with FP units, the FP
+    /// queue and free list.
+    pub log_backpressure: bool,
+    /// Top-level fields left out of every logged state and thread context,
+    /// such as `PhysicalRegisterFile` or `BusyBitTable`, to shrink the logs
+    /// of long runs. A log missing a required field no longer matches the
+    /// log schema.
+    pub log_omit: Vec<String>,
+    /// Initial values of architectural registers, starting at `x0`.
+    pub initial_registers: Vec<u64>,
+    /// Fill every register without an initial value with a poison pattern
+    /// and report the first committed instruction that depends on one.
+    pub poison_uninitialized: bool,
+    /// How instruction strings are interpreted.
+    pub input_format: InputFormat,
+    /// FP register file, queue and functional units.
+    pub fp: FpConfig,
+    /// Reservation stations and buses of the Tomasulo pipeline.
+    pub tomasulo: TomasuloConfig,
+    /// Order in which ready instructions compete for the ALUs.
+    pub issue_policy: IssuePolicy,
+    /// Which free ALU an issued micro-op is bound to.
+    pub alu_binding: AluBinding,
+    /// Which free register rename allocates next.
+    pub free_list_policy: FreeListPolicy,
+    /// When the register an architectural register held before it was
+    /// overwritten returns to the free list.
+    pub reclamation: Reclamation,
+    /// Whether rename dispatches part of a decoded bundle that does not fit.
+    pub dispatch: DispatchMode,
+    /// Which hardware thread fetches each cycle when several programs share
+    /// the pipeline.
+    pub fetch_policy: FetchPolicy,
+    /// Seed of randomized policies. With `--repeat`, run `i` uses `seed + i`.
+    pub seed: u64,
+    /// PC of the first exception handler instruction, where fetch is
+    /// redirected when an exception is taken. Programs must fit below it.
+    pub exception_vector: u64,
+    /// PCs are byte addresses that advance by 4 per instruction, as on
+    /// RISC-V, rather than instruction indices. `exception_vector` is then
+    /// a byte address too.
+    pub byte_addressed_pc: bool,
+    /// When the run ends, besides a committed `ebreak` or exit `ecall`.
+    pub termination: Termination,
+    /// A micro-op whose result is forwarded in a cycle may commit in that
+    /// same cycle, as if commit ran after execute. The reference design
+    /// commits it in the next cycle at the earliest.
+    pub same_cycle_commit: bool,
+    /// Spend a cycle clearing the exception flag once an exception has been
+    /// rolled back or flushed, as the reference design does. In the
+    /// out-of-order pipeline that cycle only shows in the log, as a state
+    /// with the flag still set and an empty active list: fetch resumes in
+    /// the cycle after the rollback completes either way, unless
+    /// `exception_resume` makes it resume in the same cycle.
+    pub exception_cooldown: bool,
+    /// When fetch resumes after the out-of-order pipeline rolled back an
+    /// exception.
+    pub exception_resume: ExceptionResume,
+    /// Exceptions the handler may raise while it handles another, nested
+    /// in each other: each saves the one it interrupts on the exception
+    /// stack (`ExceptionStack`), and `mret` returns to it. One more ends the
+    /// run, as any exception raised by the handler does by default.
+    pub exception_nesting: usize,
+    /// `x0` reads as zero and discards writes, as on RISC-V, instead of
+    /// being an ordinary register. Writes to it take no physical register.
+    pub hardwired_zero: bool,
+    /// Micro-ops held by a fetch buffer between separate fetch and decode
+    /// stages. Without one, fetch and decode are a single stage.
+    pub fetch_buffer: Option<usize>,
+    /// Rename register moves (`mv`, or an `add` of zero) by mapping the
+    /// destination onto the source's physical register, without executing
+    /// them.
+    pub move_elimination: bool,
+    /// Complete zeroing idioms (`xor`, `sub` or `slt` of a register with
+    /// itself) and constants built from immediates alone at rename, writing
+    /// the result without executing.
+    pub idiom_elimination: bool,
+    pub bypass: BypassConfig,
+    pub writeback: WritebackConfig,
+    pub register_file: RegisterFileConfig,
+    pub scheduler: SchedulerConfig,
+    pub value_prediction: ValuePredictionConfig,
+    pub latency: LatencyConfig,
+    pub roi: RoiConfig,
+    pub energy: EnergyConfig,
+    pub area: AreaConfig,
+    pub clusters: ClusterConfig,
+    pub recovery: RecoveryConfig,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Pipeline {
+    /// The renamed, out-of-order reference design.
+    #[default]
+    OutOfOrder,
+    /// A scalar five-stage in-order pipeline with full forwarding, as a
+    /// baseline for out-of-order speedups.
+    InOrder,
+    /// CDC 6600 style scoreboarding without register renaming.
+    Scoreboard,
+    /// Tomasulo's algorithm with per-ALU reservation stations.
+    Tomasulo,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Renaming {
+    /// A merged physical register file with a free list, as in the MIPS
+    /// R10000 and the reference design.
+    #[default]
+    MergedRegisterFile,
+    /// Results held in a reorder buffer sized by `active_list_capacity`,
+    /// with a retirement map table updated at commit.
+    ReorderBuffer,
+}
+
+impl std::str::FromStr for Pipeline {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "out-of-order" => Ok(Pipeline::OutOfOrder),
+            "in-order" => Ok(Pipeline::InOrder),
+            "scoreboard" => Ok(Pipeline::Scoreboard),
+            "tomasulo" => Ok(Pipeline::Tomasulo),
+            _ => Err(format!("unknown pipeline '{}'", s)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FreeListPolicy {
+    /// The register freed longest ago, as in the reference design.
+    #[default]
+    Fifo,
+    /// The register freed most recently.
+    Lifo,
+    /// A random free register, drawn from `seed`.
+    Random,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Reclamation {
+    /// When the micro-op overwriting it commits, the earliest point no
+    /// rollback can need it again, as in the reference design.
+    #[default]
+    Commit,
+    /// Only when the next writer of the same architectural register
+    /// commits as well. Always safe, but every architectural register keeps
+    /// a second physical register out of the free list.
+    NextWriterCommit,
+}
+
+/// How rename treats decoded micro-ops that do not all fit in the queues,
+/// the active list and the free lists.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum DispatchMode {
+    /// Nothing is dispatched until the whole bundle fits, as in the
+    /// reference design.
+    #[default]
+    AllOrNothing,
+    /// The oldest micro-ops that fit are dispatched and the rest stay in
+    /// the decode buffer, which fetch tops up.
+    Partial,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum IssuePolicy {
+    /// Lowest PC first, as in the reference design.
+    #[default]
+    Oldest,
+    /// Highest PC first.
+    Youngest,
+    /// A random order among the ready instructions, drawn from `seed`.
+    Random,
+    /// Queue slot order: the entry nearest the head of the queue first,
+    /// regardless of its PC.
+    Position,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum AluBinding {
+    /// The lowest-numbered free ALU that can execute the micro-op, as in
+    /// the reference design.
+    #[default]
+    FirstFree,
+    /// The first free capable ALU after the one bound last.
+    RoundRobin,
+    /// Each entry is bound at dispatch to one ALU of its cluster, the
+    /// capable ALUs taking turns, and issues only to it, as if every ALU
+    /// had its own partition of the queue.
+    Dedicated,
+    /// Multiplies and divides take the highest-numbered free capable ALU
+    /// and every other micro-op the lowest, keeping the long-latency
+    /// operations off the ALUs the simple ones use.
+    OpcodeClass,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum FetchPolicy {
+    /// Threads take turns, skipping those that cannot fetch.
+    #[default]
+    RoundRobin,
+    /// The thread with the fewest micro-ops decoded or waiting to execute,
+    /// as proposed by Tullsen et al.
+    Icount,
+}
+
+/// Condition that ends a run. A committed `ebreak` or exit `ecall` always
+/// halts it.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Termination {
+    /// Every thread fetched past the end of its program, or of the
+    /// exception handler once an exception was taken, and everything in
+    /// flight drained.
+    #[default]
+    EndOfProgram,
+    /// This many micro-ops committed, `{"commits": n}`; the run stops in
+    /// that cycle, or at the end of the program if it comes first.
+    Commits(u64),
+    /// Only a halting instruction ends the run. A program that runs off its
+    /// end without one is stopped by the watchdog.
+    Halt,
+}
+
+/// The cycle fetch resumes in once an exception has been rolled back.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ExceptionResume {
+    /// The cycle after the last micro-op was rolled back, as in the
+    /// reference design.
+    #[default]
+    NextCycle,
+    /// The cycle the last micro-op was rolled back in, as if rollback ran
+    /// before the rest of the pipeline. The exception flag is then cleared
+    /// with the rollback, so there is no cooldown cycle.
+    SameCycle,
+}
+
+/// Parameters of the FP subsystem. When disabled, FP instructions are
+/// rejected and no FP structures appear in the log.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct FpConfig {
+    pub enabled: bool,
+    /// FP physical registers, including the 32 architectural ones. Defaults
+    /// to 64 rather than 32: rename needs free registers beyond the
+    /// architectural ones, as `physical_registers` does.
+    pub physical_registers: usize,
+    pub queue_capacity: usize,
+    pub units: usize,
+    pub add_latency: u32,
+    pub mul_latency: u32,
+    pub div_latency: u32,
+    /// Initial values of the FP registers, starting at `f0`.
+    pub initial_registers: Vec<f64>,
+}
+
+impl Default for FpConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            physical_registers: 64,
+            queue_capacity: 16,
+            units: 2,
+            add_latency: 4,
+            mul_latency: 5,
+            div_latency: 12,
+            initial_registers: Vec::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct TomasuloConfig {
+    pub stations_per_unit: usize,
+    /// Results that can be broadcast per cycle.
+    pub common_data_buses: usize,
+}
+
+impl Default for TomasuloConfig {
+    fn default() -> Self {
+        Self {
+            stations_per_unit: 2,
+            common_data_buses: 1,
+        }
+    }
+}
+
+/// Result forwarding from the ALU outputs into the integer queue.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct BypassConfig {
+    /// Waiting entries pick up a result as it leaves the ALU. Without it
+    /// they read it from the register file one cycle after writeback.
+    pub forwarding: bool,
+    /// Extra cycles before a result wakes the entries waiting on it.
+    pub wakeup_delay: u32,
+    /// An entry woken by a result can issue in the same cycle, so dependent
+    /// micro-ops issue back to back. Without it, wakeup and select take a
+    /// cycle each and a woken entry issues in the next cycle at the earliest.
+    pub back_to_back: bool,
+}
+
+impl Default for BypassConfig {
+    fn default() -> Self {
+        Self {
+            forwarding: true,
+            wakeup_delay: 0,
+            back_to_back: true,
+        }
+    }
+}
+
+/// Result buses from the ALUs to the register file and integer queue.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct WritebackConfig {
+    /// Results written back per cycle; one per ALU if unset. A result that
+    /// loses arbitration stays in its ALU, which stalls until it is written.
+    pub ports: Option<usize>,
+    pub arbitration: WritebackArbitration,
+}
+
+/// Ports of the integer physical register file. Its write ports are
+/// `writeback.ports`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct RegisterFileConfig {
+    /// Register source operands read per cycle by the issued micro-ops;
+    /// unlimited if unset. A ready micro-op needing more reads than are
+    /// left waits in the queue.
+    pub read_ports: Option<usize>,
+}
+
+/// Select logic of the integer queue.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct SchedulerConfig {
+    /// Model the queue as slots with an age matrix and select the oldest
+    /// ready entries by request and grant, in dispatch order rather than
+    /// by PC. The FP queue keeps `issue_policy`.
+    pub age_matrix: bool,
+    /// Micro-ops selected per cycle; one per ALU if unset. A ready micro-op
+    /// left without a port waits in the queue.
+    pub select_ports: Option<usize>,
+}
+
+/// Which results get the write ports when more ALUs complete than there
+/// are ports.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum WritebackArbitration {
+    /// The oldest micro-ops first.
+    #[default]
+    Oldest,
+    /// Lower-numbered ALUs first.
+    FixedPriority,
+    /// The ALU after the last one granted a port first.
+    RoundRobin,
+}
+
+/// Speculation on the results of integer micro-ops, for experiments.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ValuePredictionConfig {
+    pub predictor: ValuePredictor,
+    /// Correct predictions in a row an instruction needs before its result
+    /// is predicted; a misprediction starts the count over.
+    pub confidence: u32,
+}
+
+impl Default for ValuePredictionConfig {
+    fn default() -> Self {
+        Self {
+            predictor: ValuePredictor::None,
+            confidence: 2,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ValuePredictor {
+    #[default]
+    None,
+    /// The result an instruction produced the last time it executed.
+    LastValue,
+    /// The last result plus the difference between the last two.
+    Stride,
+}
+
+/// Random extra execute latency of the integer ALUs, drawn per micro-op to
+/// stress the scheduler reproducibly.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct LatencyConfig {
+    pub distribution: LatencyDistribution,
+    /// Opcodes whose latency varies; the multiplies and divides if empty.
+    pub opcodes: Vec<String>,
+    /// Seed of the draws; `seed` if unset.
+    pub seed: Option<u64>,
+}
+
+/// Region of interest of the run, between two instructions of the program
+/// given by address: the pipeline counters cover only the cycles from the
+/// commit of `begin` to the commit of `end`, so warmup code before it and
+/// teardown after it do not skew them.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct RoiConfig {
+    /// The region opens at reset if unset.
+    pub begin: Option<u64>,
+    /// The region lasts to the end of the run if unset.
+    pub end: Option<u64>,
+    /// Log only the states of the cycles in the region.
+    pub log_only: bool,
+}
+
+impl RoiConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.begin.is_some() || self.end.is_some() || self.log_only
+    }
+}
+
+/// Distribution of the extra cycles a micro-op spends in its first execute
+/// stage.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum LatencyDistribution {
+    /// No extra cycles, as in the reference design.
+    #[default]
+    Fixed,
+    /// `{"uniform": {"min": a, "max": b}}`: any of `a..=b`, equally likely.
+    Uniform { min: u32, max: u32 },
+    /// `{"bimodal": {"fast": a, "slow": b, "slow_fraction": p}}`: `b` with
+    /// probability `p` and `a` otherwise, like hits and misses of a cache.
+    Bimodal {
+        fast: u32,
+        slow: u32,
+        slow_fraction: f64,
+    },
+}
+
+/// Energy charged per access to the integer structures of the out-of-order
+/// pipeline, in picojoules. Reported with `--stats`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct EnergyConfig {
+    pub register_read: f64,
+    pub register_write: f64,
+    pub map_table_read: f64,
+    pub map_table_write: f64,
+    pub queue_write: f64,
+    /// One result tag compared against every waiting entry.
+    pub queue_wakeup: f64,
+    pub queue_issue: f64,
+    pub alu_operation: f64,
+}
+
+impl Default for EnergyConfig {
+    fn default() -> Self {
+        Self {
+            register_read: 1.0,
+            register_write: 1.2,
+            map_table_read: 0.3,
+            map_table_write: 0.4,
+            queue_write: 0.8,
+            queue_wakeup: 1.5,
+            queue_issue: 0.6,
+            alu_operation: 2.0,
+        }
+    }
+}
+
+/// Area model of the out-of-order pipeline, in square micrometres.
+/// Reported with `--stats`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct AreaConfig {
+    /// A storage bit without ports.
+    pub bit: f64,
+    /// Added to every storage bit per read or write port.
+    pub port: f64,
+    /// One bit of a wakeup tag comparator.
+    pub comparator_bit: f64,
+    pub alu: f64,
+    pub fp_unit: f64,
+}
+
+impl Default for AreaConfig {
+    fn default() -> Self {
+        Self {
+            bit: 0.1,
+            port: 0.05,
+            comparator_bit: 0.3,
+            alu: 2500.0,
+            fp_unit: 10000.0,
+        }
+    }
+}
+
+/// A clustered out-of-order back end, whose integer queue is split evenly
+/// into `count` queues that each issue to an equal share of the ALUs.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClusterConfig {
+    /// Number of clusters; a single cluster is the unclustered design.
+    pub count: usize,
+    pub steering: ClusterSteering,
+    /// Extra cycles a result takes to wake an entry in another cluster.
+    pub bypass_delay: u32,
+}
+
+impl Default for ClusterConfig {
+    fn default() -> Self {
+        Self {
+            count: 1,
+            steering: ClusterSteering::default(),
+            bypass_delay: 1,
+        }
+    }
+}
+
+/// Which cluster's queue a renamed micro-op is dispatched to.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ClusterSteering {
+    /// The clusters take turns.
+    #[default]
+    RoundRobin,
+    /// The cluster of the producer of a waiting source, else the one with
+    /// the fewest queued entries.
+    Dependence,
+}
+
+/// How the out-of-order pipeline recovers its rename state after an
+/// exception.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RecoveryConfig {
+    pub mode: RecoveryMode,
+    /// Map table snapshots available to in-flight micro-ops in snapshot
+    /// mode.
+    pub snapshots: usize,
+}
+
+impl Default for RecoveryConfig {
+    fn default() -> Self {
+        Self {
+            mode: RecoveryMode::default(),
+            snapshots: 4,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RecoveryMode {
+    /// Walk the active list back from the tail, `COMMIT_WIDTH` entries per
+    /// cycle, as the reference design does.
+    #[default]
+    Rollback,
+    /// Restore the snapshot the excepting micro-op took at rename in one
+    /// cycle, falling back to the rollback if it took none.
+    Snapshot,
+}
+
+/// Encoding of the instructions in the program input.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum InputFormat {
+    /// Machine code if every instruction is a hex word, assembly otherwise.
+    #[default]
+    Auto,
+    Assembly,
+    /// Hex-encoded 32-bit RISC-V instruction words (`"0x00500093"`).
+    MachineCode,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            pipeline: Pipeline::default(),
+            renaming: Renaming::default(),
+            num_alus: NUM_ALUS,
+            physical_registers: 64,
+            active_list_capacity: 32,
+            integer_queue_capacity: 32,
+            alu_capabilities: Vec::new(),
+            custom_opcodes: Vec::new(),
+            alu_pipeline_depth: 2,
+            li_expansion: LiExpansion::default(),
+            trap_on_overflow: false,
+            immediate_bits: 12,
+            log_header: false,
+            log_exception_cause: false,
+            log_cycle: false,
+            log_events: false,
+            log_backpressure: false,
+            log_omit: Vec::new(),
+            initial_registers: Vec::new(),
+            poison_uninitialized: false,
+            input_format: InputFormat::default(),
+            fp: FpConfig::default(),
+            tomasulo: TomasuloConfig::default(),
+            issue_policy: IssuePolicy::default(),
+            alu_binding: AluBinding::default(),
+            reclamation: Reclamation::default(),
+            dispatch: DispatchMode::default(),
+            free_list_policy: FreeListPolicy::default(),
+            fetch_policy: FetchPolicy::default(),
+            seed: 0,
+            exception_vector: EXCEPTION_VECTOR,
+            byte_addressed_pc: false,
+            termination: Termination::default(),
+            same_cycle_commit: false,
+            exception_cooldown: true,
+            exception_resume: ExceptionResume::default(),
+            exception_nesting: 0,
+            hardwired_zero: false,
+            fetch_buffer: None,
+            move_elimination: false,
+            idiom_elimination: false,
+            bypass: BypassConfig::default(),
+            writeback: WritebackConfig::default(),
+            register_file: RegisterFileConfig::default(),
+            scheduler: SchedulerConfig::default(),
+            value_prediction: ValuePredictionConfig::default(),
+            latency: LatencyConfig::default(),
+            roi: RoiConfig::default(),
+            energy: EnergyConfig::default(),
+            area: AreaConfig::default(),
+            clusters: ClusterConfig::default(),
+            recovery: RecoveryConfig::default(),
+        }
+    }
+}
+
+/// An opcode defined by the config, used as `name rd, rs1, rs2`:
+/// `{"name": "madd", "latency": 3, "result": "a * b + a"}`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct CustomOpcode {
+    pub name: String,
+    /// Cycles the opcode occupies the first execute stage.
+    pub latency: u32,
+    /// The value written to `rd`, an expression over the operands `a` and
+    /// `b` (see `units::Expression`).
+    pub result: String,
+}
+
+/// Execution strategy for wide `li` pseudo-instructions.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LiExpansion {
+    /// Crack into a `lui` and an `addi` micro-op, each with its own physical
+    /// register and active-list entry.
+    #[default]
+    Crack,
+    /// Keep a single micro-op with twice the normal execute latency.
+    Fused,
+}
+
+impl Config {
+    /// Checks the configuration for internal consistency.
+    pub fn validate(&self) -> Result<(), String> {
+        // Registered first, so that the rest of the config and the programs
+        // can name them.
+        units::register_custom(&self.custom_opcodes)?;
+        if !(1..=64).contains(&self.immediate_bits) {
+            return Err(format!(
+                "immediate_bits must be between 1 and 64, got {}",
+                self.immediate_bits
+            ));
+        }
+        if self.initial_registers.len() > NUM_ARCH_REGS {
+            return Err(format!(
+                "initial_registers has {} entries but there are only 32 registers",
+                self.initial_registers.len()
+            ));
+        }
+        if self.hardwired_zero && self.initial_registers.first().is_some_and(|&v| v != 0) {
+            return Err("initial_registers cannot set x0 when hardwired_zero is set".to_string());
+        }
+        if self.renaming == Renaming::ReorderBuffer
+            && (self.pipeline != Pipeline::OutOfOrder || self.fp.enabled)
+        {
+            return Err(
+                "reorder-buffer renaming requires the out-of-order pipeline without FP".to_string(),
+            );
+        }
+        if self.recovery.mode == RecoveryMode::Snapshot {
+            if self.pipeline != Pipeline::OutOfOrder
+                || self.renaming != Renaming::MergedRegisterFile
+                || self.fp.enabled
+            {
+                return Err(
+                    "snapshot recovery requires the out-of-order pipeline with a merged \
+                     register file and without FP"
+                        .to_string(),
+                );
+            }
+            if self.recovery.snapshots == 0 {
+                return Err("recovery.snapshots must be positive".to_string());
+            }
+        }
+        if let Some(capacity) = self.fetch_buffer {
+            if self.pipeline != Pipeline::OutOfOrder {
+                return Err("only the out-of-order pipeline has a fetch buffer".to_string());
+            }
+            // Fetch places the micro-ops of an instruction together.
+            if capacity < 2 {
+                return Err(format!(
+                    "fetch_buffer must hold at least 2 micro-ops, got {}",
+                    capacity
+                ));
+            }
+        }
+        if (self.move_elimination || self.idiom_elimination)
+            && (self.pipeline != Pipeline::OutOfOrder
+                || self.renaming != Renaming::MergedRegisterFile)
+        {
+            return Err(
+                "move and idiom elimination require the out-of-order pipeline with a merged \
+                 register file"
+                    .to_string(),
+            );
+        }
+        if self.value_prediction.predictor != ValuePredictor::None {
+            if self.pipeline != Pipeline::OutOfOrder
+                || self.renaming != Renaming::MergedRegisterFile
+                || self.fp.enabled
+            {
+                return Err(
+                    "value prediction requires the out-of-order pipeline with a merged \
+                     register file and without FP"
+                        .to_string(),
+                );
+            }
+            if self.value_prediction.confidence == 0 {
+                return Err("value_prediction.confidence must be positive".to_string());
+            }
+            // A squash would refetch an `mret` that already chose which
+            // exception it returns to.
+            if self.exception_nesting > 0 {
+                return Err("exception_nesting does not support value prediction".to_string());
+            }
+        }
+        let clusters = self.clusters.count;
+        if clusters == 0
+            || !self.num_alus.is_multiple_of(clusters)
+            || !self.integer_queue_capacity.is_multiple_of(clusters)
+        {
+            return Err(format!(
+                "clusters.count must be positive and divide num_alus and integer_queue_capacity, got {}",
+                clusters
+            ));
+        }
+        if clusters > 1 && self.pipeline != Pipeline::OutOfOrder {
+            return Err("only the out-of-order pipeline can be clustered".to_string());
+        }
+        if self.writeback.ports == Some(0) {
+            return Err("writeback.ports must be positive".to_string());
+        }
+        // A micro-op reads at most two registers, so fewer ports could
+        // never issue it.
+        if self.register_file.read_ports.is_some_and(|ports| ports < 2) {
+            return Err("register_file.read_ports must be at least 2".to_string());
+        }
+        if self.scheduler.select_ports == Some(0) {
+            return Err("scheduler.select_ports must be at least 1".to_string());
+        }
+        if self.alu_binding != AluBinding::FirstFree && self.pipeline != Pipeline::OutOfOrder {
+            return Err("only the out-of-order pipeline has an ALU binding policy".to_string());
+        }
+        if self.scheduler.age_matrix {
+            if self.pipeline != Pipeline::OutOfOrder {
+                return Err("only the out-of-order pipeline has an age matrix".to_string());
+            }
+            if self.issue_policy != IssuePolicy::Oldest {
+                return Err(
+                    "scheduler.age_matrix selects by age; issue_policy must be oldest".to_string(),
+                );
+            }
+        }
+        if self.log_events && self.pipeline != Pipeline::OutOfOrder {
+            return Err("only the out-of-order pipeline logs cycle events".to_string());
+        }
+        if self.log_backpressure && self.pipeline != Pipeline::OutOfOrder {
+            return Err("only the out-of-order pipeline logs backpressure".to_string());
+        }
+        let schema: serde_json::Value = serde_json::from_str(crate::schema::LOG_SCHEMA).unwrap();
+        if let Some(name) = self
+            .log_omit
+            .iter()
+            .find(|name| schema["$defs"]["state"]["properties"].get(name).is_none())
+        {
+            return Err(format!(
+                "log_omit names {}, which is not a logged field",
+                name
+            ));
+        }
+        if self.fp.enabled && self.pipeline != Pipeline::OutOfOrder {
+            return Err("only the out-of-order pipeline has FP units".to_string());
+        }
+        if self.tomasulo.stations_per_unit == 0 || self.tomasulo.common_data_buses == 0 {
+            return Err(
+                "tomasulo.stations_per_unit and tomasulo.common_data_buses must be positive"
+                    .to_string(),
+            );
+        }
+        if self.fp.enabled {
+            let fp = &self.fp;
+            if fp.physical_registers < NUM_FP_ARCH_REGS + DECODE_WIDTH {
+                return Err(format!(
+                    "fp.physical_registers must be at least {}",
+                    NUM_FP_ARCH_REGS + DECODE_WIDTH
+                ));
+            }
+            if fp.initial_registers.len() > NUM_FP_ARCH_REGS {
+                return Err("fp.initial_registers has more than 32 entries".to_string());
+            }
+            if fp.units == 0 {
+                return Err("fp.units must be at least 1".to_string());
+            }
+            if fp.queue_capacity < DECODE_WIDTH {
+                return Err(format!(
+                    "fp.queue_capacity must be at least {}",
+                    DECODE_WIDTH
+                ));
+            }
+            if fp.add_latency == 0 || fp.mul_latency == 0 || fp.div_latency == 0 {
+                return Err("FP latencies must be at least one cycle".to_string());
+            }
+        }
+        if self.num_alus == 0 {
+            return Err("num_alus must be at least 1".to_string());
+        }
+        if self.alu_pipeline_depth == 0 {
+            return Err("alu_pipeline_depth must be at least 1".to_string());
+        }
+        if self.same_cycle_commit && self.pipeline != Pipeline::OutOfOrder {
+            return Err("only the out-of-order pipeline supports same_cycle_commit".to_string());
+        }
+        if self.alu_pipeline_depth != 2 && self.pipeline != Pipeline::OutOfOrder {
+            return Err("only the out-of-order pipeline models the ALU pipeline depth".to_string());
+        }
+        if self.exception_vector / self.pc_step() == 0 {
+            return Err("exception_vector must leave room for the program".to_string());
+        }
+        if !self.exception_vector.is_multiple_of(self.pc_step()) {
+            return Err(
+                "exception_vector must be a multiple of 4 with byte_addressed_pc".to_string(),
+            );
+        }
+        if self.exception_resume == ExceptionResume::SameCycle {
+            if self.pipeline != Pipeline::OutOfOrder {
+                return Err(
+                    "exception_resume same-cycle is only supported by the out-of-order pipeline"
+                        .to_string(),
+                );
+            }
+            if self.exception_cooldown {
+                return Err("exception_resume same-cycle leaves no cooldown cycle; set \
+                     exception_cooldown to false"
+                    .to_string());
+            }
+        }
+        if self.termination == Termination::Commits(0) {
+            return Err("termination commits must be at least 1".to_string());
+        }
+        // Dispatch needs room for a full decode group, or it stalls forever.
+        if self.physical_registers < NUM_ARCH_REGS + DECODE_WIDTH {
+            return Err(format!(
+                "physical_registers must be at least {}: the architectural registers plus {} free ones",
+                NUM_ARCH_REGS + DECODE_WIDTH,
+                DECODE_WIDTH
+            ));
+        }
+        if self.reclamation == Reclamation::NextWriterCommit {
+            if self.pipeline != Pipeline::OutOfOrder
+                || self.renaming != Renaming::MergedRegisterFile
+            {
+                return Err(
+                    "next-writer-commit reclamation requires the out-of-order pipeline with a \
+                     merged register file"
+                        .to_string(),
+                );
+            }
+            if self.physical_registers < 2 * NUM_ARCH_REGS + DECODE_WIDTH {
+                return Err(format!(
+                    "next-writer-commit reclamation needs at least {} physical_registers: \
+                     two per architectural register plus {} free ones",
+                    2 * NUM_ARCH_REGS + DECODE_WIDTH,
+                    DECODE_WIDTH
+                ));
+            }
+        }
+        if self.active_list_capacity < DECODE_WIDTH || self.integer_queue_capacity < DECODE_WIDTH {
+            return Err(format!(
+                "active_list_capacity and integer_queue_capacity must be at least {}",
+                DECODE_WIDTH
+            ));
+        }
+        if self.alu_capabilities.len() > self.num_alus {
+            return Err(format!(
+                "alu_capabilities has {} entries but there are only {} ALUs",
+                self.alu_capabilities.len(),
+                self.num_alus
+            ));
+        }
+        for (i, ops) in self.alu_capabilities.iter().enumerate() {
+            for op in ops {
+                if Opcode::capability(op).is_none() {
+                    return Err(format!(
+                        "alu_capabilities[{}]: unknown opcode or class '{}'",
+                        i, op
+                    ));
+                }
+            }
+        }
+        match self.latency.distribution {
+            LatencyDistribution::Fixed => {}
+            _ if self.pipeline != Pipeline::OutOfOrder => {
+                return Err("only the out-of-order pipeline draws random latencies".to_string());
+            }
+            LatencyDistribution::Uniform { min, max } if min > max => {
+                return Err(format!(
+                    "latency.distribution: uniform min {} is above max {}",
+                    min, max
+                ));
+            }
+            LatencyDistribution::Bimodal { slow_fraction, .. }
+                if !(0.0..=1.0).contains(&slow_fraction) =>
+            {
+                return Err(format!(
+                    "latency.distribution: bimodal slow_fraction must be between 0 and 1, got {}",
+                    slow_fraction
+                ));
+            }
+            _ => {}
+        }
+        if self.roi.is_enabled() && self.pipeline != Pipeline::OutOfOrder {
+            return Err("only the out-of-order pipeline supports a region of interest".to_string());
+        }
+        for (name, address) in [("begin", self.roi.begin), ("end", self.roi.end)] {
+            if address.is_some_and(|address| !address.is_multiple_of(self.pc_step())) {
+                return Err(format!(
+                    "roi.{} must be a multiple of 4 with byte_addressed_pc",
+                    name
+                ));
+            }
+        }
+        if let (Some(begin), Some(end)) = (self.roi.begin, self.roi.end)
+            && begin >= end
+        {
+            return Err(format!(
+                "roi.begin {} must come before roi.end {}",
+                begin, end
+            ));
+        }
+        for op in &self.latency.opcodes {
+            if Opcode::parse(op).is_none_or(Opcode::is_fp) {
+                return Err(format!("latency.opcodes: unknown opcode '{}'", op));
+            }
+        }
+        for op in OPCODES {
+            if !(0..self.num_alus).any(|i| self.alu_can_execute(i, *op)) {
+                return Err(format!("no ALU is capable of executing '{}'", op));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the pipeline can run `threads` hardware threads. Every
+    /// thread maps its architectural registers to physical registers of its
+    /// own, leaving the rest to the shared free list.
+    pub fn validate_threads(&self, threads: usize) -> Result<(), String> {
+        if threads == 1 {
+            return Ok(());
+        }
+        if self.pipeline != Pipeline::OutOfOrder
+            || self.renaming != Renaming::MergedRegisterFile
+            || self.fp.enabled
+        {
+            return Err(
+                "multiple threads require the out-of-order pipeline with merged register file renaming and without FP"
+                    .to_string(),
+            );
+        }
+        let per_thread = match self.reclamation {
+            Reclamation::Commit => NUM_ARCH_REGS,
+            Reclamation::NextWriterCommit => 2 * NUM_ARCH_REGS,
+        };
+        let needed = threads * per_thread + DECODE_WIDTH;
+        if self.physical_registers < needed {
+            return Err(format!(
+                "{} threads need at least {} physical_registers",
+                threads, needed
+            ));
+        }
+        Ok(())
+    }
+
+    /// Bytes between consecutive PCs.
+    pub fn pc_step(&self) -> u64 {
+        match self.byte_addressed_pc {
+            true => INSTRUCTION_BYTES,
+            false => 1,
+        }
+    }
+
+    /// Index of the first exception handler instruction.
+    pub fn vector_index(&self) -> u64 {
+        self.exception_vector / self.pc_step()
+    }
+
+    /// Returns true if ALU `alu` is allowed to execute `op`.
+    pub fn alu_can_execute(&self, alu: usize, op: Opcode) -> bool {
+        match self.alu_capabilities.get(alu) {
+            Some(ops) if !ops.is_empty() => ops
+                .iter()
+                .filter_map(|name| Opcode::capability(name))
+                .any(|ops| ops.contains(&op)),
+            _ => true,
+        }
+    }
+}
+
+/// Reference-compatibility modes. A mode pins every timing-ambiguous choice
+/// to the behavior of a reference implementation and rejects any config that
+/// would deviate from it, so logs match that reference byte for byte.
+///
+/// `cs470-2024` is the CS-470 homework 1 grader:
+/// - results of an ALU wake up dependents the cycle after they execute, and
+///   issue sees them in the same cycle as the write-back;
+/// - rollback pops up to four active list entries per cycle from the tail,
+///   then spends one cycle to clear the exception flag;
+/// - freed registers are appended to the free list, which is FIFO ordered;
+///   committed entries free their old destination in program order;
+/// - issue picks ready instructions lowest PC first, each taking the first
+///   free ALU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompatMode {
+    Cs470_2024,
+}
+
+impl std::str::FromStr for CompatMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cs470-2024" => Ok(CompatMode::Cs470_2024),
+            _ => Err(format!("unknown compatibility mode '{}'", s)),
+        }
+    }
+}
+
+/// Fields that cannot change the log and are accepted in every mode: the
+/// seed, and the energy and area models, which only `--stats` reports.
+const COMPAT_NEUTRAL_FIELDS: [&str; 3] = ["seed", "energy", "area"];
+
+impl Config {
+    /// Checks that this config reproduces the reference of `mode`, listing
+    /// every field that deviates if it does not.
+    pub fn check_compat(&self, mode: CompatMode) -> Result<(), String> {
+        let reference = match mode {
+            CompatMode::Cs470_2024 => Config::default(),
+        };
+        let ours = serde_json::to_value(self).unwrap();
+        let theirs = serde_json::to_value(reference).unwrap();
+        let deviating: Vec<&str> = ours
+            .as_object()
+            .unwrap()
+            .iter()
+            .filter(|(key, value)| {
+                !COMPAT_NEUTRAL_FIELDS.contains(&key.as_str()) && theirs.get(key) != Some(value)
+            })
+            .map(|(key, _)| key.as_str())
+            .collect();
+        if deviating.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "config deviates from the reference behavior in: {}",
+                deviating.join(", ")
+            ))
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, Write};
+
+use serde::Serialize;
+
+use crate::gdb;
+use crate::simulator::{Commit, Simulator};
+
+/// What one `advance` request simulated.
+#[derive(Serialize)]
+struct CycleReport {
+    #[serde(rename = "Cycle")]
+    cycle: usize,
+    /// The micro-ops committed in this cycle, oldest first.
+    #[serde(rename = "Commits")]
+    commits: Vec<Commit>,
+    /// The program finished or the run was stopped; further requests
+    /// simulate nothing.
+    #[serde(rename = "Done")]
+    done: bool,
+}
+
+/// Lock-step co-simulation: a testbench connected to `address` sends one
+/// request per line, and the run only advances when asked.
+///
+/// ```text
+/// advance [<cycles>]   one JSON report line per simulated cycle
+/// quit                 end the run where it is
+/// ```
+///
+/// Each report lists the committed micro-ops with the register each one
+/// wrote, so every architectural update can be compared with the design
+/// under test cycle by cycle. `advance` simulates and logs one cycle and
+/// returns whether the run must stop.
+pub fn serve(sim: &mut Simulator, address: &str, mut advance: impl FnMut(&mut Simulator) -> bool) {
+    let stream = gdb::accept(address, "co-simulation client");
+    sim.commit_log = Some(VecDeque::new());
+    if let Err(err) = session(sim, stream, &mut advance) {
+        status!("Co-simulation connection lost: {}", err);
+    }
+    sim.commit_log = None;
+}
+
+fn session(
+    sim: &mut Simulator,
+    stream: std::net::TcpStream,
+    advance: &mut impl FnMut(&mut Simulator) -> bool,
+) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let mut ended = sim.done();
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        let mut words = line.split_whitespace();
+        let cycles = match (words.next(), words.next()) {
+            (Some("advance"), None) => Ok(1),
+            (Some("advance"), Some(count)) => count
+                .parse::<usize>()
+                .map_err(|_| format!("invalid cycle count '{}'", count)),
+            (Some("quit"), None) => return Ok(()),
+            (None, _) => continue,
+            _ => Err(format!("unknown request '{}'", line.trim())),
+        };
+        let cycles = match cycles {
+            Ok(cycles) => cycles,
+            Err(err) => {
+                writeln!(writer, "{}", serde_json::json!({ "Error": err }))?;
+                continue;
+            }
+        };
+        for _ in 0..cycles {
+            if !ended {
+                ended = advance(sim) || sim.done();
+            }
+            let report = CycleReport {
+                cycle: sim.cycles(),
+                commits: sim.commit_log.as_mut().unwrap().drain(..).collect(),
+                done: ended,
+            };
+            writeln!(writer, "{}", serde_json::to_string(&report).unwrap())?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,149 @@
+use serde::{Deserialize, Serialize};
+
+use crate::asm;
+use crate::opcode::Opcode;
+use crate::simulator::Simulator;
+
+/// Cycles simulated so far; read-only.
+pub const CYCLE: u16 = 0xc00;
+/// Micro-ops committed so far, so a cracked `li` counts twice; read-only.
+pub const INSTRET: u16 = 0xc02;
+/// A register programs may use freely.
+pub const MSCRATCH: u16 = 0x340;
+
+const NAMES: &[(&str, u16)] = &[
+    ("cycle", CYCLE),
+    ("instret", INSTRET),
+    ("mscratch", MSCRATCH),
+];
+
+/// The CSR named `name`, by its name or its number such as `0xc00`.
+pub fn parse(name: &str) -> Option<u16> {
+    match NAMES.iter().find(|&&(n, _)| n == name) {
+        Some(&(_, csr)) => Some(csr),
+        None => asm::parse_immediate(name)
+            .and_then(|number| u16::try_from(number).ok())
+            .filter(|csr| NAMES.iter().any(|(_, c)| c == csr)),
+    }
+}
+
+/// Whether writing `csr` is illegal: as in RISC-V, its top two bits are set.
+pub fn is_read_only(csr: u16) -> bool {
+    csr >> 10 == 0b11
+}
+
+/// The value a CSR instruction executing `op` leaves in a CSR that held
+/// `old`, given the value `a` of its source register.
+pub fn updated(op: Opcode, old: u64, a: u64) -> u64 {
+    match op {
+        Opcode::Csrrw => a,
+        Opcode::Csrrs => old | a,
+        _ => old & !a,
+    }
+}
+
+/// The CSRs a program can write; the counters are read off the run.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Csrs {
+    mscratch: u64,
+}
+
+impl Simulator {
+    /// The value of `csr` seen by a CSR instruction issuing now, after
+    /// everything older has committed.
+    pub(crate) fn read_csr(&self, csr: u16) -> u64 {
+        match csr {
+            CYCLE => self.cycles() as u64,
+            INSTRET => self.committed,
+            _ => self.csrs.mscratch,
+        }
+    }
+
+    /// Writes a committed CSR instruction's update; the read-only counters
+    /// ignore it.
+    pub(crate) fn write_csr(&mut self, csr: u16, value: u64) {
+        if csr == MSCRATCH {
+            self.csrs.mscratch = value;
+        }
+    }
+
+    /// Whether micro-op `seq` heads the active list of its thread, so every
+    /// older micro-op of the thread has committed.
+    pub(crate) fn heads_active_list(&self, seq: u64) -> bool {
+        std::iter::once(&self.state.active_list)
+            .chain(self.state.threads.iter().map(|t| &t.active_list))
+            .any(|list| list.front().is_some_and(|e| e.seq == seq))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::SimulatorBuilder;
+
+    fn run(lines: &[&str]) -> Vec<u64> {
+        let mut sim = SimulatorBuilder::new()
+            .program(lines.iter().map(|line| line.to_string()).collect())
+            .build()
+            .unwrap();
+        while !sim.done() {
+            sim.simulate_cycle();
+        }
+        sim.architectural_registers()
+    }
+
+    #[test]
+    fn parses_names_and_numbers_of_known_csrs() {
+        assert_eq!(parse("cycle"), Some(CYCLE));
+        assert_eq!(parse("0xc02"), Some(INSTRET));
+        assert_eq!(parse("0x341"), None);
+        assert_eq!(parse("mepc"), None);
+        assert!(is_read_only(CYCLE) && is_read_only(INSTRET));
+        assert!(!is_read_only(MSCRATCH));
+    }
+
+    #[test]
+    fn writing_a_counter_does_not_build() {
+        for line in ["csrrw x1, cycle, x2", "csrrs x1, instret, x2"] {
+            let built = SimulatorBuilder::new()
+                .program(vec![line.to_string()])
+                .build();
+            assert!(built.is_err(), "{}", line);
+        }
+        assert!(
+            SimulatorBuilder::new()
+                .program(vec!["csrrs x1, cycle, x0".to_string()])
+                .build()
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn instret_counts_the_micro_ops_committed_before() {
+        let registers = run(&["addi x2, x0, 1", "li x3, 0x12345", "rdinstret x1"]);
+        assert_eq!(registers[1], 3);
+    }
+
+    #[test]
+    fn cycle_advances_between_reads() {
+        let registers = run(&["rdcycle x1", "addi x2, x0, 1", "rdcycle x3"]);
+        assert!(
+            registers[1] > 0 && registers[3] > registers[1],
+            "{:?}",
+            registers
+        );
+    }
+
+    #[test]
+    fn csr_instructions_return_the_old_value_and_update_mscratch() {
+        let registers = run(&[
+            "addi x1, x0, 5",
+            "csrrw x0, mscratch, x1",
+            "addi x2, x0, 4",
+            "csrrc x3, mscratch, x2",
+            "csrrs x4, mscratch, x2",
+            "csrr x5, mscratch",
+        ]);
+        assert_eq!(registers[3..6], [5, 1, 5]);
+    }
+}
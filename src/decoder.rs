@@ -0,0 +1,213 @@
+use crate::asm::{self, Format};
+use crate::csr;
+use crate::opcode::Opcode;
+use crate::simulator::DecodedInstructionEntry;
+
+/// Parses a hex-encoded 32-bit instruction word such as `0x00500093`.
+pub fn parse_word(text: &str) -> Option<u32> {
+    let digits = text.trim().strip_prefix("0x")?;
+    if digits.is_empty() || digits.len() > 8 {
+        return None;
+    }
+    u32::from_str_radix(digits, 16).ok()
+}
+
+/// Returns true if every instruction of a non-empty program is a hex-encoded
+/// machine-code word.
+pub fn is_machine_code(program: &[String]) -> bool {
+    !program.is_empty() && program.iter().all(|line| parse_word(line).is_some())
+}
+
+/// Decodes an RV32IM computational instruction word, `ecall`, `ebreak`, a
+/// CSR instruction with a register source or an illegal instruction into a
+/// micro-op. Returns `None` for encodings the simulator does not execute
+/// (loads, stores, branches, other system instructions).
+pub fn decode_word(pc: u64, word: u32) -> Option<DecodedInstructionEntry> {
+    let illegal = || DecodedInstructionEntry {
+        pc,
+        op: Opcode::Unimp,
+        is_imm: true,
+        dest: 0,
+        src1: None,
+        src2: None,
+        imm: 0,
+        seq: 0,
+    };
+    // The all-zero word is the canonical illegal instruction; it raises an
+    // exception when executed.
+    if word == 0 {
+        return Some(illegal());
+    }
+    let system = |op: Opcode, dest: u32, src1: Option<u32>, src2: u32| DecodedInstructionEntry {
+        pc,
+        op,
+        is_imm: false,
+        dest,
+        src1,
+        src2: Some(src2),
+        imm: 0,
+        seq: 0,
+    };
+    match word {
+        0x0000_0073 => return Some(system(Opcode::Ecall, 10, Some(17), 10)),
+        0x0010_0073 => return Some(system(Opcode::Ebreak, 0, None, 0)),
+        _ => {}
+    }
+    let rd = (word >> 7) & 0x1f;
+    let funct3 = (word >> 12) & 0x7;
+    let rs1 = (word >> 15) & 0x1f;
+    let rs2 = (word >> 20) & 0x1f;
+    let funct7 = word >> 25;
+    let imm_i = ((word as i32) >> 20) as i64;
+    let imm_u = ((word as i32) >> 12) as i64;
+
+    if word & 0x7f == 0x73 {
+        let op = match funct3 {
+            1 => Opcode::Csrrw,
+            2 => Opcode::Csrrs,
+            3 => Opcode::Csrrc,
+            _ => return None,
+        };
+        let number = (word >> 20) as u16;
+        csr::parse(&number.to_string())?;
+        // Writing a read-only CSR is illegal, as `csrrw x0, cycle, x0` is.
+        // `csrrs` and `csrrc` from x0 only read.
+        let writes = op == Opcode::Csrrw || rs1 != 0;
+        if writes && csr::is_read_only(number) {
+            return Some(illegal());
+        }
+        return Some(DecodedInstructionEntry {
+            pc,
+            op,
+            is_imm: true,
+            dest: rd,
+            src1: writes.then_some(rs1),
+            src2: None,
+            imm: number as u64,
+            seq: 0,
+        });
+    }
+
+    let (mnemonic, imm) = match word & 0x7f {
+        0x37 => ("lui", imm_u),
+        0x17 => ("auipc", imm_u),
+        0x13 => match (funct3, funct7 & !1) {
+            (0, _) => ("addi", imm_i),
+            (2, _) => ("slti", imm_i),
+            (3, _) => ("sltiu", imm_i),
+            (4, _) => ("xori", imm_i),
+            (6, _) => ("ori", imm_i),
+            (7, _) => ("andi", imm_i),
+            (1, 0x00) => ("slli", imm_i & 0x3f),
+            (5, 0x00) => ("srli", imm_i & 0x3f),
+            (5, 0x20) => ("srai", imm_i & 0x3f),
+            _ => return None,
+        },
+        0x33 => {
+            let mnemonic = match (funct7, funct3) {
+                (0x00, 0) => "add",
+                (0x20, 0) => "sub",
+                (0x00, 1) => "sll",
+                (0x00, 2) => "slt",
+                (0x00, 3) => "sltu",
+                (0x00, 4) => "xor",
+                (0x00, 5) => "srl",
+                (0x20, 5) => "sra",
+                (0x00, 6) => "or",
+                (0x00, 7) => "and",
+                (0x01, 0) => "mul",
+                (0x01, 4) => "div",
+                (0x01, 5) => "divu",
+                (0x01, 6) => "rem",
+                (0x01, 7) => "remu",
+                _ => return None,
+            };
+            (mnemonic, 0)
+        }
+        _ => return None,
+    };
+
+    let (op, format) = asm::lookup(mnemonic)?;
+    let (src1, src2) = match format {
+        Format::Register => (Some(rs1), Some(rs2)),
+        Format::Immediate => (Some(rs1), None),
+        Format::UpperImmediate => (None, None),
+        Format::FloatRegister | Format::Csr => return None,
+    };
+    Some(DecodedInstructionEntry {
+        pc,
+        op,
+        is_imm: format != Format::Register,
+        dest: rd,
+        src1,
+        src2,
+        imm: imm as u64,
+        seq: 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The decoded micro-op written as `op dest, src1, src2`, with `#`
+    /// before an immediate.
+    fn decoded(word: u32) -> Option<String> {
+        let entry = decode_word(0, word)?;
+        let register = |src: Option<u32>| src.map_or(String::new(), |r| format!("x{}", r));
+        let src2 = match entry.is_imm {
+            true => format!("#{}", entry.imm as i64),
+            false => register(entry.src2),
+        };
+        Some(format!(
+            "{} x{}, {}, {}",
+            entry.op,
+            entry.dest,
+            register(entry.src1),
+            src2
+        ))
+    }
+
+    #[test]
+    fn decodes_computational_instructions() {
+        assert_eq!(decoded(0x0050_0093).as_deref(), Some("add x1, x0, #5"));
+        assert_eq!(decoded(0xFFF0_0093).as_deref(), Some("add x1, x0, #-1"));
+        assert_eq!(decoded(0x0020_81B3).as_deref(), Some("add x3, x1, x2"));
+        assert_eq!(decoded(0x4020_8233).as_deref(), Some("sub x4, x1, x2"));
+        assert_eq!(decoded(0x0220_81B3).as_deref(), Some("mul x3, x1, x2"));
+        assert_eq!(decoded(0x0220_D1B3).as_deref(), Some("divu x3, x1, x2"));
+        assert_eq!(decoded(0x4031_5093).as_deref(), Some("sra x1, x2, #3"));
+        assert_eq!(decoded(0x1234_52B7).as_deref(), Some("lui x5, , #74565"));
+    }
+
+    #[test]
+    fn decodes_system_and_illegal_words() {
+        assert_eq!(decode_word(0, 0x0000_0073).unwrap().op, Opcode::Ecall);
+        assert_eq!(decode_word(0, 0x0010_0073).unwrap().op, Opcode::Ebreak);
+        assert_eq!(decode_word(0, 0).unwrap().op, Opcode::Unimp);
+    }
+
+    #[test]
+    fn leaves_memory_and_control_flow_undecoded() {
+        // `lw x1, 0(x0)`, `sw x1, 0(x0)` and `beq x0, x0, 0`.
+        for word in [0x0000_2083, 0x0010_2023, 0x0000_0063] {
+            assert!(decode_word(0, word).is_none(), "{:#010x}", word);
+        }
+    }
+
+    #[test]
+    fn parses_hex_words() {
+        assert_eq!(parse_word(" 0x00500093 "), Some(0x0050_0093));
+        assert_eq!(parse_word("0x"), None);
+        assert_eq!(parse_word("00500093"), None);
+        assert_eq!(parse_word("0x100000000"), None);
+        let program =
+            |lines: &[&str]| -> Vec<String> { lines.iter().map(|line| line.to_string()).collect() };
+        assert!(is_machine_code(&program(&["0x00500093", "0x0"])));
+        assert!(!is_machine_code(&program(&[
+            "0x00500093",
+            "addi x1, x0, 5"
+        ])));
+        assert!(!is_machine_code(&[]));
+    }
+}
@@ -0,0 +1,97 @@
+use serde_json::{Map, Value, json};
+
+use crate::cli::ExpandArgs;
+use crate::json_io::{load_log, save_log};
+
+/// Encodes a sequence of logged states as deltas: every `interval`-th state
+/// is written in full as `{"Keyframe": state}`, the others as
+/// `{"Delta": fields}` holding only the top-level fields that differ from
+/// the previous state, plus `"Removed": [names]` for fields that vanished.
+pub struct DeltaEncoder {
+    interval: usize,
+    previous: Option<Value>,
+    since_keyframe: usize,
+}
+
+impl DeltaEncoder {
+    pub fn new(interval: usize) -> DeltaEncoder {
+        DeltaEncoder {
+            interval,
+            previous: None,
+            since_keyframe: 0,
+        }
+    }
+
+    pub fn encode(&mut self, state: Value) -> Value {
+        let entry = match &self.previous {
+            Some(previous) if self.since_keyframe < self.interval => {
+                self.since_keyframe += 1;
+                delta(previous, &state)
+            }
+            _ => {
+                self.since_keyframe = 1;
+                json!({ "Keyframe": state })
+            }
+        };
+        self.previous = Some(state);
+        entry
+    }
+}
+
+fn delta(previous: &Value, state: &Value) -> Value {
+    let (Some(previous), Some(state)) = (previous.as_object(), state.as_object()) else {
+        return json!({ "Keyframe": state });
+    };
+    let changed: Map<String, Value> = state
+        .iter()
+        .filter(|(key, value)| previous.get(*key) != Some(value))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    let removed: Vec<&String> = previous
+        .keys()
+        .filter(|k| !state.contains_key(*k))
+        .collect();
+    let mut entry = json!({ "Delta": changed });
+    if !removed.is_empty() {
+        entry["Removed"] = json!(removed);
+    }
+    entry
+}
+
+/// Returns true if `log` was written by a `DeltaEncoder`.
+pub fn is_delta_log(log: &[Value]) -> bool {
+    log.first()
+        .is_some_and(|entry| entry.get("Keyframe").is_some())
+}
+
+/// Reconstructs the full states of a delta-encoded log.
+pub fn expand(entries: Vec<Value>) -> Result<Vec<Value>, String> {
+    let mut states: Vec<Value> = Vec::with_capacity(entries.len());
+    for (cycle, mut entry) in entries.into_iter().enumerate() {
+        let state = if let Some(keyframe) = entry.get_mut("Keyframe") {
+            keyframe.take()
+        } else if let Some(Value::Object(changed)) = entry.get_mut("Delta").map(Value::take) {
+            let Some(Value::Object(mut state)) = states.last().cloned() else {
+                return Err(format!("Entry {} is a delta without a keyframe", cycle));
+            };
+            state.extend(changed);
+            for removed in entry["Removed"].as_array().into_iter().flatten() {
+                state.remove(removed.as_str().unwrap_or_default());
+            }
+            Value::Object(state)
+        } else {
+            return Err(format!("Entry {} is neither a keyframe nor a delta", cycle));
+        };
+        states.push(state);
+    }
+    Ok(states)
+}
+
+/// Writes the full states of a delta-encoded log as a plain log.
+pub fn run_expand(args: &ExpandArgs) -> Result<(), String> {
+    // `load_log` already expands delta logs.
+    let states = load_log(&args.log_path);
+    save_log(&args.output_path, &states);
+    println!("Expanded {} states to {}", states.len(), args.output_path);
+    Ok(())
+}
@@ -0,0 +1,83 @@
+use std::ops::RangeInclusive;
+
+use crate::lifetime::Lifetime;
+
+const LEGEND: &str = "F fetch, D waiting to rename, R rename and dispatch, . queued, I issue, \
+                      E execute, - waiting to commit, C commit, X squashed";
+
+/// The stage `l` was in during `cycle`, if it was in flight.
+fn cell(l: &Lifetime, cycle: u64) -> Option<char> {
+    let end = [l.commit, l.squash].into_iter().flatten().min();
+    if cycle < l.fetch || end.is_some_and(|end| cycle > end) {
+        return None;
+    }
+    let before = |stage: Option<u64>| stage.is_none_or(|stage| cycle < stage);
+    Some(match cycle {
+        _ if Some(cycle) == l.squash => 'X',
+        _ if Some(cycle) == l.commit => 'C',
+        _ if cycle == l.fetch => 'F',
+        _ if before(l.dispatch) => 'D',
+        _ if Some(cycle) == l.dispatch => 'R',
+        _ if before(l.issue) && before(l.complete) => '.',
+        _ if Some(cycle) == l.issue => 'I',
+        _ if before(l.complete) || Some(cycle) == l.complete => 'E',
+        _ => '-',
+    })
+}
+
+/// Renders the micro-ops fetched from `pcs` as a grid with one row per
+/// micro-op in fetch order and one column per cycle, each cell holding the
+/// stage the micro-op was in. Columns span from the first fetch to the last
+/// commit or squash of the rows shown.
+pub fn render(lifetimes: &[Lifetime], pcs: &RangeInclusive<u64>) -> String {
+    let rows: Vec<&Lifetime> = lifetimes.iter().filter(|l| pcs.contains(&l.pc)).collect();
+    let Some(first) = rows.iter().map(|l| l.fetch).min() else {
+        return format!(
+            "No micro-op was fetched from PCs {}..{}\n",
+            pcs.start(),
+            pcs.end()
+        );
+    };
+    // A micro-op still in flight when the run stopped runs to the last
+    // cycle any row reached.
+    let last = rows
+        .iter()
+        .flat_map(|l| {
+            [
+                Some(l.fetch),
+                l.dispatch,
+                l.issue,
+                l.complete,
+                l.commit,
+                l.squash,
+            ]
+        })
+        .flatten()
+        .max()
+        .unwrap();
+    let width = last.to_string().len();
+    let pc_width = rows.iter().map(|l| l.pc.to_string().len()).max().unwrap();
+    let labels: Vec<String> = rows
+        .iter()
+        .map(|l| match l.thread {
+            0 => format!("{:>pc_width$} {}", l.pc, l.op),
+            thread => format!("{:>pc_width$} {} (thread {})", l.pc, l.op, thread),
+        })
+        .collect();
+    let label_width = labels.iter().map(String::len).max().unwrap();
+
+    let mut out = format!("{:label_width$}", "");
+    for cycle in first..=last {
+        out += &format!(" {:>width$}", cycle);
+    }
+    out += "\n";
+    for (l, label) in rows.iter().zip(&labels) {
+        let mut line = format!("{:label_width$}", label);
+        for cycle in first..=last {
+            line += &format!(" {:>width$}", cell(l, cycle).unwrap_or(' '));
+        }
+        out += line.trim_end();
+        out += "\n";
+    }
+    out + LEGEND + "\n"
+}
@@ -0,0 +1,98 @@
+use serde_json::Value;
+
+use crate::cli::DiffArgs;
+use crate::json_io::{load_log, logged_cycle, state_from_cycle};
+
+/// Compares a log against a golden reference and prints the fields that
+/// differ in the first diverging cycle. Fails if the logs differ.
+pub fn run(args: &DiffArgs) -> Result<(), String> {
+    let golden = load_log(&args.golden_path);
+    let mine = load_log(&args.log_path);
+    // Logs that start mid-run are compared from the first cycle both have.
+    let start = [&golden, &mine]
+        .into_iter()
+        .filter(|log| !log.is_empty())
+        .map(|log| logged_cycle(log, 0))
+        .max()
+        .unwrap_or(0);
+    let golden = &golden[state_from_cycle(&golden, start).unwrap_or(golden.len())..];
+    let mine = &mine[state_from_cycle(&mine, start).unwrap_or(mine.len())..];
+    let common = golden.len().min(mine.len());
+    let diverging: Vec<usize> = (0..common).filter(|&c| golden[c] != mine[c]).collect();
+
+    let Some(&first) = diverging.first() else {
+        if golden.len() == mine.len() {
+            println!("Logs are identical ({} cycles)", golden.len());
+            return Ok(());
+        }
+        return Err(format!(
+            "Logs agree for {} cycles, but the golden log has {} and this log {}",
+            common,
+            golden.len(),
+            mine.len()
+        ));
+    };
+
+    println!("First divergence at cycle {}:", logged_cycle(golden, first));
+    let mut lines = Vec::new();
+    differences("", &golden[first], &mine[first], &mut lines);
+    for (path, expected, actual) in lines {
+        println!("  {}: expected {}, got {}", path, expected, actual);
+    }
+    if golden.len() != mine.len() {
+        println!(
+            "The golden log has {} cycles, this log {}",
+            golden.len(),
+            mine.len()
+        );
+    }
+    Err(format!(
+        "{} of {} compared cycles differ",
+        diverging.len(),
+        common
+    ))
+}
+
+/// Collects the leaf fields that differ between `expected` and `actual` as
+/// (path, expected, actual), with paths written as in `inspect --query`.
+pub(crate) fn differences(
+    path: &str,
+    expected: &Value,
+    actual: &Value,
+    out: &mut Vec<(String, String, String)>,
+) {
+    if expected == actual {
+        return;
+    }
+    match (expected, actual) {
+        (Value::Object(e), Value::Object(a)) => {
+            let keys = e.keys().chain(a.keys().filter(|k| !e.contains_key(*k)));
+            for key in keys {
+                let child = match path.is_empty() {
+                    true => key.clone(),
+                    false => format!("{}.{}", path, key),
+                };
+                differences_at(&child, e.get(key), a.get(key), out);
+            }
+        }
+        (Value::Array(e), Value::Array(a)) => {
+            for i in 0..e.len().max(a.len()) {
+                differences_at(&format!("{}[{}]", path, i), e.get(i), a.get(i), out);
+            }
+        }
+        _ => out.push((path.to_string(), expected.to_string(), actual.to_string())),
+    }
+}
+
+fn differences_at(
+    path: &str,
+    expected: Option<&Value>,
+    actual: Option<&Value>,
+    out: &mut Vec<(String, String, String)>,
+) {
+    let show = |v: Option<&Value>| v.map_or("nothing".to_string(), Value::to_string);
+    match (expected, actual) {
+        (Some(e), Some(a)) => differences(path, e, a, out),
+        _ => out.push((path.to_string(), show(expected), show(actual))),
+    }
+}
@@ -0,0 +1,296 @@
+use std::fs::{self, File};
+use std::io::Read;
+use std::process;
+
+use crate::decoder;
+
+const ELF_MAGIC: &[u8; 4] = b"\x7fELF";
+const EM_RISCV: u16 = 243;
+const PT_LOAD: u32 = 1;
+const PF_X: u32 = 1;
+
+/// A loadable segment of an ELF file.
+pub struct Segment {
+    pub vaddr: u64,
+    pub bytes: Vec<u8>,
+    pub executable: bool,
+}
+
+/// The parts of a statically linked ELF the simulator cares about.
+pub struct ElfImage {
+    pub entry: u64,
+    pub segments: Vec<Segment>,
+}
+
+/// Returns true if `bytes` start with the ELF magic number.
+pub fn is_elf(bytes: &[u8]) -> bool {
+    bytes.starts_with(ELF_MAGIC)
+}
+
+/// Returns true if the file at `path` starts with the ELF magic number.
+pub fn is_elf_file(path: &str) -> bool {
+    let mut magic = [0; 4];
+    File::open(path)
+        .and_then(|mut f| f.read_exact(&mut magic))
+        .is_ok()
+        && is_elf(&magic)
+}
+
+/// Loads a RISC-V ELF file and returns its executable segment as hex-encoded
+/// machine-code words, one per instruction.
+pub fn load_program(input_path: &str) -> Vec<String> {
+    let bytes = fs::read(input_path).unwrap_or_else(|err| {
+        eprintln!("Failed to read input file: {}", err);
+        process::exit(1);
+    });
+    let image = parse(&bytes).unwrap_or_else(|err| {
+        eprintln!("Failed to parse ELF file: {}", err);
+        process::exit(1);
+    });
+    program_from_image(&image).unwrap_or_else(|err| {
+        eprintln!("Failed to load ELF program: {}", err);
+        process::exit(1);
+    })
+}
+
+fn program_from_image(image: &ElfImage) -> Result<Vec<String>, String> {
+    let mut text = image.segments.iter().filter(|s| s.executable);
+    let segment = text.next().ok_or("no executable segment")?;
+    if text.next().is_some() {
+        return Err("more than one executable segment".to_string());
+    }
+    if image.entry != segment.vaddr {
+        eprintln!(
+            "warning: entry point {:#x} is not the start of the text segment {:#x}; \
+             simulation starts at the segment start",
+            image.entry, segment.vaddr
+        );
+    }
+    let data_bytes: usize = image
+        .segments
+        .iter()
+        .filter(|s| !s.executable)
+        .map(|s| s.bytes.len())
+        .sum();
+    if data_bytes > 0 {
+        eprintln!(
+            "warning: ignoring {} bytes of data segments; the simulator has no data memory",
+            data_bytes
+        );
+    }
+
+    segment
+        .bytes
+        .chunks_exact(4)
+        .enumerate()
+        .map(|(i, word)| {
+            let word = u32::from_le_bytes(word.try_into().unwrap());
+            match decoder::decode_word(i as u64, word) {
+                Some(_) => Ok(format!("{:#010x}", word)),
+                None => Err(format!(
+                    "unsupported instruction {:#010x} at address {:#x}",
+                    word,
+                    segment.vaddr + 4 * i as u64
+                )),
+            }
+        })
+        .collect()
+}
+
+/// Parses the ELF header and program headers of a little-endian RISC-V
+/// executable, 32- or 64-bit.
+pub fn parse(bytes: &[u8]) -> Result<ElfImage, String> {
+    if !is_elf(bytes) {
+        return Err("not an ELF file".to_string());
+    }
+    let is_64 = match bytes.get(4) {
+        Some(1) => false,
+        Some(2) => true,
+        _ => return Err("unknown ELF class".to_string()),
+    };
+    if bytes.get(5) != Some(&1) {
+        return Err("only little-endian ELF files are supported".to_string());
+    }
+    let r = Reader { bytes };
+    if r.u16(18)? != EM_RISCV {
+        return Err("not a RISC-V ELF file".to_string());
+    }
+
+    let (entry, phoff, phentsize, phnum) = if is_64 {
+        (r.u64(24)?, r.u64(32)?, r.u16(54)?, r.u16(56)?)
+    } else {
+        (r.u32(24)? as u64, r.u32(28)? as u64, r.u16(42)?, r.u16(44)?)
+    };
+
+    let mut segments = Vec::new();
+    for i in 0..phnum as u64 {
+        let ph = i
+            .checked_mul(phentsize as u64)
+            .and_then(|at| at.checked_add(phoff))
+            .and_then(|at| usize::try_from(at).ok())
+            .ok_or_else(|| format!("program header {} extends past the end of the file", i))?;
+        let (p_type, flags, offset, vaddr, filesz) = if is_64 {
+            (
+                r.u32(ph)?,
+                r.u32(ph + 4)?,
+                r.u64(ph + 8)?,
+                r.u64(ph + 16)?,
+                r.u64(ph + 32)?,
+            )
+        } else {
+            (
+                r.u32(ph)?,
+                r.u32(ph + 24)?,
+                r.u32(ph + 4)? as u64,
+                r.u32(ph + 8)? as u64,
+                r.u32(ph + 16)? as u64,
+            )
+        };
+        if p_type != PT_LOAD || filesz == 0 {
+            continue;
+        }
+        let contents = offset
+            .checked_add(filesz)
+            .and_then(|end| Some(usize::try_from(offset).ok()?..usize::try_from(end).ok()?))
+            .and_then(|range| bytes.get(range))
+            .ok_or_else(|| format!("segment {} extends past the end of the file", i))?;
+        segments.push(Segment {
+            vaddr,
+            bytes: contents.to_vec(),
+            executable: flags & PF_X != 0,
+        });
+    }
+    Ok(ElfImage { entry, segments })
+}
+
+/// Bounds-checked little-endian field access.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn field<const N: usize>(&self, offset: usize) -> Result<[u8; N], String> {
+        offset
+            .checked_add(N)
+            .and_then(|end| self.bytes.get(offset..end))
+            .map(|b| b.try_into().unwrap())
+            .ok_or_else(|| format!("truncated ELF file at offset {:#x}", offset))
+    }
+    fn u16(&self, offset: usize) -> Result<u16, String> {
+        self.field(offset).map(u16::from_le_bytes)
+    }
+    fn u32(&self, offset: usize) -> Result<u32, String> {
+        self.field(offset).map(u32::from_le_bytes)
+    }
+    fn u64(&self, offset: usize) -> Result<u64, String> {
+        self.field(offset).map(u64::from_le_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `addi x1, x0, 5` and `add x3, x1, x2`.
+    const TEXT: [u32; 2] = [0x0050_0093, 0x0020_81B3];
+
+    /// A minimal executable with one loadable text segment at 0x1000.
+    fn executable(is_64: bool) -> Vec<u8> {
+        let (header, phentsize) = if is_64 { (64, 56) } else { (52, 32) };
+        let text_offset = header + phentsize;
+        let mut bytes = vec![0; text_offset];
+        bytes[..4].copy_from_slice(ELF_MAGIC);
+        bytes[4] = if is_64 { 2 } else { 1 };
+        bytes[5] = 1;
+        bytes[18..20].copy_from_slice(&EM_RISCV.to_le_bytes());
+        let ph = header;
+        if is_64 {
+            bytes[24..32].copy_from_slice(&0x1000u64.to_le_bytes());
+            bytes[32..40].copy_from_slice(&(ph as u64).to_le_bytes());
+            bytes[54..56].copy_from_slice(&(phentsize as u16).to_le_bytes());
+            bytes[56..58].copy_from_slice(&1u16.to_le_bytes());
+            bytes[ph..ph + 4].copy_from_slice(&PT_LOAD.to_le_bytes());
+            bytes[ph + 4..ph + 8].copy_from_slice(&(PF_X | 4).to_le_bytes());
+            bytes[ph + 8..ph + 16].copy_from_slice(&(text_offset as u64).to_le_bytes());
+            bytes[ph + 16..ph + 24].copy_from_slice(&0x1000u64.to_le_bytes());
+            bytes[ph + 32..ph + 40].copy_from_slice(&8u64.to_le_bytes());
+        } else {
+            bytes[24..28].copy_from_slice(&0x1000u32.to_le_bytes());
+            bytes[28..32].copy_from_slice(&(ph as u32).to_le_bytes());
+            bytes[42..44].copy_from_slice(&(phentsize as u16).to_le_bytes());
+            bytes[44..46].copy_from_slice(&1u16.to_le_bytes());
+            bytes[ph..ph + 4].copy_from_slice(&PT_LOAD.to_le_bytes());
+            bytes[ph + 4..ph + 8].copy_from_slice(&(text_offset as u32).to_le_bytes());
+            bytes[ph + 8..ph + 12].copy_from_slice(&0x1000u32.to_le_bytes());
+            bytes[ph + 16..ph + 20].copy_from_slice(&8u32.to_le_bytes());
+            bytes[ph + 24..ph + 28].copy_from_slice(&(PF_X | 4).to_le_bytes());
+        }
+        bytes.extend(TEXT.iter().flat_map(|word| word.to_le_bytes()));
+        bytes
+    }
+
+    fn error(bytes: &[u8]) -> String {
+        parse(bytes).err().expect("the file is rejected")
+    }
+
+    #[test]
+    fn loads_the_text_segment_of_32_and_64_bit_files() {
+        for is_64 in [false, true] {
+            let image = parse(&executable(is_64)).unwrap();
+            assert_eq!(image.entry, 0x1000);
+            assert_eq!(image.segments.len(), 1);
+            assert_eq!(image.segments[0].vaddr, 0x1000);
+            assert!(image.segments[0].executable);
+            assert_eq!(
+                program_from_image(&image).unwrap(),
+                ["0x00500093", "0x002081b3"]
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert_eq!(error(b"#!/bin/sh"), "not an ELF file");
+        let mut bytes = executable(false);
+        bytes[5] = 2;
+        assert_eq!(error(&bytes), "only little-endian ELF files are supported");
+        let mut bytes = executable(false);
+        bytes[18] = 62;
+        assert_eq!(error(&bytes), "not a RISC-V ELF file");
+        assert_eq!(
+            error(&executable(true)[..40]),
+            "truncated ELF file at offset 0x36"
+        );
+    }
+
+    #[test]
+    fn rejects_offsets_past_the_end_of_the_file() {
+        let mut bytes = executable(true);
+        bytes[32..40].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(
+            error(&bytes),
+            "truncated ELF file at offset 0xffffffffffffffff"
+        );
+
+        let mut bytes = executable(true);
+        bytes[64 + 8..64 + 16].copy_from_slice(&(u64::MAX - 4).to_le_bytes());
+        assert_eq!(error(&bytes), "segment 0 extends past the end of the file");
+
+        let mut bytes = executable(false);
+        bytes[52 + 16..52 + 20].copy_from_slice(&9u32.to_le_bytes());
+        assert_eq!(error(&bytes), "segment 0 extends past the end of the file");
+    }
+
+    #[test]
+    fn rejects_instructions_the_simulator_does_not_execute() {
+        let mut bytes = executable(false);
+        // `lw x1, 0(x0)`.
+        let at = bytes.len() - 4;
+        bytes[at..].copy_from_slice(&0x0000_2083u32.to_le_bytes());
+        let image = parse(&bytes).unwrap();
+        assert_eq!(
+            program_from_image(&image),
+            Err("unsupported instruction 0x00002083 at address 0x1004".to_string())
+        );
+    }
+}
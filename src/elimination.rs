@@ -0,0 +1,216 @@
+use serde::{Deserialize, Serialize};
+
+use crate::lifetime::Stage;
+use crate::opcode::Opcode;
+use crate::simulator::{self, ActiveEntry, DecodedInstructionEntry, Simulator};
+
+/// Move elimination: a register move is renamed by mapping its destination
+/// onto the physical register of its source, and completes without taking a
+/// queue entry or an ALU. A shared register is returned to the free list
+/// only once its last reference is released.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct MoveElimination {
+    enabled: bool,
+    /// References to each physical register beyond the one it was allocated
+    /// with, taken by eliminated moves.
+    shares: Vec<u32>,
+}
+
+impl MoveElimination {
+    pub fn new(enabled: bool, registers: usize) -> Self {
+        Self {
+            enabled,
+            shares: match enabled {
+                true => vec![0; registers],
+                false => Vec::new(),
+            },
+        }
+    }
+
+    /// References to physical register `reg` beyond its first.
+    pub fn shares(&self, reg: u32) -> u32 {
+        self.shares.get(reg as usize).copied().unwrap_or(0)
+    }
+}
+
+impl Simulator {
+    /// The architectural source `instr` copies if it is a move rename can
+    /// eliminate: an `add` of a register and zero, as `mv` decodes to.
+    pub(crate) fn move_source(&self, instr: &DecodedInstructionEntry) -> Option<usize> {
+        if !self.move_elimination.enabled || instr.op != Opcode::Add {
+            return None;
+        }
+        // A move to a hardwired x0 is discarded anyway.
+        if self.hardwired_zero && instr.dest == 0 {
+            return None;
+        }
+        let src2_is_zero = match instr.is_imm {
+            true => instr.imm == 0,
+            false => instr.src2.is_none(),
+        };
+        let src = match (instr.src1, src2_is_zero) {
+            (Some(src), true) => src,
+            (None, false) => instr.src2?,
+            _ => return None,
+        };
+        Some(src as usize)
+    }
+
+    /// Renames the move `instr` of architectural register `arch_src` onto
+    /// the physical register of its source and marks it done.
+    pub(crate) fn eliminate_move(&mut self, instr: &DecodedInstructionEntry, arch_src: usize) {
+        let arch_dest = instr.dest;
+        let phys = self.state.register_map_table[arch_src];
+        let old_phys_dest = self.state.register_map_table[arch_dest as usize];
+        self.state.register_map_table[arch_dest as usize] = phys;
+        self.move_elimination.shares[phys as usize] += 1;
+        self.stats.eliminated_moves += 1;
+        self.stats.accesses.map_table_reads += 2;
+        self.stats.accesses.map_table_writes += 1;
+        // A source still waiting on its producer is reported poisoned when
+        // the producer commits.
+        let poison = match self.state.busy_bit_table[phys as usize] {
+            true => None,
+            false => self.state.poisoned[phys as usize],
+        };
+        self.state.active_list.push_back(ActiveEntry {
+            done: true,
+            exception: false,
+            logical_destination: arch_dest,
+            old_destination: old_phys_dest,
+            destination: phys,
+            pc: instr.pc,
+            is_fp: false,
+            cause: None,
+            poison,
+            environment: None,
+            seq: instr.seq,
+            eliminated: true,
+        });
+        self.record_stage(instr.seq, Stage::Complete);
+    }
+
+    /// Drops a reference to physical register `reg`, returning it to the
+    /// free list with the last one.
+    pub(crate) fn release(&mut self, reg: u32) {
+        match self.move_elimination.shares.get_mut(reg as usize) {
+            Some(shares) if *shares > 0 => *shares -= 1,
+            _ => self.state.free_list.push_back(reg),
+        }
+    }
+
+    /// The result of `instr` if rename can produce it without executing:
+    /// zero for a subtraction, xor or comparison of a register with itself,
+    /// or the constant an `add`, `lui` or `li` builds from immediates alone.
+    pub(crate) fn idiom_value(&self, instr: &DecodedInstructionEntry) -> Option<u64> {
+        if !self.idiom_elimination || (self.hardwired_zero && instr.dest == 0) {
+            return None;
+        }
+        let same_sources = !instr.is_imm && instr.src1.is_some() && instr.src1 == instr.src2;
+        let immediates_only = instr.src1.is_none() && instr.src2.is_none();
+        match instr.op {
+            Opcode::Sub | Opcode::Xor | Opcode::Slt | Opcode::Sltu if same_sources => Some(0),
+            Opcode::Add | Opcode::Lui | Opcode::Li if immediates_only => {
+                let imm = match instr.is_imm {
+                    true => instr.imm,
+                    false => 0,
+                };
+                simulator::compute(instr.op, 0, imm, self.trap_on_overflow).ok()
+            }
+            _ => None,
+        }
+    }
+
+    /// Renames the idiom `instr` onto a new register holding its result
+    /// `value` and marks it done.
+    pub(crate) fn eliminate_idiom(&mut self, instr: &DecodedInstructionEntry, value: u64) {
+        let arch_dest = instr.dest;
+        let old_phys_dest = self.state.register_map_table[arch_dest as usize];
+        let new_phys_dest = simulator::take_free(
+            &mut self.state.free_list,
+            self.free_list_policy,
+            &mut self.rng,
+        );
+        self.state.register_map_table[arch_dest as usize] = new_phys_dest;
+        self.state.physical_register_file[new_phys_dest as usize] = value;
+        self.state.poisoned[new_phys_dest as usize] = None;
+        // Never predicted, but drops a prediction left on the register by
+        // a squashed producer.
+        self.predict_value(instr, new_phys_dest, true);
+        self.stats.eliminated_idioms += 1;
+        self.stats.accesses.map_table_reads += 1;
+        self.stats.accesses.map_table_writes += 1;
+        self.stats.accesses.register_writes += 1;
+        self.state.active_list.push_back(ActiveEntry {
+            done: true,
+            exception: false,
+            logical_destination: arch_dest,
+            old_destination: old_phys_dest,
+            destination: new_phys_dest,
+            pc: instr.pc,
+            is_fp: false,
+            cause: None,
+            poison: None,
+            environment: None,
+            seq: instr.seq,
+            eliminated: false,
+        });
+        self.record_stage(instr.seq, Stage::Complete);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::SimulatorBuilder;
+    use crate::config::Config;
+
+    fn simulator(lines: &[&str]) -> Simulator {
+        let config = Config {
+            move_elimination: true,
+            ..Config::default()
+        };
+        SimulatorBuilder::new()
+            .program(lines.iter().map(|line| line.to_string()).collect())
+            .config(&config)
+            .physical_registers(36)
+            .build()
+            .unwrap()
+    }
+
+    fn run(sim: &mut Simulator) {
+        while !sim.done() {
+            sim.simulate_cycle();
+        }
+    }
+
+    #[test]
+    fn a_move_shares_the_register_of_its_source() {
+        let mut sim = simulator(&["addi x1, x0, 5", "mv x2, x1", "addi x3, x1, 0"]);
+        sim.simulate_cycle();
+        sim.simulate_cycle();
+        let map = &sim.state.register_map_table;
+        assert_eq!((map[2], map[3]), (map[1], map[1]));
+        assert_eq!(sim.move_elimination.shares(map[1]), 2);
+        assert_eq!(sim.state.integer_queue.len(), 1);
+        run(&mut sim);
+        assert_eq!(sim.architectural_registers()[1..4], [5, 5, 5]);
+        assert_eq!(sim.stats.eliminated_moves, 2);
+    }
+
+    #[test]
+    fn a_shared_register_is_freed_with_its_last_reference() {
+        let mut lines = vec!["addi x1, x0, 5", "mv x2, x1", "addi x1, x0, 6"];
+        // Enough writes to recycle every other free register.
+        lines.extend(["addi x3, x3, 1"; 8]);
+        lines.extend(["addi x4, x2, 1", "addi x2, x0, 7"]);
+        let mut sim = simulator(&lines);
+        run(&mut sim);
+        assert_eq!(sim.architectural_registers()[1..5], [6, 7, 8, 6]);
+        let mut free: Vec<u32> = sim.state.free_list.iter().copied().collect();
+        free.sort();
+        free.dedup();
+        assert_eq!(free.len(), 4);
+        assert!((0..36).all(|reg| sim.move_elimination.shares(reg) == 0));
+    }
+}
@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::config::EnergyConfig;
+
+/// Accesses to the integer structures of the out-of-order pipeline.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Accesses {
+    /// Source operands read from the register file at rename.
+    pub register_reads: u64,
+    /// Results written back.
+    pub register_writes: u64,
+    /// Source and previous destination lookups at rename.
+    pub map_table_reads: u64,
+    /// New mappings at rename and mappings restored by a rollback.
+    pub map_table_writes: u64,
+    /// Entries dispatched into the integer queue.
+    pub queue_writes: u64,
+    /// Result tags compared against the waiting entries.
+    pub queue_wakeups: u64,
+    /// Entries selected and read out at issue.
+    pub queue_issues: u64,
+    /// Micro-ops sent to an ALU.
+    pub alu_operations: u64,
+}
+
+/// Energy of each structure in picojoules, from the access counts and the
+/// per-access energies.
+pub fn breakdown(accesses: &Accesses, config: &EnergyConfig) -> [(&'static str, f64); 4] {
+    let count = |n: u64, energy: f64| n as f64 * energy;
+    [
+        (
+            "PhysicalRegisterFile",
+            count(accesses.register_reads, config.register_read)
+                + count(accesses.register_writes, config.register_write),
+        ),
+        (
+            "MapTable",
+            count(accesses.map_table_reads, config.map_table_read)
+                + count(accesses.map_table_writes, config.map_table_write),
+        ),
+        (
+            "IntegerQueue",
+            count(accesses.queue_writes, config.queue_write)
+                + count(accesses.queue_wakeups, config.queue_wakeup)
+                + count(accesses.queue_issues, config.queue_issue),
+        ),
+        ("Alus", count(accesses.alu_operations, config.alu_operation)),
+    ]
+}
+
+/// Total and per-structure energy, in picojoules, and the energy per
+/// committed instruction.
+pub fn to_json(accesses: &Accesses, config: &EnergyConfig, committed: u64) -> Value {
+    let structures = breakdown(accesses, config);
+    let total: f64 = structures.iter().map(|(_, energy)| energy).sum();
+    let per_structure: serde_json::Map<String, Value> = structures
+        .iter()
+        .map(|&(name, energy)| (name.to_string(), json!(energy)))
+        .collect();
+    json!({
+        "Total": total,
+        "PerInstruction": total / committed.max(1) as f64,
+        "Structures": per_structure,
+    })
+}
@@ -0,0 +1,202 @@
+use std::collections::HashSet;
+use std::fs;
+
+use crate::batch::parallel_map;
+use crate::cli::{Cost, ExploreArgs};
+use crate::config::{Config, Pipeline};
+use crate::json_io::parse_config;
+use crate::rng::Rng;
+use crate::sweep::{self, Point, Space};
+use crate::{area, energy};
+
+/// IPC and cost of one simulated configuration.
+struct Outcome {
+    cycles: u64,
+    committed: u64,
+    ipc: f64,
+    /// Square micrometres.
+    area: f64,
+    /// Picojoules per committed instruction.
+    energy: f64,
+}
+
+impl Outcome {
+    /// Whether this configuration is at least as good as `other` in IPC and
+    /// every cost that counts, and better in one of them.
+    fn dominates(&self, other: &Outcome, cost: Option<Cost>) -> bool {
+        // Costs are negated so that higher is better throughout.
+        let mut pairs = vec![(self.ipc, other.ipc)];
+        if cost != Some(Cost::Energy) {
+            pairs.push((-self.area, -other.area));
+        }
+        if cost != Some(Cost::Area) {
+            pairs.push((-self.energy, -other.energy));
+        }
+        pairs.iter().all(|(mine, theirs)| mine >= theirs)
+            && pairs.iter().any(|(mine, theirs)| mine > theirs)
+    }
+}
+
+/// Simulates every configuration the parameters span, or a random sample of
+/// them, and reports the Pareto-optimal ones: those no other configuration
+/// beats in IPC without costing more area or energy.
+pub fn run(args: &ExploreArgs) -> Result<(), String> {
+    let base = match &args.config_path {
+        Some(path) => parse_config(path),
+        None => Config::default(),
+    };
+    let space = Space::new(&base, &args.params)?;
+    let program = crate::load_program(&args.input_path);
+    let points = match args.samples {
+        Some(samples) if samples < space.size() => sample(&space, samples, args.seed),
+        _ => space.grid(),
+    };
+    let results = parallel_map(&points, args.jobs, |point| evaluate(&program, point));
+
+    let front: Vec<usize> = (0..results.len())
+        .filter(|&i| {
+            results[i].as_ref().is_ok_and(|outcome| {
+                !results
+                    .iter()
+                    .flatten()
+                    .any(|other| other.dominates(outcome, args.cost))
+            })
+        })
+        .collect();
+    if front.is_empty() {
+        return Err(format!(
+            "All {} configurations failed, the first with: {}",
+            points.len(),
+            results[0].as_ref().err().unwrap()
+        ));
+    }
+    print_front(args, &points, &results, &front);
+    if let Some(path) = &args.output_path {
+        let csv = explore_csv(args, &points, &results, &front);
+        fs::write(path, csv).map_err(|err| format!("Failed to write {}: {}", path, err))?;
+        println!("Configurations saved to {}", path);
+    }
+    Ok(())
+}
+
+/// `samples` distinct configurations drawn uniformly from `space`, in grid
+/// order.
+fn sample(space: &Space, samples: usize, seed: u64) -> Vec<Point> {
+    let mut rng = Rng::new(seed);
+    let mut chosen = HashSet::new();
+    while chosen.len() < samples {
+        chosen.insert(rng.below(space.size()));
+    }
+    let mut chosen: Vec<usize> = chosen.into_iter().collect();
+    chosen.sort();
+    chosen.into_iter().map(|i| space.point(i)).collect()
+}
+
+fn evaluate(program: &[String], point: &Point) -> Result<Outcome, String> {
+    if let Ok(builder) = &point.builder
+        && builder.configuration().pipeline != Pipeline::OutOfOrder
+    {
+        return Err("the cost model covers only the out-of-order pipeline".to_string());
+    }
+    let stats = sweep::simulate(program, point)?;
+    let config = point.builder.as_ref().unwrap().configuration();
+    let energy: f64 = energy::breakdown(&stats.accesses, &config.energy)
+        .iter()
+        .map(|(_, energy)| energy)
+        .sum();
+    Ok(Outcome {
+        cycles: stats.cycles,
+        committed: stats.committed,
+        ipc: match stats.cycles {
+            0 => 0.0,
+            cycles => stats.committed as f64 / cycles as f64,
+        },
+        area: area::estimate(config).iter().map(|(_, area)| area).sum(),
+        energy: energy / stats.committed.max(1) as f64,
+    })
+}
+
+fn print_front(
+    args: &ExploreArgs,
+    points: &[Point],
+    results: &[Result<Outcome, String>],
+    front: &[usize],
+) {
+    let failed = results.iter().filter(|r| r.is_err()).count();
+    print!(
+        "Pareto front: {} of {} configurations",
+        front.len(),
+        points.len()
+    );
+    match failed {
+        0 => println!(),
+        _ => println!(" ({} failed)", failed),
+    }
+    let widths: Vec<usize> = args
+        .params
+        .iter()
+        .enumerate()
+        .map(|(i, (name, _))| {
+            front
+                .iter()
+                .map(|&p| points[p].values[i].len())
+                .max()
+                .unwrap()
+                .max(name.len())
+        })
+        .collect();
+    for ((name, _), width) in args.params.iter().zip(&widths) {
+        print!("{:<width$}  ", name);
+    }
+    println!("{:>6}  {:>12}  {:>10}", "IPC", "Area (um^2)", "pJ/instr");
+    let mut front = front.to_vec();
+    front.sort_by(|&a, &b| {
+        let (a, b) = (results[a].as_ref().unwrap(), results[b].as_ref().unwrap());
+        a.ipc.total_cmp(&b.ipc)
+    });
+    for p in front {
+        for (value, width) in points[p].values.iter().zip(&widths) {
+            print!("{:<width$}  ", value);
+        }
+        let outcome = results[p].as_ref().unwrap();
+        println!(
+            "{:>6.3}  {:>12.0}  {:>10.2}",
+            outcome.ipc, outcome.area, outcome.energy
+        );
+    }
+}
+
+fn explore_csv(
+    args: &ExploreArgs,
+    points: &[Point],
+    results: &[Result<Outcome, String>],
+    front: &[usize],
+) -> String {
+    let mut header: Vec<&str> = args.params.iter().map(|(name, _)| name.as_str()).collect();
+    header.extend([
+        "cycles",
+        "committed",
+        "ipc",
+        "area",
+        "energy_per_instruction",
+        "pareto",
+        "error",
+    ]);
+    let mut csv = header.join(",") + "\n";
+    for (i, (point, result)) in points.iter().zip(results).enumerate() {
+        csv += &(point.values.join(",") + ",");
+        csv += &match result {
+            Ok(outcome) => format!(
+                "{},{},{:.3},{:.0},{:.2},{},\n",
+                outcome.cycles,
+                outcome.committed,
+                outcome.ipc,
+                outcome.area,
+                outcome.energy,
+                front.contains(&i)
+            ),
+            Err(err) => format!(",,,,,,\"{}\"\n", err.replace('"', "\"\"")),
+        };
+    }
+    csv
+}
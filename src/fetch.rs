@@ -0,0 +1,84 @@
+use serde::{Deserialize, Serialize};
+
+use crate::checkpoint;
+use crate::json_io::serialize_decoded_pcs;
+use crate::shared::Shared;
+use crate::simulator::{DECODE_WIDTH, DecodedInstructionEntry, Simulator};
+
+/// Micro-ops fetched per cycle into the fetch buffer.
+pub const FETCH_WIDTH: usize = DECODE_WIDTH;
+
+/// The buffer between separate fetch and decode stages, logged when it is
+/// configured. Fetch fills it while rename stalls, and decode drains it
+/// into the decoded PCs.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct FetchBuffer {
+    #[serde(rename = "FetchBuffer", serialize_with = "serialize_decoded_pcs")]
+    pub entries: Shared<Vec<DecodedInstructionEntry>>,
+    #[serde(default, skip_serializing_if = "checkpoint::omit")]
+    pub capacity: usize,
+}
+
+impl FetchBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Shared::default(),
+            capacity,
+        }
+    }
+}
+
+impl Simulator {
+    /// Fetches up to `FETCH_WIDTH` micro-ops into the fetch buffer, whole
+    /// instructions only, while it has room.
+    pub(crate) fn fetch(&mut self) {
+        if self.state.exception {
+            return;
+        }
+        let mut fetched = 0;
+        loop {
+            let buffer = self.state.fetch_buffer.as_ref().unwrap();
+            let room = (buffer.capacity - buffer.entries.len()).min(FETCH_WIDTH - fetched);
+            let Some(micro_ops) = self.fetch_instruction(room) else {
+                break;
+            };
+            fetched += micro_ops.len();
+            let buffer = self.state.fetch_buffer.as_mut().unwrap();
+            buffer.entries.extend(micro_ops);
+        }
+    }
+
+    /// Moves whole instructions from the fetch buffer into the decoded PCs,
+    /// up to `DECODE_WIDTH` micro-ops, unless rename is stalled.
+    pub(crate) fn decode_fetched(&mut self) {
+        if self.fetch_blocked() || self.state.exception {
+            return;
+        }
+        let buffer = self.state.fetch_buffer.as_mut().unwrap();
+        let mut taken = 0;
+        while let Some(first) = buffer.entries.get(taken) {
+            // The micro-ops of an instruction are fetched together and share
+            // its PC.
+            let len = buffer.entries[taken..]
+                .iter()
+                .take_while(|m| m.pc == first.pc)
+                .count();
+            if self.state.decoded_pcs.len() + taken + len > DECODE_WIDTH {
+                break;
+            }
+            taken += len;
+        }
+        let decoded: Vec<_> = buffer.entries.drain(..taken).collect();
+        self.state.decoded_pcs.extend(decoded);
+    }
+
+    /// Micro-ops fetched and not yet renamed, oldest first.
+    pub(crate) fn front_end_micro_ops(&self) -> impl Iterator<Item = &DecodedInstructionEntry> {
+        let buffered = self
+            .state
+            .fetch_buffer
+            .iter()
+            .flat_map(|b| b.entries.iter());
+        self.state.decoded_pcs.iter().chain(buffered)
+    }
+}
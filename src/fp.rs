@@ -0,0 +1,261 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+use crate::checkpoint;
+use crate::config::FpConfig;
+use crate::lifetime::Stage;
+use crate::opcode::Opcode;
+use crate::shared::Shared;
+use crate::simulator::{ActiveEntry, DecodedInstructionEntry, Simulator, take_free};
+
+/// Number of architectural FP registers (`f0`..`f31`).
+pub const NUM_FP_ARCH_REGS: usize = 32;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FpQueueEntry {
+    #[serde(rename = "DestRegister")]
+    pub dest_register: u32,
+    #[serde(rename = "OpAIsReady")]
+    pub op_a_is_ready: bool,
+    #[serde(rename = "OpARegTag")]
+    pub op_a_reg_tag: u32,
+    #[serde(rename = "OpAValue", with = "checkpoint::float")]
+    pub op_a_value: f64,
+    #[serde(rename = "OpBIsReady")]
+    pub op_b_is_ready: bool,
+    #[serde(rename = "OpBRegTag")]
+    pub op_b_reg_tag: u32,
+    #[serde(rename = "OpBValue", with = "checkpoint::float")]
+    pub op_b_value: f64,
+    #[serde(rename = "OpCode")]
+    pub op_code: Opcode,
+    #[serde(rename = "PC")]
+    pub pc: u64,
+    #[serde(default, skip_serializing_if = "checkpoint::omit")]
+    pub seq: u64,
+}
+
+/// FP rename and issue structures, logged alongside their integer
+/// counterparts when the FP subsystem is enabled.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FpState {
+    #[serde(rename = "FpPhysicalRegisterFile", with = "checkpoint::floats")]
+    pub physical_register_file: Shared<Vec<f64>>,
+    #[serde(rename = "FpRegisterMapTable")]
+    pub register_map_table: Shared<Vec<u32>>,
+    #[serde(rename = "FpFreeList")]
+    pub free_list: Shared<VecDeque<u32>>,
+    #[serde(rename = "FpBusyBitTable")]
+    pub busy_bit_table: Shared<Vec<bool>>,
+    #[serde(rename = "FpQueue")]
+    pub queue: Shared<Vec<FpQueueEntry>>,
+}
+
+impl FpState {
+    pub fn new(config: &FpConfig) -> Self {
+        let size = config.physical_registers;
+        let mut physical_register_file = vec![0.0; size];
+        for (reg, &value) in config.initial_registers.iter().enumerate() {
+            physical_register_file[reg] = value;
+        }
+        Self {
+            physical_register_file: Shared::new(physical_register_file),
+            register_map_table: (0..NUM_FP_ARCH_REGS as u32).collect(),
+            free_list: (NUM_FP_ARCH_REGS as u32..size as u32).collect(),
+            busy_bit_table: Shared::new(vec![false; size]),
+            queue: Shared::default(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct FpResult {
+    pub dest: u32,
+    #[serde(with = "checkpoint::float")]
+    pub value: f64,
+    pub seq: u64,
+}
+
+/// An FP functional unit. Adds and multiplies are pipelined and accept one
+/// operation per cycle; a divide occupies the unit until it completes.
+#[derive(Serialize, Deserialize)]
+pub struct FpUnit {
+    in_flight: Vec<(u32, bool, FpResult)>,
+    accepted_this_cycle: bool,
+    pub forwarding: Vec<FpResult>,
+}
+
+impl FpUnit {
+    pub fn new() -> Self {
+        Self {
+            in_flight: Vec::new(),
+            accepted_this_cycle: false,
+            forwarding: Vec::new(),
+        }
+    }
+    pub fn can_accept(&self) -> bool {
+        !self.accepted_this_cycle && !self.in_flight.iter().any(|&(_, divide, _)| divide)
+    }
+    fn push(&mut self, instr: &FpQueueEntry, latency: u32) {
+        let (a, b) = (instr.op_a_value, instr.op_b_value);
+        let value = match instr.op_code {
+            Opcode::Fadd => a + b,
+            Opcode::Fsub => a - b,
+            Opcode::Fmul => a * b,
+            Opcode::Fdiv => a / b,
+            op => panic!("Undefined FP op: {}", op),
+        };
+        let result = FpResult {
+            dest: instr.dest_register,
+            value,
+            seq: instr.seq,
+        };
+        self.in_flight
+            .push((latency, instr.op_code == Opcode::Fdiv, result));
+        self.accepted_this_cycle = true;
+    }
+    fn execute(&mut self) {
+        self.accepted_this_cycle = false;
+        self.forwarding.clear();
+        for (remaining, _, _) in self.in_flight.iter_mut() {
+            *remaining -= 1;
+        }
+        let (done, pending) = std::mem::take(&mut self.in_flight)
+            .into_iter()
+            .partition(|&(remaining, _, _)| remaining == 0);
+        self.in_flight = pending;
+        self.forwarding = done.into_iter().map(|(_, _, result)| result).collect();
+    }
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+impl FpConfig {
+    fn latency(&self, op: Opcode) -> u32 {
+        match op {
+            Opcode::Fadd | Opcode::Fsub => self.add_latency,
+            Opcode::Fmul => self.mul_latency,
+            _ => self.div_latency,
+        }
+    }
+}
+
+impl Simulator {
+    /// Renames an FP micro-op into the FP map table and queue.
+    pub(crate) fn rename_fp(&mut self, instr: DecodedInstructionEntry) {
+        let fp = self
+            .state
+            .fp
+            .as_mut()
+            .expect("FP instruction with FP disabled");
+        let operand = |src: Option<u32>| {
+            let phys = fp.register_map_table[src.unwrap() as usize];
+            if fp.busy_bit_table[phys as usize] {
+                (false, phys, 0.0)
+            } else {
+                (true, 0, fp.physical_register_file[phys as usize])
+            }
+        };
+        let (op_a_is_ready, op_a_reg_tag, op_a_value) = operand(instr.src1);
+        let (op_b_is_ready, op_b_reg_tag, op_b_value) = operand(instr.src2);
+        let arch_dest = instr.dest;
+        let old_phys_dest = fp.register_map_table[arch_dest as usize];
+        let new_phys_dest = take_free(&mut fp.free_list, self.free_list_policy, &mut self.rng);
+        fp.register_map_table[arch_dest as usize] = new_phys_dest;
+        fp.busy_bit_table[new_phys_dest as usize] = true;
+        fp.queue.push(FpQueueEntry {
+            dest_register: new_phys_dest,
+            op_a_is_ready,
+            op_a_reg_tag,
+            op_a_value,
+            op_b_is_ready,
+            op_b_reg_tag,
+            op_b_value,
+            op_code: instr.op,
+            pc: instr.pc,
+            seq: instr.seq,
+        });
+        self.state.active_list.push_back(ActiveEntry {
+            done: false,
+            exception: false,
+            logical_destination: arch_dest,
+            old_destination: old_phys_dest,
+            destination: new_phys_dest,
+            pc: instr.pc,
+            is_fp: true,
+            cause: None,
+            poison: None,
+            environment: None,
+            seq: instr.seq,
+            eliminated: false,
+        });
+    }
+
+    /// Issues ready FP operations in issue-policy order, one per unit that
+    /// can accept.
+    pub(crate) fn issue_fp(&mut self) {
+        let Some(fp) = self.state.fp.as_ref() else {
+            return;
+        };
+        let mut ready: Vec<_> = fp
+            .queue
+            .iter()
+            .filter(|i| i.op_a_is_ready && i.op_b_is_ready)
+            .cloned()
+            .collect();
+        self.order_ready(&mut ready, |i| i.pc);
+        let mut issued = Vec::new();
+        for instr in ready {
+            if let Some(unit) = self.fp_units.iter_mut().find(|u| u.can_accept()) {
+                unit.push(&instr, self.fp_config.latency(instr.op_code));
+                issued.push(instr.seq);
+            }
+        }
+        for &seq in &issued {
+            self.record_stage(seq, Stage::Issue);
+        }
+        let fp = self.state.fp.as_mut().unwrap();
+        fp.queue.retain(|i| !issued.contains(&i.seq));
+    }
+
+    /// Advances the FP units and broadcasts completed results.
+    pub(crate) fn execute_fp(&mut self) {
+        let Some(fp) = self.state.fp.as_mut() else {
+            return;
+        };
+        for unit in self.fp_units.iter_mut() {
+            unit.execute();
+        }
+        let mut completed = Vec::new();
+        for result in self.fp_units.iter().flat_map(|u| u.forwarding.iter()) {
+            if let Some(entry) = self
+                .state
+                .active_list
+                .iter_mut()
+                .find(|e| e.seq == result.seq)
+            {
+                completed.push(result.seq);
+                entry.done = true;
+            }
+            let reg = result.dest;
+            fp.physical_register_file[reg as usize] = result.value;
+            fp.busy_bit_table[reg as usize] = false;
+            for entry in fp.queue.iter_mut() {
+                if !entry.op_a_is_ready && entry.op_a_reg_tag == reg {
+                    entry.op_a_is_ready = true;
+                    entry.op_a_value = result.value;
+                    entry.op_a_reg_tag = 0;
+                }
+                if !entry.op_b_is_ready && entry.op_b_reg_tag == reg {
+                    entry.op_b_is_ready = true;
+                    entry.op_b_value = result.value;
+                    entry.op_b_reg_tag = 0;
+                }
+            }
+        }
+        for seq in completed {
+            self.record_stage(seq, Stage::Complete);
+        }
+    }
+}
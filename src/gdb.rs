@@ -0,0 +1,362 @@
+use std::collections::{BTreeSet, VecDeque};
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process;
+
+use crate::asm;
+// gdb addresses are byte addresses, simulator PCs instruction indices.
+use crate::config::INSTRUCTION_BYTES;
+use crate::decoder;
+use crate::simulator::{NUM_ARCH_REGS, Simulator};
+use crate::syscall::Halt;
+
+/// The view of an attached gdb. The debugger applies the simulator's
+/// `commit_log` one instruction at a time to its own copy of the
+/// architectural registers, so it can stop between any two instructions,
+/// also between two committed in the same cycle, without changing the
+/// timing of the run. The commits still in the log lie past the
+/// instruction it is stopped at.
+#[derive(Default)]
+pub struct Debugger {
+    /// PCs of the instructions gdb stops before.
+    breakpoints: BTreeSet<u64>,
+    /// Integer registers at the instruction the debugger is stopped at.
+    registers: Vec<u64>,
+}
+
+impl Simulator {
+    /// The committed value of every integer register: the renamings of the
+    /// micro-ops still in the active list are undone, youngest first.
+    pub(crate) fn architectural_registers(&self) -> Vec<u64> {
+        let state = &self.state;
+        if state.rob.is_some() {
+            return state.physical_register_file[..NUM_ARCH_REGS].to_vec();
+        }
+        let mut map = state.register_map_table.clone();
+        for entry in state.active_list.iter().rev().filter(|e| !e.is_fp) {
+            map[entry.logical_destination as usize] = entry.old_destination;
+        }
+        map.iter()
+            .map(|&tag| state.physical_register_file[tag as usize])
+            .collect()
+    }
+
+    /// The encoded instruction word at `pc`, if the program is machine code.
+    fn instruction_word(&self, pc: u64) -> Option<u32> {
+        let machine_code = match pc >= self.exception_vector {
+            true => self.handler_machine_code,
+            false => self.machine_code,
+        };
+        self.instruction(pc)
+            .filter(|_| machine_code)
+            .and_then(|line| decoder::parse_word(line))
+    }
+}
+
+/// Serves the GDB remote serial protocol on `address`, letting gdb read registers and instruction memory, step,
+/// continue and set breakpoints. `advance` simulates and logs one cycle and
+/// returns whether the run must stop. A detaching gdb lets the run finish;
+/// a killing one ends it where it is.
+pub fn serve(sim: &mut Simulator, address: &str, mut advance: impl FnMut(&mut Simulator) -> bool) {
+    let stream = accept(address, "gdb");
+    sim.debugger = Some(Debugger {
+        registers: sim.architectural_registers(),
+        ..Debugger::default()
+    });
+    sim.commit_log = Some(VecDeque::new());
+    let mut session = Session {
+        stream,
+        ended: false,
+    };
+    let detach = match session.serve(sim, &mut advance) {
+        Ok(detach) => detach,
+        Err(err) => {
+            status!("gdb connection lost: {}", err);
+            true
+        }
+    };
+    sim.debugger = None;
+    sim.commit_log = None;
+    if detach && !session.ended {
+        while !sim.done() && !advance(sim) {}
+    }
+}
+
+/// Waits for one `client` to connect on `address`; `:<port>` listens on
+/// localhost.
+pub fn accept(address: &str, client: &str) -> TcpStream {
+    let address = match address.starts_with(':') {
+        true => format!("127.0.0.1{}", address),
+        false => address.to_string(),
+    };
+    let listener = TcpListener::bind(&address).unwrap_or_else(|err| {
+        eprintln!("Failed to listen for {} on {}: {}", client, address, err);
+        process::exit(1);
+    });
+    status!("Waiting for {} on {}", client, address);
+    let (stream, peer) = listener.accept().unwrap_or_else(|err| {
+        eprintln!("Failed to accept {} connection: {}", client, err);
+        process::exit(1);
+    });
+    status!("{} attached from {}", client, peer);
+    stream
+}
+
+struct Session {
+    stream: TcpStream,
+    /// The run finished or was stopped; nothing more can execute.
+    ended: bool,
+}
+
+impl Session {
+    /// Answers packets until gdb detaches (true) or kills the run (false).
+    fn serve(
+        &mut self,
+        sim: &mut Simulator,
+        advance: &mut impl FnMut(&mut Simulator) -> bool,
+    ) -> io::Result<bool> {
+        loop {
+            let Some(packet) = self.receive()? else {
+                return Ok(true);
+            };
+            let reply = match packet.as_str() {
+                "?" => "S05".to_string(),
+                "g" => {
+                    let debugger = sim.debugger.as_ref().unwrap();
+                    let pc = stopped_pc(sim) * INSTRUCTION_BYTES;
+                    debugger
+                        .registers
+                        .iter()
+                        .chain([&pc])
+                        .map(|&value| hex_u64(value))
+                        .collect()
+                }
+                "s" | "c" => self.resume(sim, advance, packet == "s")?,
+                "D" => {
+                    self.send("OK")?;
+                    return Ok(true);
+                }
+                "k" => return Ok(false),
+                "qAttached" => "1".to_string(),
+                "qC" => "QC1".to_string(),
+                "qfThreadInfo" => "m1".to_string(),
+                "qsThreadInfo" => "l".to_string(),
+                _ if packet.starts_with("qSupported") => {
+                    "PacketSize=1000;qXfer:features:read+".to_string()
+                }
+                _ if packet.starts_with("qXfer:features:read:target.xml:") => {
+                    read_target_xml(&packet["qXfer:features:read:target.xml:".len()..])
+                }
+                _ if packet.starts_with('H') || packet.starts_with('T') => "OK".to_string(),
+                _ if packet.starts_with('p') => read_register(sim, &packet[1..]),
+                _ if packet.starts_with('m') => read_memory(sim, &packet[1..]),
+                _ if packet.starts_with("Z0,") || packet.starts_with("Z1,") => {
+                    set_breakpoint(sim, &packet[3..], true)
+                }
+                _ if packet.starts_with("z0,") || packet.starts_with("z1,") => {
+                    set_breakpoint(sim, &packet[3..], false)
+                }
+                // An empty reply tells gdb the packet is not supported.
+                _ => String::new(),
+            };
+            self.send(&reply)?;
+        }
+    }
+
+    /// Runs until the next instruction (`step`), a breakpoint, a Ctrl-C
+    /// from gdb or the end of the run, and reports why it stopped. The
+    /// instruction stopped at executes first, even with a breakpoint on it.
+    fn resume(
+        &mut self,
+        sim: &mut Simulator,
+        advance: &mut impl FnMut(&mut Simulator) -> bool,
+        step: bool,
+    ) -> io::Result<String> {
+        let mut current = None;
+        loop {
+            let debugger = sim.debugger.as_mut().unwrap();
+            let commits = sim.commit_log.as_mut().unwrap();
+            while let Some(commit) = commits.front() {
+                let next = current.is_some_and(|pc| pc != commit.pc);
+                if next && (step || debugger.breakpoints.contains(&commit.pc)) {
+                    return Ok("S05".to_string());
+                }
+                current = Some(commit.pc);
+                if let Some((register, value)) = commit.write {
+                    debugger.registers[register] = value;
+                }
+                commits.pop_front();
+            }
+            if self.ended {
+                break;
+            }
+            if sim.done() || advance(sim) {
+                self.ended = true;
+            } else if !step && self.interrupted()? {
+                return Ok("S02".to_string());
+            }
+        }
+        Ok(match &sim.halt {
+            Some(Halt::Exit(code)) => format!("W{:02x}", code & 0xff),
+            Some(Halt::Break(_)) => "X05".to_string(),
+            None if sim.done() => "W00".to_string(),
+            // Stopped by the watchdog, a failed check or a hook.
+            None => "X06".to_string(),
+        })
+    }
+
+    /// Whether gdb sent Ctrl-C, without waiting for input.
+    fn interrupted(&mut self) -> io::Result<bool> {
+        let mut byte = [0u8];
+        self.stream.set_nonblocking(true)?;
+        let read = self.stream.read(&mut byte);
+        self.stream.set_nonblocking(false)?;
+        match read {
+            Ok(0) => Err(ErrorKind::UnexpectedEof.into()),
+            Ok(_) => Ok(byte[0] == 0x03),
+            Err(err) if err.kind() == ErrorKind::WouldBlock => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Reads the next `$<data>#<checksum>` packet and acknowledges it, or
+    /// returns `None` once gdb closes the connection.
+    fn receive(&mut self) -> io::Result<Option<String>> {
+        let mut byte = [0u8];
+        loop {
+            // Skip acknowledgements and stray Ctrl-Cs between packets.
+            loop {
+                if self.stream.read(&mut byte)? == 0 {
+                    return Ok(None);
+                }
+                if byte[0] == b'$' {
+                    break;
+                }
+            }
+            let mut data = Vec::new();
+            loop {
+                if self.stream.read(&mut byte)? == 0 {
+                    return Ok(None);
+                }
+                if byte[0] == b'#' {
+                    break;
+                }
+                data.push(byte[0]);
+            }
+            let mut checksum = [0u8; 2];
+            self.stream.read_exact(&mut checksum)?;
+            let expected = std::str::from_utf8(&checksum)
+                .ok()
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok());
+            if expected == Some(checksum_of(&data)) {
+                self.stream.write_all(b"+")?;
+                return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+            }
+            self.stream.write_all(b"-")?;
+        }
+    }
+
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        let packet = format!("${}#{:02x}", data, checksum_of(data.as_bytes()));
+        self.stream.write_all(packet.as_bytes())
+    }
+}
+
+/// PC of the instruction the debugger is stopped at.
+fn stopped_pc(sim: &Simulator) -> u64 {
+    match sim.commit_log.as_ref().unwrap().front() {
+        Some(commit) => commit.pc,
+        None => sim.next_commit_pc(),
+    }
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte))
+}
+
+/// `value` in target byte order (little endian) as hex digits.
+fn hex_u64(value: u64) -> String {
+    value
+        .to_le_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Parses the `<addr>,<length>` argument of memory and breakpoint packets.
+fn address_and_length(arguments: &str) -> Option<(u64, u64)> {
+    let mut fields = arguments.split([',', ';']);
+    let address = u64::from_str_radix(fields.next()?, 16).ok()?;
+    let length = u64::from_str_radix(fields.next()?, 16).ok()?;
+    Some((address, length))
+}
+
+/// Register `n` in gdb's numbering: x0..x31, then the PC.
+fn read_register(sim: &Simulator, arguments: &str) -> String {
+    match usize::from_str_radix(arguments, 16) {
+        Ok(n) if n < NUM_ARCH_REGS => hex_u64(sim.debugger.as_ref().unwrap().registers[n]),
+        Ok(n) if n == NUM_ARCH_REGS => hex_u64(stopped_pc(sim) * INSTRUCTION_BYTES),
+        _ => "E01".to_string(),
+    }
+}
+
+/// Instruction memory: the words of a machine-code program or handler.
+/// Assembly programs have no encoding to read.
+fn read_memory(sim: &Simulator, arguments: &str) -> String {
+    let Some((address, length)) = address_and_length(arguments) else {
+        return "E01".to_string();
+    };
+    let mut bytes = String::new();
+    for address in address..address.saturating_add(length) {
+        let pc = address / INSTRUCTION_BYTES;
+        let Some(word) = sim.instruction_word(pc) else {
+            break;
+        };
+        let byte = word.to_le_bytes()[(address % INSTRUCTION_BYTES) as usize];
+        bytes.push_str(&format!("{:02x}", byte));
+    }
+    match bytes.is_empty() {
+        true => "E14".to_string(),
+        false => bytes,
+    }
+}
+
+fn set_breakpoint(sim: &mut Simulator, arguments: &str, insert: bool) -> String {
+    let Some((address, _)) = address_and_length(arguments) else {
+        return "E01".to_string();
+    };
+    if address % INSTRUCTION_BYTES != 0 {
+        return "E22".to_string();
+    }
+    let breakpoints = &mut sim.debugger.as_mut().unwrap().breakpoints;
+    let pc = address / INSTRUCTION_BYTES;
+    match insert {
+        true => breakpoints.insert(pc),
+        false => breakpoints.remove(&pc),
+    };
+    "OK".to_string()
+}
+
+/// A chunk of the target description declaring the RV64 integer registers.
+fn read_target_xml(arguments: &str) -> String {
+    let Some((offset, length)) = address_and_length(arguments) else {
+        return "E01".to_string();
+    };
+    let registers: String = asm::ABI_NAMES
+        .iter()
+        .map(|name| format!("<reg name=\"{}\" bitsize=\"64\" type=\"int\"/>", name))
+        .collect();
+    let xml = format!(
+        "<?xml version=\"1.0\"?><!DOCTYPE target SYSTEM \"gdb-target.dtd\">\
+         <target><architecture>riscv:rv64</architecture>\
+         <feature name=\"org.gnu.gdb.riscv.cpu\">{}\
+         <reg name=\"pc\" bitsize=\"64\" type=\"code_ptr\"/></feature></target>",
+        registers
+    );
+    let start = (offset as usize).min(xml.len());
+    let end = start.saturating_add(length as usize).min(xml.len());
+    match end == xml.len() {
+        true => format!("l{}", &xml[start..end]),
+        false => format!("m{}", &xml[start..end]),
+    }
+}
@@ -0,0 +1,114 @@
+use crate::asm::{self, Format};
+use crate::cli::GenArgs;
+use crate::config::Config;
+use crate::json_io::save_log;
+use crate::opcode::Opcode;
+use crate::rng::Rng;
+use crate::simulator;
+use crate::warnings::UPPER_IMMEDIATE_BITS;
+
+/// Integer mnemonics the generator draws from, register and immediate forms
+/// alike.
+const MNEMONICS: &[&str] = &[
+    "add", "addi", "sub", "mulu", "divu", "remu", "mul", "div", "rem", "slt", "slti", "sltu",
+    "sltiu", "and", "andi", "or", "ori", "xor", "xori", "sll", "slli", "srl", "srli", "sra",
+    "srai", "lui", "auipc",
+];
+
+/// Recent destinations a source operand is drawn from half of the time, so
+/// results are consumed while still in flight.
+const RECENT: usize = 4;
+
+/// Generates a random, well-formed program and prints it or saves it.
+pub fn run(args: &GenArgs) -> Result<(), String> {
+    let limit = Config::default().vector_index() as usize;
+    if args.instructions > limit {
+        return Err(format!(
+            "--instructions {} does not fit below the exception vector; at most {} instructions \
+             can be generated",
+            args.instructions, limit
+        ));
+    }
+    let program = generate(args);
+    match &args.output_path {
+        Some(path) => {
+            save_log(path, &program);
+            println!("{} instructions saved to {}", program.len(), path);
+        }
+        None => println!("{}", serde_json::to_string_pretty(&program).unwrap()),
+    }
+    Ok(())
+}
+
+/// Draws `args.instructions` instructions over registers `x0` up to
+/// `args.registers`. Register values are tracked as the program would
+/// compute them, so divisions get a zero divisor with probability
+/// `args.div_zero` and a non-zero one otherwise.
+pub fn generate(args: &GenArgs) -> Vec<String> {
+    let mut rng = Rng::new(args.seed);
+    let immediate_bits = Config::default().immediate_bits;
+    let immediate_range = 1i64 << (immediate_bits - 1);
+    // Known value of each register, `None` once it depends on the PC.
+    let mut values: Vec<Option<u64>> = vec![Some(0); args.registers];
+    let mut recent: Vec<usize> = Vec::new();
+    let mut program = Vec::with_capacity(args.instructions);
+
+    while program.len() < args.instructions {
+        let mnemonic = MNEMONICS[rng.below(MNEMONICS.len())];
+        let (op, format) = asm::lookup(mnemonic).unwrap();
+        let source = |rng: &mut Rng| match recent.is_empty() || rng.below(2) == 0 {
+            true => rng.below(args.registers),
+            false => recent[rng.below(recent.len())],
+        };
+        let rd = rng.below(args.registers);
+        let rs1 = source(&mut rng);
+        let (text, value) = match format {
+            Format::Register => {
+                let rs2 = if matches!(op, Opcode::Divu | Opcode::Remu | Opcode::Div | Opcode::Rem) {
+                    let zero = (rng.next_u64() as f64 / u64::MAX as f64) < args.div_zero;
+                    let divisors: Vec<usize> = (0..args.registers)
+                        .filter(|&r| values[r].is_some_and(|v| (v == 0) == zero))
+                        .collect();
+                    if divisors.is_empty() {
+                        continue;
+                    }
+                    divisors[rng.below(divisors.len())]
+                } else {
+                    source(&mut rng)
+                };
+                let value = values[rs1]
+                    .zip(values[rs2])
+                    .and_then(|(a, b)| simulator::compute(op, a, b, false).ok());
+                (format!("{} x{}, x{}, x{}", mnemonic, rd, rs1, rs2), value)
+            }
+            Format::Immediate => {
+                let imm = match op {
+                    Opcode::Sll | Opcode::Srl | Opcode::Sra => rng.below(64) as i64,
+                    _ => rng.below(2 * immediate_range as usize) as i64 - immediate_range,
+                };
+                let value =
+                    values[rs1].and_then(|a| simulator::compute(op, a, imm as u64, false).ok());
+                (format!("{} x{}, x{}, {}", mnemonic, rd, rs1, imm), value)
+            }
+            Format::UpperImmediate => {
+                let range = 1i64 << (UPPER_IMMEDIATE_BITS - 1);
+                let imm = (rng.below(2 * range as usize) as i64 - range) as u64;
+                let value = match op {
+                    Opcode::Lui => simulator::compute(op, 0, imm, false).ok(),
+                    _ => None,
+                };
+                (format!("{} x{}, {}", mnemonic, rd, imm as i64), value)
+            }
+            Format::FloatRegister | Format::Csr => {
+                unreachable!("the generator draws integer mnemonics")
+            }
+        };
+        values[rd] = value;
+        recent.push(rd);
+        if recent.len() > RECENT {
+            recent.remove(0);
+        }
+        program.push(text);
+    }
+    program
+}
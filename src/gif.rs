@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+
+/// Bits per pixel of every frame; the palette holds `1 << COLOR_BITS` colors.
+const COLOR_BITS: u8 = 3;
+const MAX_CODE: u16 = 4096;
+
+/// A looping animated GIF with a fixed palette and full-size frames.
+pub struct Gif {
+    width: u16,
+    height: u16,
+    bytes: Vec<u8>,
+}
+
+impl Gif {
+    pub fn new(width: u16, height: u16, palette: &[[u8; 3]; 1 << COLOR_BITS]) -> Gif {
+        let mut bytes = b"GIF89a".to_vec();
+        bytes.extend(width.to_le_bytes());
+        bytes.extend(height.to_le_bytes());
+        // Global color table present, 8-bit color resolution.
+        bytes.extend([0xF0 | (COLOR_BITS - 1), 0, 0]);
+        bytes.extend(palette.iter().flatten());
+        // NETSCAPE2.0 extension: loop forever.
+        bytes.extend([0x21, 0xFF, 0x0B]);
+        bytes.extend(b"NETSCAPE2.0");
+        bytes.extend([0x03, 0x01, 0x00, 0x00, 0x00]);
+        Gif {
+            width,
+            height,
+            bytes,
+        }
+    }
+
+    /// Appends a frame of palette indices in row-major order, shown for
+    /// `delay` hundredths of a second.
+    pub fn add_frame(&mut self, pixels: &[u8], delay: u16) {
+        assert_eq!(pixels.len(), self.width as usize * self.height as usize);
+        self.bytes.extend([0x21, 0xF9, 0x04, 0x00]);
+        self.bytes.extend(delay.to_le_bytes());
+        self.bytes.extend([0x00, 0x00]);
+        self.bytes.push(0x2C);
+        self.bytes.extend([0, 0, 0, 0]);
+        self.bytes.extend(self.width.to_le_bytes());
+        self.bytes.extend(self.height.to_le_bytes());
+        self.bytes.push(0x00);
+        self.bytes.push(COLOR_BITS);
+        for block in lzw_encode(pixels).chunks(255) {
+            self.bytes.push(block.len() as u8);
+            self.bytes.extend(block);
+        }
+        self.bytes.push(0x00);
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        self.bytes.push(0x3B);
+        self.bytes
+    }
+}
+
+/// Packs variable-width codes least significant bit first.
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, width: u32) {
+        self.buffer |= (code as u32) << self.bits;
+        self.bits += width;
+        while self.bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+}
+
+/// GIF-flavored LZW compression of palette indices.
+fn lzw_encode(pixels: &[u8]) -> Vec<u8> {
+    let clear = 1u16 << COLOR_BITS;
+    let end = clear + 1;
+    let initial_width = COLOR_BITS as u32 + 1;
+    let mut out = BitWriter {
+        bytes: Vec::new(),
+        buffer: 0,
+        bits: 0,
+    };
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next_code = end + 1;
+    let mut width = initial_width;
+
+    out.write(clear, width);
+    let Some((&first, rest)) = pixels.split_first() else {
+        out.write(end, width);
+        return out.bytes;
+    };
+    let mut prefix = first as u16;
+    for &pixel in rest {
+        if let Some(&code) = table.get(&(prefix, pixel)) {
+            prefix = code;
+            continue;
+        }
+        out.write(prefix, width);
+        if next_code >= 1 << width && width < 12 {
+            width += 1;
+        }
+        if next_code < MAX_CODE {
+            table.insert((prefix, pixel), next_code);
+            next_code += 1;
+        } else {
+            out.write(clear, width);
+            table.clear();
+            next_code = end + 1;
+            width = initial_width;
+        }
+        prefix = pixel as u16;
+    }
+    out.write(prefix, width);
+    if next_code >= 1 << width && width < 12 {
+        width += 1;
+    }
+    out.write(end, width);
+    if out.bits > 0 {
+        out.bytes.push(out.buffer as u8);
+    }
+    out.bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PALETTE: [[u8; 3]; 8] = [[0; 3]; 8];
+
+    /// Decompresses GIF-flavored LZW codes back into palette indices.
+    fn lzw_decode(bytes: &[u8]) -> Vec<u8> {
+        let clear = 1usize << COLOR_BITS;
+        let end = clear + 1;
+        let initial: Vec<Vec<u8>> = (0..=end).map(|i| vec![i as u8]).collect();
+        let mut table = initial.clone();
+        let mut width = COLOR_BITS as usize + 1;
+        let mut previous: Option<Vec<u8>> = None;
+        let mut out = Vec::new();
+        let mut at = 0;
+        loop {
+            let code = (0..width).fold(0, |code, i| {
+                let bit = (bytes[(at + i) / 8] >> ((at + i) % 8)) & 1;
+                code | (bit as usize) << i
+            });
+            at += width;
+            if code == clear {
+                table = initial.clone();
+                width = COLOR_BITS as usize + 1;
+                previous = None;
+                continue;
+            }
+            if code == end {
+                return out;
+            }
+            let entry = match (table.get(code), &previous) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(previous)) => [previous.as_slice(), &previous[..1]].concat(),
+                (None, None) => panic!("code {} before any entry", code),
+            };
+            out.extend(&entry);
+            if let Some(previous) = previous
+                && table.len() < MAX_CODE as usize
+            {
+                table.push([previous.as_slice(), &entry[..1]].concat());
+            }
+            if table.len() == 1 << width && width < 12 {
+                width += 1;
+            }
+            previous = Some(entry);
+        }
+    }
+
+    #[test]
+    fn single_pixel_is_clear_pixel_end() {
+        assert_eq!(lzw_encode(&[0]), [0x08, 0x09]);
+        assert_eq!(lzw_encode(&[]), [0x98]);
+    }
+
+    #[test]
+    fn round_trips_pixels_across_a_table_reset() {
+        let mut seed = 1u32;
+        let pixels: Vec<u8> = (0..20_000)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (seed >> 16) as u8 % 8
+            })
+            .collect();
+        assert_eq!(lzw_decode(&lzw_encode(&pixels)), pixels);
+
+        let runs: Vec<u8> = (0..5000).map(|i| (i / 37 % 8) as u8).collect();
+        assert_eq!(lzw_decode(&lzw_encode(&runs)), runs);
+    }
+
+    #[test]
+    fn frames_are_framed_as_gif89a_blocks() {
+        let mut gif = Gif::new(2, 1, &PALETTE);
+        gif.add_frame(&[1, 2], 10);
+        let bytes = gif.finish();
+        assert_eq!(&bytes[..13], b"GIF89a\x02\x00\x01\x00\xF2\x00\x00");
+        let frame = 13 + 3 * 8 + 19;
+        assert_eq!(
+            &bytes[frame..frame + 8],
+            [0x21, 0xF9, 0x04, 0x00, 10, 0, 0, 0]
+        );
+        assert_eq!(bytes[frame + 8], 0x2C);
+        let data = frame + 8 + 10;
+        assert_eq!(bytes[data], COLOR_BITS);
+        let len = bytes[data + 1] as usize;
+        assert_eq!(lzw_decode(&bytes[data + 2..data + 2 + len]), [1, 2]);
+        assert_eq!(&bytes[data + 2 + len..], [0x00, 0x3B]);
+    }
+}
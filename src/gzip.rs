@@ -0,0 +1,388 @@
+use std::io::{self, Write};
+
+/// Distance limit of deflate back-references.
+const WINDOW: usize = 32 * 1024;
+/// Input compressed per deflate block.
+const BLOCK: usize = 256 * 1024;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+/// Candidates tried per match search; bounds the time spent on long chains.
+const MAX_CHAIN: usize = 64;
+const HASH_BITS: u32 = 15;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u32; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+
+/// A streaming gzip compressor: LZ77 over a 32 KiB window with the fixed
+/// deflate Huffman codes, which suit the repetitive text of cycle logs well
+/// enough without per-block code tables.
+pub struct GzipEncoder<W: Write> {
+    inner: W,
+    /// The last `history` bytes already compressed, followed by the input not
+    /// yet compressed.
+    pending: Vec<u8>,
+    history: usize,
+    bits: BitWriter,
+    crc: u32,
+    size: u32,
+}
+
+impl<W: Write> GzipEncoder<W> {
+    pub fn new(mut inner: W) -> io::Result<GzipEncoder<W>> {
+        // Magic, deflate, no flags, no mtime, no extra flags, unknown OS.
+        inner.write_all(&[0x1F, 0x8B, 8, 0, 0, 0, 0, 0, 0, 255])?;
+        Ok(GzipEncoder {
+            inner,
+            pending: Vec::new(),
+            history: 0,
+            bits: BitWriter::default(),
+            crc: !0,
+            size: 0,
+        })
+    }
+
+    /// Compresses the remaining input and writes the gzip trailer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.compress_block(true)?;
+        self.bits.align();
+        self.inner.write_all(&self.bits.bytes)?;
+        self.inner.write_all(&(!self.crc).to_le_bytes())?;
+        self.inner.write_all(&self.size.to_le_bytes())?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    fn compress_block(&mut self, last: bool) -> io::Result<()> {
+        let data = &self.pending;
+        let n = data.len();
+        let mut chains = Chains::new(n);
+        for i in 0..self.history {
+            chains.insert(data, i);
+        }
+
+        // Fixed Huffman block.
+        self.bits.write(last as u32, 1);
+        self.bits.write(1, 2);
+        let mut i = self.history;
+        while i < n {
+            let (length, distance) = chains.longest_match(data, i);
+            if length >= MIN_MATCH {
+                write_length(&mut self.bits, length);
+                write_distance(&mut self.bits, distance);
+                for j in i..i + length {
+                    chains.insert(data, j);
+                }
+                i += length;
+            } else {
+                write_literal(&mut self.bits, data[i] as u16);
+                chains.insert(data, i);
+                i += 1;
+            }
+        }
+        write_literal(&mut self.bits, 256);
+
+        self.inner.write_all(&self.bits.bytes)?;
+        self.bits.bytes.clear();
+        self.pending.drain(..n.saturating_sub(WINDOW));
+        self.history = self.pending.len();
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for GzipEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for &byte in buf {
+            self.crc = CRC_TABLE[((self.crc ^ byte as u32) & 0xFF) as usize] ^ (self.crc >> 8);
+        }
+        self.size = self.size.wrapping_add(buf.len() as u32);
+        self.pending.extend_from_slice(buf);
+        if self.pending.len() - self.history >= BLOCK {
+            self.compress_block(false)?;
+        }
+        Ok(buf.len())
+    }
+
+    /// Flushes the compressed blocks written so far; input still buffered for
+    /// the next block stays buffered.
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Earlier positions of every 3-byte prefix, most recent first.
+struct Chains {
+    head: Vec<usize>,
+    prev: Vec<usize>,
+}
+
+impl Chains {
+    fn new(len: usize) -> Chains {
+        Chains {
+            head: vec![usize::MAX; 1 << HASH_BITS],
+            prev: vec![usize::MAX; len],
+        }
+    }
+
+    fn hash(data: &[u8], i: usize) -> usize {
+        let h = (data[i] as usize) << 10 ^ (data[i + 1] as usize) << 5 ^ data[i + 2] as usize;
+        h & ((1 << HASH_BITS) - 1)
+    }
+
+    fn insert(&mut self, data: &[u8], i: usize) {
+        if i + MIN_MATCH <= data.len() {
+            let h = Chains::hash(data, i);
+            self.prev[i] = self.head[h];
+            self.head[h] = i;
+        }
+    }
+
+    /// The longest earlier occurrence of the bytes at `i`, as (length,
+    /// distance).
+    fn longest_match(&self, data: &[u8], i: usize) -> (usize, usize) {
+        if i + MIN_MATCH > data.len() {
+            return (0, 0);
+        }
+        let limit = MAX_MATCH.min(data.len() - i);
+        let (mut best, mut distance) = (0, 0);
+        let mut candidate = self.head[Chains::hash(data, i)];
+        for _ in 0..MAX_CHAIN {
+            if candidate == usize::MAX || i - candidate > WINDOW {
+                break;
+            }
+            let length = (0..limit)
+                .take_while(|&k| data[candidate + k] == data[i + k])
+                .count();
+            if length > best {
+                (best, distance) = (length, i - candidate);
+                if length == limit {
+                    break;
+                }
+            }
+            candidate = self.prev[candidate];
+        }
+        (best, distance)
+    }
+}
+
+fn write_literal(bits: &mut BitWriter, symbol: u16) {
+    let (code, width) = match symbol {
+        0..=143 => (0x30 + symbol, 8),
+        144..=255 => (0x190 + symbol - 144, 9),
+        256..=279 => (symbol - 256, 7),
+        _ => (0xC0 + symbol - 280, 8),
+    };
+    bits.write_code(code, width);
+}
+
+fn write_length(bits: &mut BitWriter, length: usize) {
+    let index = LENGTH_BASE
+        .iter()
+        .rposition(|&base| base as usize <= length)
+        .unwrap();
+    write_literal(bits, 257 + index as u16);
+    bits.write(
+        (length - LENGTH_BASE[index] as usize) as u32,
+        LENGTH_EXTRA[index],
+    );
+}
+
+fn write_distance(bits: &mut BitWriter, distance: usize) {
+    let index = DISTANCE_BASE
+        .iter()
+        .rposition(|&base| base as usize <= distance)
+        .unwrap();
+    bits.write_code(index as u16, 5);
+    let extra = if index < 4 { 0 } else { index as u32 / 2 - 1 };
+    bits.write((distance - DISTANCE_BASE[index] as usize) as u32, extra);
+}
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 {
+                0xEDB8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+}
+
+/// Packs values least significant bit first, as deflate stores everything
+/// but Huffman codes.
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u64,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, width: u32) {
+        self.buffer |= (value as u64) << self.bits;
+        self.bits += width;
+        while self.bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    /// Writes a Huffman code, which deflate stores most significant bit
+    /// first.
+    fn write_code(&mut self, code: u16, width: u32) {
+        let reversed = code.reverse_bits() >> (16 - width);
+        self.write(reversed as u32, width);
+    }
+
+    /// Pads the last partial byte with zero bits.
+    fn align(&mut self) {
+        if self.bits > 0 {
+            self.write(0, 8 - self.bits);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compress(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzipEncoder::new(Vec::new()).unwrap();
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    /// Reads the bits of a deflate stream least significant bit first.
+    struct BitReader<'a> {
+        bytes: &'a [u8],
+        at: usize,
+    }
+
+    impl BitReader<'_> {
+        fn bit(&mut self) -> u32 {
+            let bit = (self.bytes[self.at / 8] >> (self.at % 8)) & 1;
+            self.at += 1;
+            bit as u32
+        }
+
+        fn read(&mut self, width: u32) -> u32 {
+            (0..width).fold(0, |value, i| value | self.bit() << i)
+        }
+
+        /// Decodes a literal/length symbol of the fixed Huffman code.
+        fn symbol(&mut self) -> u16 {
+            let mut code = 0;
+            for width in 1..=9 {
+                code = code << 1 | self.bit() as u16;
+                match (width, code) {
+                    (7, 0..=0x17) => return 256 + code,
+                    (8, 0x30..=0xBF) => return code - 0x30,
+                    (8, 0xC0..=0xC7) => return 280 + code - 0xC0,
+                    (9, 0x190..=0x1FF) => return 144 + code - 0x190,
+                    _ => {}
+                }
+            }
+            panic!("invalid fixed Huffman code");
+        }
+    }
+
+    /// Inflates a gzip stream made of fixed Huffman blocks and checks its
+    /// trailer.
+    fn decompress(gzip: &[u8]) -> Vec<u8> {
+        assert_eq!(&gzip[..3], &[0x1F, 0x8B, 8]);
+        let mut bits = BitReader {
+            bytes: &gzip[10..],
+            at: 0,
+        };
+        let mut out: Vec<u8> = Vec::new();
+        loop {
+            let last = bits.read(1) == 1;
+            assert_eq!(bits.read(2), 1, "only fixed Huffman blocks are written");
+            loop {
+                let symbol = bits.symbol();
+                match symbol {
+                    0..=255 => out.push(symbol as u8),
+                    256 => break,
+                    _ => {
+                        let index = (symbol - 257) as usize;
+                        let length =
+                            LENGTH_BASE[index] as usize + bits.read(LENGTH_EXTRA[index]) as usize;
+                        let code = (0..5).fold(0, |code, _| code << 1 | bits.bit()) as usize;
+                        let extra = if code < 4 { 0 } else { code as u32 / 2 - 1 };
+                        let distance = DISTANCE_BASE[code] as usize + bits.read(extra) as usize;
+                        for _ in 0..length {
+                            out.push(out[out.len() - distance]);
+                        }
+                    }
+                }
+            }
+            if last {
+                break;
+            }
+        }
+        let trailer = &bits.bytes[bits.at.div_ceil(8)..];
+        assert_eq!(trailer.len(), 8);
+        let mut crc = !0u32;
+        for &byte in &out {
+            crc = CRC_TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+        }
+        assert_eq!(trailer[..4], (!crc).to_le_bytes());
+        assert_eq!(trailer[4..], (out.len() as u32).to_le_bytes());
+        out
+    }
+
+    #[test]
+    fn empty_input_is_a_single_empty_block() {
+        assert_eq!(
+            compress(b""),
+            [
+                0x1F, 0x8B, 8, 0, 0, 0, 0, 0, 0, 255, 0x03, 0x00, 0, 0, 0, 0, 0, 0, 0, 0
+            ]
+        );
+    }
+
+    #[test]
+    fn trailer_holds_the_crc32_and_size() {
+        let gzip = compress(b"123456789");
+        let trailer = &gzip[gzip.len() - 8..];
+        assert_eq!(trailer[..4], 0xCBF4_3926u32.to_le_bytes());
+        assert_eq!(trailer[4..], 9u32.to_le_bytes());
+    }
+
+    #[test]
+    fn round_trips_text_over_several_blocks() {
+        let text: String = (0..20_000)
+            .map(|cycle| format!("{{\"PC\": {}, \"Exception\": false}},\n", cycle % 97))
+            .collect();
+        assert!(text.len() > 2 * BLOCK);
+        let gzip = compress(text.as_bytes());
+        assert!(gzip.len() < text.len() / 10);
+        assert_eq!(decompress(&gzip), text.as_bytes());
+    }
+
+    #[test]
+    fn round_trips_bytes_without_repeats() {
+        let data: Vec<u8> = (0..=255).chain((0..=255).rev()).collect();
+        assert_eq!(decompress(&compress(&data)), data);
+    }
+}
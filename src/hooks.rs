@@ -0,0 +1,165 @@
+use serde_json::Value;
+use std::fs;
+
+use crate::inspect::Predicate;
+
+/// Per-cycle hooks read from a small line-oriented script:
+///
+/// ```text
+/// # comment
+/// count <name> when <predicate>
+/// stop when <predicate>
+/// ```
+///
+/// Predicates use the `inspect --when` syntax and see each state as it would
+/// be logged. A counter counts the cycles its predicate holds; the first
+/// holding stop condition ends the simulation after that cycle.
+pub struct Hooks {
+    counters: Vec<(String, Predicate, u64)>,
+    stops: Vec<(String, Predicate)>,
+    previous: Option<Value>,
+    /// Text of the stop condition that ended the run, if any.
+    pub stopped_by: Option<String>,
+}
+
+impl Hooks {
+    pub fn load(path: &str) -> Result<Hooks, String> {
+        let script = fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read hooks file: {}", err))?;
+        Hooks::parse(path, &script)
+    }
+
+    /// Parses the hooks `script` read from `path`, which its diagnostics
+    /// name.
+    fn parse(path: &str, script: &str) -> Result<Hooks, String> {
+        let mut hooks = Hooks {
+            counters: Vec::new(),
+            stops: Vec::new(),
+            previous: None,
+            stopped_by: None,
+        };
+        for (number, line) in script.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let malformed = || format!("{}:{}: cannot parse hook '{}'", path, number + 1, line);
+            let predicate = |condition: &str| {
+                Predicate::parse(condition)
+                    .map_err(|err| format!("{}:{}: {} in hook '{}'", path, number + 1, err, line))
+            };
+            if let Some(rest) = line.strip_prefix("count ") {
+                let (name, condition) = rest.split_once(" when ").ok_or_else(malformed)?;
+                if name.trim().is_empty() {
+                    return Err(malformed());
+                }
+                hooks
+                    .counters
+                    .push((name.trim().to_string(), predicate(condition)?, 0));
+            } else if let Some(condition) = line.strip_prefix("stop when ") {
+                let stop = predicate(condition)?;
+                hooks.stops.push((condition.trim().to_string(), stop));
+            } else {
+                return Err(malformed());
+            }
+        }
+        Ok(hooks)
+    }
+
+    /// Evaluates every hook against the newest state. Returns true if a stop
+    /// condition holds.
+    pub fn after_cycle(&mut self, state: Value) -> bool {
+        let window = match self.previous.take() {
+            Some(previous) => vec![previous, state],
+            None => vec![state],
+        };
+        let cycle = window.len() - 1;
+        for (_, predicate, count) in self.counters.iter_mut() {
+            if predicate.holds(&window, cycle) {
+                *count += 1;
+            }
+        }
+        let stop = self
+            .stops
+            .iter()
+            .find(|(_, predicate)| predicate.holds(&window, cycle));
+        if let Some((text, _)) = stop {
+            self.stopped_by = Some(text.clone());
+        }
+        self.previous = window.into_iter().next_back();
+        self.stopped_by.is_some()
+    }
+
+    pub fn report(&self) {
+        for (name, _, count) in &self.counters {
+            status!("  {}: {}", name, count);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn hooks(script: &str) -> Hooks {
+        Hooks::parse("hooks.txt", script).unwrap()
+    }
+
+    fn error(script: &str) -> String {
+        Hooks::parse("hooks.txt", script).err().unwrap()
+    }
+
+    #[test]
+    fn parses_counters_and_stops() {
+        let hooks = hooks("# comment\n\ncount full when ActiveList[31]\nstop when PC >= 8\n");
+        assert_eq!(hooks.counters.len(), 1);
+        assert_eq!(hooks.counters[0].0, "full");
+        assert_eq!(hooks.stops[0].0, "PC >= 8");
+    }
+
+    #[test]
+    fn reports_malformed_hooks_with_their_line() {
+        assert_eq!(
+            error("count full\n"),
+            "hooks.txt:1: cannot parse hook 'count full'"
+        );
+        assert_eq!(
+            error("\ncount  when PC\n"),
+            "hooks.txt:2: cannot parse hook 'count  when PC'"
+        );
+        assert_eq!(
+            error("stop when PC ==\n"),
+            "hooks.txt:1: 'PC ==' has no value to compare with in hook 'stop when PC =='"
+        );
+        assert_eq!(
+            error("stop when == 3"),
+            "hooks.txt:1: '== 3' has no field to test in hook 'stop when == 3'"
+        );
+        assert_eq!(
+            error("count c when  "),
+            "hooks.txt:1: cannot parse hook 'count c when'"
+        );
+        assert_eq!(Predicate::parse("  ").err().unwrap(), "empty condition");
+    }
+
+    #[test]
+    fn counters_count_the_cycles_their_predicate_holds() {
+        let mut hooks = hooks("count odd when PC == 1\ncount moved when PC changes\n");
+        for pc in [0, 1, 1, 2, 1] {
+            assert!(!hooks.after_cycle(json!({ "PC": pc })));
+        }
+        let counts: Vec<u64> = hooks.counters.iter().map(|(_, _, count)| *count).collect();
+        // The reset state has no predecessor to change from.
+        assert_eq!(counts, [3, 3]);
+    }
+
+    #[test]
+    fn the_first_holding_stop_condition_ends_the_run() {
+        let mut hooks = hooks("stop when Exception\nstop when PC > 1\n");
+        assert!(!hooks.after_cycle(json!({ "PC": 0, "Exception": false })));
+        assert!(!hooks.after_cycle(json!({ "PC": 1, "Exception": false })));
+        assert!(hooks.after_cycle(json!({ "PC": 2, "Exception": true })));
+        assert_eq!(hooks.stopped_by.as_deref(), Some("Exception"));
+    }
+}
@@ -0,0 +1,206 @@
+use crate::opcode::Opcode;
+use crate::simulator::{
+    ActiveEntry, CycleCounts, DecodedInstructionEntry, ExceptionCause, IntegerQueueEntry,
+    Simulator, compute,
+};
+use crate::units;
+use serde::{Deserialize, Serialize};
+
+/// An instruction past decode in the in-order pipeline.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Slot {
+    pc: u64,
+    seq: u64,
+    dest: u32,
+    result: Result<u64, ExceptionCause>,
+}
+
+/// Latches of a scalar five-stage pipeline (IF, ID, EX, MEM, WB) with full
+/// forwarding. Registers are not renamed: the register map table stays the
+/// identity and the first 32 physical registers hold the architectural state.
+///
+/// The stages map onto the out-of-order log schema so both models can be
+/// compared with the same tools: `DecodedPCs` is the IF/ID latch,
+/// `IntegerQueue` the ID/EX latch, and `ActiveList` the instructions in EX,
+/// MEM and WB, oldest first.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct InOrderPipeline {
+    ex: Option<Slot>,
+    mem: Option<Slot>,
+    wb: Option<Slot>,
+    /// Cycles the instruction in EX still needs before it moves on.
+    ex_stall: u32,
+}
+
+impl InOrderPipeline {
+    /// The EX stage holds an instruction.
+    pub(crate) fn executing(&self) -> bool {
+        self.ex.is_some()
+    }
+}
+
+impl Simulator {
+    /// Advances the in-order pipeline by one cycle, back to front.
+    pub(crate) fn simulate_cycle_in_order(&mut self) {
+        let mut counts = CycleCounts::default();
+        if self.state.exception {
+            // Everything was flushed when the exception reached WB; this is
+            // the cooldown cycle.
+            self.state.exception = false;
+            if self.exception_cooldown {
+                self.bandwidth.push(counts);
+                return;
+            }
+        }
+
+        if let Some(slot) = self.in_order.wb.take() {
+            match slot.result {
+                Ok(value) => {
+                    if !(self.hardwired_zero && slot.dest == 0) {
+                        self.state.physical_register_file[slot.dest as usize] = value;
+                    }
+                    self.committed += 1;
+                    counts.committed = 1;
+                }
+                Err(cause) => {
+                    self.flush_in_order(slot.pc, cause);
+                    self.bandwidth.push(counts);
+                    return;
+                }
+            }
+        }
+        self.in_order.wb = self.in_order.mem.take();
+
+        if self.in_order.ex_stall > 0 {
+            self.in_order.ex_stall -= 1;
+        } else {
+            self.in_order.mem = self.in_order.ex.take();
+            if let Some(instr) = self.state.integer_queue.pop() {
+                let op = instr.op_code;
+                let result = compute(
+                    op,
+                    instr.op_a_value,
+                    instr.op_b_value,
+                    self.trap_on_overflow,
+                );
+                self.in_order.ex_stall = units::extra_cycles(op);
+                self.in_order.ex = Some(Slot {
+                    pc: instr.pc,
+                    seq: instr.seq,
+                    dest: instr.dest_register,
+                    result,
+                });
+                counts.issued = 1;
+            }
+            if !self.state.decoded_pcs.is_empty() {
+                let instr = self.state.decoded_pcs.remove(0);
+                let entry = self.read_operands(instr);
+                self.state.integer_queue.push(entry);
+                counts.dispatched = 1;
+            }
+            // Fetch one instruction into an empty IF/ID latch. A cracked
+            // instruction occupies the latch until all its micro-ops moved on.
+            if self.state.decoded_pcs.is_empty() && (self.state.pc as usize) < self.program.len() {
+                let pc = self.state.pc;
+                for mut micro_op in self.decode(pc) {
+                    micro_op.seq = self.state.next_seq;
+                    self.state.next_seq += 1;
+                    self.state.decoded_pcs.push(micro_op);
+                    counts.fetched += 1;
+                }
+                self.state.pc += 1;
+            }
+        }
+        self.sync_in_order_state();
+        self.bandwidth.push(counts);
+    }
+
+    /// Reads the operands of an instruction entering ID, forwarding from the
+    /// youngest in-flight producer. Every producer has computed its result by
+    /// the time a consumer reaches ID, so operands are always ready.
+    fn read_operands(&self, instr: DecodedInstructionEntry) -> IntegerQueueEntry {
+        let read = |src: Option<u32>| -> u64 {
+            let Some(reg) = src else {
+                return 0;
+            };
+            let pipeline = &self.in_order;
+            [&pipeline.ex, &pipeline.mem, &pipeline.wb]
+                .into_iter()
+                .flatten()
+                .find(|slot| slot.dest == reg)
+                .map(|slot| slot.result.unwrap_or(0))
+                .unwrap_or(self.state.physical_register_file[reg as usize])
+        };
+        let op_a_value = if instr.op == Opcode::Auipc {
+            self.address(instr.pc)
+        } else {
+            read(instr.src1)
+        };
+        IntegerQueueEntry {
+            dest_register: instr.dest,
+            op_a_is_ready: true,
+            op_a_reg_tag: 0,
+            op_a_value,
+            op_b_is_ready: true,
+            op_b_reg_tag: 0,
+            op_b_value: match instr.is_imm {
+                true => instr.imm,
+                false => read(instr.src2),
+            },
+            op_b_is_imm: instr.is_imm,
+            op_code: instr.op,
+            pc: instr.pc,
+            op_a_poison: None,
+            op_b_poison: None,
+            seq: instr.seq,
+            cluster: 0,
+            bound_alu: None,
+            register_reads: 0,
+            csr: None,
+        }
+    }
+
+    /// Squashes every younger instruction and redirects fetch to the
+    /// exception handler.
+    fn flush_in_order(&mut self, pc: u64, cause: ExceptionCause) {
+        self.in_order = Default::default();
+        self.state.decoded_pcs.clear();
+        self.state.integer_queue.clear();
+        self.state.active_list.clear();
+        self.state.busy_bit_table.fill(false);
+        self.state.exception = true;
+        self.state.exception_pc = pc;
+        self.state.exception_cause = Some(cause);
+        self.state.pc = self.exception_vector;
+    }
+
+    /// Mirrors the EX, MEM and WB latches into the logged active list and busy
+    /// bits.
+    fn sync_in_order_state(&mut self) {
+        let pipeline = &self.in_order;
+        let in_flight = [&pipeline.wb, &pipeline.mem, &pipeline.ex];
+        let stalled = pipeline.ex.as_ref().filter(|_| pipeline.ex_stall > 0);
+        self.state.active_list = in_flight
+            .into_iter()
+            .flatten()
+            .map(|slot| ActiveEntry {
+                done: stalled.is_none_or(|s| s.seq != slot.seq),
+                exception: slot.result.is_err(),
+                logical_destination: slot.dest,
+                old_destination: slot.dest,
+                destination: slot.dest,
+                pc: slot.pc,
+                is_fp: false,
+                cause: slot.result.err(),
+                poison: None,
+                environment: None,
+                seq: slot.seq,
+                eliminated: false,
+            })
+            .collect();
+        self.state.busy_bit_table.fill(false);
+        for slot in in_flight.into_iter().flatten() {
+            self.state.busy_bit_table[slot.dest as usize] = true;
+        }
+    }
+}
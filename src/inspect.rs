@@ -0,0 +1,206 @@
+use serde_json::Value;
+
+use crate::asm;
+use crate::cli::InspectArgs;
+use crate::json_io::{load_log, logged_cycle, state_from_cycle};
+
+/// A condition over one logged state (and its predecessor, for `changes`).
+pub enum Predicate {
+    Changes(String),
+    Compare(String, &'static str, Value),
+    Truthy(String),
+}
+
+const COMPARISONS: [&str; 6] = ["==", "!=", "<=", ">=", "<", ">"];
+
+impl Predicate {
+    /// Parses `path changes`, `path <op> value` or a bare `path`, rejecting
+    /// a condition with the path or the value left out.
+    pub fn parse(text: &str) -> Result<Predicate, String> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err("empty condition".to_string());
+        }
+        let predicate = match text.strip_suffix(" changes") {
+            Some(path) => Predicate::Changes(path.trim().to_string()),
+            None => match COMPARISONS
+                .iter()
+                .find_map(|&op| Some((op, text.split_once(op)?)))
+            {
+                Some((op, (path, rhs))) => {
+                    let rhs = rhs.trim();
+                    if rhs.is_empty() {
+                        return Err(format!("'{}' has no value to compare with", text));
+                    }
+                    let value = serde_json::from_str(rhs).unwrap_or(Value::String(rhs.to_string()));
+                    Predicate::Compare(path.trim().to_string(), op, value)
+                }
+                None => Predicate::Truthy(text.to_string()),
+            },
+        };
+        match predicate.path().is_empty() {
+            true => Err(format!("'{}' has no field to test", text)),
+            false => Ok(predicate),
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        match self {
+            Predicate::Changes(path) | Predicate::Compare(path, _, _) | Predicate::Truthy(path) => {
+                path
+            }
+        }
+    }
+
+    /// Whether the predicate holds for the `index`th state of `log`.
+    pub fn holds(&self, log: &[Value], index: usize) -> bool {
+        let current = resolve(&log[index], self.path());
+        match self {
+            Predicate::Changes(path) => index > 0 && resolve(&log[index - 1], path) != current,
+            Predicate::Compare(_, op, rhs) => current.is_some_and(|lhs| compare(&lhs, op, rhs)),
+            Predicate::Truthy(_) => current.is_some_and(|v| truthy(&v)),
+        }
+    }
+}
+
+fn as_integer(value: &Value) -> Option<i128> {
+    value
+        .as_u64()
+        .map(i128::from)
+        .or_else(|| value.as_i64().map(i128::from))
+}
+
+fn compare(lhs: &Value, op: &str, rhs: &Value) -> bool {
+    match op {
+        "==" => lhs == rhs,
+        "!=" => lhs != rhs,
+        _ => match (as_integer(lhs), as_integer(rhs)) {
+            (Some(l), Some(r)) => match op {
+                "<" => l < r,
+                ">" => l > r,
+                "<=" => l <= r,
+                _ => l >= r,
+            },
+            _ => false,
+        },
+    }
+}
+
+fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64() != Some(0.0),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(a) => !a.is_empty(),
+        Value::Object(o) => !o.is_empty(),
+    }
+}
+
+/// Compares field names ignoring case and underscores, so `active_list`
+/// matches `ActiveList` and `exception_pc` matches `ExceptionPC`.
+fn same_name(query: &str, key: &str) -> bool {
+    let normalize = |s: &str| s.replace('_', "").to_lowercase();
+    normalize(query) == normalize(key)
+}
+
+/// Resolves a query path such as `active_list[0].pc` against a state. A
+/// bare architectural register name (`x3`, `a0`) reads that register's
+/// value through the (speculative) register map table.
+pub fn resolve(state: &Value, path: &str) -> Option<Value> {
+    let mut current = state.clone();
+    for (i, segment) in path.split('.').enumerate() {
+        let (name, indices) = match segment.find('[') {
+            Some(at) => segment.split_at(at),
+            None => (segment, ""),
+        };
+        if !name.is_empty() {
+            current = match current.as_object() {
+                Some(object) => match object.iter().find(|(key, _)| same_name(name, key)) {
+                    Some((_, value)) => value.clone(),
+                    None if i == 0 => architectural_register(state, name)?,
+                    None => return None,
+                },
+                None => return None,
+            };
+        }
+        for index in indices.split('[').skip(1) {
+            let index: usize = index.strip_suffix(']')?.trim().parse().ok()?;
+            current = current.get(index)?.clone();
+        }
+    }
+    Some(current)
+}
+
+fn architectural_register(state: &Value, name: &str) -> Option<Value> {
+    let reg = asm::register_index(name)? as usize;
+    let phys = state.get("RegisterMapTable")?.get(reg)?.as_u64()? as usize;
+    state.get("PhysicalRegisterFile")?.get(phys).cloned()
+}
+
+fn print_value(value: &Value) {
+    println!("{}", serde_json::to_string_pretty(value).unwrap());
+}
+
+/// Answers a point query or a first-cycle search over a saved log.
+pub fn run(args: &InspectArgs) -> Result<(), String> {
+    let log = load_log(&args.log_path);
+    if log.is_empty() {
+        return Err("log contains no states".to_string());
+    }
+    let first = logged_cycle(&log, 0);
+    let last = logged_cycle(&log, log.len() - 1);
+    // Positions in the log, which are the cycles only if it starts at reset.
+    let start = match args.cycle {
+        Some(cycle) => match state_from_cycle(&log, cycle) {
+            Some(index) if args.when.is_some() || logged_cycle(&log, index) == cycle => index,
+            _ => {
+                return Err(format!(
+                    "cycle {} is not in the log; it has {} states (cycles {}..{})",
+                    cycle,
+                    log.len(),
+                    first,
+                    last
+                ));
+            }
+        },
+        None => 0,
+    };
+
+    let (index, path) = match &args.when {
+        Some(text) => {
+            let predicate = Predicate::parse(text)?;
+            let found = (start..log.len()).find(|&index| predicate.holds(&log, index));
+            let Some(index) = found else {
+                return Err(format!(
+                    "no cycle from {} on satisfies '{}'",
+                    logged_cycle(&log, start),
+                    text
+                ));
+            };
+            println!("cycle {}", logged_cycle(&log, index));
+            let path = args.query.clone().unwrap_or(predicate.path().to_string());
+            (index, Some(path))
+        }
+        None if args.cycle.is_none() && args.query.is_none() => {
+            println!("{} states (cycles {}..{})", log.len(), first, last);
+            return Ok(());
+        }
+        None => (start, args.query.clone()),
+    };
+
+    match path {
+        Some(path) => match resolve(&log[index], &path) {
+            Some(value) => print_value(&value),
+            None => {
+                return Err(format!(
+                    "'{}' does not exist at cycle {}",
+                    path,
+                    logged_cycle(&log, index)
+                ));
+            }
+        },
+        None => print_value(&log[index]),
+    }
+    Ok(())
+}
@@ -0,0 +1,48 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether Ctrl-C was pressed since the handler was installed.
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+mod sys {
+    pub const SIGINT: i32 = 2;
+    pub const SIG_DFL: usize = 0;
+
+    unsafe extern "C" {
+        pub fn signal(signum: i32, handler: usize) -> usize;
+    }
+}
+
+#[cfg(unix)]
+extern "C" fn on_interrupt(_: i32) {
+    REQUESTED.store(true, Ordering::SeqCst);
+    // A second Ctrl-C kills the process as if no handler were installed,
+    // in case the run does not get to stop.
+    unsafe {
+        sys::signal(sys::SIGINT, sys::SIG_DFL);
+    }
+}
+
+/// Makes Ctrl-C request that the run stop at the end of the cycle, so that
+/// the log collected so far and the statistics are still saved. Elsewhere
+/// than on Unix systems Ctrl-C keeps killing the process.
+pub fn install() {
+    #[cfg(unix)]
+    unsafe {
+        sys::signal(sys::SIGINT, on_interrupt as extern "C" fn(i32) as usize);
+    }
+}
+
+/// Makes Ctrl-C kill the process again once the run is over, so that it
+/// does not go unnoticed while the log is saved.
+pub fn uninstall() {
+    #[cfg(unix)]
+    unsafe {
+        sys::signal(sys::SIGINT, sys::SIG_DFL);
+    }
+}
+
+/// Whether the run was interrupted and should stop.
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::SeqCst)
+}
@@ -0,0 +1,121 @@
+use crate::simulator::{ExceptionCause, Simulator};
+
+impl Simulator {
+    /// Raises external interrupt `id` at each `(cycle, id)` of `schedule`.
+    pub fn schedule_interrupts(&mut self, mut schedule: Vec<(u64, u32)>) {
+        schedule.sort();
+        self.interrupts = schedule.into();
+    }
+
+    /// PC of the oldest instruction not yet committed.
+    pub(crate) fn next_commit_pc(&self) -> u64 {
+        let state = &self.state;
+        match (state.active_list.front(), self.front_end_micro_ops().next()) {
+            (Some(entry), _) => entry.pc,
+            (None, Some(micro_op)) => micro_op.pc,
+            (None, None) => state.pc,
+        }
+    }
+
+    /// Takes the earliest raised interrupt at this commit boundary like a
+    /// precise exception: everything in flight is rolled back and fetch is
+    /// redirected to the exception vector. The oldest uncommitted instruction
+    /// becomes the exception PC, so `mret` re-executes it; a cracked `li`
+    /// interrupted between its micro-ops restarts from the first, which
+    /// rewrites the same register. Interrupts are masked while the handler
+    /// runs and stay pending until it returns. Returns whether one was taken.
+    pub(crate) fn take_interrupt(&mut self) -> bool {
+        let Some(&(cycle, id)) = self.interrupts.front() else {
+            return false;
+        };
+        if cycle > self.cycle() {
+            return false;
+        }
+        let pc = self.next_commit_pc();
+        if pc >= self.exception_vector {
+            return false;
+        }
+        self.interrupts.pop_front();
+        self.take_exception(
+            pc,
+            Some(ExceptionCause::Interrupt(id)),
+            self.exception_vector,
+        );
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::SimulatorBuilder;
+    use crate::config::Config;
+
+    fn program(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    /// An interrupt taken in `cycle`, after `committed` micro-ops.
+    struct Taken {
+        cycle: u64,
+        committed: u64,
+        pc: u64,
+        cause: ExceptionCause,
+    }
+
+    /// Counts in x1 while interrupts are taken at `schedule`, with a handler
+    /// counting them in x2. Returns the registers and the interrupts taken.
+    fn run(schedule: Vec<(u64, u32)>) -> (Vec<u64>, Vec<Taken>) {
+        let config = Config::default();
+        let mut sim = SimulatorBuilder::new()
+            .program(program(&["addi x1, x1, 1"; 12]))
+            .config(&config)
+            .build()
+            .unwrap();
+        sim.set_handler(program(&["addi x2, x2, 1", "mret"]), &config);
+        sim.schedule_interrupts(schedule);
+        let mut taken = Vec::new();
+        while !sim.done() {
+            let (cycle, committed) = (sim.cycle(), sim.committed);
+            let recovering = sim.state.exception;
+            sim.simulate_cycle();
+            if sim.state.exception && !recovering {
+                taken.push(Taken {
+                    cycle,
+                    committed,
+                    pc: sim.state.exception_pc,
+                    cause: sim.state.exception_cause.unwrap(),
+                });
+            }
+        }
+        (sim.architectural_registers(), taken)
+    }
+
+    #[test]
+    fn an_interrupt_is_precise_and_returns_to_the_oldest_uncommitted_instruction() {
+        let (registers, taken) = run(vec![(8, 7)]);
+        assert_eq!(registers[1..3], [12, 1]);
+        assert_eq!(taken.len(), 1);
+        let Taken {
+            cycle,
+            committed,
+            pc,
+            cause,
+        } = taken[0];
+        assert_eq!((cycle, cause), (8, ExceptionCause::Interrupt(7)));
+        assert!(committed > 0);
+        assert_eq!(pc, committed);
+    }
+
+    #[test]
+    fn interrupts_stay_pending_while_the_handler_runs() {
+        let (registers, taken) = run(vec![(4, 2), (4, 1)]);
+        assert_eq!(registers[1..3], [12, 2]);
+        let causes: Vec<_> = taken.iter().map(|t| t.cause).collect();
+        assert_eq!(
+            causes,
+            [ExceptionCause::Interrupt(1), ExceptionCause::Interrupt(2)]
+        );
+        assert!(taken[1].cycle > taken[0].cycle + 2);
+    }
+}
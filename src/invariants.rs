@@ -0,0 +1,214 @@
+use std::collections::VecDeque;
+
+use crate::simulator::{ActiveEntry, Simulator};
+
+impl Simulator {
+    /// Panics with a dump of the state if the out-of-order bookkeeping is
+    /// inconsistent, as checked by `--assert-invariants` after every cycle.
+    pub(crate) fn assert_invariants(&self) {
+        if let Err(violation) = self.check_invariants() {
+            panic!(
+                "invariant violated after cycle {}: {}\n{}",
+                self.cycles(),
+                violation,
+                serde_json::to_string_pretty(&self.state.to_log_value()).unwrap()
+            );
+        }
+    }
+
+    /// Checks that the free list, map tables and active-list old destinations
+    /// partition the physical registers, that busy bits are set exactly for
+    /// registers with an outstanding producer, and that no queue exceeds its
+    /// capacity.
+    fn check_invariants(&self) -> Result<(), String> {
+        let state = &self.state;
+        let maps: Vec<&Vec<u32>> = std::iter::once(&*state.register_map_table)
+            .chain(state.threads.iter().map(|t| &*t.register_map_table))
+            .collect();
+        let active: Vec<&ActiveEntry> = std::iter::once(&state.active_list)
+            .chain(state.threads.iter().map(|t| &t.active_list))
+            .flatten()
+            .collect();
+
+        if state.integer_queue.len() > self.integer_queue_capacity {
+            return Err(format!(
+                "the integer queue holds {} entries, more than its capacity of {}",
+                state.integer_queue.len(),
+                self.integer_queue_capacity
+            ));
+        }
+        for cluster in 0..self.clusters.count() {
+            let occupancy = self.cluster_occupancy(cluster);
+            if occupancy > self.clusters.capacity() {
+                return Err(format!(
+                    "the integer queue of cluster {} holds {} entries, more than its capacity of {}",
+                    cluster,
+                    occupancy,
+                    self.clusters.capacity()
+                ));
+            }
+        }
+        for (t, list) in std::iter::once(&state.active_list)
+            .chain(state.threads.iter().map(|t| &t.active_list))
+            .enumerate()
+        {
+            if list.len() > self.active_list_capacity {
+                return Err(format!(
+                    "the active list of thread {} holds {} entries, more than its capacity of {}",
+                    t,
+                    list.len(),
+                    self.active_list_capacity
+                ));
+            }
+        }
+
+        if state.rob.is_some() {
+            // Architectural registers hold committed values; only ROB tags
+            // can be waiting on a producer.
+            let int: Vec<&ActiveEntry> = active.into_iter().filter(|e| !e.is_fp).collect();
+            return check_busy_bits("", &state.busy_bit_table, &int);
+        }
+
+        // Writes to a hardwired x0 hold no register of their own.
+        let int: Vec<&ActiveEntry> = active
+            .iter()
+            .copied()
+            .filter(|e| !e.is_fp && !self.discards_write(e))
+            .collect();
+        check_partition(
+            "",
+            state.physical_register_file.len(),
+            &maps,
+            &state.free_list,
+            &int,
+            &|p| self.move_elimination.shares(p),
+            self.reclaimer.held().map(|(t, r, p)| {
+                (
+                    p,
+                    format!("the register thread {} register {} held last", t, r),
+                )
+            }),
+        )?;
+        check_busy_bits("", &state.busy_bit_table, &int)?;
+
+        if let Some(fp) = &state.fp {
+            if fp.queue.len() > self.fp_config.queue_capacity {
+                return Err(format!(
+                    "the FP queue holds {} entries, more than its capacity of {}",
+                    fp.queue.len(),
+                    self.fp_config.queue_capacity
+                ));
+            }
+            let fp_active: Vec<&ActiveEntry> = active.into_iter().filter(|e| e.is_fp).collect();
+            let fp_maps = [&*fp.register_map_table];
+            let size = fp.physical_register_file.len();
+            let held = std::iter::empty();
+            check_partition(
+                "FP ",
+                size,
+                &fp_maps,
+                &fp.free_list,
+                &fp_active,
+                &|_| 0,
+                held,
+            )?;
+            check_busy_bits("FP ", &fp.busy_bit_table, &fp_active)?;
+        }
+        Ok(())
+    }
+}
+
+/// Every physical register must appear exactly once among the map tables,
+/// the free list, the old destinations of the active entries and the
+/// registers `held` back from the free list, and once more for each of its
+/// `shares` taken by an eliminated move.
+fn check_partition(
+    kind: &str,
+    size: usize,
+    maps: &[&Vec<u32>],
+    free_list: &VecDeque<u32>,
+    active: &[&ActiveEntry],
+    shares: &dyn Fn(u32) -> u32,
+    held: impl Iterator<Item = (u32, String)>,
+) -> Result<(), String> {
+    let mut owners: Vec<Vec<String>> = vec![Vec::new(); size];
+    let mapped = maps.iter().enumerate().flat_map(|(t, map)| {
+        map.iter()
+            .enumerate()
+            .map(move |(r, &p)| (p, format!("thread {} map table entry {}", t, r)))
+    });
+    let free = free_list.iter().map(|&p| (p, "the free list".to_string()));
+    let old = active.iter().map(|e| {
+        (
+            e.old_destination,
+            format!("the old destination of PC {}", e.pc),
+        )
+    });
+    for (p, place) in mapped.chain(free).chain(old).chain(held) {
+        let Some(places) = owners.get_mut(p as usize) else {
+            return Err(format!(
+                "{}physical register {} in {} does not exist",
+                kind, p, place
+            ));
+        };
+        let references = 1 + shares(p) as usize;
+        // A free register is referenced by nothing else.
+        let free = |place: &String| place == "the free list";
+        if places.len() == references
+            || (!places.is_empty() && (free(&place) || places.iter().any(free)))
+        {
+            return Err(format!(
+                "{}physical register {} is in both {} and {}",
+                kind, p, places[0], place
+            ));
+        }
+        places.push(place);
+    }
+    for (p, places) in owners.iter().enumerate() {
+        let references = 1 + shares(p as u32) as usize;
+        if places.is_empty() {
+            return Err(format!(
+                "{}physical register {} is neither mapped, free nor an old destination",
+                kind, p
+            ));
+        }
+        if places.len() < references {
+            return Err(format!(
+                "{}physical register {} has {} references but only appears in {}",
+                kind,
+                p,
+                references,
+                places.join(", ")
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// A register is busy exactly while an active entry writing it has not
+/// produced its value. A faulting entry never does.
+fn check_busy_bits(kind: &str, busy: &[bool], active: &[&ActiveEntry]) -> Result<(), String> {
+    let mut pending = vec![false; busy.len()];
+    for entry in active {
+        if !entry.done || entry.exception {
+            pending[entry.destination as usize] = true;
+        }
+    }
+    match (0..busy.len()).find(|&p| busy[p] != pending[p]) {
+        Some(p) if busy[p] => Err(format!(
+            "{}physical register {} is busy without an outstanding producer",
+            kind, p
+        )),
+        Some(p) => Err(format!(
+            "{}physical register {} is not busy although PC {} has yet to write it",
+            kind,
+            p,
+            active
+                .iter()
+                .find(|e| e.destination as usize == p && (!e.done || e.exception))
+                .unwrap()
+                .pc
+        )),
+        None => Ok(()),
+    }
+}
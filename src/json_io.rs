@@ -1,10 +1,57 @@
-use std::fs;
-use std::process;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Stdout, Write};
+use std::path::Path;
+use std::process::{self, Child, ChildStdin, Command, Stdio};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+/// Whether the log of the run goes to standard output, which then carries
+/// nothing else.
+static LOG_ON_STDOUT: AtomicBool = AtomicBool::new(false);
+
+pub fn log_on_stdout() -> bool {
+    LOG_ON_STDOUT.load(Ordering::Relaxed)
+}
+
+/// Sends the messages of the run to standard error if the log is written
+/// to standard output at `output_path`.
+pub fn claim_stdout(output_path: &str) {
+    LOG_ON_STDOUT.store(is_stdio(output_path), Ordering::Relaxed);
+}
+
+/// Returns true for `-`, which reads standard input or writes standard
+/// output.
+pub fn is_stdio(path: &str) -> bool {
+    path == "-"
+}
+
+/// Standard input, read once however many times the input is parsed.
+static STDIN: OnceLock<Result<String, String>> = OnceLock::new();
+
+/// The contents of the input file at `path`, or of standard input for `-`.
+pub fn read_input(path: &str) -> Result<String, String> {
+    match is_stdio(path) {
+        true => STDIN
+            .get_or_init(|| {
+                let mut input = String::new();
+                io::stdin()
+                    .read_to_string(&mut input)
+                    .map(|_| input)
+                    .map_err(|err| err.to_string())
+            })
+            .clone(),
+        false => fs::read_to_string(path).map_err(|err| err.to_string()),
+    }
+}
 
 /// Parses the input JSON file and returns a vector of instruction strings.
-/// Expects the JSON file to contain an array of instructions.
-pub fn parse_instructions(input_path: &str) -> Vec<String> {
-    let input_data = fs::read_to_string(input_path).unwrap_or_else(|err| {
+/// Expects the JSON file to contain an array of instructions, or a test
+/// case with the array as its `program`. Every entry that is not a string
+/// is reported and left blank, so the others can still be checked.
+pub fn parse_instructions(input_path: &str) -> (Vec<String>, Vec<Diagnostic>) {
+    let input_data = read_input(input_path).unwrap_or_else(|err| {
         eprintln!("Failed to read input file: {}", err);
         process::exit(1);
     });
@@ -14,42 +61,607 @@ pub fn parse_instructions(input_path: &str) -> Vec<String> {
         process::exit(1);
     });
 
-    if let Some(array) = instructions.as_array() {
-        array
-            .iter()
-            .map(|v| v.as_str().unwrap_or("").to_string())
-            .collect()
-    } else {
-        eprintln!("Input JSON is not an array.");
-        vec![]
+    let program = match instructions.get("program") {
+        Some(program) => program,
+        None => &instructions,
+    };
+    let Some(array) = program.as_array() else {
+        eprintln!("Input JSON is not an array of instruction strings.");
+        process::exit(1);
+    };
+    let mut diagnostics = Vec::new();
+    let program = array
+        .iter()
+        .enumerate()
+        .map(|(index, v)| match v.as_str() {
+            Some(line) => line.to_string(),
+            None => {
+                // A bare number is most likely a machine-code word.
+                let suggestion = v
+                    .as_u64()
+                    .filter(|&n| n <= u32::MAX as u64)
+                    .map(|n| format!("{:#010x}", n));
+                diagnostics.push(Diagnostic {
+                    index,
+                    token: v.to_string(),
+                    expected: "an instruction string".to_string(),
+                    suggestion,
+                });
+                String::new()
+            }
+        })
+        .collect();
+    (program, diagnostics)
+}
+
+/// Returns true for plain-text assembly files (`.s`, `.S` or `.asm`).
+pub fn is_assembly(path: &str) -> bool {
+    path.ends_with(".s") || path.ends_with(".S") || path.ends_with(".asm")
+}
+
+/// Reads a plain-text assembly file, one instruction per line.
+pub fn parse_assembly(input_path: &str) -> Vec<String> {
+    let source = read_input(input_path).unwrap_or_else(|err| {
+        eprintln!("Failed to read input file: {}", err);
+        process::exit(1);
+    });
+    asm::parse_source(&source)
+}
+
+/// Returns true for dynamic instruction traces (`.ndjson`).
+pub fn is_trace(path: &str) -> bool {
+    path.ends_with(".ndjson")
+}
+
+/// One retired instruction of a dynamic trace recorded by another tool.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TraceRecord {
+    pc: u64,
+    opcode: String,
+    #[serde(default)]
+    operands: Vec<String>,
+    /// Data address of a memory access.
+    #[serde(default)]
+    address: Option<u64>,
+}
+
+/// Reads a dynamic trace, one JSON record per line such as
+/// `{"pc": 4096, "opcode": "add", "operands": ["x1", "x2", "x3"]}`, and
+/// returns its instructions in the order they retired. The trace already
+/// follows the taken path, so it is replayed as straight-line code; the
+/// recorded PCs only reveal where control transferred. Every line that is
+/// not a record is reported.
+pub fn parse_trace(input_path: &str) -> Result<Vec<String>, Vec<Diagnostic>> {
+    let input_data = read_input(input_path).unwrap_or_else(|err| {
+        eprintln!("Failed to read input file: {}", err);
+        process::exit(1);
+    });
+    let mut diagnostics = Vec::new();
+    let mut program = Vec::new();
+    let mut last_pc = None;
+    let (mut transfers, mut accesses) = (0, 0);
+    for (index, line) in input_data
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .enumerate()
+    {
+        let record: TraceRecord = match serde_json::from_str(line) {
+            Ok(record) => record,
+            Err(_) => {
+                diagnostics.push(Diagnostic {
+                    index,
+                    token: line.to_string(),
+                    expected: "a trace record with a pc, opcode and operands".to_string(),
+                    suggestion: None,
+                });
+                program.push(String::new());
+                continue;
+            }
+        };
+        if last_pc.is_some_and(|pc: u64| record.pc != pc.wrapping_add(4)) {
+            transfers += 1;
+        }
+        last_pc = Some(record.pc);
+        accesses += record.address.is_some() as usize;
+        program.push(
+            format!("{} {}", record.opcode, record.operands.join(", "))
+                .trim_end()
+                .to_string(),
+        );
     }
+    if !diagnostics.is_empty() {
+        return Err(diagnostics);
+    }
+    if transfers > 0 {
+        eprintln!(
+            "warning: {} control transfers in the trace; it is replayed in recorded order",
+            transfers
+        );
+    }
+    if accesses > 0 {
+        eprintln!(
+            "warning: ignoring {} memory addresses in the trace; the simulator has no data memory",
+            accesses
+        );
+    }
+    Ok(program)
 }
 
-/// Saves the simulation log (a vector of JSON states) to the specified output file.
-pub fn save_log(output_path: &str, log: &Vec<serde_json::Value>) {
-    let output = serde_json::to_string_pretty(&log).unwrap_or_else(|err| {
-        eprintln!("Failed to serialize simulation log: {}", err);
+/// Parses an interrupt schedule: a JSON object mapping the cycle an
+/// interrupt is raised at to its id, such as `{"12": 1, "40": 3}`.
+pub fn parse_interrupts(path: &str) -> Vec<(u64, u32)> {
+    let data = fs::read_to_string(path).unwrap_or_else(|err| {
+        eprintln!("Failed to read interrupt schedule: {}", err);
         process::exit(1);
     });
-    fs::write(output_path, output).unwrap_or_else(|err| {
-        eprintln!("Failed to write output file: {}", err);
+    let schedule: BTreeMap<String, u32> = serde_json::from_str(&data).unwrap_or_else(|err| {
+        eprintln!("Failed to parse interrupt schedule: {}", err);
         process::exit(1);
     });
+    schedule
+        .into_iter()
+        .map(|(cycle, id)| match cycle.parse::<u64>() {
+            Ok(cycle) if cycle > 0 => (cycle, id),
+            _ => {
+                eprintln!(
+                    "Invalid interrupt schedule: {:?} is not a cycle number (1 or more)",
+                    cycle
+                );
+                process::exit(1);
+            }
+        })
+        .collect()
+}
+
+/// Parses a JSON machine configuration file. Missing fields take their
+/// reference defaults.
+pub fn parse_config(config_path: &str) -> Config {
+    let config_data = fs::read_to_string(config_path).unwrap_or_else(|err| {
+        eprintln!("Failed to read config file: {}", err);
+        process::exit(1);
+    });
+
+    let config: Config = serde_json::from_str(&config_data).unwrap_or_else(|err| {
+        eprintln!("Failed to parse config file: {}", err);
+        process::exit(1);
+    });
+
+    if let Err(err) = config.validate() {
+        eprintln!("Invalid config: {}", err);
+        process::exit(1);
+    }
+    config
+}
+
+/// Loads a saved simulation log and returns its per-cycle states. Logs
+/// written with a header are unwrapped.
+pub fn load_log(log_path: &str) -> Vec<serde_json::Value> {
+    if is_compressed(log_path) {
+        eprintln!("Compressed logs must be decompressed first: {}", log_path);
+        process::exit(1);
+    }
+    let log_data = fs::read_to_string(log_path).unwrap_or_else(|err| {
+        eprintln!("Failed to read log file: {}", err);
+        process::exit(1);
+    });
+    if is_ndjson(log_path) {
+        return expand_delta(parse_ndjson(&log_data));
+    }
+
+    let log: serde_json::Value = serde_json::from_str(&log_data).unwrap_or_else(|err| {
+        eprintln!("Failed to parse log file: {}", err);
+        process::exit(1);
+    });
+
+    let states = match log {
+        serde_json::Value::Array(states) => states,
+        serde_json::Value::Object(mut wrapped) => match wrapped.remove("Log") {
+            Some(serde_json::Value::Array(states)) => states,
+            _ => {
+                eprintln!("Log file has no \"Log\" array.");
+                process::exit(1);
+            }
+        },
+        _ => {
+            eprintln!("Log file is not an array of states.");
+            process::exit(1);
+        }
+    };
+    expand_delta(states)
+}
+
+/// The cycle of the `index`th state of a log. Logs that start mid-run
+/// record it in `Cycle`; otherwise the position in the log is the cycle.
+pub fn logged_cycle(log: &[serde_json::Value], index: usize) -> usize {
+    log[index]
+        .get("Cycle")
+        .and_then(serde_json::Value::as_u64)
+        .map_or(index, |cycle| cycle as usize)
+}
+
+/// The position in the log of the first state of `cycle` or later.
+pub fn state_from_cycle(log: &[serde_json::Value], cycle: usize) -> Option<usize> {
+    (0..log.len()).find(|&index| logged_cycle(log, index) >= cycle)
+}
+
+/// Reconstructs the full states if `states` is a delta-encoded log.
+fn expand_delta(states: Vec<serde_json::Value>) -> Vec<serde_json::Value> {
+    if !delta::is_delta_log(&states) {
+        return states;
+    }
+    delta::expand(states).unwrap_or_else(|err| {
+        eprintln!("Failed to expand delta log: {}", err);
+        process::exit(1);
+    })
+}
+
+/// Parses a streamed log, dropping its header line.
+fn parse_ndjson(log_data: &str) -> Vec<serde_json::Value> {
+    log_data
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            serde_json::from_str(line).unwrap_or_else(|err| {
+                eprintln!("Failed to parse log file: {}", err);
+                process::exit(1);
+            })
+        })
+        .filter(|state: &serde_json::Value| state.get("Header").is_none())
+        .collect()
+}
+
+/// Returns true if `path` names a newline-delimited JSON log, which is
+/// written one state per line as the simulation runs. A compression
+/// extension after it does not count. Standard output takes one too.
+pub fn is_ndjson(path: &str) -> bool {
+    if is_stdio(path) {
+        return true;
+    }
+    let path = path
+        .strip_suffix(".gz")
+        .or(path.strip_suffix(".zst"))
+        .unwrap_or(path);
+    path.ends_with(".ndjson") || path.ends_with(".jsonl")
+}
+
+/// Returns true if `path` names an output that is compressed as it is
+/// written.
+pub fn is_compressed(path: &str) -> bool {
+    path.ends_with(".gz") || path.ends_with(".zst")
+}
+
+/// An output file, compressed with gzip or zstd if its name ends in `.gz` or
+/// `.zst`. The zstd format is too involved to write here, so `.zst` output is
+/// piped through the `zstd` program. `-` writes standard output.
+enum OutputFile {
+    Plain(BufWriter<File>),
+    Stdout(BufWriter<Stdout>),
+    Gzip(GzipEncoder<BufWriter<File>>),
+    Zstd(Child, BufWriter<ChildStdin>),
+}
+
+impl OutputFile {
+    fn create(path: &str) -> io::Result<OutputFile> {
+        if is_stdio(path) {
+            return Ok(OutputFile::Stdout(BufWriter::new(io::stdout())));
+        }
+        if path.ends_with(".zst") {
+            let mut child = Command::new("zstd")
+                .args(["-q", "-f", "-o", path])
+                .stdin(Stdio::piped())
+                .spawn()
+                .map_err(|err| io::Error::other(format!("Failed to run zstd: {}", err)))?;
+            let stdin = child.stdin.take().unwrap();
+            return Ok(OutputFile::Zstd(child, BufWriter::new(stdin)));
+        }
+        let file = BufWriter::new(File::create(path)?);
+        match path.ends_with(".gz") {
+            true => Ok(OutputFile::Gzip(GzipEncoder::new(file)?)),
+            false => Ok(OutputFile::Plain(file)),
+        }
+    }
+
+    fn finish(self) -> io::Result<()> {
+        match self {
+            OutputFile::Plain(mut file) => file.flush(),
+            OutputFile::Stdout(mut stdout) => stdout.flush(),
+            OutputFile::Gzip(gzip) => gzip.finish().map(drop),
+            OutputFile::Zstd(mut child, mut stdin) => {
+                stdin.flush()?;
+                drop(stdin);
+                match child.wait()? {
+                    status if status.success() => Ok(()),
+                    status => Err(io::Error::other(format!("zstd exited with {}", status))),
+                }
+            }
+        }
+    }
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            OutputFile::Plain(file) => file.write(buf),
+            OutputFile::Stdout(stdout) => stdout.write(buf),
+            OutputFile::Gzip(gzip) => gzip.write(buf),
+            OutputFile::Zstd(_, stdin) => stdin.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            OutputFile::Plain(file) => file.flush(),
+            OutputFile::Stdout(stdout) => stdout.flush(),
+            OutputFile::Gzip(gzip) => gzip.flush(),
+            OutputFile::Zstd(_, stdin) => stdin.flush(),
+        }
+    }
+}
+
+/// A log written one compact JSON state per line as the simulation runs, so
+/// memory use does not grow with the number of cycles.
+pub struct LogStream {
+    writer: OutputFile,
+    delta: Option<DeltaEncoder>,
+}
+
+impl LogStream {
+    pub fn create(path: &str) -> LogStream {
+        let writer = OutputFile::create(path).unwrap_or_else(|err| {
+            eprintln!("Failed to create output file: {}", err);
+            process::exit(1);
+        });
+        LogStream {
+            writer,
+            delta: None,
+        }
+    }
+
+    /// Delta-encodes the states written with `write_state`.
+    pub fn with_delta(mut self, keyframe_interval: usize) -> LogStream {
+        self.delta = Some(DeltaEncoder::new(keyframe_interval));
+        self
+    }
+
+    /// Writes one cycle's state, delta-encoded if the stream asks for it.
+    pub fn write_state(&mut self, state: serde_json::Value) {
+        let entry = match self.delta.as_mut() {
+            Some(encoder) => encoder.encode(state),
+            None => state,
+        };
+        self.write(&entry);
+    }
+
+    pub fn write<T: Serialize>(&mut self, value: &T) {
+        serde_json::to_writer(&mut self.writer, value)
+            .map_err(io::Error::from)
+            .and_then(|()| self.writer.write_all(b"\n"))
+            .unwrap_or_else(|err| {
+                eprintln!("Failed to write output file: {}", err);
+                process::exit(1);
+            });
+    }
+
+    pub fn finish(self) {
+        self.writer.finish().unwrap_or_else(|err| {
+            eprintln!("Failed to write output file: {}", err);
+            process::exit(1);
+        });
+    }
+}
+
+/// Saves the simulation log (a vector of JSON states, possibly wrapped with a
+/// header) to the specified output file, compressed if its name asks for it.
+pub fn save_log<T: Serialize>(output_path: &str, log: &T) {
+    let output = serde_json::to_string_pretty(log).unwrap_or_else(|err| {
+        eprintln!("Failed to serialize simulation log: {}", err);
+        process::exit(1);
+    });
+    write_output(output_path, output.as_bytes());
+}
+
+/// Saves a log as `save_log` does, printing its states on `jobs` threads,
+/// the available parallelism by default.
+pub fn save_log_value(output_path: &str, log: &serde_json::Value, jobs: Option<usize>) {
+    let jobs = jobs.unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()));
+    if jobs == 1 {
+        return save_log(output_path, log);
+    }
+    let jobs = Some(jobs);
+    let mut output = String::new();
+    write_pretty(log, 0, jobs, &mut output);
+    write_output(output_path, output.as_bytes());
+}
+
+/// Arrays with fewer elements than this are printed on one thread.
+const PARALLEL_ELEMENTS: usize = 64;
+
+/// Appends `value` as `serde_json::to_string_pretty` prints it at nesting
+/// `depth`. The elements of a long array at the top level or in a top-level
+/// object, the states of a log with or without its header, are printed in
+/// parallel and joined in order.
+fn write_pretty(value: &serde_json::Value, depth: usize, jobs: Option<usize>, out: &mut String) {
+    let indent = |depth: usize| "  ".repeat(depth);
+    let mut separator = "\n";
+    match value {
+        serde_json::Value::Array(items) if depth <= 1 && items.len() >= PARALLEL_ELEMENTS => {
+            let printed = crate::batch::parallel_map(items, jobs, |item| {
+                let mut out = String::new();
+                write_pretty(item, depth + 1, jobs, &mut out);
+                out
+            });
+            out.push('[');
+            for item in printed {
+                out.push_str(separator);
+                out.push_str(&indent(depth + 1));
+                out.push_str(&item);
+                separator = ",\n";
+            }
+            out.push('\n');
+            out.push_str(&indent(depth));
+            out.push(']');
+        }
+        serde_json::Value::Object(fields) if depth == 0 && !fields.is_empty() => {
+            out.push('{');
+            for (key, field) in fields {
+                out.push_str(separator);
+                out.push_str(&indent(depth + 1));
+                out.push_str(&serde_json::to_string(key).unwrap());
+                out.push_str(": ");
+                write_pretty(field, depth + 1, jobs, out);
+                separator = ",\n";
+            }
+            out.push('\n');
+            out.push_str(&indent(depth));
+            out.push('}');
+        }
+        // Strings escape their newlines, so every newline printed ends a
+        // line to indent.
+        _ => {
+            let printed = serde_json::to_string_pretty(value).unwrap();
+            out.push_str(&printed.replace('\n', &format!("\n{}", indent(depth))));
+        }
+    }
+}
+
+/// Writes `output` to the file `output_path`, compressed if its name asks
+/// for it.
+/// Writes `output` next to the output file and renames it over the file
+/// once complete, so that a save interrupted by Ctrl-C leaves no truncated
+/// log behind. Standard output, devices and symlinks are written directly.
+fn write_output(output_path: &str, output: &[u8]) {
+    let replaced = !is_stdio(output_path)
+        && fs::symlink_metadata(output_path).map_or(true, |metadata| metadata.is_file());
+    let path = Path::new(output_path);
+    let partial = match (replaced, path.file_name()) {
+        (true, Some(name)) => path.with_file_name(format!(".partial-{}", name.display())),
+        _ => path.to_path_buf(),
+    };
+    OutputFile::create(&partial.to_string_lossy())
+        .and_then(|mut file| {
+            file.write_all(output)?;
+            file.finish()
+        })
+        .and_then(|()| match partial == path {
+            true => Ok(()),
+            false => fs::rename(&partial, path),
+        })
+        .unwrap_or_else(|err| {
+            eprintln!("Failed to write output file: {}", err);
+            process::exit(1);
+        });
+}
+
+/// Saves the simulation log as MessagePack, compressed if the name of the
+/// output file asks for it.
+pub fn save_msgpack(output_path: &str, log: &serde_json::Value) {
+    let mut output = Vec::new();
+    msgpack::encode(log, &mut output);
+    write_output(output_path, &output);
 }
 
-use serde::Serialize;
 use serde::ser::Serializer;
+use serde::{Deserialize, Serialize};
 
-use crate::simulator::DecodedInstructionEntry;
+use crate::asm;
+use crate::checkpoint;
+use crate::config::Config;
+use crate::delta::{self, DeltaEncoder};
+use crate::gzip::GzipEncoder;
+use crate::lifetime::Lifetime;
+use crate::msgpack;
+use crate::simulator::{CycleCounts, DecodedInstructionEntry};
+use crate::stats::CycleMetrics;
+use crate::validate::Diagnostic;
 
 pub fn serialize_decoded_pcs<S>(
-    decoded: &Vec<DecodedInstructionEntry>,
+    decoded: &[DecodedInstructionEntry],
     serializer: S,
 ) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
 {
+    // Checkpoints keep the whole micro-op.
+    if checkpoint::is_writing() {
+        return decoded.serialize(serializer);
+    }
     // Map each DecodedInstructionEntry to its pc field.
     let pcs: Vec<u64> = decoded.iter().map(|d| d.pc).collect();
     pcs.serialize(serializer)
 }
+
+/// Writes per-cycle stage counts as CSV, one row per simulated cycle.
+pub fn save_bandwidth(path: &str, bandwidth: &[CycleCounts]) {
+    let mut csv = String::from("cycle,fetched,dispatched,issued,committed\n");
+    for (i, c) in bandwidth.iter().enumerate() {
+        csv += &format!(
+            "{},{},{},{},{}\n",
+            i + 1,
+            c.fetched,
+            c.dispatched,
+            c.issued,
+            c.committed
+        );
+    }
+    fs::write(path, csv).unwrap_or_else(|err| {
+        eprintln!("Failed to write bandwidth file: {}", err);
+        process::exit(1);
+    });
+}
+
+/// Writes the per-cycle metrics as CSV, one row per simulated cycle, ready
+/// for a spreadsheet or a dataframe.
+pub fn save_metrics(path: &str, metrics: &[CycleMetrics]) {
+    let mut csv =
+        String::from("cycle,pc,integer_queue,active_list,free_list,committed,exception\n");
+    for (i, m) in metrics.iter().enumerate() {
+        csv += &format!(
+            "{},{},{},{},{},{},{}\n",
+            i + 1,
+            m.pc,
+            m.integer_queue,
+            m.active_list,
+            m.free_list,
+            m.committed,
+            m.exception
+        );
+    }
+    fs::write(path, csv).unwrap_or_else(|err| {
+        eprintln!("Failed to write metrics file: {}", err);
+        process::exit(1);
+    });
+}
+
+/// Writes the stage cycles of every micro-op as CSV, one row per micro-op in
+/// fetch order. Stages a micro-op never reached are left empty; `queued` is
+/// the number of cycles it waited in an issue queue.
+pub fn save_timeline(path: &str, lifetimes: &[Lifetime]) {
+    let cell = |cycle: Option<u64>| cycle.map_or(String::new(), |c| c.to_string());
+    let mut csv =
+        String::from("seq,thread,pc,op,fetch,dispatch,issue,complete,commit,squash,queued\n");
+    for l in lifetimes {
+        let queued = l
+            .issue
+            .zip(l.dispatch)
+            .map(|(issue, dispatch)| issue - dispatch);
+        csv += &format!(
+            "{},{},{},{},{},{},{},{},{},{},{}\n",
+            l.seq,
+            l.thread,
+            l.pc,
+            l.op,
+            l.fetch,
+            cell(l.dispatch),
+            cell(l.issue),
+            cell(l.complete),
+            cell(l.commit),
+            cell(l.squash),
+            cell(queued)
+        );
+    }
+    fs::write(path, csv).unwrap_or_else(|err| {
+        eprintln!("Failed to write timeline file: {}", err);
+        process::exit(1);
+    });
+}
@@ -0,0 +1,71 @@
+use serde::{Deserialize, Serialize};
+
+use crate::config::{LatencyConfig, LatencyDistribution};
+use crate::opcode::Opcode;
+use crate::rng::Rng;
+
+/// Extra execute cycles drawn for every micro-op of the chosen opcodes as it
+/// issues, to test how the scheduler copes with latencies it cannot know in
+/// advance. The draws come from their own generator, so a seed reproduces
+/// them and the randomized policies draw as they would without them.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RandomLatency {
+    distribution: LatencyDistribution,
+    opcodes: Vec<Opcode>,
+    rng: Rng,
+    /// Micro-ops given a drawn latency.
+    pub drawn: u64,
+    /// Extra cycles drawn in total.
+    pub extra_cycles: u64,
+}
+
+impl RandomLatency {
+    pub fn new(config: &LatencyConfig, seed: u64) -> Option<Self> {
+        if config.distribution == LatencyDistribution::Fixed {
+            return None;
+        }
+        let opcodes = match config.opcodes.is_empty() {
+            true => crate::opcode::OPCODES
+                .iter()
+                .copied()
+                .filter(|op| op.is_mul_div())
+                .collect(),
+            false => config
+                .opcodes
+                .iter()
+                .map(|name| Opcode::parse(name).unwrap())
+                .collect(),
+        };
+        Some(Self {
+            distribution: config.distribution,
+            opcodes,
+            rng: Rng::new(config.seed.unwrap_or(seed)),
+            drawn: 0,
+            extra_cycles: 0,
+        })
+    }
+
+    /// The extra cycles a micro-op executing `op` takes this time.
+    pub fn draw(&mut self, op: Opcode) -> u32 {
+        if !self.opcodes.contains(&op) {
+            return 0;
+        }
+        let extra = match self.distribution {
+            LatencyDistribution::Fixed => 0,
+            LatencyDistribution::Uniform { min, max } => {
+                min + self.rng.below((max - min) as usize + 1) as u32
+            }
+            LatencyDistribution::Bimodal {
+                fast,
+                slow,
+                slow_fraction,
+            } => match (self.rng.next_u64() as f64 / u64::MAX as f64) < slow_fraction {
+                true => slow,
+                false => fast,
+            },
+        };
+        self.drawn += 1;
+        self.extra_cycles += extra as u64;
+        extra
+    }
+}
@@ -0,0 +1,114 @@
+use crate::opcode::Opcode;
+use crate::simulator::{DecodedInstructionEntry, Simulator};
+use serde::{Deserialize, Serialize};
+
+/// Cycles at which one dynamic micro-op of the out-of-order pipeline passed
+/// each stage. Cycle `n` is the one that produced log entry `n`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Lifetime {
+    pub seq: u64,
+    pub thread: usize,
+    pub pc: u64,
+    pub op: Opcode,
+    pub fetch: u64,
+    pub dispatch: Option<u64>,
+    pub issue: Option<u64>,
+    /// The result was written back and the active list entry marked done.
+    pub complete: Option<u64>,
+    pub commit: Option<u64>,
+    /// Rolled back or dropped from the decode buffer by an exception.
+    pub squash: Option<u64>,
+}
+
+/// Addresses of the micro-ops that passed each stage in the cycle just
+/// simulated, logged with `log_events`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct CycleEvents {
+    #[serde(rename = "Fetched")]
+    pub fetched: Vec<u64>,
+    #[serde(rename = "Dispatched")]
+    pub dispatched: Vec<u64>,
+    #[serde(rename = "Issued")]
+    pub issued: Vec<u64>,
+    #[serde(rename = "Completed")]
+    pub completed: Vec<u64>,
+    #[serde(rename = "Committed")]
+    pub committed: Vec<u64>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Stage {
+    Dispatch,
+    Issue,
+    Complete,
+    Commit,
+    Squash,
+}
+
+impl Simulator {
+    /// The cycle being simulated.
+    pub(crate) fn cycle(&self) -> u64 {
+        self.bandwidth.len() as u64 + 1
+    }
+
+    pub(crate) fn record_fetch(&mut self, micro_op: &DecodedInstructionEntry) {
+        let lifetime = Lifetime {
+            seq: micro_op.seq,
+            thread: self.smt.current,
+            pc: self.address(micro_op.pc),
+            op: micro_op.op,
+            fetch: self.cycle(),
+            dispatch: None,
+            issue: None,
+            complete: None,
+            commit: None,
+            squash: None,
+        };
+        for observer in self.observers.iter_mut() {
+            observer.on_fetch(lifetime.fetch, &lifetime);
+        }
+        if let Some(events) = self.state.events.as_mut() {
+            events.fetched.push(lifetime.pc);
+        }
+        self.lifetimes.push(lifetime);
+    }
+
+    /// Stamps micro-op `seq` with the current cycle for `stage`.
+    pub(crate) fn record_stage(&mut self, seq: u64, stage: Stage) {
+        let cycle = self.cycle();
+        // Sequence numbers are handed out in fetch order, so they index
+        // `lifetimes` directly.
+        let Some(lifetime) = self.lifetimes.get_mut(seq as usize) else {
+            return;
+        };
+        let slot = match stage {
+            Stage::Dispatch => &mut lifetime.dispatch,
+            Stage::Issue => &mut lifetime.issue,
+            Stage::Complete => &mut lifetime.complete,
+            Stage::Commit => &mut lifetime.commit,
+            Stage::Squash => &mut lifetime.squash,
+        };
+        *slot = Some(cycle);
+        if let Some(events) = self.state.events.as_mut() {
+            let pcs = match stage {
+                Stage::Dispatch => Some(&mut events.dispatched),
+                Stage::Issue => Some(&mut events.issued),
+                Stage::Complete => Some(&mut events.completed),
+                Stage::Commit => Some(&mut events.committed),
+                Stage::Squash => None,
+            };
+            if let Some(pcs) = pcs {
+                pcs.push(lifetime.pc);
+            }
+        }
+        for observer in self.observers.iter_mut() {
+            match stage {
+                Stage::Dispatch => observer.on_dispatch(cycle, lifetime),
+                Stage::Issue => observer.on_issue(cycle, lifetime),
+                Stage::Complete => observer.on_writeback(cycle, lifetime),
+                Stage::Commit => observer.on_commit(cycle, lifetime),
+                Stage::Squash => {}
+            }
+        }
+    }
+}
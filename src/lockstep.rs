@@ -0,0 +1,163 @@
+use std::collections::VecDeque;
+
+use crate::builder::SimulatorBuilder;
+use crate::cli::LockstepArgs;
+use crate::config::Pipeline;
+use crate::json_io::parse_config;
+use crate::simulator::{Commit, Simulator};
+
+/// One of the two runs: its pipeline, the commits the other run has not
+/// matched yet, and the cycle each region of its commits ended in.
+struct Side {
+    name: &'static str,
+    path: String,
+    sim: Simulator,
+    unmatched: VecDeque<Commit>,
+    committed: u64,
+    region_ends: Vec<usize>,
+    finished: bool,
+}
+
+impl Side {
+    /// Simulates a cycle and queues what it committed.
+    fn step(&mut self, region: u64) {
+        self.sim.simulate_cycle();
+        let cycle = self.sim.cycles();
+        for commit in self.sim.commit_log.as_mut().unwrap().drain(..) {
+            self.unmatched.push_back(commit);
+            self.committed += 1;
+            if self.committed.is_multiple_of(region) {
+                self.region_ends.push(cycle);
+            }
+        }
+        self.finished = self.sim.done() || self.sim.watchdog_expired();
+    }
+}
+
+fn describe(commit: &Commit) -> String {
+    match commit.write {
+        _ if commit.exception => format!("instruction {} raising an exception", commit.pc),
+        Some((reg, value)) => format!("instruction {} writing x{} = {}", commit.pc, reg, value),
+        None => format!("instruction {}", commit.pc),
+    }
+}
+
+/// Runs the program under two configs cycle by cycle and checks that both
+/// commit the same micro-ops with the same results, in the same order,
+/// whatever their timing. The cycles each config took are compared per
+/// region of `args.region` committed micro-ops, so a speedup or slowdown
+/// shows where in the program it comes from.
+pub fn run(args: &LockstepArgs) -> Result<(), String> {
+    let program = crate::load_program(&args.input_path);
+    let mut sides = Vec::new();
+    for (name, path) in ["A", "B"].into_iter().zip(&args.config_paths) {
+        let config = parse_config(path);
+        if config.pipeline != Pipeline::OutOfOrder {
+            return Err(format!(
+                "{}: lockstep is only supported by the out-of-order pipeline",
+                path
+            ));
+        }
+        let mut sim = SimulatorBuilder::new()
+            .program(program.clone())
+            .config(&config)
+            .build()?;
+        sim.commit_log = Some(VecDeque::new());
+        sides.push(Side {
+            name,
+            path: path.clone(),
+            sim,
+            unmatched: VecDeque::new(),
+            committed: 0,
+            region_ends: Vec::new(),
+            finished: false,
+        });
+    }
+
+    let mut matched: u64 = 0;
+    while sides.iter().any(|side| !side.finished) {
+        for side in sides.iter_mut().filter(|side| !side.finished) {
+            side.step(args.region);
+        }
+        let [a, b] = &mut sides[..] else {
+            unreachable!()
+        };
+        while let (Some(x), Some(y)) = (a.unmatched.front(), b.unmatched.front()) {
+            if x != y {
+                return Err(format!(
+                    "Diverged at micro-op {}: config A committed {} in cycle {}, config B {} in \
+                     cycle {}",
+                    matched,
+                    describe(x),
+                    a.sim.cycles(),
+                    describe(y),
+                    b.sim.cycles()
+                ));
+            }
+            a.unmatched.pop_front();
+            b.unmatched.pop_front();
+            matched += 1;
+        }
+    }
+
+    for side in &sides {
+        if let Some(report) = &side.sim.watchdog {
+            return Err(format!("Config {} stopped: {}", side.name, report));
+        }
+    }
+    if let Some(side) = sides.iter().find(|side| !side.unmatched.is_empty()) {
+        return Err(format!(
+            "Config {} committed {} micro-ops more than the other, from {}",
+            side.name,
+            side.unmatched.len(),
+            describe(&side.unmatched[0])
+        ));
+    }
+
+    let [a, b] = &sides[..] else { unreachable!() };
+    println!("Lockstep: {} micro-ops committed identically", matched);
+    for side in &sides {
+        println!(
+            "  Config {} ({}): {} cycles",
+            side.name,
+            side.path,
+            side.sim.cycles()
+        );
+    }
+    let speedup = |a: usize, b: usize| match b {
+        0 => "-".to_string(),
+        b => format!("{:.2}x", a as f64 / b as f64),
+    };
+    println!(
+        "  B takes {:+} cycles, a speedup of {}",
+        b.sim.cycles() as i64 - a.sim.cycles() as i64,
+        speedup(a.sim.cycles(), b.sim.cycles())
+    );
+    println!(
+        "{:>6} {:>15} {:>10} {:>10} {:>8} {:>8}",
+        "Region", "Micro-ops", "Cycles A", "Cycles B", "Delta", "Speedup"
+    );
+    let regions = matched.div_ceil(args.region) as usize;
+    let (mut start_a, mut start_b) = (0, 0);
+    for i in 0..regions {
+        // The last region ends with the run, which drains after its commits.
+        let end = |side: &Side| match i + 1 == regions {
+            true => side.sim.cycles(),
+            false => side.region_ends[i],
+        };
+        let (end_a, end_b) = (end(a), end(b));
+        let (cycles_a, cycles_b) = (end_a - start_a, end_b - start_b);
+        let first = i as u64 * args.region;
+        println!(
+            "{:>6} {:>15} {:>10} {:>10} {:>+8} {:>8}",
+            i,
+            format!("{}..{}", first, (first + args.region).min(matched)),
+            cycles_a,
+            cycles_b,
+            cycles_b as i64 - cycles_a as i64,
+            speedup(cycles_a, cycles_b)
+        );
+        (start_a, start_b) = (end_a, end_b);
+    }
+    Ok(())
+}
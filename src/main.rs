@@ -1,42 +1,1116 @@
+/// Prints a message of the run as `println!` does, or on standard error
+/// while the log is written to standard output.
+macro_rules! status {
+    ($($arg:tt)*) => {
+        match crate::json_io::log_on_stdout() {
+            true => eprintln!($($arg)*),
+            false => println!($($arg)*),
+        }
+    };
+}
+
+mod animate;
+mod area;
+mod asm;
+mod batch;
+mod builder;
+mod bypass;
+mod check;
+mod checkpoint;
+mod cli;
+mod cluster;
+mod config;
+mod cosim;
+mod csr;
+mod decoder;
+mod delta;
+mod diagram;
+mod diff;
+mod elf;
+mod elimination;
+mod energy;
+mod explore;
+mod fetch;
+mod fp;
+mod gdb;
+mod generator;
+mod gif;
+mod gzip;
+mod hooks;
+mod inorder;
+mod inspect;
+mod interrupt;
+mod interrupts;
+mod invariants;
 mod json_io;
+mod latency;
+mod lifetime;
+mod lockstep;
+mod msgpack;
+mod observer;
+mod opcode;
+mod prediction;
+mod profile;
+mod progress;
+mod reclamation;
+mod recovery;
+mod replay;
+mod report;
+mod rng;
+mod rob;
+mod roi;
+mod sampling;
+mod scheduler;
+mod schema;
+mod scoreboard;
+mod selftest;
+mod shared;
 mod simulator;
+mod smt;
+mod stats;
+mod sweep;
+mod syscall;
+mod testcase;
+mod tomasulo;
+mod trace;
+mod tui;
+mod units;
+mod until;
+mod validate;
+mod verify;
+mod warnings;
+mod watch;
+mod watchdog;
+mod writeback;
 
-use json_io::{parse_instructions, save_log};
-use simulator::Simulator;
+use builder::SimulatorBuilder;
+use cli::{Command, LogFormat, Remote, SimulateArgs, Until};
+use config::{AluBinding, Config, Pipeline, RecoveryMode, Renaming, ValuePredictor};
+use hooks::Hooks;
+use json_io::{
+    LogStream, parse_config, parse_instructions, save_bandwidth, save_log, save_log_value,
+    save_metrics, save_msgpack, save_timeline,
+};
+use profile::Profile;
+use progress::{HostTimer, Progress};
+use replay::Replay;
+use shared::Shared;
+use simulator::{Simulator, SimulatorState};
+use stats::Summary;
 use std::env;
+use std::path::Path;
 use std::process;
+use std::time::Instant;
+use validate::Diagnostic;
+use warnings::Warning;
 
 fn main() {
-    // Expect two commandline arguments: input file and output file.
     let args: Vec<String> = env::args().collect();
-    if args.len() != 3 {
-        eprintln!("Usage: {} <input.json> <output.json>", args[0]);
+    let result = match cli::parse_args(&args) {
+        Command::Simulate(args) => {
+            simulate(&args);
+            Ok(())
+        }
+        Command::Inspect(args) => inspect::run(&args),
+        Command::Animate(args) => animate::run(&args),
+        Command::Batch(args) => batch::run(&args),
+        Command::Sweep(args) => sweep::run(&args),
+        Command::Sample(args) => sampling::run(&args),
+        Command::Lockstep(args) => lockstep::run(&args),
+        Command::Watch(args) => watch::run(&args),
+        Command::Explore(args) => explore::run(&args),
+        Command::VerifyTrace(args) => verify::run(&args),
+        Command::Tui(args) => tui::run(&args),
+        Command::Validate(args) => schema::run(&args),
+        Command::Expand(args) => delta::run_expand(&args),
+        Command::Convert(args) => msgpack::run_convert(&args),
+        Command::Diff(args) => diff::run(&args),
+        Command::Selftest(args) => selftest::run(&args),
+        Command::Gen(args) => generator::run(&args),
+    };
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
+
+fn simulate(args: &SimulateArgs) {
+    let output_path = &args.output_path;
+    json_io::claim_stdout(output_path);
+    let restored = args.restore_path.as_deref().map(|path| {
+        checkpoint::restore(path).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            process::exit(1);
+        })
+    });
+    let replay = args.replay_path.as_deref().map(|path| {
+        replay::load(path).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            process::exit(1);
+        })
+    });
+    let (mut config, restored) = match (restored, &replay, &args.config_path) {
+        (Some(checkpoint), _, _) => (checkpoint.config, Some(checkpoint.simulator)),
+        (None, Some(replay), _) => (replay.config.clone(), None),
+        (None, None, Some(path)) => (parse_config(path), None),
+        (None, None, None) => (Config::default(), None),
+    };
+    if let Some(pipeline) = args.pipeline {
+        config.pipeline = pipeline;
+    }
+    if let Some(fields) = &args.log_omit {
+        config.log_omit = fields.clone();
+    }
+    if (args.pipeline.is_some() || args.log_omit.is_some())
+        && let Err(err) = config.validate()
+    {
+        eprintln!("Invalid config: {}", err);
+        process::exit(1);
+    }
+    let test_case = match (&restored, &replay) {
+        (None, None) => testcase::load(&args.input_path).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            process::exit(1);
+        }),
+        _ => None,
+    };
+    if let Some(case) = &test_case {
+        if let Err(err) = case.apply(&mut config) {
+            eprintln!("Invalid test case: {}", err);
+            process::exit(1);
+        }
+        if !case.memory.is_empty() {
+            eprintln!(
+                "warning: ignoring {} memory words of the test case; the simulator has no data \
+                 memory",
+                case.memory.len()
+            );
+        }
+    }
+    if args.format == LogFormat::Msgpack && json_io::is_ndjson(output_path) {
+        eprintln!("MessagePack logs cannot be streamed as NDJSON");
+        process::exit(1);
+    }
+    let tracks_lifetimes = args.timeline_path.is_some()
+        || args.trace_path.is_some()
+        || args.pipeview_path.is_some()
+        || args.events_path.is_some()
+        || args.commits_path.is_some()
+        || args.renames_path.is_some()
+        || args.diagram.is_some()
+        || args.report_path.is_some();
+    if tracks_lifetimes && config.pipeline != Pipeline::OutOfOrder {
+        eprintln!(
+            "--timeline, --trace, --o3pipeview, --events, --commits, --renames, --diagram and \
+             --report are only supported by the out-of-order pipeline"
+        );
+        process::exit(1);
+    }
+    if args.renames_path.is_some() && config.renaming != Renaming::MergedRegisterFile {
+        eprintln!("--renames is only supported with a merged register file");
+        process::exit(1);
+    }
+    if args.profile.is_some() && config.pipeline != Pipeline::OutOfOrder {
+        eprintln!("--profile is only supported by the out-of-order pipeline");
+        process::exit(1);
+    }
+    if args.fast_forward.is_some() && config.fp.enabled {
+        eprintln!("--fast-forward is only supported without FP units");
+        process::exit(1);
+    }
+    if let Some(until @ Until::Pc(address)) = args.until {
+        if config.pipeline != Pipeline::OutOfOrder {
+            eprintln!(
+                "{} is only supported by the out-of-order pipeline",
+                until.option()
+            );
+            process::exit(1);
+        }
+        if !address.is_multiple_of(config.pc_step()) {
+            eprintln!(
+                "{} {:#x} is not a multiple of the instruction size {}",
+                until.option(),
+                address,
+                config.pc_step()
+            );
+            process::exit(1);
+        }
+    }
+    if args.check && (config.pipeline != Pipeline::OutOfOrder || config.fp.enabled) {
+        eprintln!("--check is only supported by the out-of-order pipeline without FP units");
+        process::exit(1);
+    }
+    if args.handler_path.is_some()
+        && (config.pipeline != Pipeline::OutOfOrder || !args.thread_paths.is_empty())
+    {
+        eprintln!("--handler is only supported by the single-threaded out-of-order pipeline");
+        process::exit(1);
+    }
+    if args.interrupts_path.is_some()
+        && (config.pipeline != Pipeline::OutOfOrder || !args.thread_paths.is_empty())
+    {
+        eprintln!("--interrupts is only supported by the single-threaded out-of-order pipeline");
+        process::exit(1);
+    }
+    if replay.is_some() {
+        // The replay file holds every input of the run.
+        let fixed = [
+            ("--config", args.config_path.is_some()),
+            ("--pipeline", args.pipeline.is_some()),
+            ("--handler", args.handler_path.is_some()),
+            ("--interrupts", args.interrupts_path.is_some()),
+            ("--record", args.record_path.is_some()),
+        ];
+        if let Some((option, _)) = fixed.iter().find(|(_, given)| *given) {
+            eprintln!("{} cannot be combined with --replay", option);
+            process::exit(1);
+        }
+    }
+    if restored.is_some() {
+        // The checkpoint holds everything the run was set up with.
+        let fixed = [
+            ("--config", args.config_path.is_some()),
+            ("--pipeline", args.pipeline.is_some()),
+            ("--handler", args.handler_path.is_some()),
+            ("--interrupts", args.interrupts_path.is_some()),
+            ("--check", args.check),
+            ("--fast-forward", args.fast_forward.is_some()),
+            ("--repeat", args.repeat > 1),
+            (
+                "--log-from and --log-to",
+                args.log_range != (0..=usize::MAX),
+            ),
+        ];
+        if let Some((option, _)) = fixed.iter().find(|(_, given)| *given) {
+            eprintln!("{} cannot be combined with --restore-checkpoint", option);
+            process::exit(1);
+        }
+    }
+    if (args.save_path.is_some() || restored.is_some()) && json_io::is_ndjson(output_path) {
+        eprintln!("Checkpoints keep the log in memory and cannot be combined with NDJSON output");
+        process::exit(1);
+    }
+    if let Some(remote) = &args.remote {
+        if config.pipeline != Pipeline::OutOfOrder || !args.thread_paths.is_empty() {
+            eprintln!(
+                "{} is only supported by the single-threaded out-of-order pipeline",
+                remote.option()
+            );
+            process::exit(1);
+        }
+        if args.repeat > 1 {
+            eprintln!("{} cannot be combined with --repeat", remote.option());
+            process::exit(1);
+        }
+    }
+    if args.assert_invariants && config.pipeline != Pipeline::OutOfOrder {
+        eprintln!("--assert-invariants is only supported by the out-of-order pipeline");
+        process::exit(1);
+    }
+    if let Some(mode) = args.compat {
+        if let Err(err) = config.check_compat(mode) {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+        // Stop hooks and log windows truncate the log; delta encoding,
+        // NDJSON and binary formats change its shape, as further threads do.
+        if args.handler_path.is_some() {
+            eprintln!("--handler is not allowed in compatibility mode");
+            process::exit(1);
+        }
+        if args.interrupts_path.is_some() {
+            eprintln!("--interrupts is not allowed in compatibility mode");
+            process::exit(1);
+        }
+        if !args.thread_paths.is_empty() {
+            eprintln!("Programs of further threads are not allowed in compatibility mode");
+            process::exit(1);
+        }
+        if let Some(remote) = &args.remote {
+            eprintln!("{} is not allowed in compatibility mode", remote.option());
+            process::exit(1);
+        }
+        if args.hooks_path.is_some() {
+            eprintln!("--hooks is not allowed in compatibility mode");
+            process::exit(1);
+        }
+        if args.delta.is_some() {
+            eprintln!("--delta is not allowed in compatibility mode");
+            process::exit(1);
+        }
+        if args.format != LogFormat::Json {
+            eprintln!("--format is not allowed in compatibility mode");
+            process::exit(1);
+        }
+        if json_io::is_ndjson(output_path) {
+            eprintln!("NDJSON output is not allowed in compatibility mode");
+            process::exit(1);
+        }
+        if args.log_range != (0..=usize::MAX) {
+            eprintln!("--log-from and --log-to are not allowed in compatibility mode");
+            process::exit(1);
+        }
+        if let Some(until) = args.until {
+            eprintln!("{} is not allowed in compatibility mode", until.option());
+            process::exit(1);
+        }
+        // They skip or cut off states of the reference log.
+        let limits = [
+            ("--fast-forward", args.fast_forward.is_some()),
+            ("--max-instructions", args.max_instructions.is_some()),
+            ("--max-cycles", args.max_cycles.is_some()),
+        ];
+        if let Some((option, _)) = limits.iter().find(|(_, given)| *given) {
+            eprintln!("{} is not allowed in compatibility mode", option);
+            process::exit(1);
+        }
+    }
+
+    if args.print_config {
+        status!("{}", serde_json::to_string_pretty(&config).unwrap());
+    }
+
+    let mut hooks = args.hooks_path.as_deref().map(|path| {
+        Hooks::load(path).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            process::exit(1);
+        })
+    });
+    let resumed = restored.is_some();
+    let (mut sim, inputs, mut warnings) = match restored {
+        Some(mut sim) => {
+            status!("Checkpoint restored after cycle {}.", sim.cycles());
+            sim.watchdog = None;
+            sim.state.log_omit = Shared::new(config.log_omit.clone());
+            let warnings = warnings::check_program(&sim.program, &config);
+            (sim, None, warnings)
+        }
+        None => {
+            let (sim, inputs, warnings) = load(args, &config, replay);
+            (sim, Some(inputs), warnings)
+        }
+    };
+    sim.assert_invariants = args.assert_invariants;
+    sim.max_cycles = args.max_cycles;
+    sim.max_instructions = args.max_instructions;
+    if args.profile.is_some() && sim.profile.is_none() {
+        sim.profile = Some(Profile::default());
+    }
+    if let Some(path) = &args.events_path {
+        let writer = observer::EventWriter::create(path).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            process::exit(1);
+        });
+        sim.add_observer(Box::new(writer));
+    }
+    if let Some(path) = &args.commits_path {
+        let writer = observer::RetireWriter::create(path).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            process::exit(1);
+        });
+        sim.add_observer(Box::new(writer));
+    }
+    if let Some(path) = &args.renames_path {
+        let writer =
+            observer::RenameWriter::create(path, &args.rename_snapshots).unwrap_or_else(|err| {
+                eprintln!("{}", err);
+                process::exit(1);
+            });
+        sim.add_observer(Box::new(writer));
+    }
+    if let Some(narrator) = observer::Narrator::from_env() {
+        sim.add_observer(Box::new(narrator));
+    }
+    if json_io::is_ndjson(output_path) {
+        let stream = LogStream::create(output_path);
+        sim.log_stream = Some(match args.delta {
+            Some(interval) => stream.with_delta(interval),
+            None => stream,
+        });
+    }
+    let mut progress = Progress::new(args.quiet);
+    let timer = HostTimer::start(&sim);
+    interrupt::install();
+    // The state reached is logged first, as the reset state would be.
+    let resumed = resumed && args.until.is_none();
+    if let Some(until) = args.until
+        && !sim.run_until(until, progress.as_mut())
+    {
+        eprintln!(
+            "warning: the run ended before reaching {}; only its last state is logged",
+            until.option()
+        );
+    }
+    let mut sim = run(sim, hooks.as_mut(), resumed, args.remote.as_ref(), progress);
+    interrupt::uninstall();
+    let interrupted = interrupt::requested();
+    if interrupted {
+        eprintln!(
+            "Interrupted after cycle {}: {} micro-ops committed (IPC {:.3}), PC {}; the log is \
+             truncated there",
+            sim.cycles(),
+            sim.committed,
+            sim.committed as f64 / sim.cycles().max(1) as f64,
+            sim.state.pc
+        );
+    }
+    if !sim.console.is_empty() {
+        status!("Program output:\n{}", sim.console);
+    }
+    if let Some(halt) = sim.halt {
+        status!("{} after {} cycles", halt, sim.cycles());
+    }
+
+    if sim.roi.as_ref().is_some_and(|roi| !roi.began()) {
+        eprintln!("warning: the region of interest never began; the counters cover the whole run");
+    }
+    if let Some(poisoned) = &sim.first_poisoned_use {
+        let warning = warnings::poisoned_use_warning(poisoned);
+        eprintln!(
+            "warning: instruction {}: {}",
+            warning.index, warning.message
+        );
+        warnings.push(warning);
+    }
+
+    // 3. Save the output JSON log, or finish the streamed one.
+    let save_start = Instant::now();
+    match sim.log_stream.take() {
+        Some(mut stream) => {
+            if config.log_header || interrupted {
+                // Warnings are complete only now, so the header comes last.
+                let header = header(&sim, &config, &warnings);
+                stream.write(&serde_json::json!({ "Header": header }));
+            }
+            stream.finish();
+        }
+        None => {
+            let log = log_output(&sim, &config, &warnings, args.delta, None);
+            match args.format {
+                LogFormat::Json => save_log_value(output_path, &log, None),
+                LogFormat::Msgpack => save_msgpack(output_path, &log),
+            }
+        }
+    }
+    let host = timer.report(&sim, save_start.elapsed());
+    match json_io::is_stdio(output_path) {
+        true => status!("Simulation log written to standard output"),
+        false => status!("Simulation log saved to {}", output_path),
+    }
+    if let Some(path) = &args.save_path {
+        // Reaching --max-cycles pauses the run rather than failing it.
+        if sim.max_cycles.is_some_and(|max| sim.cycles() >= max) {
+            sim.watchdog = None;
+        }
+        checkpoint::save(path, &config, &sim).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            process::exit(1);
+        });
+        status!("Checkpoint saved to {} after cycle {}", path, sim.cycles());
+    }
+    if let Some(report) = &sim.watchdog {
+        eprint!("{}", report);
+        process::exit(1);
+    }
+    if let Some(failure) = sim.checker.as_ref().and_then(|c| c.failure.as_ref()) {
+        eprintln!("Check failed at {}", failure);
+        process::exit(1);
+    }
+    if sim.checker.is_some() {
+        status!(
+            "Check passed: {} commits match the reference",
+            sim.committed
+        );
+    }
+    if let Some(path) = &args.bandwidth_path {
+        save_bandwidth(path, &sim.bandwidth);
+        status!("Bandwidth timeline saved to {}", path);
+    }
+    if let Some(path) = &args.metrics_path {
+        save_metrics(path, &sim.metrics);
+        status!("Cycle metrics saved to {}", path);
+    }
+    if let Some(path) = &args.timeline_path {
+        save_timeline(path, &sim.lifetimes);
+        status!("Instruction timeline saved to {}", path);
+    }
+    if let Some(path) = &args.trace_path {
+        save_log(path, &trace::trace_events(&sim.lifetimes));
+        status!("Chrome trace saved to {}", path);
+    }
+    if let Some(path) = &args.pipeview_path {
+        trace::save_o3_pipeview(path, &sim);
+        status!("O3PipeView trace saved to {}", path);
+    }
+    if let Some(path) = &args.report_path {
+        report::save_report(path, &args.input_path, &sim);
+        status!("Report saved to {}", path);
+    }
+    // Dropping the writers flushes them.
+    sim.observers.clear();
+    if let Some(path) = &args.events_path {
+        status!("Pipeline events saved to {}", path);
+    }
+    if let Some(path) = &args.commits_path {
+        status!("Commit trace saved to {}", path);
+    }
+    if let Some(path) = &args.renames_path {
+        status!("Rename trace saved to {}", path);
+    }
+    if let Some(pcs) = &args.diagram {
+        let diagram = diagram::render(&sim.lifetimes, pcs);
+        status!("{}", diagram.strip_suffix('\n').unwrap_or(&diagram));
+    }
+    if let Some(n) = args.profile {
+        let profile = sim.render_profile(n);
+        status!("{}", profile.strip_suffix('\n').unwrap_or(&profile));
+    }
+    if args.stats {
+        let path = Path::new(output_path).with_file_name("stats.json");
+        let path = path.to_string_lossy();
+        let mut stats = sim.stats.to_json();
+        // Only the out-of-order pipeline counts structure accesses.
+        let energy = config.pipeline == Pipeline::OutOfOrder;
+        if energy {
+            stats["Energy"] =
+                energy::to_json(&sim.stats.accesses, &config.energy, sim.stats.committed);
+            stats["Area"] = area::to_json(&config);
+        }
+        save_log(&path, &stats);
+        status!("Statistics saved to {}", path);
+        if let Some(stack) = sim.stats.cpi_stack() {
+            status!("CPI stack:");
+            for (component, cpi) in stack {
+                status!("  {:<20} {:.3}", component, cpi);
+            }
+        }
+        if energy {
+            let structures = energy::breakdown(&sim.stats.accesses, &config.energy);
+            let total: f64 = structures.iter().map(|(_, energy)| energy).sum();
+            status!(
+                "Energy: {:.1} pJ, {:.2} pJ per instruction",
+                total,
+                total / sim.stats.committed.max(1) as f64
+            );
+            for (structure, energy) in structures {
+                status!("  {:<20} {:.1} pJ", structure, energy);
+            }
+            let structures = area::estimate(&config);
+            let total: f64 = structures.iter().map(|(_, area)| area).sum();
+            status!("Area: {:.0} um^2", total);
+            for (structure, area) in structures {
+                status!("  {:<20} {:.0} um^2", structure, area);
+            }
+        }
+    }
+
+    if sim.num_threads() > 1 {
+        let cycles = sim.cycles().max(1) as f64;
+        for (t, committed) in sim.smt_committed().iter().enumerate() {
+            status!(
+                "Thread {}: {} committed, IPC {:.3}",
+                t,
+                committed,
+                *committed as f64 / cycles
+            );
+        }
+    }
+
+    if !config.alu_capabilities.is_empty() {
+        status!(
+            "Issued per ALU: {:?}, capability stalls: {}",
+            sim.steering.issued_per_alu,
+            sim.steering.capability_stalls
+        );
+    }
+    if config.alu_binding != AluBinding::FirstFree {
+        let cycles = sim.stats.cycles.max(1) as f64;
+        let utilization: Vec<String> = sim
+            .stats
+            .alu_busy_cycles
+            .iter()
+            .map(|&busy| format!("{:.3}", busy as f64 / cycles))
+            .collect();
+        status!(
+            "ALU utilization: [{}], binding stalls: {}",
+            utilization.join(", "),
+            sim.steering.binding_stalls
+        );
+    }
+
+    if let Some(vp) = &sim.value_prediction {
+        status!(
+            "Value prediction: {} of {} eligible predicted (coverage {:.3}), {} correct (accuracy {:.3}), {} squashes",
+            vp.predicted,
+            vp.eligible,
+            vp.coverage(),
+            vp.correct,
+            vp.accuracy(),
+            vp.squashes
+        );
+    }
+
+    if let Some(latency) = &sim.random_latency {
+        status!(
+            "Random latency: {} extra cycles over {} micro-ops",
+            latency.extra_cycles,
+            latency.drawn
+        );
+    }
+
+    if sim.recovery.is_enabled() {
+        status!(
+            "Exception recovery: {} cycles, {} by rollback; {} restored from a snapshot",
+            sim.stats.recovery_cycles,
+            sim.stats.rollback_recovery_cycles,
+            sim.recovery.restored
+        );
+    }
+
+    if config.move_elimination {
+        status!("Eliminated moves: {}", sim.stats.eliminated_moves);
+    }
+    if config.idiom_elimination {
+        status!("Eliminated idioms: {}", sim.stats.eliminated_idioms);
+    }
+
+    if sim.writeback.is_limited() {
+        status!("Writeback stalls: {}", sim.stats.writeback_stalls);
+    }
+    if sim.read_ports.is_some() {
+        status!("Read port stalls: {}", sim.stats.read_port_stalls);
+    }
+    if sim.scheduler.select_ports.is_some() {
+        status!("Select port stalls: {}", sim.stats.select_port_stalls);
+    }
+
+    if let Some(hooks) = &hooks {
+        if let Some(condition) = &hooks.stopped_by {
+            status!(
+                "Stopped after cycle {} by hook '{}'",
+                sim.cycles(),
+                condition
+            );
+        }
+        status!("Hook counters:");
+        hooks.report();
+    }
+
+    status!("{}", host);
+
+    if interrupted {
+        process::exit(130);
+    }
+    if let Some(case) = &test_case {
+        let mismatches = case.check(&sim);
+        if !mismatches.is_empty() {
+            for mismatch in &mismatches {
+                eprintln!("error: {}", mismatch);
+            }
+            eprintln!("The final state does not match the test case");
+            process::exit(1);
+        }
+        status!("The final state matches the test case");
+    }
+
+    // A restored run cannot be repeated.
+    if args.repeat > 1
+        && let Some(inputs) = &inputs
+    {
+        report_repeats(inputs, &config, sim, args);
+    }
+}
+
+/// Loads and validates the programs, from the input files or a replay
+/// file, and builds the simulator for a fresh run, returning it with the
+/// inputs of the run and the program warnings.
+fn load(
+    args: &SimulateArgs,
+    config: &Config,
+    replay: Option<Replay>,
+) -> (Simulator, Replay, Vec<Warning>) {
+    // 0. Parse JSON (or load an ELF binary) to get the program.
+    // A replay holds the programs as loaded, with no file to point into.
+    let (inputs, files) = match replay {
+        Some(replay) => {
+            status!("Replaying {}.", args.replay_path.as_deref().unwrap());
+            (replay, None)
+        }
+        None => {
+            let program = load_input(&args.input_path);
+            let threads: Vec<Input> = args.thread_paths.iter().map(|p| load_input(p)).collect();
+            let handler = args.handler_path.as_deref().map(load_input);
+            let replay = Replay {
+                config: config.clone(),
+                program: program.program.clone(),
+                threads: threads.iter().map(|t| t.program.clone()).collect(),
+                handler: handler.as_ref().map(|h| h.program.clone()),
+                interrupts: args
+                    .interrupts_path
+                    .as_deref()
+                    .map(json_io::parse_interrupts)
+                    .unwrap_or_default(),
+            };
+            (replay, Some((program, threads, handler)))
+        }
+    };
+    if let Some(path) = &args.record_path {
+        replay::record(path, &inputs).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            process::exit(1);
+        });
+        status!("Run inputs recorded to {}", path);
+    }
+    let Replay {
+        program,
+        threads,
+        handler,
+        ..
+    } = &inputs;
+    status!("Program loaded. {} instructions.", program.len());
+    if let Err(err) = config.validate_threads(1 + threads.len()) {
+        eprintln!("Invalid config: {}", err);
+        process::exit(1);
+    }
+    for (t, thread) in threads.iter().enumerate() {
+        status!(
+            "Thread {} program loaded. {} instructions.",
+            t + 1,
+            thread.len()
+        );
+        for warning in warnings::check_program(thread, config) {
+            eprintln!(
+                "warning: thread {} instruction {}: {}",
+                t + 1,
+                warning.index,
+                warning.message
+            );
+        }
+    }
+    let in_input = |found: Vec<Diagnostic>, input: Option<&Input>| match input {
+        Some(input) => input.in_input(found),
+        None => found,
+    };
+    let (program_file, thread_files, handler_file) = match &files {
+        Some((program, threads, handler)) => (Some(program), Some(threads), handler.as_ref()),
+        None => (None, None, None),
+    };
+    let mut diagnostics: Vec<String> =
+        in_input(validate::check_program(program, config), program_file)
+            .iter()
+            .map(|diagnostic| diagnostic.to_string())
+            .collect();
+    for (t, thread) in threads.iter().enumerate() {
+        let found = validate::check_program(thread, config);
+        let thread_file = thread_files.map(|files| &files[t]);
+        for diagnostic in in_input(found, thread_file) {
+            diagnostics.push(format!("thread {} {}", t + 1, diagnostic));
+        }
+    }
+    if let Some(handler) = handler {
+        status!("Exception handler loaded. {} instructions.", handler.len());
+        let found = validate::check_handler(handler, config);
+        for diagnostic in in_input(found, handler_file) {
+            diagnostics.push(format!("handler {}", diagnostic));
+        }
+    }
+    if !diagnostics.is_empty() {
+        for diagnostic in &diagnostics {
+            eprintln!("error: {}", diagnostic);
+        }
+        eprintln!("Invalid program; nothing was simulated");
+        process::exit(1);
+    }
+    let warnings = warnings::check_program(program, config);
+    for warning in &warnings {
+        eprintln!(
+            "warning: instruction {}: {}",
+            warning.index, warning.message
+        );
+    }
+    if config.value_prediction.predictor != ValuePredictor::None && !threads.is_empty() {
+        eprintln!("Value prediction does not support multiple threads");
+        process::exit(1);
+    }
+    if config.recovery.mode == RecoveryMode::Snapshot && !threads.is_empty() {
+        eprintln!("Snapshot recovery does not support multiple threads");
+        process::exit(1);
+    }
+    if config.fetch_buffer.is_some() && !threads.is_empty() {
+        eprintln!("The fetch buffer does not support multiple threads");
+        process::exit(1);
+    }
+    if config.move_elimination && !threads.is_empty() {
+        eprintln!("Move elimination does not support multiple threads");
+        process::exit(1);
+    }
+    if args.check && !threads.is_empty() {
+        eprintln!("--check does not support multiple threads");
+        process::exit(1);
+    }
+    if args.fast_forward.is_some() && !threads.is_empty() {
+        eprintln!("--fast-forward does not support multiple threads");
         process::exit(1);
     }
-    let input_path = &args[1];
-    let output_path = &args[2];
+    let (sim, fast_forwarded) = prepare(args, config, &inputs);
+    if let Some(executed) = fast_forwarded {
+        status!("Fast-forwarded {} instructions.", executed);
+    }
+    (sim, inputs, warnings)
+}
+
+/// Builds the simulator for a fresh run of `inputs` under `config`, set up
+/// as `args` ask, returning it with the number of instructions
+/// fast-forwarded.
+fn prepare(args: &SimulateArgs, config: &Config, inputs: &Replay) -> (Simulator, Option<u64>) {
+    let mut sim = build(inputs.program.clone(), &inputs.threads, config);
+    sim.log_range = args.log_range.clone();
+    // Logs that start mid-run record the cycle of each state, as its
+    // position in the log no longer is.
+    if *args.log_range.start() > 0 || args.until.is_some() {
+        sim.state.log_cycle = true;
+    }
+    let fast_forwarded = args.fast_forward.map(|k| sim.fast_forward(config, k));
+    if args.check {
+        sim.checker = Some(check::Checker::new(&sim, config));
+    }
+    if let Some(handler) = &inputs.handler {
+        sim.set_handler(handler.clone(), config);
+    }
+    if !inputs.interrupts.is_empty() {
+        sim.schedule_interrupts(inputs.interrupts.clone());
+    }
+    (sim, fast_forwarded)
+}
+
+/// The log as saved: the per-cycle states, delta-encoded with the given
+/// keyframe interval if one is given, wrapped with a header if the config
+/// asks for one or the run was interrupted. The states are converted on `jobs` threads, the available
+/// parallelism by default.
+fn log_output(
+    sim: &Simulator,
+    config: &Config,
+    warnings: &[Warning],
+    delta: Option<usize>,
+    jobs: Option<usize>,
+) -> serde_json::Value {
+    let mut log_as_json = batch::parallel_map(&sim.log, jobs, SimulatorState::to_log_value);
+    if let Some(interval) = delta {
+        let mut encoder = delta::DeltaEncoder::new(interval);
+        log_as_json = log_as_json
+            .into_iter()
+            .map(|state| encoder.encode(state))
+            .collect();
+    }
+    if config.log_header || interrupt::requested() {
+        serde_json::json!({
+            "Header": header(sim, config, warnings),
+            "Log": log_as_json,
+        })
+    } else {
+        serde_json::Value::Array(log_as_json)
+    }
+}
 
-    // 0. Parse JSON to get the program.
-    let program = parse_instructions(input_path);
-    println!("Program loaded. {} instructions.", program.len());
+/// The log header: the warnings found in the program and the config the
+/// run used, so that the log alone is enough to reproduce it, and the cycle
+/// the log stops at if the run was interrupted.
+fn header(sim: &Simulator, config: &Config, warnings: &[Warning]) -> serde_json::Value {
+    let mut header = serde_json::json!({ "Warnings": warnings, "Config": config });
+    if interrupt::requested() {
+        header["Truncated"] = serde_json::json!({ "Reason": "interrupted", "Cycle": sim.cycles() });
+    }
+    header
+}
 
-    let mut sim = Simulator::new(program);
+/// Parses a JSON program, plain assembly file or dynamic trace and resolves
+/// its labels, or loads an ELF binary.
+fn load_program(path: &str) -> Vec<String> {
+    let input = load_input(path);
+    if !input.diagnostics.is_empty() {
+        for diagnostic in input.diagnostics {
+            eprintln!("error: {}: {}", path, diagnostic);
+        }
+        process::exit(1);
+    }
+    input.program
+}
+
+/// A program as loaded from its input file.
+struct Input {
+    program: Vec<String>,
+    /// The index in the input of every instruction, which dropped comments
+    /// and blank entries shift.
+    indices: Vec<usize>,
+    /// Entries that are not instructions and labels that are not well
+    /// defined, at their index in the input.
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Input {
+    /// The entries of the input at fault in `diagnostics` of the program,
+    /// after the problems found while loading it.
+    fn in_input(&self, found: Vec<Diagnostic>) -> Vec<Diagnostic> {
+        let mut diagnostics = self.diagnostics.clone();
+        diagnostics.extend(found.into_iter().map(|mut diagnostic| {
+            diagnostic.index = self.indices[diagnostic.index];
+            diagnostic
+        }));
+        diagnostics.sort_by_key(|diagnostic| diagnostic.index);
+        diagnostics
+    }
+}
+
+/// `load_program`, keeping every problem with the input to report along
+/// with those of the instructions.
+fn load_input(path: &str) -> Input {
+    if elf::is_elf_file(path) {
+        let program = elf::load_program(path);
+        return Input {
+            indices: (0..program.len()).collect(),
+            program,
+            diagnostics: Vec::new(),
+        };
+    }
+    let (program, mut diagnostics) = if json_io::is_assembly(path) {
+        (json_io::parse_assembly(path), Vec::new())
+    } else if json_io::is_trace(path) {
+        match json_io::parse_trace(path) {
+            Ok(program) => (program, Vec::new()),
+            Err(diagnostics) => (Vec::new(), diagnostics),
+        }
+    } else {
+        parse_instructions(path)
+    };
+    let (lines, found) = asm::resolve_labels_partially(&program);
+    diagnostics.extend(found);
+    diagnostics.sort_by_key(|diagnostic| diagnostic.index);
+    let (indices, program) = lines.into_iter().unzip();
+    Input {
+        program,
+        indices,
+        diagnostics,
+    }
+}
+
+/// A simulator running `program` and one more hardware thread per entry of
+/// `threads`.
+fn build(program: Vec<String>, threads: &[Vec<String>], config: &Config) -> Simulator {
+    let builder = threads.iter().fold(
+        SimulatorBuilder::new().program(program),
+        |builder, thread| builder.thread(thread.clone()),
+    );
+    builder.config(config).build().unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(1);
+    })
+}
+
+/// Simulates until the program finishes or the run is stopped, under the
+/// control of a `remote` client if one is given. A `resumed` run continues
+/// from a checkpoint whose states are already logged.
+fn run(
+    mut sim: Simulator,
+    mut hooks: Option<&mut Hooks>,
+    resumed: bool,
+    remote: Option<&Remote>,
+    mut progress: Option<Progress>,
+) -> Simulator {
+    let mut after_cycle = |sim: &Simulator| match hooks.as_deref_mut() {
+        Some(hooks) => hooks.after_cycle(sim.state.to_log_value()),
+        None => false,
+    };
 
     // 1. Dump the state of the reset system.
-    sim.dump_state_into_log();
+    let mut stopped = false;
+    if !resumed {
+        sim.dump_state_into_log();
+        if sim.assert_invariants {
+            sim.assert_invariants();
+        }
+        stopped = after_cycle(&sim);
+    }
+    // A run the watchdog or Ctrl-C stopped before it began, in `run_until`,
+    // stays stopped.
+    stopped |= sim.watchdog.is_some() || interrupt::requested();
 
     // 2. Cycle-by-cycle simulation loop.
-    while !sim.done() {
+    let mut advance = |sim: &mut Simulator| {
         sim.simulate_cycle();
         sim.dump_state_into_log();
+        if sim.assert_invariants {
+            sim.assert_invariants();
+        }
+        let mut stopped = after_cycle(sim);
+        // A failed check aborts the run at the divergent cycle.
+        stopped |= sim.checker.as_ref().is_some_and(|c| c.failure.is_some());
+        stopped |= interrupt::requested();
+        stopped | sim.watchdog_expired()
+    };
+    match remote {
+        Some(Remote::Gdb(address)) if !stopped => gdb::serve(&mut sim, address, advance),
+        Some(Remote::Cosim(address)) if !stopped => cosim::serve(&mut sim, address, advance),
+        _ => {
+            while !stopped && !sim.done() {
+                stopped = advance(&mut sim);
+                if let Some(progress) = progress.as_mut() {
+                    progress.update(&sim);
+                }
+            }
+        }
     }
+    if let Some(progress) = progress {
+        progress.finish();
+    }
+    sim.finish_roi();
+    sim
+}
 
-    // 3. Save the output JSON log.
-    let log_as_json: Vec<serde_json::Value> = sim
-        .log
-        .iter()
-        .map(|state| serde_json::to_value(state).unwrap())
-        .collect();
-    save_log(output_path, &log_as_json);
-    println!("Simulation log saved to {}", output_path);
+/// Reruns the program with seeds `seed + 1 ..` and summarizes the metrics of
+/// all `repeat` runs, the already finished first one included. Each rerun
+/// is set up as the first was, with the same handler, interrupts, hooks,
+/// check and limits, but logs only its reset state.
+fn report_repeats(inputs: &Replay, config: &Config, first: Simulator, args: &SimulateArgs) {
+    let repeat = args.repeat;
+    let mut cycles = Vec::new();
+    let mut ipc = Vec::new();
+    let mut stalls = Vec::new();
+    let mut record = |sim: &Simulator| {
+        let n = sim.cycles() as f64;
+        cycles.push(n);
+        ipc.push(if n > 0.0 {
+            sim.committed as f64 / n
+        } else {
+            0.0
+        });
+        stalls.push(sim.steering.capability_stalls as f64);
+    };
+    record(&first);
+    drop(first);
+    for i in 1..repeat {
+        let mut config = config.clone();
+        config.seed = config.seed.wrapping_add(i as u64);
+        let (mut sim, _) = prepare(args, &config, inputs);
+        sim.log_range = 0..=0;
+        sim.assert_invariants = args.assert_invariants;
+        sim.max_cycles = args.max_cycles;
+        sim.max_instructions = args.max_instructions;
+        let mut hooks = args.hooks_path.as_deref().map(|path| {
+            Hooks::load(path).unwrap_or_else(|err| {
+                eprintln!("{}", err);
+                process::exit(1);
+            })
+        });
+        let sim = run(sim, hooks.as_mut(), false, None, None);
+        if let Some(failure) = sim.checker.as_ref().and_then(|c| c.failure.as_ref()) {
+            eprintln!("warning: run {}: check failed at {}", i, failure);
+        }
+        record(&sim);
+    }
+    status!("Summary over {} runs (seeds {}..):", repeat, config.seed);
+    status!("  IPC: {}", Summary::of(&ipc));
+    status!("  Cycles: {}", Summary::of(&cycles));
+    if !config.alu_capabilities.is_empty() {
+        status!("  Capability stalls: {}", Summary::of(&stalls));
+    }
 }
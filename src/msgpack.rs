@@ -0,0 +1,306 @@
+use serde_json::{Map, Number, Value};
+use std::fs;
+
+use crate::cli::ConvertArgs;
+use crate::json_io::save_log;
+
+/// Encodes `value` as MessagePack, choosing the smallest representation of
+/// every integer, string, array and map.
+pub fn encode(value: &Value, out: &mut Vec<u8>) {
+    match value {
+        Value::Null => out.push(0xC0),
+        Value::Bool(b) => out.push(if *b { 0xC3 } else { 0xC2 }),
+        Value::Number(n) => {
+            if let Some(u) = n.as_u64() {
+                encode_uint(u, out);
+            } else if let Some(i) = n.as_i64() {
+                encode_int(i, out);
+            } else {
+                out.push(0xCB);
+                out.extend(n.as_f64().unwrap_or(0.0).to_be_bytes());
+            }
+        }
+        Value::String(s) => {
+            encode_len(s.len(), 0xA0, 32, [0xD9, 0xDA, 0xDB], out);
+            out.extend(s.as_bytes());
+        }
+        Value::Array(items) => {
+            encode_len(items.len(), 0x90, 16, [0, 0xDC, 0xDD], out);
+            for item in items {
+                encode(item, out);
+            }
+        }
+        Value::Object(fields) => {
+            encode_len(fields.len(), 0x80, 16, [0, 0xDE, 0xDF], out);
+            for (key, item) in fields {
+                encode_len(key.len(), 0xA0, 32, [0xD9, 0xDA, 0xDB], out);
+                out.extend(key.as_bytes());
+                encode(item, out);
+            }
+        }
+    }
+}
+
+fn encode_uint(u: u64, out: &mut Vec<u8>) {
+    if u < 0x80 {
+        out.push(u as u8);
+    } else if u <= u8::MAX as u64 {
+        out.extend([0xCC, u as u8]);
+    } else if u <= u16::MAX as u64 {
+        out.push(0xCD);
+        out.extend((u as u16).to_be_bytes());
+    } else if u <= u32::MAX as u64 {
+        out.push(0xCE);
+        out.extend((u as u32).to_be_bytes());
+    } else {
+        out.push(0xCF);
+        out.extend(u.to_be_bytes());
+    }
+}
+
+/// Encodes a negative integer.
+fn encode_int(i: i64, out: &mut Vec<u8>) {
+    if i >= -32 {
+        out.push(i as u8);
+    } else if i >= i8::MIN as i64 {
+        out.extend([0xD0, i as u8]);
+    } else if i >= i16::MIN as i64 {
+        out.push(0xD1);
+        out.extend((i as i16).to_be_bytes());
+    } else if i >= i32::MIN as i64 {
+        out.push(0xD2);
+        out.extend((i as i32).to_be_bytes());
+    } else {
+        out.push(0xD3);
+        out.extend(i.to_be_bytes());
+    }
+}
+
+/// Writes the header of a string, array or map of `len` elements: the fixed
+/// form below `fixed_limit`, else the 8-, 16- or 32-bit length form. Arrays
+/// and maps have no 8-bit form (`markers[0]` is 0 for them).
+fn encode_len(len: usize, fixed: u8, fixed_limit: usize, markers: [u8; 3], out: &mut Vec<u8>) {
+    if len < fixed_limit {
+        out.push(fixed | len as u8);
+    } else if markers[0] != 0 && len <= u8::MAX as usize {
+        out.extend([markers[0], len as u8]);
+    } else if len <= u16::MAX as usize {
+        out.push(markers[1]);
+        out.extend((len as u16).to_be_bytes());
+    } else {
+        out.push(markers[2]);
+        out.extend((len as u32).to_be_bytes());
+    }
+}
+
+/// Decodes one MessagePack document. Binary and extension values have no
+/// JSON counterpart and are rejected.
+pub fn decode(bytes: &[u8]) -> Result<Value, String> {
+    let mut reader = Reader { bytes, pos: 0 };
+    let value = reader.value()?;
+    if reader.pos != bytes.len() {
+        return Err(format!("trailing data at byte {}", reader.pos));
+    }
+    Ok(value)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, n: usize) -> Result<&[u8], String> {
+        let end = self.pos + n;
+        let slice = self
+            .bytes
+            .get(self.pos..end)
+            .ok_or_else(|| format!("unexpected end of data at byte {}", self.pos))?;
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn uint(&mut self, n: usize) -> Result<u64, String> {
+        Ok(self.take(n)?.iter().fold(0, |acc, &b| acc << 8 | b as u64))
+    }
+
+    fn int(&mut self, n: usize) -> Result<i64, String> {
+        let u = self.uint(n)?;
+        let shift = 64 - 8 * n as u32;
+        Ok((u << shift) as i64 >> shift)
+    }
+
+    fn string(&mut self, len: usize) -> Result<String, String> {
+        let at = self.pos;
+        String::from_utf8(self.take(len)?.to_vec())
+            .map_err(|_| format!("invalid UTF-8 string at byte {}", at))
+    }
+
+    fn array(&mut self, len: usize) -> Result<Value, String> {
+        (0..len)
+            .map(|_| self.value())
+            .collect::<Result<_, _>>()
+            .map(Value::Array)
+    }
+
+    fn map(&mut self, len: usize) -> Result<Value, String> {
+        let mut fields = Map::new();
+        for _ in 0..len {
+            let Value::String(key) = self.value()? else {
+                return Err(format!("non-string map key before byte {}", self.pos));
+            };
+            fields.insert(key, self.value()?);
+        }
+        Ok(Value::Object(fields))
+    }
+
+    fn float(f: f64) -> Value {
+        Number::from_f64(f).map_or(Value::Null, Value::Number)
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        let at = self.pos;
+        let marker = self.take(1)?[0];
+        Ok(match marker {
+            0x00..=0x7F => Value::from(marker),
+            0x80..=0x8F => self.map((marker & 0x0F) as usize)?,
+            0x90..=0x9F => self.array((marker & 0x0F) as usize)?,
+            0xA0..=0xBF => Value::String(self.string((marker & 0x1F) as usize)?),
+            0xC0 => Value::Null,
+            0xC2 => Value::Bool(false),
+            0xC3 => Value::Bool(true),
+            0xCA => {
+                let bits = self.uint(4)? as u32;
+                Reader::float(f32::from_bits(bits) as f64)
+            }
+            0xCB => Reader::float(f64::from_bits(self.uint(8)?)),
+            0xCC => Value::from(self.uint(1)?),
+            0xCD => Value::from(self.uint(2)?),
+            0xCE => Value::from(self.uint(4)?),
+            0xCF => Value::from(self.uint(8)?),
+            0xD0 => Value::from(self.int(1)?),
+            0xD1 => Value::from(self.int(2)?),
+            0xD2 => Value::from(self.int(4)?),
+            0xD3 => Value::from(self.int(8)?),
+            0xD9 => {
+                let len = self.uint(1)? as usize;
+                Value::String(self.string(len)?)
+            }
+            0xDA => {
+                let len = self.uint(2)? as usize;
+                Value::String(self.string(len)?)
+            }
+            0xDB => {
+                let len = self.uint(4)? as usize;
+                Value::String(self.string(len)?)
+            }
+            0xDC => {
+                let len = self.uint(2)? as usize;
+                self.array(len)?
+            }
+            0xDD => {
+                let len = self.uint(4)? as usize;
+                self.array(len)?
+            }
+            0xDE => {
+                let len = self.uint(2)? as usize;
+                self.map(len)?
+            }
+            0xDF => {
+                let len = self.uint(4)? as usize;
+                self.map(len)?
+            }
+            0xE0..=0xFF => Value::from(marker as i8),
+            _ => {
+                return Err(format!(
+                    "unsupported value type {:#04x} at byte {}",
+                    marker, at
+                ));
+            }
+        })
+    }
+}
+
+/// Writes a MessagePack log as pretty JSON.
+pub fn run_convert(args: &ConvertArgs) -> Result<(), String> {
+    let bytes =
+        fs::read(&args.log_path).map_err(|err| format!("Failed to read log file: {}", err))?;
+    let log = decode(&bytes).map_err(|err| format!("Failed to parse log file: {}", err))?;
+    save_log(&args.output_path, &log);
+    println!("Converted log saved to {}", args.output_path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn encoded(value: Value) -> Vec<u8> {
+        let mut out = Vec::new();
+        encode(&value, &mut out);
+        out
+    }
+
+    #[test]
+    fn encodes_the_smallest_forms() {
+        assert_eq!(encoded(json!(null)), [0xC0]);
+        assert_eq!(encoded(json!(true)), [0xC3]);
+        assert_eq!(encoded(json!(127)), [0x7F]);
+        assert_eq!(encoded(json!(128)), [0xCC, 0x80]);
+        assert_eq!(encoded(json!(256)), [0xCD, 0x01, 0x00]);
+        assert_eq!(encoded(json!(-32)), [0xE0]);
+        assert_eq!(encoded(json!(-33)), [0xD0, 0xDF]);
+        assert_eq!(encoded(json!(1.5)), [0xCB, 0x3F, 0xF8, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(encoded(json!("PC")), [0xA2, b'P', b'C']);
+        assert_eq!(encoded(json!([1, 2])), [0x92, 0x01, 0x02]);
+        assert_eq!(encoded(json!({"a": 1})), [0x81, 0xA1, b'a', 0x01]);
+        assert_eq!(&encoded(json!("x".repeat(32)))[..2], [0xD9, 32]);
+        assert_eq!(&encoded(json!(vec![0; 16]))[..3], [0xDC, 0, 16]);
+    }
+
+    #[test]
+    fn round_trips_a_state() {
+        let state = json!({
+            "PC": 4_294_967_296u64,
+            "ExceptionPC": 0,
+            "Exception": false,
+            "Offset": -40_000,
+            "Min": i64::MIN,
+            "Max": u64::MAX,
+            "Fp": [0.25, -1e300],
+            "IntegerQueue": [{"OpCode": "addi", "DestRegister": 33}],
+            "Note": "é".repeat(200),
+            "Long": vec![7; 70_000],
+            "Missing": null
+        });
+        assert_eq!(decode(&encoded(state.clone())), Ok(state));
+    }
+
+    #[test]
+    fn decodes_floats_and_fixed_negatives() {
+        assert_eq!(decode(&[0xCA, 0x3F, 0xC0, 0, 0]), Ok(json!(1.5)));
+        assert_eq!(decode(&[0xFF]), Ok(json!(-1)));
+        assert_eq!(decode(&[0xD1, 0xFF, 0x00]), Ok(json!(-256)));
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        assert_eq!(
+            decode(&[0x92, 0x01]),
+            Err("unexpected end of data at byte 2".to_string())
+        );
+        assert_eq!(
+            decode(&[0x01, 0x02]),
+            Err("trailing data at byte 1".to_string())
+        );
+        assert_eq!(
+            decode(&[0x81, 0x01, 0x01]),
+            Err("non-string map key before byte 2".to_string())
+        );
+        assert_eq!(
+            decode(&[0xC4, 0x00]),
+            Err("unsupported value type 0xc4 at byte 0".to_string())
+        );
+    }
+}
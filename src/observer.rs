@@ -0,0 +1,339 @@
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use serde_json::json;
+
+use crate::lifetime::Lifetime;
+use crate::simulator::{ActiveEntry, Commit, ExceptionCause, Simulator, SimulatorState};
+
+/// Callbacks on the events of the out-of-order pipeline, called by the
+/// simulator as it runs. Each receives the cycle and the lifetime of the
+/// micro-op with the stage just stamped, so custom metrics or
+/// visualizations need no changes to the cycle loop. Every callback
+/// defaults to doing nothing.
+pub trait PipelineObserver {
+    fn on_fetch(&mut self, _cycle: u64, _micro_op: &Lifetime) {}
+    fn on_dispatch(&mut self, _cycle: u64, _micro_op: &Lifetime) {}
+    /// The micro-op was renamed into the active list entry `entry`, after
+    /// every micro-op dispatched with it.
+    fn on_rename(&mut self, _cycle: u64, _micro_op: &Lifetime, _entry: &ActiveEntry) {}
+    fn on_issue(&mut self, _cycle: u64, _micro_op: &Lifetime) {}
+    /// The result was written back and the active list entry marked done.
+    fn on_writeback(&mut self, _cycle: u64, _micro_op: &Lifetime) {}
+    fn on_commit(&mut self, _cycle: u64, _micro_op: &Lifetime) {}
+    /// The micro-op left the active list with the architectural effect in
+    /// `commit`: the integer register it wrote, or the exception it raised.
+    fn on_retire(&mut self, _cycle: u64, _micro_op: &Lifetime, _commit: &Commit) {}
+    /// An exception or interrupt was taken at `pc`.
+    fn on_exception(&mut self, _cycle: u64, _pc: u64, _cause: Option<ExceptionCause>) {}
+    /// Rename stalled thread `thread` for the given reasons, such as
+    /// `free list short by 3`.
+    fn on_backpressure(&mut self, _cycle: u64, _thread: usize, _reasons: &[String]) {}
+    /// Cycle `cycle` was simulated, leaving `state`.
+    fn on_cycle(&mut self, _cycle: u64, _state: &SimulatorState) {}
+}
+
+impl Simulator {
+    pub fn add_observer(&mut self, observer: Box<dyn PipelineObserver>) {
+        self.observers.push(observer);
+    }
+
+    /// Reports the renames of the `dispatched` micro-ops just dispatched,
+    /// which took the youngest active list entries.
+    pub(crate) fn report_renames(&mut self, dispatched: usize) {
+        let cycle = self.cycle();
+        let list = &self.state.active_list;
+        for entry in list.iter().skip(list.len() - dispatched) {
+            let Some(lifetime) = self.lifetimes.get(entry.seq as usize) else {
+                continue;
+            };
+            for observer in self.observers.iter_mut() {
+                observer.on_rename(cycle, lifetime, entry);
+            }
+        }
+    }
+}
+
+/// Writes every pipeline event as a line of JSON (`--events`).
+pub struct EventWriter {
+    out: BufWriter<File>,
+}
+
+impl EventWriter {
+    pub fn create(path: &str) -> Result<EventWriter, String> {
+        let file =
+            File::create(path).map_err(|err| format!("Failed to create events file: {}", err))?;
+        Ok(EventWriter {
+            out: BufWriter::new(file),
+        })
+    }
+
+    fn write(&mut self, cycle: u64, event: &str, micro_op: &Lifetime) {
+        let line = json!({
+            "Cycle": cycle,
+            "Event": event,
+            "Seq": micro_op.seq,
+            "Thread": micro_op.thread,
+            "PC": micro_op.pc,
+            "Op": micro_op.op,
+        });
+        writeln!(self.out, "{}", line).expect("Failed to write events file");
+    }
+}
+
+impl PipelineObserver for EventWriter {
+    fn on_fetch(&mut self, cycle: u64, micro_op: &Lifetime) {
+        self.write(cycle, "fetch", micro_op);
+    }
+
+    fn on_dispatch(&mut self, cycle: u64, micro_op: &Lifetime) {
+        self.write(cycle, "dispatch", micro_op);
+    }
+
+    fn on_issue(&mut self, cycle: u64, micro_op: &Lifetime) {
+        self.write(cycle, "issue", micro_op);
+    }
+
+    fn on_writeback(&mut self, cycle: u64, micro_op: &Lifetime) {
+        self.write(cycle, "writeback", micro_op);
+    }
+
+    fn on_commit(&mut self, cycle: u64, micro_op: &Lifetime) {
+        self.write(cycle, "commit", micro_op);
+    }
+
+    fn on_exception(&mut self, cycle: u64, pc: u64, cause: Option<ExceptionCause>) {
+        let line = json!({
+            "Cycle": cycle,
+            "Event": "exception",
+            "PC": pc,
+            "Cause": cause,
+        });
+        writeln!(self.out, "{}", line).expect("Failed to write events file");
+    }
+}
+
+/// Writes every retired micro-op as a line of JSON (`--commits`): the
+/// cycle, PC and opcode, and the architectural register written with its
+/// new value or the exception raised instead.
+pub struct RetireWriter {
+    out: BufWriter<File>,
+}
+
+impl RetireWriter {
+    pub fn create(path: &str) -> Result<RetireWriter, String> {
+        let file =
+            File::create(path).map_err(|err| format!("Failed to create commit trace: {}", err))?;
+        Ok(RetireWriter {
+            out: BufWriter::new(file),
+        })
+    }
+}
+
+impl PipelineObserver for RetireWriter {
+    fn on_retire(&mut self, cycle: u64, micro_op: &Lifetime, commit: &Commit) {
+        let mut line = json!({
+            "Cycle": cycle,
+            "PC": micro_op.pc,
+            "Op": micro_op.op,
+        });
+        if let Some((register, value)) = commit.write {
+            line["Rd"] = json!(format!("x{}", register));
+            line["Value"] = json!(value);
+        }
+        if commit.exception {
+            line["Exception"] = json!(true);
+        }
+        writeln!(self.out, "{}", line).expect("Failed to write commit trace");
+    }
+}
+
+/// Writes every rename as a line of JSON (`--renames`): the cycle, PC and
+/// opcode, the architectural destination, the physical register allocated
+/// to it and the one it was mapped to, freed when the micro-op commits. An
+/// eliminated move shares its source's register instead of allocating one,
+/// and a write to a hardwired `x0` keeps its mapping and has neither. After
+/// each cycle of `snapshots` a line records the map table and free list.
+pub struct RenameWriter {
+    out: BufWriter<File>,
+    snapshots: BTreeSet<u64>,
+}
+
+impl RenameWriter {
+    pub fn create(path: &str, snapshots: &[u64]) -> Result<RenameWriter, String> {
+        let file =
+            File::create(path).map_err(|err| format!("Failed to create rename trace: {}", err))?;
+        Ok(RenameWriter {
+            out: BufWriter::new(file),
+            snapshots: snapshots.iter().copied().collect(),
+        })
+    }
+}
+
+impl PipelineObserver for RenameWriter {
+    fn on_rename(&mut self, cycle: u64, micro_op: &Lifetime, entry: &ActiveEntry) {
+        let file = if entry.is_fp { 'f' } else { 'x' };
+        let mut line = json!({
+            "Cycle": cycle,
+            "Seq": micro_op.seq,
+            "Thread": micro_op.thread,
+            "PC": micro_op.pc,
+            "Op": micro_op.op,
+            "Rd": format!("{}{}", file, entry.logical_destination),
+        });
+        if entry.eliminated {
+            line["Shared"] = json!(entry.destination);
+            line["Freed"] = json!(entry.old_destination);
+        } else if entry.destination != entry.old_destination {
+            line["Allocated"] = json!(entry.destination);
+            line["Freed"] = json!(entry.old_destination);
+        }
+        writeln!(self.out, "{}", line).expect("Failed to write rename trace");
+    }
+
+    fn on_cycle(&mut self, cycle: u64, state: &SimulatorState) {
+        if !self.snapshots.contains(&cycle) {
+            return;
+        }
+        let mut line = json!({
+            "Cycle": cycle,
+            "RegisterMapTable": *state.register_map_table,
+            "FreeList": *state.free_list,
+        });
+        if let Some(fp) = &state.fp {
+            line["FpRegisterMapTable"] = json!(*fp.register_map_table);
+            line["FpFreeList"] = json!(*fp.free_list);
+        }
+        writeln!(self.out, "{}", line).expect("Failed to write rename trace");
+    }
+}
+
+/// Targets of the narration, one per kind of event.
+const TARGETS: &[&str] = &[
+    "fetch",
+    "dispatch",
+    "issue",
+    "writeback",
+    "commit",
+    "exception",
+    "backpressure",
+];
+
+/// Narrates pipeline events on stderr, one line each, as selected by
+/// `RUST_LOG`: comma-separated directives `<level>` or `<target>=<level>`,
+/// where a target is an event kind such as `issue`, optionally prefixed
+/// with `ooo470::`. Events are narrated at level `debug`, so
+/// `RUST_LOG=debug` narrates everything and `RUST_LOG=issue=debug,commit=debug`
+/// only issues and commits.
+pub struct Narrator {
+    enabled: Vec<&'static str>,
+}
+
+impl Narrator {
+    /// The narrator `RUST_LOG` asks for, if it enables any target.
+    pub fn from_env() -> Option<Narrator> {
+        let filter = std::env::var("RUST_LOG").ok()?;
+        let mut enabled: Vec<&'static str> = Vec::new();
+        for directive in filter.split(',').map(str::trim) {
+            let (target, level) = match directive.split_once('=') {
+                Some((target, level)) => (Some(target), level),
+                None => (None, directive),
+            };
+            let on = matches!(level, "debug" | "trace");
+            let targets: Vec<&'static str> =
+                match target.map(|t| t.strip_prefix("ooo470::").unwrap_or(t)) {
+                    None | Some("ooo470") => TARGETS.to_vec(),
+                    Some(target) => TARGETS.iter().copied().filter(|t| *t == target).collect(),
+                };
+            for target in targets {
+                enabled.retain(|t| *t != target);
+                if on {
+                    enabled.push(target);
+                }
+            }
+        }
+        match enabled.is_empty() {
+            true => None,
+            false => Some(Narrator { enabled }),
+        }
+    }
+
+    fn say(&self, target: &str, cycle: u64, thread: usize, event: std::fmt::Arguments) {
+        if !self.enabled.contains(&target) {
+            return;
+        }
+        match thread {
+            0 => eprintln!("cycle {}: {}", cycle, event),
+            _ => eprintln!("cycle {}: thread {}: {}", cycle, thread, event),
+        }
+    }
+}
+
+impl PipelineObserver for Narrator {
+    fn on_fetch(&mut self, cycle: u64, m: &Lifetime) {
+        self.say(
+            "fetch",
+            cycle,
+            m.thread,
+            format_args!("fetched PC {} ({})", m.pc, m.op),
+        );
+    }
+
+    fn on_dispatch(&mut self, cycle: u64, m: &Lifetime) {
+        self.say(
+            "dispatch",
+            cycle,
+            m.thread,
+            format_args!("dispatched PC {}", m.pc),
+        );
+    }
+
+    fn on_issue(&mut self, cycle: u64, m: &Lifetime) {
+        self.say("issue", cycle, m.thread, format_args!("issued PC {}", m.pc));
+    }
+
+    fn on_writeback(&mut self, cycle: u64, m: &Lifetime) {
+        self.say(
+            "writeback",
+            cycle,
+            m.thread,
+            format_args!("wrote back PC {}", m.pc),
+        );
+    }
+
+    fn on_commit(&mut self, cycle: u64, m: &Lifetime) {
+        self.say(
+            "commit",
+            cycle,
+            m.thread,
+            format_args!("committed PC {}", m.pc),
+        );
+    }
+
+    fn on_exception(&mut self, cycle: u64, pc: u64, cause: Option<ExceptionCause>) {
+        match cause {
+            Some(cause) => self.say(
+                "exception",
+                cycle,
+                0,
+                format_args!("exception at PC {}: {:?}", pc, cause),
+            ),
+            None => self.say(
+                "exception",
+                cycle,
+                0,
+                format_args!("exception at PC {}", pc),
+            ),
+        }
+    }
+
+    fn on_backpressure(&mut self, cycle: u64, thread: usize, reasons: &[String]) {
+        self.say(
+            "backpressure",
+            cycle,
+            thread,
+            format_args!("backpressure: {}", reasons.join(", ")),
+        );
+    }
+}
@@ -0,0 +1,186 @@
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::units;
+
+/// An opcode as parsed once at decode: every micro-op, queue entry and ALU
+/// capability carries one of these instead of its name. It is logged as
+/// the name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Opcode {
+    #[default]
+    Add,
+    Sub,
+    Mulu,
+    Divu,
+    Remu,
+    Mul,
+    Div,
+    Rem,
+    Slt,
+    Sltu,
+    And,
+    Or,
+    Xor,
+    Sll,
+    Srl,
+    Sra,
+    Lui,
+    Auipc,
+    /// A fused wide `li` pseudo-instruction.
+    Li,
+    Unimp,
+    Ecall,
+    Ebreak,
+    Fadd,
+    Fsub,
+    Fmul,
+    Fdiv,
+    Csrrw,
+    Csrrs,
+    Csrrc,
+    /// An opcode registered with `units::register`, such as one of the
+    /// `custom_opcodes` of the config.
+    Custom(&'static str),
+}
+
+/// Opcodes understood by the ALUs of the reference design.
+pub const OPCODES: &[Opcode] = &[
+    Opcode::Add,
+    Opcode::Sub,
+    Opcode::Mulu,
+    Opcode::Divu,
+    Opcode::Remu,
+    Opcode::Mul,
+    Opcode::Div,
+    Opcode::Rem,
+    Opcode::Slt,
+    Opcode::Sltu,
+    Opcode::And,
+    Opcode::Or,
+    Opcode::Xor,
+    Opcode::Sll,
+    Opcode::Srl,
+    Opcode::Sra,
+    Opcode::Lui,
+    Opcode::Auipc,
+    Opcode::Li,
+    Opcode::Unimp,
+    Opcode::Ecall,
+    Opcode::Ebreak,
+];
+
+const FP_OPCODES: &[Opcode] = &[Opcode::Fadd, Opcode::Fsub, Opcode::Fmul, Opcode::Fdiv];
+
+/// CSR instructions, which every ALU executes, so that
+/// `alu_capabilities` need not list them.
+const CSR_OPCODES: &[Opcode] = &[Opcode::Csrrw, Opcode::Csrrs, Opcode::Csrrc];
+
+impl Opcode {
+    pub fn name(self) -> &'static str {
+        match self {
+            Opcode::Add => "add",
+            Opcode::Sub => "sub",
+            Opcode::Mulu => "mulu",
+            Opcode::Divu => "divu",
+            Opcode::Remu => "remu",
+            Opcode::Mul => "mul",
+            Opcode::Div => "div",
+            Opcode::Rem => "rem",
+            Opcode::Slt => "slt",
+            Opcode::Sltu => "sltu",
+            Opcode::And => "and",
+            Opcode::Or => "or",
+            Opcode::Xor => "xor",
+            Opcode::Sll => "sll",
+            Opcode::Srl => "srl",
+            Opcode::Sra => "sra",
+            Opcode::Lui => "lui",
+            Opcode::Auipc => "auipc",
+            Opcode::Li => "li",
+            Opcode::Unimp => "unimp",
+            Opcode::Ecall => "ecall",
+            Opcode::Ebreak => "ebreak",
+            Opcode::Fadd => "fadd",
+            Opcode::Fsub => "fsub",
+            Opcode::Fmul => "fmul",
+            Opcode::Fdiv => "fdiv",
+            Opcode::Csrrw => "csrrw",
+            Opcode::Csrrs => "csrrs",
+            Opcode::Csrrc => "csrrc",
+            Opcode::Custom(name) => name,
+        }
+    }
+
+    /// The opcode named `name`, reference or registered.
+    pub fn parse(name: &str) -> Option<Opcode> {
+        OPCODES
+            .iter()
+            .chain(FP_OPCODES)
+            .chain(CSR_OPCODES)
+            .copied()
+            .find(|op| op.name() == name)
+            .or_else(|| units::lookup(name).map(Opcode::Custom))
+    }
+
+    /// The opcodes an `alu_capabilities` entry names: a single integer
+    /// opcode, or the class `simple` of the reference opcodes other than
+    /// multiplies, divides and remainders, or the class `mul-div` of those.
+    /// Registered opcodes belong to no class.
+    pub fn capability(name: &str) -> Option<Vec<Opcode>> {
+        let class = |mul_div| {
+            OPCODES
+                .iter()
+                .copied()
+                .filter(move |op| op.is_mul_div() == mul_div)
+        };
+        match name {
+            "simple" => Some(class(false).collect()),
+            "mul-div" => Some(class(true).collect()),
+            _ => Opcode::parse(name)
+                .filter(|op| !op.is_fp())
+                .map(|op| vec![op]),
+        }
+    }
+
+    /// A multiply, divide or remainder, the long-latency class of
+    /// `alu_binding: "opcode-class"`.
+    pub fn is_mul_div(self) -> bool {
+        matches!(
+            self,
+            Opcode::Mul | Opcode::Mulu | Opcode::Div | Opcode::Divu | Opcode::Rem | Opcode::Remu
+        )
+    }
+
+    /// Executes on the FP units rather than the integer ALUs.
+    pub fn is_fp(self) -> bool {
+        FP_OPCODES.contains(&self)
+    }
+
+    /// Reads and updates a CSR; it issues only once every older micro-op
+    /// of its thread has committed.
+    pub fn is_csr(self) -> bool {
+        CSR_OPCODES.contains(&self)
+    }
+}
+
+impl fmt::Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl Serialize for Opcode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for Opcode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Opcode, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Opcode::parse(&name)
+            .ok_or_else(|| serde::de::Error::custom(format!("unknown opcode '{}'", name)))
+    }
+}
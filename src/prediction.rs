@@ -0,0 +1,291 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{ValuePredictionConfig, ValuePredictor};
+use crate::lifetime::Stage;
+use crate::opcode::Opcode;
+use crate::simulator::{DecodedInstructionEntry, Simulator};
+
+/// Results an instruction produced before, for predicting its next one.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+struct History {
+    last: u64,
+    stride: u64,
+    /// Correct predictions in a row.
+    confidence: u32,
+}
+
+/// A predicted result of an in-flight micro-op.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Prediction {
+    seq: u64,
+    value: u64,
+    /// A consumer was renamed with the predicted value.
+    used: bool,
+}
+
+/// Predicts the results of integer micro-ops at rename, so consumers can
+/// issue before their producer executes. A consumed misprediction squashes
+/// every younger micro-op when the producer writes back, and fetch restarts
+/// after it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ValuePrediction {
+    predictor: ValuePredictor,
+    confidence: u32,
+    /// History per instruction PC.
+    table: HashMap<u64, History>,
+    /// Predicted values of the physical registers still to be written.
+    predictions: HashMap<u32, Prediction>,
+    /// Renamed micro-ops whose result could be predicted.
+    pub eligible: u64,
+    pub predicted: u64,
+    pub correct: u64,
+    /// Mispredictions that squashed younger micro-ops.
+    pub squashes: u64,
+}
+
+impl ValuePrediction {
+    pub fn new(config: &ValuePredictionConfig) -> Option<Self> {
+        match config.predictor {
+            ValuePredictor::None => None,
+            predictor => Some(Self {
+                predictor,
+                confidence: config.confidence,
+                table: HashMap::new(),
+                predictions: HashMap::new(),
+                eligible: 0,
+                predicted: 0,
+                correct: 0,
+                squashes: 0,
+            }),
+        }
+    }
+
+    fn predict(&self, history: &History) -> u64 {
+        match self.predictor {
+            ValuePredictor::Stride => history.last.wrapping_add(history.stride),
+            _ => history.last,
+        }
+    }
+
+    fn train(&mut self, pc: u64, value: u64) {
+        let predicted = self.table.get(&pc).map(|h| self.predict(h));
+        let history = self.table.entry(pc).or_default();
+        history.confidence = match predicted == Some(value) {
+            true => history.confidence + 1,
+            false => 0,
+        };
+        history.stride = value.wrapping_sub(history.last);
+        history.last = value;
+    }
+
+    /// Fraction of the eligible micro-ops that were predicted.
+    pub fn coverage(&self) -> f64 {
+        self.predicted as f64 / self.eligible.max(1) as f64
+    }
+
+    /// Fraction of the predictions that were correct.
+    pub fn accuracy(&self) -> f64 {
+        self.correct as f64 / self.predicted.max(1) as f64
+    }
+}
+
+impl Simulator {
+    /// Predicts the result `instr` writes to `dest` if its instruction is
+    /// confident enough. System and CSR instructions, instructions cracked into
+    /// several micro-ops and discarded writes are never predicted.
+    pub(crate) fn predict_value(
+        &mut self,
+        instr: &DecodedInstructionEntry,
+        dest: u32,
+        discarded: bool,
+    ) {
+        let eligible = !discarded
+            && !matches!(instr.op, Opcode::Ecall | Opcode::Ebreak | Opcode::Unimp)
+            && !instr.op.is_csr()
+            && self
+                .decoded_instruction(instr.pc)
+                .is_some_and(|i| i.micro_ops.len() == 1 && !i.returns);
+        let Some(vp) = self.value_prediction.as_mut() else {
+            return;
+        };
+        // The register may still carry the prediction of a squashed producer.
+        vp.predictions.remove(&dest);
+        if !eligible {
+            return;
+        }
+        vp.eligible += 1;
+        let Some(history) = vp.table.get(&instr.pc) else {
+            return;
+        };
+        if history.confidence >= vp.confidence {
+            let value = vp.predict(history);
+            vp.predicted += 1;
+            vp.predictions.insert(
+                dest,
+                Prediction {
+                    seq: instr.seq,
+                    value,
+                    used: false,
+                },
+            );
+        }
+    }
+
+    /// `operand` as read at rename, made ready with the predicted value of
+    /// its producer if there is one.
+    pub(crate) fn speculate(&mut self, operand: (bool, u32, u64)) -> (bool, u32, u64) {
+        let (ready, tag, _) = operand;
+        let Some(vp) = self.value_prediction.as_mut() else {
+            return operand;
+        };
+        match vp.predictions.get_mut(&tag) {
+            Some(prediction) if !ready => {
+                prediction.used = true;
+                (true, 0, prediction.value)
+            }
+            _ => operand,
+        }
+    }
+
+    /// Checks the predictions of the `results` written back this cycle,
+    /// given as `(seq, pc, register, value)`, and trains the predictor.
+    pub(crate) fn verify_predictions(&mut self, results: &[(u64, u64, u32, u64)]) {
+        let Some(vp) = self.value_prediction.as_mut() else {
+            return;
+        };
+        let mut squash_after = None;
+        for &(seq, pc, reg, value) in results {
+            vp.train(pc, value);
+            let Some(prediction) = vp.predictions.remove(&reg).filter(|p| p.seq == seq) else {
+                continue;
+            };
+            if prediction.value == value {
+                vp.correct += 1;
+            } else if prediction.used {
+                squash_after = Some(squash_after.map_or(seq, |s: u64| s.min(seq)));
+            }
+        }
+        if let Some(seq) = squash_after {
+            vp.squashes += 1;
+            self.squash_younger(seq);
+        }
+    }
+
+    /// Rolls back every micro-op younger than `seq` at once and refetches
+    /// from the oldest of them.
+    fn squash_younger(&mut self, seq: u64) {
+        let mut squashed = HashSet::new();
+        let mut refetch = None;
+        while self.state.active_list.back().is_some_and(|e| e.seq > seq) {
+            let entry = self.state.active_list.pop_back().unwrap();
+            squashed.insert(entry.seq);
+            refetch = Some(entry.pc);
+            if self.discards_write(&entry) {
+                continue;
+            }
+            let arch = entry.logical_destination as usize;
+            self.state.register_map_table[arch] = entry.old_destination;
+            self.stats.accesses.map_table_writes += 1;
+            self.release(entry.destination);
+            if !entry.eliminated {
+                self.state.busy_bit_table[entry.destination as usize] = false;
+            }
+        }
+        self.recovery.release_after(seq);
+        let decoded: Vec<_> = self.front_end_micro_ops().cloned().collect();
+        self.state.decoded_pcs.clear();
+        if let Some(buffer) = self.state.fetch_buffer.as_mut() {
+            buffer.entries.clear();
+        }
+        refetch = refetch.or(decoded.first().map(|i| i.pc));
+        squashed.extend(decoded.iter().map(|i| i.seq));
+        let mut seqs: Vec<u64> = squashed.iter().copied().collect();
+        seqs.sort();
+        for seq in seqs {
+            self.record_stage(seq, Stage::Squash);
+        }
+        self.state
+            .integer_queue
+            .retain(|e| !squashed.contains(&e.seq));
+        for alu in self.alus.iter_mut() {
+            alu.squash(&squashed);
+        }
+        if let Some(pc) = refetch {
+            self.state.pc = pc;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::SimulatorBuilder;
+    use crate::config::Config;
+
+    fn value_prediction(predictor: ValuePredictor) -> ValuePrediction {
+        let config = ValuePredictionConfig {
+            predictor,
+            confidence: 2,
+        };
+        ValuePrediction::new(&config).unwrap()
+    }
+
+    /// Runs a producer and its consumer, with the producer's last results
+    /// having been `previous`.
+    fn run(previous: u64) -> Simulator {
+        let config = Config {
+            value_prediction: ValuePredictionConfig {
+                predictor: ValuePredictor::LastValue,
+                confidence: 2,
+            },
+            ..Config::default()
+        };
+        let program = ["addi x1, x0, 7", "add x2, x1, x1"];
+        let mut sim = SimulatorBuilder::new()
+            .program(program.iter().map(|line| line.to_string()).collect())
+            .config(&config)
+            .build()
+            .unwrap();
+        let vp = sim.value_prediction.as_mut().unwrap();
+        for _ in 0..3 {
+            vp.train(0, previous);
+        }
+        while !sim.done() {
+            sim.simulate_cycle();
+        }
+        sim
+    }
+
+    #[test]
+    fn predicts_after_enough_correct_results_in_a_row() {
+        let mut vp = value_prediction(ValuePredictor::Stride);
+        for value in [5, 7, 9] {
+            vp.train(0, value);
+        }
+        let history = &vp.table[&0];
+        assert_eq!((history.confidence, vp.predict(history)), (1, 11));
+        vp.train(0, 11);
+        assert_eq!(vp.table[&0].confidence, 2);
+        vp.train(0, 20);
+        assert_eq!(vp.table[&0].confidence, 0);
+    }
+
+    #[test]
+    fn a_correct_prediction_lets_the_consumer_go_ahead() {
+        let sim = run(7);
+        let vp = sim.value_prediction.as_ref().unwrap();
+        assert_eq!((vp.predicted, vp.correct, vp.squashes), (1, 1, 0));
+        assert_eq!(sim.architectural_registers()[1..3], [7, 14]);
+    }
+
+    #[test]
+    fn a_consumed_misprediction_squashes_and_replays_the_consumer() {
+        let sim = run(5);
+        let vp = sim.value_prediction.as_ref().unwrap();
+        assert_eq!((vp.predicted, vp.correct, vp.squashes), (1, 0, 1));
+        assert_eq!(sim.architectural_registers()[1..3], [7, 14]);
+        assert_eq!(sim.committed, 2);
+    }
+}
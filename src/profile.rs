@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::simulator::{COMMIT_WIDTH, Simulator};
+
+/// Dynamic execution and stall counts of one static instruction.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+pub struct PcCounts {
+    /// Times an instance of the instruction committed.
+    pub executed: u64,
+    /// Commit slots lost to the instruction, charged as the lost commit
+    /// slots are: to the instruction itself while it was incomplete, to the
+    /// head it waited behind otherwise.
+    pub stall_slots: u64,
+}
+
+impl PcCounts {
+    /// Commit slots the instruction occupied, by committing or by stalling.
+    fn slots(&self) -> u64 {
+        self.executed + self.stall_slots
+    }
+}
+
+/// Per-PC counts for `--profile`, by thread and instruction index.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Profile {
+    threads: Vec<HashMap<u64, PcCounts>>,
+    /// Lost commit slots no instruction was there to take: an empty active
+    /// list, rename backpressure or exception recovery.
+    pub unattributed: u64,
+}
+
+impl Profile {
+    fn counts(&mut self, thread: usize, pc: u64) -> &mut PcCounts {
+        if self.threads.len() <= thread {
+            self.threads.resize_with(thread + 1, HashMap::new);
+        }
+        self.threads[thread].entry(pc).or_default()
+    }
+
+    pub fn record_commit(&mut self, thread: usize, pc: u64) {
+        self.counts(thread, pc).executed += 1;
+    }
+
+    pub fn record_stall(&mut self, thread: usize, pc: Option<u64>) {
+        match pc {
+            Some(pc) => self.counts(thread, pc).stall_slots += 1,
+            None => self.unattributed += 1,
+        }
+    }
+}
+
+impl Simulator {
+    /// The `n` instructions that took the most commit slots as a table, each
+    /// with its share of the slots offered. A cycle offers `COMMIT_WIDTH`
+    /// slots per thread, so an instruction's cycles are its slots over that
+    /// width, and the shares of all instructions and the unattributed slots
+    /// add up to the whole run.
+    pub fn render_profile(&self, n: usize) -> String {
+        let Some(profile) = &self.profile else {
+            return String::new();
+        };
+        let mut hot: Vec<((usize, u64), &PcCounts)> = profile
+            .threads
+            .iter()
+            .enumerate()
+            .flat_map(|(t, counts)| counts.iter().map(move |(&pc, c)| ((t, pc), c)))
+            .collect();
+        hot.sort_by(|(a, x), (b, y)| y.slots().cmp(&x.slots()).then(a.cmp(b)));
+        let threads = self.num_threads() > 1;
+        let offered = self.stats.commit_slots.max(1) as f64;
+        let mut out = format!(
+            "Hottest PCs ({} of {}):\n{:>8}{} {:>10} {:>10} {:>10} {:>7}  Instruction\n",
+            hot.len().min(n),
+            hot.len(),
+            "PC",
+            if threads { " Thread" } else { "" },
+            "Executed",
+            "Stalls",
+            "Cycles",
+            "Share"
+        );
+        for ((thread, pc), counts) in hot.into_iter().take(n) {
+            let cycles_taken = counts.slots() as f64 / COMMIT_WIDTH as f64;
+            let text = self
+                .thread_instruction(thread, pc)
+                .map_or("", String::as_str);
+            out += &format!(
+                "{:>8}{} {:>10} {:>10} {:>10.1} {:>6.1}%  {}\n",
+                self.address(pc),
+                match threads {
+                    true => format!(" {:>6}", thread),
+                    false => String::new(),
+                },
+                counts.executed,
+                counts.stall_slots,
+                cycles_taken,
+                100.0 * counts.slots() as f64 / offered,
+                text
+            );
+        }
+        out + &format!(
+            "Slots lost with no instruction to commit: {}\n",
+            profile.unattributed
+        )
+    }
+}
@@ -0,0 +1,108 @@
+use std::io::{self, IsTerminal, Write};
+use std::time::{Duration, Instant};
+
+use crate::simulator::Simulator;
+
+/// Cycles a run simulates before progress is first shown.
+const FIRST_REPORT_CYCLE: usize = 5000;
+
+/// Interval between two updates of the progress line.
+const UPDATE_INTERVAL: Duration = Duration::from_millis(200);
+
+/// A progress line on stderr for long runs, rewritten in place: cycles
+/// simulated, instructions committed, simulation rate and the time
+/// remaining, estimated from the part of the program committed so far.
+pub struct Progress {
+    start: Instant,
+    last_update: Option<Instant>,
+}
+
+impl Progress {
+    /// Progress for a run, unless `quiet` or stderr is not a terminal.
+    pub fn new(quiet: bool) -> Option<Progress> {
+        match quiet || !io::stderr().is_terminal() {
+            true => None,
+            false => Some(Progress {
+                start: Instant::now(),
+                last_update: None,
+            }),
+        }
+    }
+
+    pub fn update(&mut self, sim: &Simulator) {
+        let now = Instant::now();
+        if sim.cycles() < FIRST_REPORT_CYCLE
+            || self
+                .last_update
+                .is_some_and(|last| now - last < UPDATE_INTERVAL)
+        {
+            return;
+        }
+        self.last_update = Some(now);
+        let elapsed = (now - self.start).as_secs_f64();
+        let rate = sim.cycles() as f64 / elapsed;
+        let committed = sim.smt_committed()[0];
+        let remaining = (sim.program.len() as u64).saturating_sub(committed);
+        let eta = match committed {
+            0 => "unknown".to_string(),
+            _ => format!("{:.0}s", elapsed * remaining as f64 / committed as f64),
+        };
+        eprint!(
+            "\rCycle {}, {} instructions committed, {:.0} cycles/s, ETA {}\x1b[K",
+            sim.cycles(),
+            sim.committed,
+            rate,
+            eta
+        );
+        io::stderr().flush().ok();
+    }
+
+    /// Clears the progress line once the run stops.
+    pub fn finish(self) {
+        if self.last_update.is_some() {
+            eprint!("\r\x1b[K");
+        }
+    }
+}
+
+/// Measures how fast the host simulates a run, for the report printed at its
+/// end: wall-clock time, simulation rate, and how that time divides between
+/// simulating and logging. Cycles simulated before a restored checkpoint do
+/// not count.
+pub struct HostTimer {
+    start: Instant,
+    cycles: usize,
+    committed: u64,
+    log_time: Duration,
+}
+
+impl HostTimer {
+    pub fn start(sim: &Simulator) -> HostTimer {
+        HostTimer {
+            start: Instant::now(),
+            cycles: sim.cycles(),
+            committed: sim.committed,
+            log_time: sim.log_time,
+        }
+    }
+
+    /// The report, measured up to now, once the run and the writing of its
+    /// log, which took `save_time`, have finished.
+    pub fn report(&self, sim: &Simulator, save_time: Duration) -> String {
+        let wall = self.start.elapsed().as_secs_f64();
+        let logging = (sim.log_time - self.log_time + save_time).as_secs_f64();
+        let rate = |n: f64| match wall {
+            0.0 => 0.0,
+            wall => n / wall,
+        };
+        format!(
+            "Host: {:.3}s wall clock, {:.0} cycles/s, {:.0} instructions/s; \
+             {:.3}s simulating, {:.3}s logging",
+            wall,
+            rate((sim.cycles() - self.cycles) as f64),
+            rate((sim.committed - self.committed) as f64),
+            (wall - logging).max(0.0),
+            logging
+        )
+    }
+}
@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+
+use crate::config::Reclamation;
+use crate::simulator::{NUM_ARCH_REGS, Simulator};
+
+/// Old destinations of committed micro-ops not yet returned to the free
+/// list, for reclamation policies later than the commit of the overwriting
+/// micro-op.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Reclaimer {
+    policy: Reclamation,
+    /// The register each thread's architectural register last held, indexed
+    /// by `thread * NUM_ARCH_REGS + register`.
+    held: Vec<Option<u32>>,
+}
+
+impl Reclaimer {
+    pub fn new(policy: Reclamation) -> Self {
+        let mut reclaimer = Self {
+            policy,
+            held: Vec::new(),
+        };
+        reclaimer.add_thread();
+        reclaimer
+    }
+
+    pub fn add_thread(&mut self) {
+        if self.policy == Reclamation::NextWriterCommit {
+            self.held.extend([None; NUM_ARCH_REGS]);
+        }
+    }
+
+    /// The registers held back from the free list with the architectural
+    /// register each belongs to, given as `(thread, register, physical)`.
+    pub fn held(&self) -> impl Iterator<Item = (usize, usize, u32)> + '_ {
+        self.held
+            .iter()
+            .enumerate()
+            .filter_map(|(i, &p)| p.map(|p| (i / NUM_ARCH_REGS, i % NUM_ARCH_REGS, p)))
+    }
+}
+
+impl Simulator {
+    /// Reclaims `old_destination`, which architectural register `arch` of
+    /// the current thread held until a micro-op overwriting it committed.
+    pub(crate) fn reclaim(&mut self, arch: usize, old_destination: u32) {
+        let reclaimed = match self.reclaimer.policy {
+            Reclamation::Commit => Some(old_destination),
+            Reclamation::NextWriterCommit => {
+                let slot = self.smt.current * NUM_ARCH_REGS + arch;
+                self.reclaimer.held[slot].replace(old_destination)
+            }
+        };
+        if let Some(reg) = reclaimed {
+            self.release(reg);
+        }
+    }
+}
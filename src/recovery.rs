@@ -0,0 +1,191 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{RecoveryConfig, RecoveryMode};
+use crate::opcode::Opcode;
+use crate::simulator::{DecodedInstructionEntry, Simulator};
+
+/// The register map table as it was before a micro-op was renamed.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Snapshot {
+    seq: u64,
+    map: Vec<u32>,
+}
+
+/// Rename snapshots for recovering from an exception in one cycle. A
+/// micro-op that may raise takes a snapshot at rename while one is free;
+/// if the excepting micro-op holds one, it is restored and every younger
+/// register freed at once instead of rolling the active list back from the
+/// tail.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Recovery {
+    enabled: bool,
+    capacity: usize,
+    /// Held snapshots, oldest first.
+    snapshots: VecDeque<Snapshot>,
+    /// Exceptions recovered from a snapshot.
+    pub restored: u64,
+}
+
+impl Recovery {
+    pub fn new(config: &RecoveryConfig) -> Self {
+        Self {
+            enabled: config.mode == RecoveryMode::Snapshot,
+            capacity: config.snapshots,
+            snapshots: VecDeque::new(),
+            restored: 0,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Frees the snapshots of micro-ops up to `seq`, which has committed.
+    pub(crate) fn release_through(&mut self, seq: u64) {
+        while self.snapshots.front().is_some_and(|s| s.seq <= seq) {
+            self.snapshots.pop_front();
+        }
+    }
+
+    /// Frees the snapshots of micro-ops younger than `seq`, which were
+    /// squashed.
+    pub(crate) fn release_after(&mut self, seq: u64) {
+        self.snapshots.retain(|s| s.seq <= seq);
+    }
+}
+
+/// Whether `op` can raise an exception when it executes.
+fn may_raise(op: Opcode, trap_on_overflow: bool) -> bool {
+    match op {
+        Opcode::Divu
+        | Opcode::Remu
+        | Opcode::Div
+        | Opcode::Rem
+        | Opcode::Unimp
+        | Opcode::Ecall
+        | Opcode::Custom(_) => true,
+        Opcode::Add | Opcode::Sub | Opcode::Mul => trap_on_overflow,
+        _ => false,
+    }
+}
+
+impl Simulator {
+    /// Snapshots the map table before renaming `instr` if it may raise and
+    /// a snapshot is free.
+    pub(crate) fn take_snapshot(&mut self, instr: &DecodedInstructionEntry) {
+        let recovery = &mut self.recovery;
+        if !recovery.enabled
+            || recovery.snapshots.len() == recovery.capacity
+            || !may_raise(instr.op, self.trap_on_overflow)
+        {
+            return;
+        }
+        recovery.snapshots.push_back(Snapshot {
+            seq: instr.seq,
+            map: self.state.register_map_table.to_vec(),
+        });
+    }
+
+    /// Restores the snapshot of the oldest active entry, which raised the
+    /// exception being recovered from, and frees the registers of every
+    /// active entry. Returns false, leaving the rollback to walk the active
+    /// list, if the entry holds no snapshot.
+    pub(crate) fn restore_snapshot(&mut self) -> bool {
+        let Some(head) = self.state.active_list.front() else {
+            return false;
+        };
+        let recovery = &mut self.recovery;
+        if recovery.snapshots.front().is_none_or(|s| s.seq != head.seq) {
+            return false;
+        }
+        let snapshot = recovery.snapshots.pop_front().unwrap();
+        recovery.snapshots.clear();
+        recovery.restored += 1;
+        *self.state.register_map_table = snapshot.map;
+        self.stats.accesses.map_table_writes += 1;
+        while let Some(entry) = self.state.active_list.pop_back() {
+            if self.discards_write(&entry) {
+                continue;
+            }
+            self.release(entry.destination);
+            if !entry.eliminated {
+                self.state.busy_bit_table[entry.destination as usize] = false;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::SimulatorBuilder;
+    use crate::config::Config;
+
+    fn run(lines: &[&str], mode: RecoveryMode, snapshots: usize) -> Simulator {
+        let config = Config {
+            initial_registers: vec![0, 6],
+            recovery: RecoveryConfig { mode, snapshots },
+            ..Config::default()
+        };
+        let mut sim = SimulatorBuilder::new()
+            .program(lines.iter().map(|line| line.to_string()).collect())
+            .config(&config)
+            .build()
+            .unwrap();
+        while !sim.done() {
+            sim.simulate_cycle();
+        }
+        sim
+    }
+
+    /// A division by zero followed by enough independent micro-ops to fill
+    /// the active list behind it.
+    fn faulting_program() -> Vec<&'static str> {
+        let mut lines = vec!["addi x2, x1, 1", "divu x3, x1, x0"];
+        lines.extend(["addi x4, x4, 1"; 16]);
+        lines
+    }
+
+    #[test]
+    fn a_snapshot_recovers_the_same_state_in_fewer_cycles() {
+        let program = faulting_program();
+        let rollback = run(&program, RecoveryMode::Rollback, 4);
+        let snapshot = run(&program, RecoveryMode::Snapshot, 4);
+        assert_eq!(snapshot.recovery.restored, 1);
+        assert_eq!(rollback.recovery.restored, 0);
+        assert_eq!(
+            snapshot.architectural_registers(),
+            rollback.architectural_registers()
+        );
+        assert_eq!(snapshot.state.exception_pc, 1);
+        assert_eq!(
+            snapshot.state.free_list.len(),
+            rollback.state.free_list.len()
+        );
+        assert!(snapshot.stats.recovery_cycles < rollback.stats.recovery_cycles);
+        assert_eq!(
+            snapshot.stats.rollback_recovery_cycles,
+            rollback.stats.recovery_cycles
+        );
+    }
+
+    #[test]
+    fn an_exception_without_a_snapshot_rolls_back() {
+        let mut program = vec!["divu x5, x1, x1"];
+        program.extend(faulting_program());
+        let rollback = run(&program, RecoveryMode::Rollback, 1);
+        let snapshot = run(&program, RecoveryMode::Snapshot, 1);
+        assert_eq!(snapshot.recovery.restored, 0);
+        assert_eq!(
+            snapshot.architectural_registers(),
+            rollback.architectural_registers()
+        );
+        assert_eq!(
+            snapshot.stats.recovery_cycles,
+            rollback.stats.recovery_cycles
+        );
+    }
+}
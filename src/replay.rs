@@ -0,0 +1,38 @@
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+
+/// Every input a run depends on: the effective config with its RNG seed,
+/// the programs and the external interrupts. Simulation is deterministic
+/// given these, so `--replay` reproduces a recorded run exactly.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Replay {
+    pub config: Config,
+    pub program: Vec<String>,
+    pub threads: Vec<Vec<String>>,
+    pub handler: Option<Vec<String>>,
+    /// Interrupt schedule as `(cycle, id)`.
+    pub interrupts: Vec<(u64, u32)>,
+}
+
+/// Writes the inputs of a run for `--replay`.
+pub fn record(path: &str, replay: &Replay) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(replay)
+        .map_err(|err| format!("Failed to serialize replay file: {}", err))?;
+    fs::write(path, json).map_err(|err| format!("Failed to write replay file: {}", err))
+}
+
+/// Reads a replay file written by `record`.
+pub fn load(path: &str) -> Result<Replay, String> {
+    let json =
+        fs::read_to_string(path).map_err(|err| format!("Failed to read replay file: {}", err))?;
+    let replay: Replay = serde_json::from_str(&json)
+        .map_err(|err| format!("Failed to parse replay file: {}", err))?;
+    replay
+        .config
+        .validate()
+        .map_err(|err| format!("Invalid config in replay file: {}", err))?;
+    Ok(replay)
+}
@@ -0,0 +1,227 @@
+use std::fs;
+use std::process;
+
+use crate::simulator::Simulator;
+use crate::stats::StallCause;
+
+/// Charts draw at most this many points per series; longer runs are
+/// averaged over buckets of cycles.
+const MAX_POINTS: usize = 600;
+
+/// Cycles the IPC chart averages over.
+const IPC_WINDOW: usize = 16;
+
+const CHART_WIDTH: f64 = 760.0;
+const CHART_HEIGHT: f64 = 220.0;
+/// Room left of and below the plot for the axis labels.
+const MARGIN: f64 = 40.0;
+
+const COLORS: [&str; 6] = [
+    "#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd", "#8c564b",
+];
+
+const STYLE: &str = "body { font-family: sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; font-size: 13px; }
+th, td { border: 1px solid #ccc; padding: 2px 8px; text-align: right; }
+td.text { text-align: left; font-family: monospace; }
+th { background: #eee; position: sticky; top: 0; }
+.summary td { text-align: left; }
+svg text { font-size: 11px; }
+input { margin: 0.5em 0; padding: 4px; width: 30em; }";
+
+/// Hides the table rows that do not contain the search text.
+const SCRIPT: &str = "function filterRows(query) {
+  query = query.toLowerCase();
+  for (const row of document.querySelectorAll('#commits tbody tr')) {
+    row.hidden = !row.textContent.toLowerCase().includes(query);
+  }
+}";
+
+/// Writes a standalone HTML page summarizing the run: IPC and queue
+/// occupancy over time and the lost commit slots by cause as inline SVG,
+/// and a table of every committed micro-op that can be searched in the
+/// browser. The page loads nothing from elsewhere.
+pub fn save_report(path: &str, input_path: &str, sim: &Simulator) {
+    // Restored and replayed runs have no input path.
+    let title = match input_path {
+        "" => "Simulation report".to_string(),
+        input_path => format!("Simulation report: {}", input_path),
+    };
+    let stats = &sim.stats;
+    let ipc = match stats.cycles {
+        0 => 0.0,
+        cycles => stats.committed as f64 / cycles as f64,
+    };
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+         <style>\n{}\n</style>\n<script>\n{}\n</script>\n</head>\n<body>\n<h1>{}</h1>\n",
+        escape(&title),
+        STYLE,
+        SCRIPT,
+        escape(&title)
+    );
+    html += &format!(
+        "<table class=\"summary\">\n<tr><th>Cycles</th><td>{}</td></tr>\n\
+         <tr><th>Committed</th><td>{}</td></tr>\n<tr><th>IPC</th><td>{:.3}</td></tr>\n</table>\n",
+        stats.cycles, stats.committed, ipc
+    );
+
+    // IPC over the last `IPC_WINDOW` cycles, from the running commit count.
+    let ipc_series: Vec<f64> = (0..sim.metrics.len())
+        .map(|c| {
+            let start = c.checked_sub(IPC_WINDOW);
+            let before = start.map_or(0, |s| sim.metrics[s].committed);
+            let cycles = start.map_or(c + 1, |s| c - s);
+            (sim.metrics[c].committed - before) as f64 / cycles as f64
+        })
+        .collect();
+    html += &format!("<h2>IPC over time ({}-cycle window)</h2>\n", IPC_WINDOW);
+    html += &line_chart(&[("IPC", ipc_series)]);
+    html += "<h2>Occupancy</h2>\n";
+    let occupancy = |f: fn(&crate::stats::CycleMetrics) -> usize| {
+        sim.metrics.iter().map(|m| f(m) as f64).collect()
+    };
+    html += &line_chart(&[
+        ("Integer queue", occupancy(|m| m.integer_queue)),
+        ("Active list", occupancy(|m| m.active_list)),
+        ("Free list", occupancy(|m| m.free_list)),
+    ]);
+    html += "<h2>Lost commit slots</h2>\n";
+    html += &bar_chart(
+        &StallCause::ALL.map(|cause| (cause.name(), stats.lost_commit_slots[cause as usize])),
+        stats.commit_slots,
+    );
+
+    html += "<h2>Committed instructions</h2>\n\
+             <input type=\"search\" placeholder=\"Filter\" oninput=\"filterRows(this.value)\">\n\
+             <table id=\"commits\">\n<thead><tr><th>Seq</th><th>Thread</th><th>PC</th>\
+             <th>Instruction</th><th>Fetch</th><th>Dispatch</th><th>Issue</th>\
+             <th>Complete</th><th>Commit</th></tr></thead>\n<tbody>\n";
+    let cell = |cycle: Option<u64>| cycle.map_or(String::new(), |c| c.to_string());
+    for l in sim.lifetimes.iter().filter(|l| l.commit.is_some()) {
+        let text = sim
+            .thread_instruction(l.thread, l.pc / sim.pc_step)
+            .map_or(l.op.to_string(), Clone::clone);
+        html += &format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td class=\"text\">{}</td><td>{}</td>\
+             <td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            l.seq,
+            l.thread,
+            l.pc,
+            escape(&text),
+            l.fetch,
+            cell(l.dispatch),
+            cell(l.issue),
+            cell(l.complete),
+            cell(l.commit)
+        );
+    }
+    html += "</tbody>\n</table>\n</body>\n</html>\n";
+    fs::write(path, html).unwrap_or_else(|err| {
+        eprintln!("Failed to write report: {}", err);
+        process::exit(1);
+    });
+}
+
+/// An SVG chart of per-cycle series sharing one y axis, with a legend.
+fn line_chart(series: &[(&str, Vec<f64>)]) -> String {
+    let cycles = series.iter().map(|(_, s)| s.len()).max().unwrap_or(0);
+    let bucket = cycles.div_ceil(MAX_POINTS).max(1);
+    let max = series
+        .iter()
+        .flat_map(|(_, s)| s.iter().copied())
+        .fold(1.0, f64::max)
+        .ceil();
+    let (plot_width, plot_height) = (CHART_WIDTH - MARGIN, CHART_HEIGHT - MARGIN);
+    let mut svg = format!(
+        "<svg width=\"{}\" height=\"{}\" xmlns=\"http://www.w3.org/2000/svg\">\n\
+         <line x1=\"{m}\" y1=\"0\" x2=\"{m}\" y2=\"{h}\" stroke=\"#888\"/>\n\
+         <line x1=\"{m}\" y1=\"{h}\" x2=\"{w}\" y2=\"{h}\" stroke=\"#888\"/>\n\
+         <text x=\"{lx}\" y=\"10\" text-anchor=\"end\">{max}</text>\n\
+         <text x=\"{lx}\" y=\"{h}\" text-anchor=\"end\">0</text>\n\
+         <text x=\"{m}\" y=\"{ty}\">1</text>\n\
+         <text x=\"{w}\" y=\"{ty}\" text-anchor=\"end\">{cycles}</text>\n",
+        CHART_WIDTH,
+        CHART_HEIGHT + 20.0 * series.len() as f64,
+        m = MARGIN,
+        h = plot_height,
+        w = CHART_WIDTH,
+        lx = MARGIN - 4.0,
+        ty = plot_height + 14.0,
+        max = max,
+        cycles = cycles,
+    );
+    for (i, (name, values)) in series.iter().enumerate() {
+        let color = COLORS[i % COLORS.len()];
+        let points: Vec<String> = values
+            .chunks(bucket)
+            .enumerate()
+            .map(|(b, chunk)| {
+                let mean = chunk.iter().sum::<f64>() / chunk.len() as f64;
+                let x = MARGIN + plot_width * (b * bucket) as f64 / cycles.max(1) as f64;
+                let y = plot_height * (1.0 - mean / max);
+                format!("{:.1},{:.1}", x, y)
+            })
+            .collect();
+        svg += &format!(
+            "<polyline fill=\"none\" stroke=\"{}\" points=\"{}\"/>\n",
+            color,
+            points.join(" ")
+        );
+        let y = CHART_HEIGHT + 20.0 * i as f64;
+        svg += &format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"12\" height=\"12\" fill=\"{}\"/>\
+             <text x=\"{}\" y=\"{}\">{}</text>\n",
+            MARGIN,
+            y,
+            color,
+            MARGIN + 18.0,
+            y + 10.0,
+            escape(name)
+        );
+    }
+    svg + "</svg>\n"
+}
+
+/// An SVG chart of horizontal bars, each labelled with its share of
+/// `total`.
+fn bar_chart(bars: &[(&str, u64)], total: u64) -> String {
+    const LABEL_WIDTH: f64 = 160.0;
+    const ROW: f64 = 22.0;
+    let max = bars.iter().map(|&(_, n)| n).max().unwrap_or(0).max(1);
+    let mut svg = format!(
+        "<svg width=\"{}\" height=\"{}\" xmlns=\"http://www.w3.org/2000/svg\">\n",
+        CHART_WIDTH,
+        ROW * bars.len() as f64
+    );
+    for (i, &(name, n)) in bars.iter().enumerate() {
+        let y = ROW * i as f64;
+        let width = (CHART_WIDTH - LABEL_WIDTH - 80.0) * n as f64 / max as f64;
+        let share = 100.0 * n as f64 / total.max(1) as f64;
+        svg += &format!(
+            "<text x=\"{}\" y=\"{}\" text-anchor=\"end\">{}</text>\
+             <rect x=\"{}\" y=\"{}\" width=\"{:.1}\" height=\"{}\" fill=\"{}\"/>\
+             <text x=\"{:.1}\" y=\"{}\">{} ({:.1}%)</text>\n",
+            LABEL_WIDTH - 6.0,
+            y + 15.0,
+            name,
+            LABEL_WIDTH,
+            y + 3.0,
+            width,
+            ROW - 6.0,
+            COLORS[i % COLORS.len()],
+            LABEL_WIDTH + width + 6.0,
+            y + 15.0,
+            n,
+            share
+        );
+    }
+    svg + "</svg>\n"
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,119 @@
+use serde::{Deserialize, Serialize};
+
+use crate::checkpoint;
+use crate::shared::Shared;
+use crate::simulator::{NUM_ARCH_REGS, Simulator};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RobEntry {
+    #[serde(rename = "Done")]
+    pub done: bool,
+    #[serde(rename = "Exception")]
+    pub exception: bool,
+    #[serde(rename = "LogicalDestination")]
+    pub logical_destination: u32,
+    #[serde(rename = "PC")]
+    pub pc: u64,
+    /// Rename tag of the entry: `32 + slot`.
+    #[serde(rename = "Tag")]
+    pub tag: u32,
+    #[serde(rename = "Value")]
+    pub value: u64,
+}
+
+/// Reorder-buffer renaming: every in-flight result lives in its ROB slot
+/// until commit copies it into the architectural register file. The
+/// `RegisterMapTable` maps each register either to itself (the committed
+/// value) or to the tag of its youngest in-flight producer. The retirement
+/// map table holds the committed mappings and is copied back into the
+/// `RegisterMapTable` in a single cycle when an exception is taken, instead
+/// of walking the active list.
+///
+/// The first 32 entries of `PhysicalRegisterFile` are the architectural
+/// register file and the rest are the ROB value fields. The log carries the
+/// ROB in place of the `ActiveList`/`FreeList` pair.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RobState {
+    #[serde(rename = "ReorderBuffer")]
+    pub entries: Shared<Vec<RobEntry>>,
+    #[serde(rename = "RetirementRegisterMapTable")]
+    pub retirement_map_table: Shared<Vec<u32>>,
+    /// Slot of the oldest entry.
+    #[serde(default, skip_serializing_if = "checkpoint::omit")]
+    pub head: usize,
+    #[serde(default, skip_serializing_if = "checkpoint::omit")]
+    pub capacity: usize,
+}
+
+impl RobState {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Shared::default(),
+            retirement_map_table: (0..NUM_ARCH_REGS as u32).collect(),
+            head: 0,
+            capacity,
+        }
+    }
+
+    fn tag(&self, slot: usize) -> u32 {
+        (NUM_ARCH_REGS + slot) as u32
+    }
+}
+
+impl Simulator {
+    /// The ROB tag the next dispatched instruction receives.
+    pub(crate) fn allocate_rob_tag(&self, rob: &RobState) -> u32 {
+        rob.tag((rob.head + self.state.active_list.len()) % rob.capacity)
+    }
+
+    /// Retires the head entry: copies its value into the architectural
+    /// register and points the register back at it if no younger producer
+    /// renamed it since.
+    pub(crate) fn retire_rob_head(&mut self, arch: usize) {
+        let rob = self.state.rob.as_mut().unwrap();
+        let tag = rob.tag(rob.head);
+        rob.head = (rob.head + 1) % rob.capacity;
+        rob.retirement_map_table[arch] = arch as u32;
+        if self.hardwired_zero && arch == 0 {
+            return;
+        }
+        self.state.physical_register_file[arch] = self.state.physical_register_file[tag as usize];
+        if self.state.register_map_table[arch] == tag {
+            self.state.register_map_table[arch] = arch as u32;
+        }
+    }
+
+    /// Restores the committed mappings after an exception and drops every
+    /// in-flight result.
+    pub(crate) fn recover_rob(&mut self) {
+        let rob = self.state.rob.as_ref().unwrap();
+        self.state.register_map_table = rob.retirement_map_table.clone();
+        self.state.active_list.clear();
+        self.state.busy_bit_table[NUM_ARCH_REGS..].fill(false);
+    }
+
+    /// Mirrors the active list and ROB value fields into the logged ROB.
+    pub(crate) fn sync_rob_state(&mut self) {
+        let Some(rob) = self.state.rob.as_mut() else {
+            return;
+        };
+        let prf = &self.state.physical_register_file;
+        rob.entries = self
+            .state
+            .active_list
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                let tag = (NUM_ARCH_REGS + (rob.head + i) % rob.capacity) as u32;
+                RobEntry {
+                    done: entry.done,
+                    exception: entry.exception,
+                    logical_destination: entry.logical_destination,
+                    pc: entry.pc,
+                    tag,
+                    value: if entry.done { prf[tag as usize] } else { 0 },
+                }
+            })
+            .collect();
+    }
+}
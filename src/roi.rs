@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+
+use crate::config::RoiConfig;
+use crate::simulator::Simulator;
+use crate::stats::CycleStats;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    Before,
+    Inside,
+    After,
+}
+
+/// Tracks the region of interest of the main program. The counters restart
+/// in the cycle its first instruction commits and are set aside at the end
+/// of the cycle its last one commits, to be put back once the run is over.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RegionOfInterest {
+    /// Instruction indices of the markers.
+    begin: Option<u64>,
+    end: Option<u64>,
+    log_only: bool,
+    phase: Phase,
+    /// The counters of the region and the cycle it closed in.
+    closed: Option<(CycleStats, usize)>,
+}
+
+impl RegionOfInterest {
+    pub fn new(config: &RoiConfig, pc_step: u64) -> Option<Self> {
+        if !config.is_enabled() {
+            return None;
+        }
+        Some(Self {
+            begin: config.begin.map(|address| address / pc_step),
+            end: config.end.map(|address| address / pc_step),
+            log_only: config.log_only,
+            phase: match config.begin {
+                Some(_) => Phase::Before,
+                None => Phase::Inside,
+            },
+            closed: None,
+        })
+    }
+
+    pub fn began(&self) -> bool {
+        self.phase != Phase::Before
+    }
+}
+
+impl Simulator {
+    /// Opens or closes the region when the main program commits one of its
+    /// markers at `pc`.
+    pub(crate) fn roi_commit(&mut self, pc: u64) {
+        let num_alus = self.alus.len();
+        let main_thread = self.smt.current == 0;
+        let Some(roi) = self.roi.as_mut().filter(|_| main_thread) else {
+            return;
+        };
+        match roi.phase {
+            Phase::Before if roi.begin == Some(pc) => {
+                roi.phase = Phase::Inside;
+                self.stats = CycleStats::new(num_alus);
+                // A region of one instruction closes as it opens.
+                if roi.end == Some(pc) {
+                    roi.phase = Phase::After;
+                }
+            }
+            Phase::Inside if roi.end == Some(pc) => roi.phase = Phase::After,
+            _ => {}
+        }
+    }
+
+    /// Sets the counters of the region aside once the cycle it closed in
+    /// has been counted.
+    pub(crate) fn end_roi_cycle(&mut self) {
+        let cycle = self.cycles();
+        if let Some(roi) = self.roi.as_mut()
+            && roi.phase == Phase::After
+            && roi.closed.is_none()
+        {
+            roi.closed = Some((self.stats.clone(), cycle));
+        }
+    }
+
+    /// Whether the state of the current cycle is left out of the log.
+    pub(crate) fn outside_roi_log(&self) -> bool {
+        self.roi.as_ref().is_some_and(|roi| {
+            roi.log_only
+                && match roi.phase {
+                    Phase::Before => true,
+                    Phase::Inside => false,
+                    Phase::After => roi.closed.as_ref().is_none_or(|&(_, c)| c != self.cycles()),
+                }
+        })
+    }
+
+    /// Puts the counters of a closed region back over the live ones.
+    pub(crate) fn finish_roi(&mut self) {
+        if let Some((stats, _)) = self.roi.as_ref().and_then(|roi| roi.closed.as_ref()) {
+            self.stats = stats.clone();
+        }
+    }
+}
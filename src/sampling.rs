@@ -0,0 +1,116 @@
+use std::fs;
+
+use crate::builder::SimulatorBuilder;
+use crate::cli::SampleArgs;
+use crate::config::Config;
+use crate::json_io::parse_config;
+use crate::stats::Summary;
+
+/// One detailed window: the instructions it covers and how the pipeline
+/// ran them.
+struct Window {
+    start: u64,
+    end: u64,
+    committed: u64,
+    cycles: u64,
+}
+
+impl Window {
+    fn ipc(&self) -> f64 {
+        match self.cycles {
+            0 => 0.0,
+            cycles => self.committed as f64 / cycles as f64,
+        }
+    }
+}
+
+/// Simulates a program in detail only in a window of `window` instructions
+/// at the start of every `period`, and executes the rest with the reference
+/// interpreter. Each window starts a fresh pipeline from the registers the
+/// interpreter reached, so the windows are measured cold. The IPC of the
+/// whole program is extrapolated from the windows with a 95% confidence
+/// interval over them.
+pub fn run(args: &SampleArgs) -> Result<(), String> {
+    let config = match &args.config_path {
+        Some(path) => parse_config(path),
+        None => Config::default(),
+    };
+    if config.fp.enabled {
+        return Err("Sampling is only supported without FP units".to_string());
+    }
+    let program = crate::load_program(&args.input_path);
+    let mut functional = SimulatorBuilder::new()
+        .program(program.clone())
+        .config(&config)
+        .build()?;
+
+    let len = program.len() as u64;
+    let mut windows: Vec<Window> = Vec::new();
+    let mut start = 0;
+    while start < len {
+        let end = (start + args.window).min(len);
+        let mut sim = SimulatorBuilder::new()
+            .program(program.clone())
+            .config(&config)
+            .build()?;
+        sim.set_architectural_state(&functional.architectural_registers(), start);
+        sim.log_range = 0..=0;
+        sim.max_instructions = Some((start..end).map(|pc| sim.decode(pc).len() as u64).sum());
+        let sim = crate::run(sim, None, false, None, None);
+        windows.push(Window {
+            start,
+            end,
+            committed: sim.committed,
+            cycles: sim.cycles() as u64,
+        });
+        // The interpreter stops at an ecall, an ebreak or an exception,
+        // which only the pipeline executes; sampling ends there.
+        let next = start + args.period;
+        if sim.halt.is_some() || functional.fast_forward(&config, next.min(len)) < next.min(len) {
+            break;
+        }
+        start = next;
+    }
+
+    let ipc: Vec<f64> = windows.iter().map(Window::ipc).collect();
+    let summary = Summary::of(&ipc);
+    let detailed: u64 = windows.iter().map(|w| w.end - w.start).sum();
+    let micro_ops: u64 = (0..functional.state.pc.max(windows.last().unwrap().end))
+        .map(|pc| functional.decode(pc).len() as u64)
+        .sum();
+    let cycles = |ipc: f64| match ipc > 0.0 {
+        true => format!("{:.0}", micro_ops as f64 / ipc),
+        false => "unbounded".to_string(),
+    };
+    println!(
+        "Sampled {} windows of {} instructions every {}: {} of {} instructions in detail",
+        windows.len(),
+        args.window,
+        args.period,
+        detailed,
+        len
+    );
+    println!("  IPC: {}", summary);
+    println!(
+        "  Estimated cycles: {}, 95% CI [{}, {}]",
+        cycles(summary.mean),
+        cycles(summary.mean + summary.ci95),
+        cycles(summary.mean - summary.ci95)
+    );
+    if let Some(path) = &args.output_path {
+        let mut csv = "start,end,committed,cycles,ipc\n".to_string();
+        for w in &windows {
+            csv += &format!(
+                "{},{},{},{},{:.3}\n",
+                w.start,
+                w.end,
+                w.committed,
+                w.cycles,
+                w.ipc()
+            );
+        }
+        fs::write(path, csv).map_err(|err| format!("Failed to write {}: {}", path, err))?;
+        println!("Windows saved to {}", path);
+    }
+    Ok(())
+}
@@ -0,0 +1,173 @@
+use serde::{Deserialize, Serialize};
+
+use crate::config::{AluBinding, Config};
+use crate::opcode::Opcode;
+use crate::simulator::{IntegerQueueEntry, Simulator};
+
+/// An age matrix over the slots of the integer queue. Bit `older[i][j]` is
+/// set when the entry in slot `j` was dispatched before the one in slot
+/// `i`, so a slot is the oldest of a set of requests when its row has no
+/// bit set for any other requesting slot. Unlike sorting by PC, this orders
+/// loop iterations, the exception handler and the entries of different
+/// threads by when they entered the queue.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct AgeMatrix {
+    /// Sequence number of the micro-op in each slot.
+    slots: Vec<Option<u64>>,
+    older: Vec<Vec<bool>>,
+}
+
+impl AgeMatrix {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            slots: vec![None; capacity],
+            older: vec![vec![false; capacity]; capacity],
+        }
+    }
+
+    /// Frees the slots of the micro-ops no longer in the queue and
+    /// allocates one to each micro-op dispatched since, given in queue
+    /// order, which is dispatch order.
+    fn sync(&mut self, queue: &[u64]) {
+        for slot in self.slots.iter_mut() {
+            if slot.is_some_and(|seq| !queue.contains(&seq)) {
+                *slot = None;
+            }
+        }
+        for &seq in queue {
+            if !self.slots.contains(&Some(seq)) {
+                self.allocate(seq);
+            }
+        }
+    }
+
+    /// Places `seq` in the first free slot, younger than every occupied
+    /// one: its row records them all as older and its column is cleared.
+    fn allocate(&mut self, seq: u64) {
+        let slot = match self.slots.iter().position(Option::is_none) {
+            Some(slot) => slot,
+            // Only a state loaded from a file can overfill the queue.
+            None => {
+                self.slots.push(None);
+                for row in self.older.iter_mut() {
+                    row.push(false);
+                }
+                self.older.push(vec![false; self.slots.len()]);
+                self.slots.len() - 1
+            }
+        };
+        for j in 0..self.slots.len() {
+            self.older[slot][j] = self.slots[j].is_some();
+            self.older[j][slot] = false;
+        }
+        self.slots[slot] = Some(seq);
+    }
+
+    /// The requesting slot with no older request, as granted by one select
+    /// port.
+    fn grant(&self, requests: &[bool]) -> Option<usize> {
+        (0..self.slots.len()).find(|&i| {
+            requests[i] && !(0..self.slots.len()).any(|j| requests[j] && self.older[i][j])
+        })
+    }
+
+    fn slot(&self, seq: u64) -> Option<usize> {
+        self.slots.iter().position(|&slot| slot == Some(seq))
+    }
+}
+
+/// Select logic of the integer queue and binding of the selected
+/// micro-ops to ALUs.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Scheduler {
+    matrix: Option<AgeMatrix>,
+    /// Micro-ops selected per cycle; one per ALU if unset.
+    pub select_ports: Option<usize>,
+    pub binding: AluBinding,
+    /// ALU after the one bound last, where round-robin binding starts.
+    pub(crate) next_alu: usize,
+    /// ALU dedicated binding ties the next dispatched micro-op to.
+    next_dedicated: usize,
+}
+
+impl Scheduler {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            matrix: config
+                .scheduler
+                .age_matrix
+                .then(|| AgeMatrix::new(config.integer_queue_capacity)),
+            select_ports: config.scheduler.select_ports,
+            binding: config.alu_binding,
+            next_alu: 0,
+            next_dedicated: 0,
+        }
+    }
+
+    pub fn has_age_matrix(&self) -> bool {
+        self.matrix.is_some()
+    }
+}
+
+impl Simulator {
+    /// Whether ALU `alu` is free and can execute `instr`.
+    pub(crate) fn usable_alu(&self, alu: usize, instr: &IntegerQueueEntry) -> bool {
+        self.alus[alu].is_free()
+            && self.alus[alu].can_execute(instr.op_code)
+            && self.alu_cluster(alu) == instr.cluster
+    }
+
+    /// The ALU the binding policy issues `instr` to this cycle, if one is
+    /// free.
+    pub(crate) fn select_alu(&self, instr: &IntegerQueueEntry) -> Option<usize> {
+        let n = self.alus.len();
+        let usable = |&i: &usize| self.usable_alu(i, instr);
+        match self.scheduler.binding {
+            AluBinding::FirstFree => (0..n).find(usable),
+            AluBinding::RoundRobin => (0..n)
+                .map(|k| (self.scheduler.next_alu + k) % n)
+                .find(usable),
+            AluBinding::Dedicated => match instr.bound_alu {
+                Some(alu) => Some(alu).filter(usable),
+                None => (0..n).find(usable),
+            },
+            AluBinding::OpcodeClass if instr.op_code.is_mul_div() => (0..n).rev().find(usable),
+            AluBinding::OpcodeClass => (0..n).find(usable),
+        }
+    }
+
+    /// The ALU dedicated binding ties a micro-op executing `op` in
+    /// `cluster` to, the next capable ALU of the cluster in turn. Other
+    /// policies bind at issue.
+    pub(crate) fn bind_at_dispatch(&mut self, op: Opcode, cluster: usize) -> Option<usize> {
+        if self.scheduler.binding != AluBinding::Dedicated {
+            return None;
+        }
+        let n = self.alus.len();
+        let alu = (0..n)
+            .map(|k| (self.scheduler.next_dedicated + k) % n)
+            .find(|&i| self.alus[i].can_execute(op) && self.alu_cluster(i) == cluster)?;
+        self.scheduler.next_dedicated = (alu + 1) % n;
+        Some(alu)
+    }
+
+    /// Orders the ready sequence numbers by the grants of the age matrix:
+    /// each round grants the oldest remaining request, as a chain of
+    /// select ports would. The issue loop then gives each granted micro-op
+    /// a select port and an ALU in this order.
+    pub(crate) fn grant_by_age(&mut self, ready: &[u64]) -> Vec<u64> {
+        let queue: Vec<u64> = self.state.integer_queue.iter().map(|i| i.seq).collect();
+        let matrix = self.scheduler.matrix.as_mut().unwrap();
+        matrix.sync(&queue);
+        let mut requests = vec![false; matrix.slots.len()];
+        for &seq in ready {
+            requests[matrix.slot(seq).unwrap()] = true;
+        }
+        let mut granted = Vec::with_capacity(ready.len());
+        while let Some(slot) = matrix.grant(&requests) {
+            requests[slot] = false;
+            granted.push(matrix.slots[slot].unwrap());
+        }
+        granted
+    }
+}
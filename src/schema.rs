@@ -0,0 +1,266 @@
+use std::fs;
+
+use serde_json::Value;
+
+use crate::cli::{Document, ValidateArgs};
+use crate::config::Config;
+use crate::{asm, delta, json_io, validate};
+
+/// JSON Schema of the input programs.
+pub const PROGRAM_SCHEMA: &str = include_str!("../schemas/program.schema.json");
+/// JSON Schema of the logs written by a simulation run.
+pub const LOG_SCHEMA: &str = include_str!("../schemas/log.schema.json");
+
+/// Reports at most this many violations.
+const MAX_ERRORS: usize = 20;
+
+/// Checks a program or a log against its JSON Schema and prints every
+/// violation with its path, such as `$[3].IntegerQueue[0].OpCode`. A
+/// program that matches the schema is also decoded, so an instruction that
+/// does not parse is reported too.
+pub fn run(args: &ValidateArgs) -> Result<(), String> {
+    let text = fs::read_to_string(&args.path)
+        .map_err(|err| format!("Failed to read {}: {}", args.path, err))?;
+    let document = match json_io::is_ndjson(&args.path) {
+        // One state per line, after the header line if there is one.
+        true => text
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .enumerate()
+            .map(|(i, line)| {
+                serde_json::from_str::<Value>(line)
+                    .map_err(|err| format!("{} line {}: {}", args.path, i + 1, err))
+            })
+            .filter(|state| !matches!(state, Ok(state) if state.get("Header").is_some()))
+            .collect::<Result<Vec<Value>, String>>()
+            .map(Value::Array)?,
+        false => serde_json::from_str(&text)
+            .map_err(|err| format!("Failed to parse {}: {}", args.path, err))?,
+    };
+    let kind = args.document.unwrap_or_else(|| detect(&document));
+    let states = match &document {
+        Value::Object(wrapped) => wrapped.get("Log").and_then(Value::as_array),
+        states => states.as_array(),
+    };
+    if kind == Document::Log && states.is_some_and(|states| delta::is_delta_log(states)) {
+        return Err(format!(
+            "{} is delta-encoded; expand it before validating",
+            args.path
+        ));
+    }
+
+    let schema = match kind {
+        Document::Program => PROGRAM_SCHEMA,
+        Document::Log => LOG_SCHEMA,
+    };
+    let mut errors = check(&serde_json::from_str(schema).unwrap(), &document);
+    // The entries that are strings are checked as instructions even if
+    // others break the schema, so that every problem is reported at once.
+    let entries = document.get("program").unwrap_or(&document).as_array();
+    if kind == Document::Program
+        && let Some(entries) = entries
+    {
+        let program: Vec<String> = entries
+            .iter()
+            .map(|entry| entry.as_str().unwrap_or_default().to_string())
+            .collect();
+        let (lines, mut diagnostics) = asm::resolve_labels_partially(&program);
+        let (indices, program): (Vec<usize>, Vec<String>) = lines.into_iter().unzip();
+        // Report the entries of the document, which comments and blank
+        // entries shift from the instructions.
+        for mut diagnostic in validate::check_program(&program, &Config::default()) {
+            diagnostic.index = indices[diagnostic.index];
+            diagnostics.push(diagnostic);
+        }
+        diagnostics.sort_by_key(|diagnostic| diagnostic.index);
+        errors.extend(diagnostics.iter().map(ToString::to_string));
+    }
+    if errors.is_empty() {
+        println!("{} is a valid {}", args.path, kind.name());
+        return Ok(());
+    }
+    for error in errors.iter().take(MAX_ERRORS) {
+        println!("{}", error);
+    }
+    if errors.len() > MAX_ERRORS {
+        println!("... and {} more", errors.len() - MAX_ERRORS);
+    }
+    Err(format!(
+        "{} is not a valid {}: {} errors",
+        args.path,
+        kind.name(),
+        errors.len()
+    ))
+}
+
+/// Whether `document` looks like a program, an array of strings, rather
+/// than a log.
+fn detect(document: &Value) -> Document {
+    match document.as_array().and_then(|entries| entries.first()) {
+        Some(Value::Object(_)) => Document::Log,
+        None if document.get("Log").is_some() => Document::Log,
+        _ => Document::Program,
+    }
+}
+
+/// The violations of `schema` by `value`, each as `path: message`.
+pub fn check(schema: &Value, value: &Value) -> Vec<String> {
+    let mut validator = Validator {
+        root: schema,
+        errors: Vec::new(),
+    };
+    validator.check(schema, value, "$");
+    validator.errors
+}
+
+/// A validator for the subset of JSON Schema the shipped schemas use:
+/// `type`, `enum`, `minimum`, `items`, `properties`, `required`,
+/// `additionalProperties`, `oneOf` and `$ref`s within the schema.
+struct Validator<'a> {
+    root: &'a Value,
+    errors: Vec<String>,
+}
+
+impl<'a> Validator<'a> {
+    fn check(&mut self, schema: &'a Value, value: &Value, path: &str) {
+        let schema = self.resolve(schema);
+        if let Some(types) = schema.get("type") {
+            let types: Vec<&str> = match types {
+                Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+                types => types.as_str().into_iter().collect(),
+            };
+            if !types.iter().any(|t| has_type(value, t)) {
+                let error = format!("{}: expected {}, got {}", path, types.join(" or "), value);
+                self.errors.push(error);
+                return;
+            }
+        }
+        if let Some(choices) = schema.get("enum").and_then(Value::as_array)
+            && !choices.contains(value)
+        {
+            let choices: Vec<String> = choices.iter().map(Value::to_string).collect();
+            let error = format!("{}: expected one of {}", path, choices.join(", "));
+            self.errors.push(error);
+        }
+        if let Some(minimum) = schema.get("minimum").and_then(Value::as_f64)
+            && value.as_f64().is_some_and(|n| n < minimum)
+        {
+            let error = format!("{}: {} is less than {}", path, value, minimum);
+            self.errors.push(error);
+        }
+        if let Some(branches) = schema.get("oneOf").and_then(Value::as_array) {
+            self.check_one_of(branches, value, path);
+        }
+        if let Some(items) = schema.get("items")
+            && let Value::Array(entries) = value
+        {
+            for (i, entry) in entries.iter().enumerate() {
+                self.check(items, entry, &format!("{}[{}]", path, i));
+            }
+        }
+        if let Value::Object(fields) = value {
+            self.check_object(schema, fields, path);
+        }
+    }
+
+    fn check_object(
+        &mut self,
+        schema: &'a Value,
+        fields: &serde_json::Map<String, Value>,
+        path: &str,
+    ) {
+        for name in schema
+            .get("required")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+        {
+            if !fields.contains_key(name) {
+                self.errors.push(format!("{}: missing {}", path, name));
+            }
+        }
+        let properties = schema.get("properties").and_then(Value::as_object);
+        for (name, field) in fields {
+            let field_path = format!("{}.{}", path, name);
+            match properties.and_then(|properties| properties.get(name)) {
+                Some(property) => self.check(property, field, &field_path),
+                None => match schema.get("additionalProperties") {
+                    Some(Value::Bool(false)) => {
+                        self.errors
+                            .push(format!("{}: unexpected field", field_path));
+                    }
+                    Some(additional @ Value::Object(_)) => {
+                        self.check(additional, field, &field_path)
+                    }
+                    _ => {}
+                },
+            }
+        }
+    }
+
+    /// Checks `value` against the one branch whose type it has if there is
+    /// exactly one, so that its errors keep their paths. Otherwise exactly
+    /// one branch must accept it.
+    fn check_one_of(&mut self, branches: &'a [Value], value: &Value, path: &str) {
+        let typed: Vec<&Value> = branches
+            .iter()
+            .filter(|branch| {
+                let branch = self.resolve(branch);
+                match branch.get("type").and_then(Value::as_str) {
+                    Some(t) => has_type(value, t),
+                    None => true,
+                }
+            })
+            .collect();
+        if let [branch] = typed[..] {
+            self.check(branch, value, path);
+            return;
+        }
+        let accepted = typed
+            .iter()
+            .filter(|branch| {
+                let mut trial = Validator {
+                    root: self.root,
+                    errors: Vec::new(),
+                };
+                trial.check(branch, value, path);
+                trial.errors.is_empty()
+            })
+            .count();
+        if accepted != 1 {
+            let error = format!(
+                "{}: matches {} of the {} alternatives instead of one",
+                path,
+                accepted,
+                branches.len()
+            );
+            self.errors.push(error);
+        }
+    }
+
+    /// Follows a `$ref` such as `#/$defs/state` to the schema it names.
+    fn resolve(&self, schema: &'a Value) -> &'a Value {
+        match schema.get("$ref").and_then(Value::as_str) {
+            Some(reference) => {
+                let pointer = reference.strip_prefix('#').unwrap_or(reference);
+                let target = self.root.pointer(pointer);
+                self.resolve(target.unwrap_or_else(|| panic!("Unresolved $ref {}", reference)))
+            }
+            None => schema,
+        }
+    }
+}
+
+fn has_type(value: &Value, name: &str) -> bool {
+    match name {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "string" => value.is_string(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        _ => false,
+    }
+}
@@ -0,0 +1,348 @@
+use crate::opcode::Opcode;
+use crate::simulator::{
+    ActiveEntry, CycleCounts, DecodedInstructionEntry, ExceptionCause, IntegerQueueEntry,
+    NUM_ARCH_REGS, Simulator, compute,
+};
+use crate::units;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    /// Waiting for its source registers to be written.
+    Issued,
+    /// Operands read; executing.
+    Executing,
+    /// Result computed; waiting to write it back.
+    Executed,
+}
+
+/// The functional unit status of one busy ALU.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Unit {
+    micro_op: DecodedInstructionEntry,
+    dest: u32,
+    /// Source registers read from the register file, if any.
+    sources: [Option<u32>; 2],
+    /// Sequence numbers of the instructions producing the sources (Qj, Qk).
+    producers: [Option<u64>; 2],
+    values: [u64; 2],
+    stage: Stage,
+    result: Result<u64, ExceptionCause>,
+    /// Remaining extra execute cycles of a fused `li`.
+    stall: u32,
+}
+
+impl Unit {
+    fn reads(&self, reg: u32) -> bool {
+        self.stage == Stage::Issued && self.sources.contains(&Some(reg))
+    }
+}
+
+/// CDC 6600 style scoreboard: instructions issue in order to a free ALU
+/// unless another in-flight instruction writes the same register (WAW),
+/// read their operands once every producer has written (RAW), and write back
+/// once no older instruction still has to read the old value (WAR). There
+/// is no renaming: the register map table stays the identity and the first
+/// 32 physical registers are the architectural registers.
+///
+/// An exception is taken when the faulting instruction is the oldest in
+/// flight. Younger instructions that already wrote back stay written, so
+/// exceptions are imprecise, as on the CDC 6600.
+///
+/// In the log, `DecodedPCs` is the fetch buffer, `IntegerQueue` holds the
+/// instructions waiting for operands (tags are architectural source
+/// registers), `ActiveList` every instruction holding an ALU in program
+/// order, and `BusyBitTable` the register result status.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Scoreboard {
+    units: Vec<Option<Unit>>,
+    /// Sequence number of the in-flight writer of each register.
+    result_status: Vec<Option<u64>>,
+}
+
+impl Scoreboard {
+    pub fn new(num_units: usize) -> Self {
+        Self {
+            units: vec![None; num_units],
+            result_status: vec![None; NUM_ARCH_REGS],
+        }
+    }
+
+    pub(crate) fn unit_busy(&self, unit: usize) -> bool {
+        self.units[unit].is_some()
+    }
+}
+
+impl Simulator {
+    /// Advances the scoreboard by one cycle, back to front.
+    pub(crate) fn simulate_cycle_scoreboard(&mut self) {
+        let mut counts = CycleCounts::default();
+        if self.state.exception {
+            // Cooldown cycle after the flush.
+            self.state.exception = false;
+            if self.exception_cooldown {
+                self.bandwidth.push(counts);
+                return;
+            }
+        }
+
+        // Write result.
+        let oldest = self.scoreboard_units().map(|u| u.micro_op.seq).min();
+        let mut written = Vec::new();
+        for (i, unit) in self.scoreboard.units.iter().enumerate() {
+            let Some(unit) = unit.as_ref().filter(|u| u.stage == Stage::Executed) else {
+                continue;
+            };
+            if let Err(cause) = unit.result {
+                if Some(unit.micro_op.seq) == oldest {
+                    let pc = unit.micro_op.pc;
+                    self.flush_scoreboard(pc, cause);
+                    self.bandwidth.push(counts);
+                    return;
+                }
+                continue;
+            }
+            let war = self
+                .scoreboard
+                .units
+                .iter()
+                .flatten()
+                .any(|other| other.micro_op.seq < unit.micro_op.seq && other.reads(unit.dest));
+            if !war {
+                written.push(i);
+            }
+        }
+        for i in written {
+            let unit = self.scoreboard.units[i].take().unwrap();
+            if !(self.hardwired_zero && unit.dest == 0) {
+                self.state.physical_register_file[unit.dest as usize] = unit.result.unwrap();
+            }
+            self.scoreboard.result_status[unit.dest as usize] = None;
+            self.committed += 1;
+            counts.committed += 1;
+        }
+
+        // Execute.
+        for unit in self.scoreboard.units.iter_mut().flatten() {
+            if unit.stage != Stage::Executing {
+                continue;
+            }
+            if unit.stall > 0 {
+                unit.stall -= 1;
+                continue;
+            }
+            let [a, b] = unit.values;
+            unit.result = compute(unit.micro_op.op, a, b, self.trap_on_overflow);
+            unit.stage = Stage::Executed;
+        }
+
+        // Read operands once no producer is still pending.
+        for unit in self.scoreboard.units.iter_mut().flatten() {
+            if unit.stage != Stage::Issued {
+                continue;
+            }
+            let pending = unit.producers.iter().zip(unit.sources).any(|(p, src)| {
+                p.is_some() && src.is_some_and(|r| self.scoreboard.result_status[r as usize] == *p)
+            });
+            if pending {
+                continue;
+            }
+            for (value, src) in unit.values.iter_mut().zip(unit.sources) {
+                if let Some(reg) = src {
+                    *value = self.state.physical_register_file[reg as usize];
+                }
+            }
+            unit.stage = Stage::Executing;
+            counts.issued += 1;
+        }
+
+        // Issue the head of the fetch buffer.
+        if let Some(instr) = self.state.decoded_pcs.first() {
+            let dest = instr.dest;
+            let waw = self.scoreboard.result_status[dest as usize].is_some();
+            let free = (0..self.alus.len()).find(|&i| {
+                self.scoreboard.units[i].is_none() && self.alus[i].can_execute(instr.op)
+            });
+            if let (false, Some(i)) = (waw, free) {
+                let instr = self.state.decoded_pcs.remove(0);
+                let seq = instr.seq;
+                self.scoreboard.units[i] = Some(self.issue_to_unit(instr, dest));
+                self.scoreboard.result_status[dest as usize] = Some(seq);
+                counts.dispatched = 1;
+            }
+        }
+
+        // Fetch into an empty buffer.
+        if self.state.decoded_pcs.is_empty() && (self.state.pc as usize) < self.program.len() {
+            let pc = self.state.pc;
+            for mut micro_op in self.decode(pc) {
+                micro_op.seq = self.state.next_seq;
+                self.state.next_seq += 1;
+                self.state.decoded_pcs.push(micro_op);
+                counts.fetched += 1;
+            }
+            self.state.pc += 1;
+        }
+
+        self.sync_scoreboard_state();
+        self.bandwidth.push(counts);
+    }
+
+    fn scoreboard_units(&self) -> impl Iterator<Item = &Unit> {
+        self.scoreboard.units.iter().flatten()
+    }
+
+    /// Builds the unit status of an instruction issuing now, recording the
+    /// producers of its sources and any immediate or PC operand.
+    fn issue_to_unit(&self, micro_op: DecodedInstructionEntry, dest: u32) -> Unit {
+        let mut sources = [None, None];
+        let mut values = [0, 0];
+        if micro_op.op == Opcode::Auipc {
+            values[0] = self.address(micro_op.pc);
+        } else {
+            sources[0] = micro_op.src1;
+        }
+        if micro_op.is_imm {
+            values[1] = micro_op.imm;
+        } else {
+            sources[1] = micro_op.src2;
+        }
+        let producers =
+            sources.map(|src| src.and_then(|r| self.scoreboard.result_status[r as usize]));
+        let stall = units::extra_cycles(micro_op.op);
+        Unit {
+            micro_op,
+            dest,
+            sources,
+            producers,
+            values,
+            stage: Stage::Issued,
+            result: Ok(0),
+            stall,
+        }
+    }
+
+    fn flush_scoreboard(&mut self, pc: u64, cause: ExceptionCause) {
+        self.scoreboard = Scoreboard::new(self.alus.len());
+        self.state.decoded_pcs.clear();
+        self.state.integer_queue.clear();
+        self.state.active_list.clear();
+        self.state.busy_bit_table.fill(false);
+        self.state.exception = true;
+        self.state.exception_pc = pc;
+        self.state.exception_cause = Some(cause);
+        self.state.pc = self.exception_vector;
+    }
+
+    /// Mirrors the functional unit and register result status into the
+    /// logged structures.
+    fn sync_scoreboard_state(&mut self) {
+        let mut units: Vec<&Unit> = self.scoreboard.units.iter().flatten().collect();
+        units.sort_by_key(|u| u.micro_op.seq);
+        let result_status = &self.scoreboard.result_status;
+        self.state.integer_queue = units
+            .iter()
+            .filter(|u| u.stage == Stage::Issued)
+            .map(|u| {
+                let operand = |k: usize| {
+                    let waiting = u.producers[k].is_some()
+                        && u.sources[k]
+                            .is_some_and(|r| result_status[r as usize] == u.producers[k]);
+                    match (waiting, u.sources[k]) {
+                        (true, Some(reg)) => (false, reg, 0),
+                        _ => (true, 0, u.values[k]),
+                    }
+                };
+                let (op_a_is_ready, op_a_reg_tag, op_a_value) = operand(0);
+                let (op_b_is_ready, op_b_reg_tag, op_b_value) = operand(1);
+                IntegerQueueEntry {
+                    dest_register: u.dest,
+                    op_a_is_ready,
+                    op_a_reg_tag,
+                    op_a_value,
+                    op_b_is_ready,
+                    op_b_reg_tag,
+                    op_b_value,
+                    op_b_is_imm: u.micro_op.is_imm,
+                    op_code: u.micro_op.op,
+                    pc: u.micro_op.pc,
+                    op_a_poison: None,
+                    op_b_poison: None,
+                    seq: u.micro_op.seq,
+                    cluster: 0,
+                    bound_alu: None,
+                    register_reads: 0,
+                    csr: None,
+                }
+            })
+            .collect();
+        self.state.active_list = units
+            .iter()
+            .map(|u| ActiveEntry {
+                done: u.stage == Stage::Executed,
+                exception: u.stage == Stage::Executed && u.result.is_err(),
+                logical_destination: u.dest,
+                old_destination: u.dest,
+                destination: u.dest,
+                pc: u.micro_op.pc,
+                is_fp: false,
+                cause: u.result.err(),
+                poison: None,
+                environment: None,
+                seq: u.micro_op.seq,
+                eliminated: false,
+            })
+            .collect();
+        for (reg, status) in result_status.iter().enumerate() {
+            self.state.busy_bit_table[reg] = status.is_some();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::SimulatorBuilder;
+    use crate::config::Config;
+    use crate::simulator::Simulator;
+
+    /// Runs `lines` on the scoreboard with x1 = 100, where a fused `li` of a
+    /// wide constant takes longer to execute than any other instruction.
+    fn run(lines: &[&str]) -> Simulator {
+        let config: Config = serde_json::from_str(
+            r#"{"pipeline": "scoreboard", "li_expansion": "fused", "initial_registers": [0, 100]}"#,
+        )
+        .unwrap();
+        let mut sim = SimulatorBuilder::new()
+            .program(lines.iter().map(|line| line.to_string()).collect())
+            .config(&config)
+            .build()
+            .unwrap();
+        while !sim.done() {
+            let writers = sim.scoreboard_units().filter(|u| u.dest == 1).count();
+            assert!(writers <= 1, "cycle {}", sim.cycles());
+            sim.simulate_cycle();
+        }
+        sim
+    }
+
+    #[test]
+    fn reads_operands_after_the_producer_writes() {
+        let sim = run(&["li x1, 0x10000", "add x2, x1, x1"]);
+        assert_eq!(sim.state.physical_register_file[1..3], [0x10000, 0x20000]);
+    }
+
+    #[test]
+    fn does_not_issue_a_second_writer_of_a_register() {
+        let sim = run(&["li x1, 0x10000", "addi x1, x0, 2"]);
+        assert_eq!(sim.state.physical_register_file[1], 2);
+    }
+
+    #[test]
+    fn writes_back_after_older_instructions_read_the_old_value() {
+        let sim = run(&["li x3, 0x10000", "add x2, x3, x1", "addi x1, x0, 9"]);
+        assert_eq!(
+            sim.state.physical_register_file[1..4],
+            [9, 0x10064, 0x10000]
+        );
+    }
+}
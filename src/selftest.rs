@@ -0,0 +1,88 @@
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+use crate::builder::SimulatorBuilder;
+use crate::cli::SelftestArgs;
+use crate::config::Config;
+use crate::diff;
+use crate::json_io::{load_log, parse_config};
+
+/// Field differences shown for a failing case.
+const MAX_DIFFERENCES: usize = 10;
+
+/// Runs every case under the testcases directory and compares the log with
+/// the expected one. A case is a directory holding `input.json`,
+/// `output.json` and optionally `config.json`.
+pub fn run(args: &SelftestArgs) -> Result<(), String> {
+    let dir = Path::new(&args.dir);
+    let entries =
+        fs::read_dir(dir).map_err(|err| format!("Failed to read {}: {}", dir.display(), err))?;
+    let mut cases: Vec<_> = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.join("input.json").is_file())
+        .collect();
+    if cases.is_empty() {
+        return Err(format!("No test cases found in {}", dir.display()));
+    }
+    cases.sort();
+
+    let mut failed = 0;
+    for case in &cases {
+        let name = case.file_name().unwrap().to_string_lossy();
+        match check(case) {
+            Ok(()) => println!("ok    {}", name),
+            Err(report) => {
+                failed += 1;
+                println!("FAIL  {}: {}", name, report);
+            }
+        }
+    }
+    println!("{} passed, {} failed", cases.len() - failed, failed);
+    if failed > 0 {
+        return Err(format!("{} of {} test cases failed", failed, cases.len()));
+    }
+    Ok(())
+}
+
+/// Simulates one case, describing the first divergent cycle on failure.
+fn check(case: &Path) -> Result<(), String> {
+    let path = |name: &str| case.join(name).to_string_lossy().into_owned();
+    let config_path = case.join("config.json");
+    let config = match config_path.is_file() {
+        true => parse_config(&path("config.json")),
+        false => Config::default(),
+    };
+    let program = crate::load_program(&path("input.json"));
+    let sim = SimulatorBuilder::new()
+        .program(program)
+        .config(&config)
+        .build()?;
+    let sim = crate::run(sim, None, false, None, None);
+    let log: Vec<Value> = sim.log.iter().map(|state| state.to_log_value()).collect();
+    let expected = load_log(&path("output.json"));
+
+    let common = log.len().min(expected.len());
+    let Some(first) = (0..common).find(|&c| log[c] != expected[c]) else {
+        if log.len() == expected.len() {
+            return Ok(());
+        }
+        return Err(format!(
+            "first divergence at cycle {}: expected {} cycles, got {}",
+            common,
+            expected.len(),
+            log.len()
+        ));
+    };
+    let mut lines = Vec::new();
+    diff::differences("", &expected[first], &log[first], &mut lines);
+    let mut report = format!("first divergence at cycle {}", first);
+    for (path, expected, actual) in lines.iter().take(MAX_DIFFERENCES) {
+        report += &format!("\n        {}: expected {}, got {}", path, expected, actual);
+    }
+    if lines.len() > MAX_DIFFERENCES {
+        report += &format!("\n        ... {} more", lines.len() - MAX_DIFFERENCES);
+    }
+    Err(report)
+}
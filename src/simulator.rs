@@ -1,21 +1,89 @@
-use crate::json_io::serialize_decoded_pcs;
+use crate::asm::{self, Field, Operand};
+use crate::bypass::Bypass;
+use crate::check::Checker;
+use crate::checkpoint;
+use crate::cluster::Clusters;
+use crate::config::{
+    Config, DispatchMode, ExceptionResume, FpConfig, FreeListPolicy, InputFormat, IssuePolicy,
+    LiExpansion, Pipeline, Renaming, Termination,
+};
+use crate::csr::{self, Csrs};
+use crate::decoder;
+use crate::elimination::MoveElimination;
+use crate::fetch::FetchBuffer;
+use crate::fp::{FpState, FpUnit};
+use crate::gdb::Debugger;
+use crate::inorder::InOrderPipeline;
+use crate::json_io::{LogStream, serialize_decoded_pcs};
+use crate::latency::RandomLatency;
+use crate::lifetime::{CycleEvents, Lifetime, Stage};
+use crate::observer::PipelineObserver;
+use crate::opcode::Opcode;
+use crate::prediction::ValuePrediction;
+use crate::profile::Profile;
+use crate::reclamation::Reclaimer;
+use crate::recovery::Recovery;
+use crate::rng::Rng;
+use crate::rob::RobState;
+use crate::roi::RegionOfInterest;
+use crate::scheduler::Scheduler;
+use crate::scoreboard::Scoreboard;
+use crate::shared::Shared;
+use crate::smt::{Smt, ThreadContext};
+use crate::stats::{CommitSlots, CycleMetrics, CycleStats};
+use crate::syscall::{Environment, Halt};
+use crate::tomasulo::Tomasulo;
+use crate::units::{self, Operands};
+use crate::writeback::Writeback;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashSet, VecDeque};
+use std::ops::RangeInclusive;
+use std::time::{Duration, Instant};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DecodedInstructionEntry {
     #[serde(rename = "PC")]
     pub pc: u64,
-    #[serde(skip_serializing)]
-    pub op: String,
-    #[serde(skip_serializing)]
+    #[serde(default, skip_serializing_if = "checkpoint::omit")]
+    pub op: Opcode,
+    #[serde(default, skip_serializing_if = "checkpoint::omit")]
     pub is_imm: bool,
-    #[serde(skip_serializing)]
-    pub dest: String,
-    #[serde(skip_serializing)]
-    pub src1: String,
-    #[serde(skip_serializing)]
-    pub src2: String,
+    /// Architectural destination register, an FP register for FP opcodes.
+    #[serde(default, skip_serializing_if = "checkpoint::omit")]
+    pub dest: u32,
+    /// Source registers; a source without one reads as a ready zero.
+    #[serde(default, skip_serializing_if = "checkpoint::omit")]
+    pub src1: Option<u32>,
+    #[serde(default, skip_serializing_if = "checkpoint::omit")]
+    pub src2: Option<u32>,
+    /// The second operand in place of `src2` when `is_imm` is set: an
+    /// immediate, or the CSR number of a CSR instruction.
+    #[serde(default, skip_serializing_if = "checkpoint::omit")]
+    pub imm: u64,
+    /// Dynamic sequence number; unique among in-flight micro-ops.
+    #[serde(default, skip_serializing_if = "checkpoint::omit")]
+    pub seq: u64,
+}
+
+/// An instruction of the program or exception handler, decoded at load.
+#[derive(Debug, Clone)]
+pub(crate) struct DecodedInstruction {
+    pub micro_ops: Vec<DecodedInstructionEntry>,
+    /// The instruction is an `mret`.
+    pub returns: bool,
+}
+
+/// A committed micro-op as reported to a debugger or co-simulation client.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Commit {
+    #[serde(rename = "PC")]
+    pub pc: u64,
+    /// The integer register written and its new value.
+    #[serde(rename = "Write", skip_serializing_if = "Option::is_none")]
+    pub write: Option<(usize, u64)>,
+    /// The micro-op raised an exception instead of committing a result.
+    #[serde(rename = "Exception")]
+    pub exception: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -28,8 +96,28 @@ pub struct ActiveEntry {
     pub logical_destination: u32,
     #[serde(rename = "OldDestination")]
     pub old_destination: u32,
+    /// Physical register (or ROB tag) the result is written to.
+    #[serde(default, skip_serializing_if = "checkpoint::omit")]
+    pub destination: u32,
     #[serde(rename = "PC")]
     pub pc: u64,
+    /// The destination is an FP register.
+    #[serde(rename = "IsFP", default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_fp: bool,
+    #[serde(default, skip_serializing_if = "checkpoint::omit")]
+    pub cause: Option<ExceptionCause>,
+    /// Uninitialized architectural register the result depends on, if any.
+    #[serde(default, skip_serializing_if = "checkpoint::omit")]
+    pub poison: Option<u32>,
+    /// System instruction performed when the entry commits.
+    #[serde(default, skip_serializing_if = "checkpoint::omit")]
+    pub environment: Option<Environment>,
+    #[serde(default, skip_serializing_if = "checkpoint::omit")]
+    pub seq: u64,
+    /// An eliminated move: the destination is the source's register, shared
+    /// with its other mappings.
+    #[serde(default, skip_serializing_if = "checkpoint::omit")]
+    pub eliminated: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
@@ -48,68 +136,250 @@ pub struct IntegerQueueEntry {
     pub op_b_reg_tag: u32,
     #[serde(rename = "OpBValue")]
     pub op_b_value: u64,
+    /// Operand B is an immediate, logged as the signed value written.
+    #[serde(default, skip_serializing_if = "checkpoint::omit")]
+    pub op_b_is_imm: bool,
     #[serde(rename = "OpCode")]
-    pub op_code: String,
+    pub op_code: Opcode,
+    #[serde(rename = "PC")]
+    pub pc: u64,
+    #[serde(default, skip_serializing_if = "checkpoint::omit")]
+    pub op_a_poison: Option<u32>,
+    #[serde(default, skip_serializing_if = "checkpoint::omit")]
+    pub op_b_poison: Option<u32>,
+    #[serde(default, skip_serializing_if = "checkpoint::omit")]
+    pub seq: u64,
+    /// Cluster whose queue holds the entry, in a clustered back end.
+    #[serde(default, skip_serializing_if = "checkpoint::omit")]
+    pub cluster: usize,
+    /// ALU the entry was bound to at dispatch, under dedicated binding.
+    #[serde(default, skip_serializing_if = "checkpoint::omit")]
+    pub bound_alu: Option<usize>,
+    /// Register source operands, read from the register file at issue.
+    #[serde(default, skip_serializing_if = "checkpoint::omit")]
+    pub register_reads: u32,
+    /// CSR a CSR instruction reads, whose old value replaces operand B at
+    /// issue.
+    #[serde(default, skip_serializing_if = "checkpoint::omit")]
+    pub csr: Option<u16>,
+}
+
+/// Takes a register off `free_list`, which must not be empty, in the order
+/// of `policy`.
+pub(crate) fn take_free(
+    free_list: &mut VecDeque<u32>,
+    policy: FreeListPolicy,
+    rng: &mut Rng,
+) -> u32 {
+    let index = match policy {
+        FreeListPolicy::Fifo => 0,
+        FreeListPolicy::Lifo => free_list.len() - 1,
+        FreeListPolicy::Random => rng.below(free_list.len()),
+    };
+    free_list.remove(index).unwrap()
+}
+
+/// The source operands of `instr` that name a register.
+fn register_sources(instr: &DecodedInstructionEntry) -> impl Iterator<Item = u32> {
+    let src1 = instr.src1.filter(|_| instr.op != Opcode::Auipc);
+    let src2 = instr.src2.filter(|_| !instr.is_imm);
+    [src1, src2].into_iter().flatten()
+}
+
+/// Extra cycles a fused `li` spends in the first execute stage, doubling its
+/// latency.
+pub(crate) const FUSED_LI_EXTRA_CYCLES: u32 = 2;
+
+/// Why an exception was taken.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ExceptionCause {
+    DivideByZero,
+    /// Signed result not representable in 64 bits (e.g. `i64::MIN / -1`).
+    Overflow,
+    /// An `unimp` instruction, or the all-zero instruction word.
+    IllegalInstruction,
+    /// A memory access to an unmapped page. There are no memory
+    /// instructions yet, so nothing raises it.
+    PageFault,
+    /// External interrupt with the given id, from `--interrupts`.
+    Interrupt(u32),
+}
+
+/// An exception a nested one interrupted, which `mret` returns to.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NestedException {
     #[serde(rename = "PC")]
     pub pc: u64,
+    #[serde(rename = "Cause")]
+    pub cause: Option<ExceptionCause>,
+}
+
+/// A result leaving an ALU pipeline stage.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct AluResult {
+    pub dest: u32,
+    pub value: u64,
+    pub seq: u64,
+    pub exception: Option<ExceptionCause>,
+    pub poison: Option<u32>,
+    pub environment: Option<Environment>,
+}
+
+/// Computes `op` on raw 64-bit operands with the functional unit
+/// registered for it.
+pub(crate) fn compute(
+    op: Opcode,
+    a: u64,
+    b: u64,
+    trap_on_overflow: bool,
+) -> Result<u64, ExceptionCause> {
+    let operands = Operands {
+        a,
+        b,
+        trap_on_overflow,
+    };
+    units::execute(op, operands).unwrap_or_else(|| panic!("Undefined op: {}", op))
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Alu {
-    pub forwarding: Option<(u32, u64, u64, bool)>,
-    pipeline_stage1: Option<(u32, u64, u64, bool)>,
+    pub forwarding: Option<AluResult>,
+    /// `forwarding` lost writeback arbitration and is still to be written;
+    /// the ALU does not advance until it is.
+    held: bool,
+    /// Results moving through the execute stages after the first, oldest
+    /// first; one slot per stage.
+    later_stages: VecDeque<Option<AluResult>>,
     instruction_in_flight: Option<IntegerQueueEntry>,
+    extra_cycles: u32,
+    capabilities: Vec<Opcode>,
+    trap_on_overflow: bool,
 }
 
 impl Alu {
-    pub fn new() -> Self {
+    pub fn new(capabilities: Vec<Opcode>, trap_on_overflow: bool, depth: usize) -> Self {
         Self {
             forwarding: None,
-            pipeline_stage1: None,
+            held: false,
+            later_stages: VecDeque::from(vec![None; depth - 1]),
             instruction_in_flight: None,
+            extra_cycles: 0,
+            capabilities,
+            trap_on_overflow,
         }
     }
     pub fn is_free(&self) -> bool {
         self.instruction_in_flight.is_none()
     }
+    pub fn can_execute(&self, op: Opcode) -> bool {
+        self.capabilities.is_empty() || op.is_csr() || self.capabilities.contains(&op)
+    }
     pub fn push_instr(&mut self, instr: IntegerQueueEntry) {
+        self.extra_cycles = units::extra_cycles(instr.op_code);
         self.instruction_in_flight = Some(instr);
     }
     pub fn execute(&mut self) {
-        self.forwarding = self.pipeline_stage1.take();
-        if let Some(instr) = self.instruction_in_flight.take() {
-            let (r, pc, a, b, op) = (
-                instr.dest_register,
-                instr.pc,
-                instr.op_a_value,
-                instr.op_b_value,
-                instr.op_code.as_str(),
-            );
-            let (mut ans, mut exception) = (0, false);
-            match op {
-                "add" | "addi" => ans = a.wrapping_add(b),
-                "sub" => ans = a.wrapping_sub(b),
-                "mulu" => ans = a.wrapping_mul(b),
-                "divu" => {
-                    if b == 0 {
-                        exception = true;
-                    } else {
-                        ans = a / b;
-                    }
-                }
-                "remu" => {
-                    if b == 0 {
-                        exception = true;
-                    } else {
-                        ans = a % b;
-                    }
-                }
-                _ => panic!("Undefined op: {}", op),
+        if std::mem::take(&mut self.held) {
+            return;
+        }
+        let result = match self.extra_cycles {
+            0 => self
+                .instruction_in_flight
+                .take()
+                .map(|instr| self.result_of(instr)),
+            _ => {
+                self.extra_cycles -= 1;
+                None
+            }
+        };
+        self.later_stages.push_back(result);
+        self.forwarding = self.later_stages.pop_front().unwrap();
+    }
+    fn result_of(&self, instr: IntegerQueueEntry) -> AluResult {
+        let (a, b, op) = (instr.op_a_value, instr.op_b_value, instr.op_code);
+        let (value, exception) = match compute(op, a, b, self.trap_on_overflow) {
+            Ok(value) => (value, None),
+            Err(cause) => (0, Some(cause)),
+        };
+        AluResult {
+            dest: instr.dest_register,
+            value,
+            seq: instr.seq,
+            exception,
+            poison: instr.op_a_poison.or(instr.op_b_poison),
+            environment: match op {
+                Opcode::Ecall => Some(Environment::Call(a)),
+                Opcode::Ebreak => Some(Environment::Break),
+                op if op.is_csr() => instr
+                    .csr
+                    .map(|csr| Environment::Csr(csr, csr::updated(op, b, a))),
+                _ => None,
+            },
+        }
+    }
+    /// Keeps the micro-op just pushed in the first execute stage for
+    /// `cycles` more cycles.
+    pub fn add_latency(&mut self, cycles: u32) {
+        self.extra_cycles += cycles;
+    }
+    /// Keeps the result in `forwarding` for another cycle.
+    pub fn hold(&mut self) {
+        self.held = true;
+    }
+    /// One-line description of the execute stages, for display.
+    pub fn describe(&self) -> String {
+        let stage1 = match &self.instruction_in_flight {
+            Some(i) if self.extra_cycles > 0 => {
+                format!("{} @{} ({} more)", i.op_code, i.pc, self.extra_cycles)
             }
-            self.pipeline_stage1 = Some((r, ans, pc, exception));
+            Some(i) => format!("{} @{}", i.op_code, i.pc),
+            None => "-".to_string(),
+        };
+        let mut out = format!("EX1 {:<20}", stage1);
+        // The stages after the first, youngest first as they follow EX1.
+        for (i, stage) in self.later_stages.iter().rev().enumerate() {
+            let stage = match stage {
+                Some(AluResult {
+                    exception: Some(cause),
+                    ..
+                }) => format!("{:?}", cause),
+                Some(r) => format!("p{} <- {:#x}", r.dest, r.value),
+                None => "-".to_string(),
+            };
+            out += &format!(" EX{} {:<20}", i + 2, stage);
         }
+        out.trim_end().to_string()
+    }
+
+    /// Whether the micro-op `seq` is in one of the execute stages.
+    pub fn holds(&self, seq: u64) -> bool {
+        self.instruction_in_flight
+            .as_ref()
+            .is_some_and(|i| i.seq == seq)
+            || self.later_stages.iter().flatten().any(|r| r.seq == seq)
+            || self.forwarding.is_some_and(|r| r.seq == seq)
     }
-    fn reset(&mut self) {
-        *self = Self::new();
+
+    /// Drops the in-flight work of the micro-ops in `squashed`.
+    pub(crate) fn squash(&mut self, squashed: &HashSet<u64>) {
+        let hit = |seq: u64| squashed.contains(&seq);
+        if self.forwarding.is_some_and(|r| hit(r.seq)) {
+            self.forwarding = None;
+            self.held = false;
+        }
+        for stage in self.later_stages.iter_mut() {
+            if stage.is_some_and(|r| hit(r.seq)) {
+                *stage = None;
+            }
+        }
+        if self
+            .instruction_in_flight
+            .as_ref()
+            .is_some_and(|i| hit(i.seq))
+        {
+            self.instruction_in_flight = None;
+            self.extra_cycles = 0;
+        }
     }
 }
 
@@ -118,145 +388,1078 @@ pub struct SimulatorState {
     #[serde(rename = "PC")]
     pub pc: u64,
     #[serde(rename = "PhysicalRegisterFile")]
-    pub physical_register_file: Vec<u64>,
+    pub physical_register_file: Shared<Vec<u64>>,
     #[serde(rename = "DecodedPCs", serialize_with = "serialize_decoded_pcs")]
-    pub decoded_pcs: Vec<DecodedInstructionEntry>,
+    pub decoded_pcs: Shared<Vec<DecodedInstructionEntry>>,
     #[serde(rename = "ExceptionPC")]
     pub exception_pc: u64,
     #[serde(rename = "Exception")]
     pub exception: bool,
+    /// Cause of the last exception taken, logged with `log_exception_cause`.
+    #[serde(default, skip_serializing_if = "checkpoint::omit")]
+    pub exception_cause: Option<ExceptionCause>,
+    #[serde(default, skip_serializing_if = "checkpoint::omit")]
+    pub log_exception_cause: bool,
+    /// Exceptions interrupted by one the handler raised, outermost first;
+    /// the innermost is `exception_pc`. Logged as `ExceptionStack` with
+    /// `exception_nesting`, the depth it may reach.
+    #[serde(default, skip_serializing_if = "checkpoint::omit")]
+    pub exception_stack: Vec<NestedException>,
+    #[serde(default, skip_serializing_if = "checkpoint::omit")]
+    pub exception_nesting: usize,
+    /// `mret`s fetched but not yet committed, each returning from one
+    /// exception further out.
+    #[serde(default, skip_serializing_if = "checkpoint::omit")]
+    pub returns_in_flight: usize,
+    /// Cycles simulated and micro-ops committed up to this state, logged as
+    /// `Cycle` and `RetiredInstructions` with `log_cycle`.
+    #[serde(default, skip_serializing_if = "checkpoint::omit")]
+    pub cycle: u64,
+    #[serde(default, skip_serializing_if = "checkpoint::omit")]
+    pub retired: u64,
+    #[serde(default, skip_serializing_if = "checkpoint::omit")]
+    pub log_cycle: bool,
+    /// Queues the integer queue is logged as, one per cluster, if the back
+    /// end is clustered.
+    #[serde(default, skip_serializing_if = "checkpoint::omit")]
+    pub num_clusters: usize,
+    /// Bytes per instruction the logged PCs are scaled by, with byte
+    /// addressed PCs.
+    #[serde(default, skip_serializing_if = "checkpoint::omit")]
+    pub pc_step: u64,
     #[serde(rename = "RegisterMapTable")]
-    pub register_map_table: Vec<u32>,
+    pub register_map_table: Shared<Vec<u32>>,
     #[serde(rename = "FreeList")]
-    pub free_list: VecDeque<u32>,
+    pub free_list: Shared<VecDeque<u32>>,
     #[serde(rename = "BusyBitTable")]
-    pub busy_bit_table: Vec<bool>,
+    pub busy_bit_table: Shared<Vec<bool>>,
     #[serde(rename = "ActiveList")]
-    pub active_list: VecDeque<ActiveEntry>,
+    pub active_list: Shared<VecDeque<ActiveEntry>>,
     #[serde(rename = "IntegerQueue")]
-    pub integer_queue: Vec<IntegerQueueEntry>,
-    #[serde(skip_serializing)]
-    pub backpressure: bool,
+    pub integer_queue: Shared<Vec<IntegerQueueEntry>>,
+    #[serde(flatten)]
+    pub fp: Option<FpState>,
+    #[serde(flatten)]
+    pub rob: Option<RobState>,
+    #[serde(flatten)]
+    pub fetch_buffer: Option<FetchBuffer>,
+    /// Micro-ops that passed each stage this cycle, with `log_events`.
+    #[serde(flatten)]
+    pub events: Option<CycleEvents>,
+    /// Contexts of the hardware threads after thread 0.
+    #[serde(rename = "Threads", default, skip_serializing_if = "Vec::is_empty")]
+    pub threads: Vec<ThreadContext>,
+    /// The structures that stalled rename this cycle, logged with
+    /// `log_backpressure`.
+    #[serde(default, skip_serializing_if = "checkpoint::omit")]
+    pub backpressure: Backpressure,
+    #[serde(default, skip_serializing_if = "checkpoint::omit")]
+    pub log_backpressure: bool,
+    /// Fields left out of the log, with `log_omit`.
+    #[serde(default, skip_serializing_if = "checkpoint::omit")]
+    pub log_omit: Shared<Vec<String>>,
+    /// Sequence number given to the next decoded micro-op.
+    #[serde(default, skip_serializing_if = "checkpoint::omit")]
+    pub next_seq: u64,
+    /// For each physical register, the uninitialized architectural register
+    /// its value was derived from, if any.
+    #[serde(default, skip_serializing_if = "checkpoint::omit")]
+    pub poisoned: Shared<Vec<Option<u32>>>,
+}
+
+/// The structures too small for the micro-ops waiting to be renamed, each
+/// of which stalls rename on its own.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Backpressure {
+    #[serde(rename = "IntegerQueueFull")]
+    pub integer_queue: bool,
+    #[serde(rename = "ActiveListFull")]
+    pub active_list: bool,
+    #[serde(rename = "FreeListEmpty")]
+    pub free_list: bool,
+    #[serde(rename = "FpQueueFull")]
+    pub fp_queue: bool,
+    #[serde(rename = "FpFreeListEmpty")]
+    pub fp_free_list: bool,
+}
+
+impl Backpressure {
+    /// Names of the causes, in the order of `causes`.
+    pub const NAMES: [&str; 5] = [
+        "IntegerQueueFull",
+        "ActiveListFull",
+        "FreeListEmpty",
+        "FpQueueFull",
+        "FpFreeListEmpty",
+    ];
+
+    pub fn causes(self) -> [bool; 5] {
+        [
+            self.integer_queue,
+            self.active_list,
+            self.free_list,
+            self.fp_queue,
+            self.fp_free_list,
+        ]
+    }
+
+    pub fn any(self) -> bool {
+        self.causes().contains(&true)
+    }
 }
 
-impl Default for SimulatorState {
-    fn default() -> Self {
+/// Number of architectural integer registers (`x0`..`x31`).
+pub const NUM_ARCH_REGS: usize = 32;
+
+/// Micro-ops fetched and decoded per cycle.
+pub const DECODE_WIDTH: usize = 4;
+
+/// Active list entries committed, or rolled back, per cycle.
+pub const COMMIT_WIDTH: usize = 4;
+
+/// Multiplies every PC in a logged state by `step`: the `PC` fields and
+/// the PCs listed by the decode and fetch buffers.
+fn scale_pcs(value: &mut serde_json::Value, step: u64) {
+    let scale = |pc: &mut serde_json::Value| *pc = (pc.as_u64().unwrap() * step).into();
+    match value {
+        serde_json::Value::Object(fields) => {
+            for (key, field) in fields.iter_mut() {
+                match key.as_str() {
+                    "PC" | "ExceptionPC" => scale(field),
+                    "DecodedPCs" | "FetchBuffer" => {
+                        field.as_array_mut().unwrap().iter_mut().for_each(scale)
+                    }
+                    _ => scale_pcs(field, step),
+                }
+            }
+        }
+        serde_json::Value::Array(items) => {
+            for item in items {
+                scale_pcs(item, step);
+            }
+        }
+        _ => {}
+    }
+}
+
+impl SimulatorState {
+    /// The state as it appears in the log. Immediate operands are signed, and
+    /// exception causes are added if requested.
+    /// Under reorder-buffer renaming the ROB replaces the active list and
+    /// free list. The fields of `log_omit` are left out last.
+    pub fn to_log_value(&self) -> serde_json::Value {
+        let mut value = serde_json::to_value(self).unwrap();
+        let queue = value["IntegerQueue"].as_array_mut().unwrap();
+        for (entry, logged) in self.integer_queue.iter().zip(queue) {
+            if entry.op_b_is_imm {
+                logged["OpBValue"] = (entry.op_b_value as i64).into();
+            }
+        }
+        if self.log_exception_cause {
+            let fields = value.as_object_mut().unwrap();
+            fields.insert(
+                "ExceptionCause".to_string(),
+                serde_json::to_value(self.exception_cause).unwrap(),
+            );
+            let list = fields["ActiveList"].as_array_mut().unwrap();
+            for (entry, logged) in self.active_list.iter().zip(list) {
+                logged["Cause"] = serde_json::to_value(entry.cause).unwrap();
+            }
+        }
+        if self.exception_nesting > 0 {
+            value["ExceptionStack"] = serde_json::to_value(&self.exception_stack).unwrap();
+        }
+        if self.log_cycle {
+            value["Cycle"] = self.cycle.into();
+            value["RetiredInstructions"] = self.retired.into();
+        }
+        if self.log_backpressure {
+            let logged = |backpressure: Backpressure| {
+                let mut logged = serde_json::to_value(backpressure).unwrap();
+                if self.fp.is_none() {
+                    let flags = logged.as_object_mut().unwrap();
+                    flags.remove("FpQueueFull");
+                    flags.remove("FpFreeListEmpty");
+                }
+                logged
+            };
+            value["Backpressure"] = logged(self.backpressure);
+            if let Some(threads) = value.get_mut("Threads") {
+                for (context, logged_context) in
+                    self.threads.iter().zip(threads.as_array_mut().unwrap())
+                {
+                    logged_context["Backpressure"] = logged(context.backpressure);
+                }
+            }
+        }
+        if self.rob.is_some() {
+            let fields = value.as_object_mut().unwrap();
+            fields.remove("ActiveList");
+            fields.remove("FreeList");
+        }
+        if self.num_clusters > 1 {
+            let fields = value.as_object_mut().unwrap();
+            let queue = fields.remove("IntegerQueue").unwrap();
+            let mut queues = vec![Vec::new(); self.num_clusters];
+            for (entry, logged) in self.integer_queue.iter().zip(queue.as_array().unwrap()) {
+                queues[entry.cluster].push(logged.clone());
+            }
+            fields.insert("IntegerQueues".to_string(), queues.into());
+        }
+        if self.pc_step > 1 {
+            scale_pcs(&mut value, self.pc_step);
+        }
+        for name in self.log_omit.iter() {
+            value.as_object_mut().unwrap().remove(name);
+            let threads = value
+                .get_mut("Threads")
+                .and_then(serde_json::Value::as_array_mut);
+            for context in threads.into_iter().flatten() {
+                context.as_object_mut().unwrap().remove(name);
+            }
+        }
+        value
+    }
+
+    /// The reset state with `physical_registers` physical registers, of which
+    /// all but the architectural ones start on the free list.
+    pub fn new(physical_registers: usize) -> Self {
         Self {
             pc: 0,
-            physical_register_file: vec![0; 64],
-            decoded_pcs: Vec::new(),
+            physical_register_file: Shared::new(vec![0; physical_registers]),
+            decoded_pcs: Shared::default(),
             exception_pc: 0,
             exception: false,
-            register_map_table: (0..32).collect(),
-            free_list: (32..64).collect::<VecDeque<u32>>(),
-            busy_bit_table: vec![false; 64],
-            active_list: VecDeque::new(),
-            integer_queue: Vec::new(),
-            backpressure: false,
+            exception_cause: None,
+            log_exception_cause: false,
+            exception_stack: Vec::new(),
+            exception_nesting: 0,
+            returns_in_flight: 0,
+            cycle: 0,
+            retired: 0,
+            log_cycle: false,
+            num_clusters: 0,
+            pc_step: 1,
+            register_map_table: Shared::new((0..NUM_ARCH_REGS as u32).collect()),
+            free_list: Shared::new((NUM_ARCH_REGS as u32..physical_registers as u32).collect()),
+            busy_bit_table: Shared::new(vec![false; physical_registers]),
+            active_list: Shared::default(),
+            integer_queue: Shared::default(),
+            fp: None,
+            rob: None,
+            fetch_buffer: None,
+            events: None,
+            threads: Vec::new(),
+            backpressure: Backpressure::default(),
+            log_backpressure: false,
+            log_omit: Shared::default(),
+            next_seq: 0,
+            poisoned: Shared::new(vec![None; physical_registers]),
         }
     }
 }
 
+/// PC of the first exception handler instruction in the reference design,
+/// the default of `exception_vector`.
+pub const EXCEPTION_VECTOR: u64 = 0x10000;
+
+/// Poison pattern for registers without a configured initial value.
+pub const POISON_PATTERN: u64 = 0xDEAD_BEEF_DEAD_BEEF;
+
+/// A committed instruction whose result depended on an uninitialized
+/// architectural register.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct PoisonedUse {
+    pub pc: u64,
+    pub register: u32,
+}
+
+/// Micro-ops moved by each pipeline stage in one cycle.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+pub struct CycleCounts {
+    pub fetched: usize,
+    pub dispatched: usize,
+    pub issued: usize,
+    pub committed: u64,
+}
+
+/// Counts how issue distributed instructions across ALUs with differing
+/// capabilities.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SteeringStats {
+    pub issued_per_alu: Vec<u64>,
+    /// Ready instructions left in the queue although an ALU was free, because
+    /// no free ALU was capable of executing them.
+    pub capability_stalls: u64,
+    /// Ready instructions left in the queue although a capable ALU was
+    /// free, because the binding policy tied them to another one.
+    pub binding_stalls: u64,
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct Simulator {
     pub program: Vec<String>,
     pub state: SimulatorState,
     pub log: Vec<SimulatorState>,
+    /// Cycles whose state is recorded in `log`; the reset state is cycle 0.
+    pub log_range: RangeInclusive<usize>,
+    /// Writes each state out as it is dumped instead of keeping it in `log`.
+    #[serde(skip)]
+    pub log_stream: Option<LogStream>,
+    /// Host time spent appending states to the log or writing them out.
+    #[serde(skip)]
+    pub(crate) log_time: Duration,
     pub alus: Vec<Alu>,
+    pub steering: SteeringStats,
+    /// First committed instruction that consumed a poisoned register.
+    pub first_poisoned_use: Option<PoisonedUse>,
+    /// Micro-ops committed so far.
+    pub committed: u64,
+    /// Stage throughput of every simulated cycle, in order.
+    pub bandwidth: Vec<CycleCounts>,
+    /// Counters accumulated over all simulated cycles.
+    pub stats: CycleStats,
+    /// Reference interpreter verifying every commit, if `--check` is given.
+    pub checker: Option<Checker>,
+    /// Panic as soon as the bookkeeping is inconsistent (`--assert-invariants`).
+    pub assert_invariants: bool,
+    /// Cycle limit of the run (`--max-cycles`).
+    pub max_cycles: Option<usize>,
+    /// Instruction limit of the run (`--max-instructions`); it ends as the
+    /// `commits` termination does.
+    pub max_instructions: Option<u64>,
+    /// Diagnostic report of a run the watchdog stopped.
+    pub watchdog: Option<String>,
+    /// Output of the committed print `ecall`s.
+    pub console: String,
+    /// Set once an exit `ecall` or an `ebreak` commits, ending the run.
+    pub halt: Option<Halt>,
+    /// Breakpoints and registers of an attached gdb (`--gdb`).
+    #[serde(skip)]
+    pub(crate) debugger: Option<Debugger>,
+    /// Commits not yet taken by the attached gdb or co-simulation client,
+    /// oldest first. Only recorded while one is attached.
+    #[serde(skip)]
+    pub(crate) commit_log: Option<VecDeque<Commit>>,
+    /// Notified of every pipeline event of the out-of-order pipeline.
+    #[serde(skip)]
+    pub(crate) observers: Vec<Box<dyn PipelineObserver>>,
+    pub metrics: Vec<CycleMetrics>,
+    /// Stage timestamps of every micro-op fetched by the out-of-order
+    /// pipeline, indexed by sequence number.
+    pub lifetimes: Vec<Lifetime>,
+    pub(crate) active_list_capacity: usize,
+    pub(crate) integer_queue_capacity: usize,
+    issue_policy: IssuePolicy,
+    pub(crate) rng: Rng,
+    pub(crate) free_list_policy: FreeListPolicy,
+    pub(crate) dispatch_mode: DispatchMode,
+    li_expansion: LiExpansion,
+    /// Instructions are hex-encoded machine-code words rather than assembly.
+    pub(crate) machine_code: bool,
+    /// Exception handler mapped at `exception_vector`, empty if none.
+    pub(crate) handler: Vec<String>,
+    pub(crate) handler_machine_code: bool,
+    /// `program` and `handler` decoded at load, indexed like them.
+    /// Checkpoints leave them out and are decoded again when restored.
+    #[serde(skip)]
+    pub(crate) decoded: Vec<DecodedInstruction>,
+    #[serde(skip)]
+    pub(crate) handler_decoded: Vec<DecodedInstruction>,
+    /// External interrupts not yet taken, as `(cycle, id)` in cycle order.
+    pub(crate) interrupts: VecDeque<(u64, u32)>,
+    pub(crate) fp_units: Vec<FpUnit>,
+    pub(crate) fp_config: FpConfig,
+    pub(crate) pipeline: Pipeline,
+    pub(crate) in_order: InOrderPipeline,
+    pub(crate) scoreboard: Scoreboard,
+    pub(crate) tomasulo: Tomasulo,
+    pub(crate) trap_on_overflow: bool,
+    pub(crate) smt: Smt,
+    /// Index of the first handler instruction.
+    pub(crate) exception_vector: u64,
+    /// Bytes between consecutive PCs as programs and outputs see them.
+    pub(crate) pc_step: u64,
+    pub(crate) termination: Termination,
+    pub(crate) exception_cooldown: bool,
+    pub(crate) exception_resume: ExceptionResume,
+    pub(crate) same_cycle_commit: bool,
+    pub(crate) hardwired_zero: bool,
+    pub(crate) bypass: Bypass,
+    pub(crate) writeback: Writeback,
+    pub(crate) scheduler: Scheduler,
+    pub(crate) value_prediction: Option<ValuePrediction>,
+    pub(crate) random_latency: Option<RandomLatency>,
+    /// Per-PC counts for `--profile`, if it was given.
+    pub(crate) profile: Option<Profile>,
+    pub(crate) roi: Option<RegionOfInterest>,
+    pub(crate) csrs: Csrs,
+    pub(crate) clusters: Clusters,
+    pub(crate) move_elimination: MoveElimination,
+    pub(crate) idiom_elimination: bool,
+    pub(crate) recovery: Recovery,
+    /// Register file reads available to the micro-ops issued in a cycle;
+    /// unlimited if unset.
+    pub(crate) read_ports: Option<usize>,
+    pub(crate) reclaimer: Reclaimer,
 }
 
 impl Simulator {
-    pub fn new(program: Vec<String>) -> Simulator {
-        Self {
-            program,
-            state: SimulatorState::default(),
+    /// A simulator at reset. `SimulatorBuilder` checks the config and the
+    /// program first and should be used instead.
+    pub(crate) fn new(program: Vec<String>, config: &Config) -> Simulator {
+        let mut state = match config.renaming {
+            Renaming::MergedRegisterFile => SimulatorState::new(config.physical_registers),
+            Renaming::ReorderBuffer => {
+                let mut state = SimulatorState::new(NUM_ARCH_REGS + config.active_list_capacity);
+                state.free_list.clear();
+                state.rob = Some(RobState::new(config.active_list_capacity));
+                state
+            }
+        };
+        for (reg, &value) in config.initial_registers.iter().enumerate() {
+            state.physical_register_file[reg] = value;
+        }
+        if config.poison_uninitialized {
+            // A hardwired x0 always reads as zero, so it is never poisoned.
+            let initialized = config
+                .initial_registers
+                .len()
+                .max(config.hardwired_zero as usize);
+            for reg in initialized..state.physical_register_file.len() {
+                state.physical_register_file[reg] = POISON_PATTERN;
+            }
+            for reg in initialized..NUM_ARCH_REGS {
+                state.poisoned[reg] = Some(reg as u32);
+            }
+        }
+        if config.fp.enabled {
+            state.fp = Some(FpState::new(&config.fp));
+        }
+        state.log_exception_cause = config.log_exception_cause;
+        state.exception_nesting = config.exception_nesting;
+        // A log that leaves out the states before the region has to say
+        // which cycle each state is.
+        state.log_cycle = config.log_cycle || config.roi.log_only;
+        state.log_backpressure = config.log_backpressure;
+        state.log_omit = Shared::new(config.log_omit.clone());
+        state.pc_step = config.pc_step();
+        if config.clusters.count > 1 {
+            state.num_clusters = config.clusters.count;
+        }
+        state.fetch_buffer = config.fetch_buffer.map(FetchBuffer::new);
+        if config.log_events {
+            state.events = Some(CycleEvents::default());
+        }
+        let registers = state.physical_register_file.len();
+        let mut sim = Self {
+            state,
             log: Vec::new(),
-            alus: vec![Alu::new(), Alu::new(), Alu::new(), Alu::new()],
+            log_range: 0..=usize::MAX,
+            log_stream: None,
+            log_time: Duration::ZERO,
+            alus: (0..config.num_alus)
+                .map(|i| {
+                    // The config validated every listed opcode.
+                    let capabilities = config
+                        .alu_capabilities
+                        .get(i)
+                        .map(|ops| {
+                            ops.iter()
+                                .flat_map(|op| Opcode::capability(op).unwrap())
+                                .collect()
+                        })
+                        .unwrap_or_default();
+                    Alu::new(
+                        capabilities,
+                        config.trap_on_overflow,
+                        config.alu_pipeline_depth,
+                    )
+                })
+                .collect(),
+            steering: SteeringStats {
+                issued_per_alu: vec![0; config.num_alus],
+                capability_stalls: 0,
+                binding_stalls: 0,
+            },
+            first_poisoned_use: None,
+            committed: 0,
+            bandwidth: Vec::new(),
+            stats: CycleStats::new(config.num_alus),
+            checker: None,
+            assert_invariants: false,
+            max_cycles: None,
+            max_instructions: None,
+            watchdog: None,
+            console: String::new(),
+            halt: None,
+            debugger: None,
+            commit_log: None,
+            observers: Vec::new(),
+            metrics: Vec::new(),
+            lifetimes: Vec::new(),
+            active_list_capacity: config.active_list_capacity,
+            integer_queue_capacity: config.integer_queue_capacity,
+            issue_policy: config.issue_policy,
+            rng: Rng::new(config.seed),
+            free_list_policy: config.free_list_policy,
+            dispatch_mode: config.dispatch,
+            li_expansion: config.li_expansion,
+            machine_code: Self::is_machine_code(&program, config),
+            program,
+            handler: Vec::new(),
+            handler_machine_code: false,
+            interrupts: VecDeque::new(),
+            fp_units: (0..config.fp.units).map(|_| FpUnit::new()).collect(),
+            fp_config: config.fp.clone(),
+            pipeline: config.pipeline,
+            in_order: InOrderPipeline::default(),
+            scoreboard: Scoreboard::new(config.num_alus),
+            tomasulo: Tomasulo::new(
+                config.num_alus,
+                config.tomasulo.stations_per_unit,
+                config.tomasulo.common_data_buses,
+            ),
+            trap_on_overflow: config.trap_on_overflow,
+            smt: Smt::new(config),
+            exception_vector: config.vector_index(),
+            pc_step: config.pc_step(),
+            termination: config.termination,
+            exception_cooldown: config.exception_cooldown,
+            exception_resume: config.exception_resume,
+            same_cycle_commit: config.same_cycle_commit,
+            hardwired_zero: config.hardwired_zero,
+            bypass: Bypass::new(&config.bypass),
+            writeback: Writeback::new(&config.writeback),
+            scheduler: Scheduler::new(config),
+            value_prediction: ValuePrediction::new(&config.value_prediction),
+            random_latency: RandomLatency::new(&config.latency, config.seed),
+            profile: None,
+            roi: RegionOfInterest::new(&config.roi, config.pc_step()),
+            csrs: Csrs::default(),
+            clusters: Clusters::new(&config.clusters, config.integer_queue_capacity, registers),
+            move_elimination: MoveElimination::new(config.move_elimination, registers),
+            idiom_elimination: config.idiom_elimination,
+            recovery: Recovery::new(&config.recovery),
+            read_ports: config.register_file.read_ports,
+            reclaimer: Reclaimer::new(config.reclamation),
+            decoded: Vec::new(),
+            handler_decoded: Vec::new(),
+        };
+        sim.predecode();
+        sim
+    }
+
+    pub(crate) fn is_machine_code(program: &[String], config: &Config) -> bool {
+        match config.input_format {
+            InputFormat::Auto => decoder::is_machine_code(program),
+            InputFormat::Assembly => false,
+            InputFormat::MachineCode => true,
         }
     }
-    pub fn dump_state_into_log(&mut self) {
-        self.log.push(self.state.clone());
+
+    /// Maps `handler` at the exception vector, so a taken exception runs it
+    /// instead of ending the program. `mret` in the handler resumes after the
+    /// faulting instruction.
+    pub fn set_handler(&mut self, handler: Vec<String>, config: &Config) {
+        self.handler_machine_code = Self::is_machine_code(&handler, config);
+        self.handler = handler;
+        self.handler_decoded = (0..self.handler.len() as u64)
+            .map(|offset| self.decode_instruction(self.exception_vector + offset))
+            .collect();
     }
 
-    pub fn done(&self) -> bool {
-        let pipeline_empty = self.state.active_list.is_empty()
-            && self.state.integer_queue.is_empty()
-            && self.state.decoded_pcs.is_empty();
+    /// Decodes the programs of all threads and the handler, so fetch only
+    /// indexes into them.
+    pub(crate) fn predecode(&mut self) {
+        for t in 0..self.num_threads() {
+            self.on_thread(t, Self::decode_program);
+        }
+        self.handler_decoded = (0..self.handler.len() as u64)
+            .map(|offset| self.decode_instruction(self.exception_vector + offset))
+            .collect();
+    }
+
+    /// Decodes the program of the current thread.
+    pub(crate) fn decode_program(&mut self) {
+        self.decoded = (0..self.program.len() as u64)
+            .map(|pc| self.decode_instruction(pc))
+            .collect();
+    }
 
-        if !pipeline_empty {
-            return false;
+    /// The decoded instruction at `pc`, in the program or the exception
+    /// handler.
+    pub(crate) fn decoded_instruction(&self, pc: u64) -> Option<&DecodedInstruction> {
+        match pc.checked_sub(self.exception_vector) {
+            Some(offset) => self.handler_decoded.get(offset as usize),
+            None => self.decoded.get(pc as usize),
         }
+    }
 
-        if self.state.pc == 0x10000 {
-            // Exception scenario: terminate only after the cooldown cycle.
-            return !self.state.exception;
-        } else {
-            // Normal scenario: terminate if PC is past the end of the program.
-            return self.state.pc as usize >= self.program.len();
+    /// The instruction at `pc`, in the program or the exception handler.
+    pub(crate) fn instruction(&self, pc: u64) -> Option<&String> {
+        match pc.checked_sub(self.exception_vector) {
+            Some(offset) => self.handler.get(offset as usize),
+            None => self.program.get(pc as usize),
         }
     }
 
+    /// Appends the current state to the log, or writes it to `log_stream`, if the current cycle lies in
+    /// `log_range`.
+    pub fn dump_state_into_log(&mut self) {
+        if !self.log_range.contains(&self.cycles()) || self.outside_roi_log() {
+            return;
+        }
+        let start = Instant::now();
+        self.sync_rob_state();
+        match self.log_stream.as_mut() {
+            Some(stream) => stream.write_state(self.state.to_log_value()),
+            // The registers and queues are shared with the live state until
+            // it next changes them.
+            None => self.log.push(self.state.clone()),
+        }
+        self.log_time += start.elapsed();
+    }
+
+    /// The `commits` termination target or the instruction limit has been
+    /// reached.
+    pub(crate) fn commit_target_reached(&self) -> bool {
+        matches!(self.termination, Termination::Commits(n) if self.committed >= n)
+            || self.max_instructions.is_some_and(|n| self.committed >= n)
+    }
+
+    /// The address of the instruction at `pc` as programs and outputs see
+    /// it, a byte address with byte-addressed PCs.
+    pub fn address(&self, pc: u64) -> u64 {
+        pc * self.pc_step
+    }
+
+    /// Cycles simulated so far.
+    pub fn cycles(&self) -> usize {
+        self.bandwidth.len()
+    }
+
+    pub fn done(&self) -> bool {
+        self.halt.is_some()
+            || self.commit_target_reached()
+            || self.state.integer_queue.is_empty()
+                && self.state.fp.as_ref().is_none_or(|fp| fp.queue.is_empty())
+                && (0..self.num_threads()).all(|t| self.thread_done(t))
+    }
+
     pub fn simulate_cycle(&mut self) {
-        let pipeline_stalled = self.commit();
+        match self.pipeline {
+            Pipeline::OutOfOrder => self.simulate_cycle_out_of_order(),
+            Pipeline::InOrder => self.simulate_cycle_in_order(),
+            Pipeline::Scoreboard => self.simulate_cycle_scoreboard(),
+            Pipeline::Tomasulo => self.simulate_cycle_tomasulo(),
+        }
+        self.record_stats();
+        self.end_roi_cycle();
+        self.state.cycle = self.cycles() as u64;
+        self.state.retired = self.committed;
+        for observer in self.observers.iter_mut() {
+            observer.on_cycle(self.state.cycle, &self.state);
+        }
+    }
 
-        if !pipeline_stalled {
+    fn simulate_cycle_out_of_order(&mut self) {
+        if let Some(events) = self.state.events.as_mut() {
+            *events = CycleEvents::default();
+        }
+        let committed_before = self.committed;
+        // Executing first lets commit see the results forwarded this cycle.
+        if self.same_cycle_commit {
             self.execute();
+            self.execute_fp();
+        }
+        let (stalled, slots): (Vec<bool>, Vec<CommitSlots>) = (0..self.num_threads())
+            .map(|t| {
+                self.on_thread(t, |sim| {
+                    let before = sim.committed;
+                    let recovering = sim.state.exception;
+                    let stalled = sim.commit();
+                    sim.smt.committed[t] += sim.committed - before;
+                    (
+                        stalled,
+                        sim.commit_slots(sim.committed - before, recovering),
+                    )
+                })
+            })
+            .unzip();
+        let mut counts = CycleCounts {
+            committed: self.committed - committed_before,
+            ..CycleCounts::default()
+        };
+
+        // A thread rolling back after an exception stalls its own front
+        // end; the shared back end keeps running for the other threads.
+        if stalled.contains(&false) {
+            if !self.same_cycle_commit {
+                self.execute();
+                self.execute_fp();
+            }
+            let queued_before = self.queued();
             self.issue();
-            self.rename_and_dispatch();
-            self.fetch_and_decode();
+            self.issue_fp();
+            counts.issued = queued_before - self.queued();
+            // Rotate rename priority so no thread monopolizes the shared
+            // queue and free list.
+            let n = self.num_threads();
+            let decoded_before = self.decoded();
+            for i in 0..n {
+                let t = (self.bandwidth.len() + i) % n;
+                if !stalled[t] {
+                    self.on_thread(t, Self::rename_and_dispatch);
+                }
+            }
+            counts.dispatched = decoded_before - self.decoded();
+            let decoded_before = self.decoded();
+            if let Some(t) = self.select_fetch_thread(&stalled) {
+                self.on_thread(t, Self::fetch_and_decode);
+            }
+            counts.fetched = self.decoded() - decoded_before;
+        }
+        for slots in &slots {
+            self.attribute_commit_slots(slots);
         }
+        self.bandwidth.push(counts);
+    }
+
+    /// Micro-ops decoded and waiting for rename, over all threads.
+    fn decoded(&self) -> usize {
+        let others: usize = self.state.threads.iter().map(|t| t.decoded_pcs.len()).sum();
+        self.state.decoded_pcs.len() + others
+    }
+
+    /// Micro-ops waiting in the integer and FP queues.
+    fn queued(&self) -> usize {
+        self.state.integer_queue.len() + self.state.fp.as_ref().map_or(0, |fp| fp.queue.len())
     }
 
     pub fn fetch_and_decode(&mut self) {
-        if self.state.backpressure || self.state.exception {
+        // With a fetch buffer, decode drains what earlier cycles fetched
+        // before fetch refills it.
+        if self.state.fetch_buffer.is_some() {
+            self.decode_fetched();
+            self.fetch();
+            return;
+        }
+        if self.fetch_blocked() || self.state.exception {
             return;
         }
-        for _ in 0..4 {
-            if self.state.pc as usize >= self.program.len() {
+        // Decode width is counted in micro-ops, so a cracked instruction
+        // takes two of the four slots.
+        while self.state.decoded_pcs.len() < DECODE_WIDTH {
+            let room = DECODE_WIDTH - self.state.decoded_pcs.len();
+            let Some(micro_ops) = self.fetch_instruction(room) else {
                 break;
+            };
+            self.state.decoded_pcs.extend(micro_ops);
+        }
+    }
+
+    /// Fetches the instruction at the PC if its micro-ops fit in `room`
+    /// slots, numbering them and advancing the PC past it.
+    pub(crate) fn fetch_instruction(
+        &mut self,
+        room: usize,
+    ) -> Option<Vec<DecodedInstructionEntry>> {
+        let pc = self.state.pc;
+        let instruction = self.decoded_instruction(pc)?;
+        let returns = instruction.returns;
+        let mut micro_ops = instruction.micro_ops.clone();
+        if micro_ops.len() > room {
+            return None;
+        }
+        for micro_op in &mut micro_ops {
+            micro_op.seq = self.state.next_seq;
+            self.state.next_seq += 1;
+            self.record_fetch(micro_op);
+        }
+        // Fetch only reaches the handler once the exception has been
+        // taken, so `mret` can redirect it right away. An interrupted
+        // instruction has not executed and resumes itself.
+        let (exception_pc, cause) = self.return_target();
+        self.state.pc = match (returns, cause) {
+            (true, Some(ExceptionCause::Interrupt(_))) => exception_pc,
+            (true, _) => exception_pc + 1,
+            (false, _) => pc + 1,
+        };
+        if returns && self.state.exception_nesting > 0 {
+            self.state.returns_in_flight += 1;
+        }
+        Some(micro_ops)
+    }
+
+    /// The exception the next `mret` fetched returns from: the innermost
+    /// one, or with `mret`s still in flight the one they return to.
+    fn return_target(&self) -> (u64, Option<ExceptionCause>) {
+        let state = &self.state;
+        let stack = &state.exception_stack;
+        match state.returns_in_flight {
+            0 => (state.exception_pc, state.exception_cause),
+            n => stack
+                .get(stack.len().saturating_sub(n))
+                .map_or((state.exception_pc, state.exception_cause), |outer| {
+                    (outer.pc, outer.cause)
+                }),
+        }
+    }
+
+    /// Returns from a nested exception to the one it interrupted once its
+    /// `mret`, at `pc`, commits.
+    fn retire_return(&mut self, pc: u64) {
+        if self.state.exception_nesting == 0
+            || !self.decoded_instruction(pc).is_some_and(|i| i.returns)
+        {
+            return;
+        }
+        let state = &mut self.state;
+        state.returns_in_flight = state.returns_in_flight.saturating_sub(1);
+        if let Some(outer) = state.exception_stack.pop() {
+            state.exception_pc = outer.pc;
+            state.exception_cause = outer.cause;
+        }
+    }
+
+    /// The micro-ops of the instruction at `pc`.
+    pub(crate) fn decode(&self, pc: u64) -> Vec<DecodedInstructionEntry> {
+        self.decoded_instruction(pc).unwrap().micro_ops.clone()
+    }
+
+    fn decode_instruction(&self, pc: u64) -> DecodedInstruction {
+        let line = self.instruction(pc).unwrap();
+        let mut micro_ops = self.decode_line(pc, line);
+        if self.hardwired_zero {
+            for micro_op in micro_ops.iter_mut().filter(|m| !m.op.is_fp()) {
+                micro_op.src1 = micro_op.src1.filter(|&r| r != 0);
+                micro_op.src2 = micro_op.src2.filter(|&r| r != 0);
             }
-            let pc = self.state.pc;
-            let instr_line = self.program[pc as usize].clone();
-            let parts: Vec<&str> = instr_line.split_whitespace().collect();
-            if parts.len() < 4 {
-                continue;
-            }
-            let raw_op = parts[0];
-            self.state.decoded_pcs.push(DecodedInstructionEntry {
+        }
+        DecodedInstruction {
+            micro_ops,
+            returns: line.trim() == "mret",
+        }
+    }
+
+    /// Decodes the instruction `line` at `pc` into one or more micro-ops,
+    /// with register operands as indices and immediates as values; a source
+    /// register left out reads as a ready zero.
+    fn decode_line(&self, pc: u64, line: &str) -> Vec<DecodedInstructionEntry> {
+        let machine_code = match pc >= self.exception_vector {
+            true => self.handler_machine_code,
+            false => self.machine_code,
+        };
+        if machine_code {
+            let word = decoder::parse_word(line)
+                .unwrap_or_else(|| panic!("Malformed instruction word: {}", line));
+            let micro_op = decoder::decode_word(pc, word)
+                .unwrap_or_else(|| panic!("Unsupported instruction word: {:#010x}", word));
+            return vec![micro_op];
+        }
+        let (mnemonic, operands) =
+            asm::split_instruction(line).unwrap_or_else(|| panic!("Empty instruction"));
+        let micro_op = |op: Opcode,
+                        is_imm: bool,
+                        dest: u32,
+                        src1: Option<u32>,
+                        src2: Option<u32>,
+                        imm: i64| {
+            DecodedInstructionEntry {
                 pc,
-                op: raw_op.trim_end_matches('i').to_string(),
-                is_imm: raw_op.ends_with('i'),
-                dest: parts[1].trim_end_matches(',').to_string(),
-                src1: parts[2].trim_end_matches(',').to_string(),
-                src2: parts[3].to_string(),
-            });
-            self.state.pc += 1;
+                op,
+                is_imm,
+                dest,
+                src1,
+                src2,
+                imm: imm as u64,
+                seq: 0,
+            }
+        };
+
+        if mnemonic == "li" {
+            let (dest, imm) = (
+                asm::register_index(operands[0]).unwrap(),
+                asm::parse_immediate(operands[1]).unwrap() as i64,
+            );
+            // Constants that fit a 12-bit immediate need no upper part and
+            // become an `addi` from zero.
+            if (-2048..2048).contains(&imm) {
+                return vec![micro_op(Opcode::Add, true, dest, None, None, imm)];
+            }
+            return match self.li_expansion {
+                LiExpansion::Crack => {
+                    // Round the upper part so the lower part is a signed
+                    // 12-bit value, as an assembler would. Near i64::MAX the
+                    // rounded split would overflow, so truncate instead.
+                    let upper = match imm.checked_add(0x800) {
+                        Some(rounded) => rounded >> 12,
+                        None => imm >> 12,
+                    };
+                    let lower = imm - (upper << 12);
+                    vec![
+                        micro_op(Opcode::Lui, true, dest, None, None, upper),
+                        micro_op(Opcode::Add, true, dest, Some(dest), None, lower),
+                    ]
+                }
+                LiExpansion::Fused => vec![micro_op(Opcode::Li, true, dest, None, None, imm)],
+            };
         }
+
+        let (op, descriptor) = asm::descriptor_of(mnemonic)
+            .unwrap_or_else(|| panic!("Unknown instruction: {}", mnemonic));
+        if op.is_fp() && self.state.fp.is_none() {
+            panic!(
+                "FP instruction {} requires fp.enabled in the config",
+                mnemonic
+            );
+        }
+        let register = |field: Field| match field {
+            Field::Operand(i) => Some(match descriptor.operands[i] {
+                Operand::FpRegister => asm::fp_register_index(operands[i]).unwrap(),
+                _ => asm::register_index(operands[i]).unwrap(),
+            }),
+            Field::Register(index) => Some(index),
+            Field::Zero | Field::Immediate(_) => None,
+        };
+        let immediate = |field: Field| match field {
+            Field::Operand(i) => match descriptor.operands[i] {
+                Operand::Csr => csr::parse(operands[i]).unwrap() as i64,
+                _ => asm::parse_immediate(operands[i]).unwrap() as i64,
+            },
+            Field::Immediate(value) => value,
+            Field::Register(_) | Field::Zero => 0,
+        };
+        let mut src1 = register(descriptor.src1);
+        // `csrrs` and `csrrc` from x0 only read the CSR, whatever x0 holds.
+        if matches!(op, Opcode::Csrrs | Opcode::Csrrc) && src1 == Some(0) {
+            src1 = None;
+        }
+        let (src2, imm) = match descriptor.is_imm {
+            true => (None, immediate(descriptor.src2)),
+            false => (register(descriptor.src2), 0),
+        };
+        vec![micro_op(
+            op,
+            descriptor.is_imm,
+            register(descriptor.dest).unwrap(),
+            src1,
+            src2,
+            imm,
+        )]
     }
 
     pub fn rename_and_dispatch(&mut self) {
         let num_instr = self.state.decoded_pcs.len();
-        self.state.backpressure = self.state.integer_queue.len() + num_instr > 32
-            || self.state.active_list.len() + num_instr > 32
-            || self.state.free_list.len() < num_instr;
-        if self.state.backpressure || num_instr == 0 {
+        let num_fp = self
+            .state
+            .decoded_pcs
+            .iter()
+            .filter(|i| i.op.is_fp())
+            .count();
+        let num_int = num_instr - num_fp;
+        let num_allocating = self
+            .state
+            .decoded_pcs
+            .iter()
+            .filter(|i| self.allocates_register(i))
+            .count();
+        let state = &self.state;
+        let fp = state.fp.as_ref();
+        self.state.backpressure = Backpressure {
+            integer_queue: state.integer_queue.len() + num_int > self.integer_queue_capacity,
+            active_list: state.active_list.len() + num_instr > self.active_list_capacity,
+            free_list: state.rob.is_none() && state.free_list.len() < num_allocating,
+            fp_queue: fp.is_some_and(|fp| fp.queue.len() + num_fp > self.fp_config.queue_capacity),
+            fp_free_list: fp.is_some_and(|fp| fp.free_list.len() < num_fp),
+        };
+        if self.state.backpressure.any() && !self.observers.is_empty() {
+            self.report_backpressure(num_int, num_allocating, num_fp);
+        }
+        let dispatched = match self.dispatch_mode {
+            DispatchMode::AllOrNothing if self.state.backpressure.any() => 0,
+            DispatchMode::AllOrNothing => num_instr,
+            DispatchMode::Partial => self.dispatchable(),
+        };
+        if dispatched == 0 {
             return;
         }
-        for instr in std::mem::take(&mut self.state.decoded_pcs) {
-            let (op_a_is_ready, op_a_reg_tag, op_a_value) =
-                self.get_operand_state(&instr.src1, false);
-            let (op_b_is_ready, op_b_reg_tag, op_b_value) =
-                self.get_operand_state(&instr.src2, instr.is_imm);
-            let arch_dest: u32 = instr.dest[1..].parse().unwrap();
+        let bundle: Vec<_> = self.state.decoded_pcs.drain(..dispatched).collect();
+        for instr in bundle {
+            self.record_stage(instr.seq, Stage::Dispatch);
+            if instr.op.is_fp() {
+                self.rename_fp(instr);
+                continue;
+            }
+            if let Some(arch_src) = self.move_source(&instr) {
+                self.eliminate_move(&instr, arch_src);
+                continue;
+            }
+            if let Some(value) = self.idiom_value(&instr) {
+                self.eliminate_idiom(&instr, value);
+                continue;
+            }
+            self.take_snapshot(&instr);
+            // auipc adds to its own PC, which is known at rename.
+            let (op_a_is_ready, op_a_reg_tag, op_a_value) = if instr.op == Opcode::Auipc {
+                (true, 0, self.address(instr.pc))
+            } else {
+                let operand = self.get_operand_state(instr.src1);
+                self.speculate(operand)
+            };
+            let operand = match instr.is_imm {
+                true => (true, 0, instr.imm),
+                false => self.get_operand_state(instr.src2),
+            };
+            let (op_b_is_ready, op_b_reg_tag, op_b_value) = self.speculate(operand);
+            self.count_rename_accesses(&instr);
+            let op_a_poison = self.operand_poison(instr.src1);
+            let op_b_poison = self.operand_poison(instr.src2);
+            let arch_dest = instr.dest;
             let old_phys_dest = self.state.register_map_table[arch_dest as usize];
-            let new_phys_dest = self.state.free_list.pop_front().unwrap();
-            self.state.register_map_table[arch_dest as usize] = new_phys_dest;
-            self.state.busy_bit_table[new_phys_dest as usize] = true;
+            let discarded = self.hardwired_zero && arch_dest == 0;
+            let new_phys_dest = match &self.state.rob {
+                Some(rob) => self.allocate_rob_tag(rob),
+                // A write to a hardwired x0 keeps its mapping and frees
+                // nothing at commit.
+                None if discarded => old_phys_dest,
+                None => take_free(
+                    &mut self.state.free_list,
+                    self.free_list_policy,
+                    &mut self.rng,
+                ),
+            };
+            if !discarded {
+                self.state.register_map_table[arch_dest as usize] = new_phys_dest;
+                self.stats.accesses.map_table_writes += 1;
+            }
+            if new_phys_dest != old_phys_dest {
+                self.state.busy_bit_table[new_phys_dest as usize] = true;
+            }
+            self.predict_value(&instr, new_phys_dest, discarded);
+            let waiting_on: Vec<u32> =
+                [(op_a_is_ready, op_a_reg_tag), (op_b_is_ready, op_b_reg_tag)]
+                    .into_iter()
+                    .filter(|&(ready, _)| !ready)
+                    .map(|(_, tag)| tag)
+                    .collect();
+            let cluster = self.steer(&waiting_on, new_phys_dest);
+            let bound_alu = self.bind_at_dispatch(instr.op, cluster);
+            let register_reads = register_sources(&instr).count() as u32;
             self.state.active_list.push_back(ActiveEntry {
                 done: false,
                 exception: false,
                 logical_destination: arch_dest,
                 old_destination: old_phys_dest,
+                destination: new_phys_dest,
                 pc: instr.pc,
+                is_fp: false,
+                cause: None,
+                poison: None,
+                environment: None,
+                seq: instr.seq,
+                eliminated: false,
             });
             self.state.integer_queue.push(IntegerQueueEntry {
                 dest_register: new_phys_dest,
@@ -266,18 +1469,90 @@ impl Simulator {
                 op_b_is_ready,
                 op_b_reg_tag,
                 op_b_value,
+                op_b_is_imm: instr.is_imm,
                 op_code: instr.op,
                 pc: instr.pc,
+                op_a_poison,
+                op_b_poison,
+                seq: instr.seq,
+                cluster,
+                bound_alu,
+                register_reads,
+                csr: None,
             });
         }
+        // Every micro-op took an active list entry, eliminated or not.
+        if !self.observers.is_empty() {
+            self.report_renames(dispatched);
+        }
+    }
+
+    /// The number of decoded micro-ops, oldest first, the queues, the active
+    /// list and the free lists have room for.
+    fn dispatchable(&self) -> usize {
+        let state = &self.state;
+        let mut queue = self
+            .integer_queue_capacity
+            .saturating_sub(state.integer_queue.len());
+        let mut active_list = self
+            .active_list_capacity
+            .saturating_sub(state.active_list.len());
+        let mut free_list = match state.rob {
+            Some(_) => usize::MAX,
+            None => state.free_list.len(),
+        };
+        let (mut fp_queue, mut fp_free_list) = match &state.fp {
+            Some(fp) => (
+                self.fp_config.queue_capacity.saturating_sub(fp.queue.len()),
+                fp.free_list.len(),
+            ),
+            None => (0, 0),
+        };
+        state
+            .decoded_pcs
+            .iter()
+            .take_while(|instr| {
+                let (queue, free_list) = match instr.op.is_fp() {
+                    true => (&mut fp_queue, &mut fp_free_list),
+                    false => (&mut queue, &mut free_list),
+                };
+                let allocates = instr.op.is_fp() || self.allocates_register(instr);
+                if *queue == 0 || active_list == 0 || (allocates && *free_list == 0) {
+                    return false;
+                }
+                *queue -= 1;
+                active_list -= 1;
+                *free_list -= allocates as usize;
+                true
+            })
+            .count()
+    }
+
+    /// Whether backpressure keeps fetch from refilling the decode buffer.
+    /// Under partial dispatch, fetch refills the slots rename emptied.
+    pub(crate) fn fetch_blocked(&self) -> bool {
+        self.state.backpressure.any() && self.dispatch_mode == DispatchMode::AllOrNothing
     }
 
-    fn get_operand_state(&self, src: &str, is_imm: bool) -> (bool, u32, u64) {
-        if is_imm {
-            return (true, 0, src.parse().unwrap());
+    /// Counts the accesses of renaming `instr` and dispatching it: a map
+    /// table lookup per register source and of the destination, a register
+    /// read per source that is ready, and the queue entry written.
+    fn count_rename_accesses(&mut self, instr: &DecodedInstructionEntry) {
+        for src in register_sources(instr) {
+            let phys_reg = self.state.register_map_table[src as usize];
+            let accesses = &mut self.stats.accesses;
+            accesses.map_table_reads += 1;
+            accesses.register_reads += !self.state.busy_bit_table[phys_reg as usize] as u64;
         }
-        let arch_reg: usize = src[1..].parse().unwrap();
-        let phys_reg = self.state.register_map_table[arch_reg];
+        self.stats.accesses.map_table_reads += 1;
+        self.stats.accesses.queue_writes += 1;
+    }
+
+    fn get_operand_state(&self, src: Option<u32>) -> (bool, u32, u64) {
+        let Some(arch_reg) = src else {
+            return (true, 0, 0);
+        };
+        let phys_reg = self.state.register_map_table[arch_reg as usize];
         if self.state.busy_bit_table[phys_reg as usize] {
             (false, phys_reg, 0)
         } else {
@@ -289,20 +1564,84 @@ impl Simulator {
         }
     }
 
+    /// Poison carried by a ready source operand. Operands still waiting on a
+    /// producer pick up its poison at wakeup instead.
+    fn operand_poison(&self, src: Option<u32>) -> Option<u32> {
+        let phys_reg = self.state.register_map_table[src? as usize] as usize;
+        if self.state.busy_bit_table[phys_reg] {
+            None
+        } else {
+            self.state.poisoned[phys_reg]
+        }
+    }
+
+    /// Orders ready queue entries, given in queue order, by the issue policy.
+    pub(crate) fn order_ready<T>(&mut self, ready: &mut [T], pc: impl Fn(&T) -> u64) {
+        match self.issue_policy {
+            IssuePolicy::Oldest => ready.sort_by_key(pc),
+            IssuePolicy::Youngest => ready.sort_by_key(|i| std::cmp::Reverse(pc(i))),
+            IssuePolicy::Random => self.rng.shuffle(ready),
+            IssuePolicy::Position => {}
+        }
+    }
+
     pub fn issue(&mut self) {
         let mut ready_instr: Vec<_> = self
             .state
             .integer_queue
             .iter()
-            .filter(|i| i.op_a_is_ready && i.op_b_is_ready)
+            .filter(|i| i.op_a_is_ready && i.op_b_is_ready && self.selectable(i.seq))
+            .filter(|i| !i.op_code.is_csr() || self.heads_active_list(i.seq))
             .cloned()
             .collect();
-        ready_instr.sort_by_key(|k| k.pc);
+        if self.scheduler.has_age_matrix() {
+            let ready: Vec<u64> = ready_instr.iter().map(|i| i.seq).collect();
+            let granted = self.grant_by_age(&ready);
+            ready_instr.sort_by_key(|i| granted.iter().position(|&seq| seq == i.seq));
+        } else {
+            self.order_ready(&mut ready_instr, |i| i.pc);
+        }
         let mut issued = HashSet::new();
+        let mut free_read_ports = self.read_ports.unwrap_or(usize::MAX);
+        let mut free_select_ports = self.scheduler.select_ports.unwrap_or(usize::MAX);
         for instr in ready_instr {
-            if let Some(alu) = self.alus.iter_mut().find(|a| a.is_free()) {
-                alu.push_instr(instr.clone());
+            let capable = self.select_alu(&instr);
+            let reads = instr.register_reads as usize;
+            if capable.is_some() && free_select_ports == 0 {
+                self.stats.select_port_stalls += 1;
+                continue;
+            }
+            if capable.is_some() && reads > free_read_ports {
+                self.stats.read_port_stalls += 1;
+                continue;
+            }
+            if let Some(i) = capable {
+                free_read_ports -= reads;
+                free_select_ports -= 1;
+                self.record_stage(instr.seq, Stage::Issue);
+                self.stats.accesses.queue_issues += 1;
+                self.stats.accesses.alu_operations += 1;
+                let extra = match self.random_latency.as_mut() {
+                    Some(latency) => latency.draw(instr.op_code),
+                    None => 0,
+                };
+                let mut in_flight = instr.clone();
+                if instr.op_code.is_csr() {
+                    let number = instr.op_b_value as u16;
+                    in_flight.csr = Some(number);
+                    in_flight.op_b_value = self.read_csr(number);
+                }
+                self.alus[i].push_instr(in_flight);
+                self.alus[i].add_latency(extra);
+                self.steering.issued_per_alu[i] += 1;
+                self.scheduler.next_alu = (i + 1) % self.alus.len();
                 issued.insert(instr);
+            } else if (0..self.alus.len()).any(|i| self.usable_alu(i, &instr)) {
+                self.steering.binding_stalls += 1;
+            } else if (0..self.alus.len())
+                .any(|i| self.alus[i].is_free() && self.alu_cluster(i) == instr.cluster)
+            {
+                self.steering.capability_stalls += 1;
             }
         }
         self.state.integer_queue.retain(|i| !issued.contains(i));
@@ -312,82 +1651,449 @@ impl Simulator {
         for alu in self.alus.iter_mut() {
             alu.execute();
         }
-        for alu in &self.alus {
-            if let Some((reg, val, pc, exception)) = alu.forwarding {
-                if let Some(entry) = self.state.active_list.iter_mut().find(|e| e.pc == pc) {
+        self.bypass.woken.clear();
+        let mut completed = Vec::new();
+        let mut results = Vec::new();
+        let mut written = Vec::new();
+        for i in self.arbitrate_writeback() {
+            if let Some(AluResult {
+                dest: reg,
+                value: val,
+                seq,
+                exception,
+                poison,
+                environment,
+            }) = self.alus[i].forwarding
+            {
+                let mut active = self.state.active_list.iter_mut().chain(
+                    self.state
+                        .threads
+                        .iter_mut()
+                        .flat_map(|t| t.active_list.iter_mut()),
+                );
+                let mut discarded = false;
+                let mut pc = None;
+                if let Some(entry) = active.find(|e| e.seq == seq) {
+                    pc = Some(entry.pc);
+                    completed.push(seq);
                     entry.done = true;
-                    entry.exception = exception;
+                    entry.exception = exception.is_some();
+                    entry.cause = exception;
+                    entry.poison = poison;
+                    entry.environment = environment;
+                    // A write to a hardwired x0 was renamed onto the
+                    // register x0 is mapped to, which stays zero.
+                    discarded = entry.destination == entry.old_destination;
                 }
-                if !exception {
-                    self.state.physical_register_file[reg as usize] = val;
-                    self.state.busy_bit_table[reg as usize] = false;
-                    for entry in self.state.integer_queue.iter_mut() {
-                        if !entry.op_a_is_ready && entry.op_a_reg_tag == reg {
-                            entry.op_a_is_ready = true;
-                            entry.op_a_value = val;
-                            entry.op_a_reg_tag = 0;
-                        }
-                        if !entry.op_b_is_ready && entry.op_b_reg_tag == reg {
-                            entry.op_b_is_ready = true;
-                            entry.op_b_value = val;
-                            entry.op_b_reg_tag = 0;
-                        }
+                if exception.is_none() && !discarded {
+                    results.push((reg, val, poison, self.alu_cluster(i)));
+                    if let Some(pc) = pc {
+                        written.push((seq, pc, reg, val));
                     }
                 }
             }
         }
+        self.deliver_wakeups();
+        for (reg, val, poison, cluster) in results {
+            self.state.physical_register_file[reg as usize] = val;
+            self.state.busy_bit_table[reg as usize] = false;
+            self.state.poisoned[reg as usize] = poison;
+            self.stats.accesses.register_writes += 1;
+            self.stats.accesses.queue_wakeups += 1;
+            self.broadcast(reg, val, poison, cluster);
+        }
+        self.verify_predictions(&written);
+        for seq in completed {
+            self.record_stage(seq, Stage::Complete);
+        }
     }
 
     // Returns true if the pipeline should be stalled for this cycle
     pub fn commit(&mut self) -> bool {
         if self.state.exception {
+            self.stats.recovery_cycles += 1;
             if self.state.active_list.is_empty() {
                 self.state.exception = false;
                 return false;
             }
+            // A restored snapshot leaves nothing to walk back.
+            self.restore_snapshot();
 
-            for _ in 0..4 {
+            for _ in 0..COMMIT_WIDTH {
                 if let Some(entry) = self.state.active_list.pop_back() {
-                    let new_phys_dest =
-                        self.state.register_map_table[entry.logical_destination as usize];
-                    self.state.register_map_table[entry.logical_destination as usize] =
-                        entry.old_destination;
-                    self.state.free_list.push_back(new_phys_dest);
-                    self.state.busy_bit_table[new_phys_dest as usize] = false;
+                    let arch = entry.logical_destination as usize;
+                    if entry.is_fp {
+                        let fp = self.state.fp.as_mut().unwrap();
+                        let new_phys_dest = fp.register_map_table[arch];
+                        fp.register_map_table[arch] = entry.old_destination;
+                        fp.free_list.push_back(new_phys_dest);
+                        fp.busy_bit_table[new_phys_dest as usize] = false;
+                        continue;
+                    }
+                    if self.discards_write(&entry) {
+                        continue;
+                    }
+                    let new_phys_dest = self.state.register_map_table[arch];
+                    self.state.register_map_table[arch] = entry.old_destination;
+                    self.stats.accesses.map_table_writes += 1;
+                    self.release(new_phys_dest);
+                    // An eliminated move shares the register of an older
+                    // producer, which may still be running.
+                    if !entry.eliminated {
+                        self.state.busy_bit_table[new_phys_dest as usize] = false;
+                    }
                 } else {
                     break;
                 }
             }
+            if !self.exception_cooldown && self.state.active_list.is_empty() {
+                self.state.exception = false;
+                // The rest of the pipeline runs after the rollback.
+                return self.exception_resume == ExceptionResume::NextCycle;
+            }
             return true;
         }
 
         // Normal commit.
-        for _ in 0..4 {
+        if self.take_interrupt() {
+            return true;
+        }
+        for _ in 0..COMMIT_WIDTH {
+            if self.halt.is_some() || self.commit_target_reached() {
+                break;
+            }
             if let Some(entry) = self.state.active_list.front() {
                 if !entry.done {
                     break;
                 }
 
                 if entry.exception {
-                    self.state.exception_pc = entry.pc;
-                    self.state.pc = 0x10000;
-                    self.state.decoded_pcs.clear();
-                    self.state.integer_queue.clear();
-                    for alu in self.alus.iter_mut() {
-                        alu.reset();
-                    }
-                    self.state.exception = true;
+                    let entry = entry.clone();
+                    self.check_commit(&entry, None);
+                    self.record_commit(
+                        entry.seq,
+                        Commit {
+                            pc: entry.pc,
+                            write: None,
+                            exception: true,
+                        },
+                    );
+                    // An exception raised by the handler itself nests in
+                    // the one it handles up to `exception_nesting` deep, and
+                    // beyond ends the run.
+                    let vector = self.exception_vector;
+                    let state = &mut self.state;
+                    let target = if entry.pc < vector {
+                        vector
+                    } else if state.exception_stack.len() < state.exception_nesting {
+                        state.exception_stack.push(NestedException {
+                            pc: state.exception_pc,
+                            cause: state.exception_cause,
+                        });
+                        vector
+                    } else {
+                        vector + self.handler.len() as u64
+                    };
+                    self.take_exception(entry.pc, entry.cause, target);
                     return true;
                 }
 
                 let committed_entry = self.state.active_list.pop_front().unwrap();
-                self.state
-                    .free_list
-                    .push_back(committed_entry.old_destination);
+                self.record_stage(committed_entry.seq, Stage::Commit);
+                self.retire_return(committed_entry.pc);
+                self.recovery.release_through(committed_entry.seq);
+                if self.state.rob.is_some() {
+                    let arch = committed_entry.logical_destination as usize;
+                    self.retire_rob_head(arch);
+                    let value = self.state.physical_register_file[arch];
+                    self.check_commit(&committed_entry, Some(value));
+                    self.record_commit(
+                        committed_entry.seq,
+                        Commit {
+                            pc: committed_entry.pc,
+                            write: Some((arch, value)),
+                            exception: false,
+                        },
+                    );
+                    if let Some(environment) = committed_entry.environment {
+                        self.commit_environment(environment, committed_entry.pc, value);
+                    }
+                    self.committed += 1;
+                    self.count_commit(committed_entry.pc);
+                    continue;
+                }
+                if !committed_entry.is_fp {
+                    let value =
+                        self.state.physical_register_file[committed_entry.destination as usize];
+                    self.check_commit(&committed_entry, Some(value));
+                    let arch = committed_entry.logical_destination as usize;
+                    self.record_commit(
+                        committed_entry.seq,
+                        Commit {
+                            pc: committed_entry.pc,
+                            write: Some((arch, value)),
+                            exception: false,
+                        },
+                    );
+                    if let Some(environment) = committed_entry.environment {
+                        self.commit_environment(environment, committed_entry.pc, value);
+                    }
+                } else {
+                    self.record_commit(
+                        committed_entry.seq,
+                        Commit {
+                            pc: committed_entry.pc,
+                            write: None,
+                            exception: false,
+                        },
+                    );
+                }
+                if committed_entry.is_fp {
+                    let fp = self.state.fp.as_mut().unwrap();
+                    fp.free_list.push_back(committed_entry.old_destination);
+                } else if !self.discards_write(&committed_entry) {
+                    let arch = committed_entry.logical_destination as usize;
+                    self.reclaim(arch, committed_entry.old_destination);
+                }
+                self.committed += 1;
+                self.count_commit(committed_entry.pc);
+                if let Some(register) = committed_entry.poison {
+                    self.first_poisoned_use.get_or_insert(PoisonedUse {
+                        pc: committed_entry.pc,
+                        register,
+                    });
+                }
             } else {
                 break;
             }
         }
         false
     }
+
+    /// Counts a committed micro-op at `pc` for the profile and the region
+    /// of interest.
+    fn count_commit(&mut self, pc: u64) {
+        let thread = self.smt.current;
+        if let Some(profile) = self.profile.as_mut() {
+            profile.record_commit(thread, pc);
+        }
+        self.roi_commit(pc);
+    }
+
+    /// `entry` writes a hardwired x0: it holds no register of its own and
+    /// its result is dropped.
+    pub(crate) fn discards_write(&self, entry: &ActiveEntry) -> bool {
+        self.hardwired_zero && !entry.is_fp && entry.logical_destination == 0
+    }
+
+    /// Whether renaming the integer micro-op `instr` takes a register off
+    /// the free list: a write to a hardwired x0 and an eliminated move do
+    /// not.
+    fn allocates_register(&self, instr: &DecodedInstructionEntry) -> bool {
+        let discarded = self.hardwired_zero && instr.dest == 0;
+        !instr.op.is_fp() && !discarded && self.move_source(instr).is_none()
+    }
+
+    /// Tells the observers which resources stall rename of `num_int`
+    /// integer and `num_fp` FP micro-ops, `num_allocating` of the integer
+    /// ones taking a free register.
+    fn report_backpressure(&mut self, num_int: usize, num_allocating: usize, num_fp: usize) {
+        let short = |name: &str, needed: usize, available: usize| {
+            (needed > available).then(|| format!("{} short by {}", name, needed - available))
+        };
+        let state = &self.state;
+        let mut reasons: Vec<String> = [
+            short(
+                "integer queue",
+                num_int,
+                self.integer_queue_capacity
+                    .saturating_sub(state.integer_queue.len()),
+            ),
+            short(
+                "active list",
+                num_int + num_fp,
+                self.active_list_capacity
+                    .saturating_sub(state.active_list.len()),
+            ),
+            short("free list", num_allocating, state.free_list.len())
+                .filter(|_| state.rob.is_none()),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        if let Some(fp) = &state.fp {
+            reasons.extend(short(
+                "FP queue",
+                num_fp,
+                self.fp_config.queue_capacity.saturating_sub(fp.queue.len()),
+            ));
+            reasons.extend(short("FP free list", num_fp, fp.free_list.len()));
+        }
+        let (cycle, thread) = (self.cycle(), self.smt.current);
+        for observer in self.observers.iter_mut() {
+            observer.on_backpressure(cycle, thread, &reasons);
+        }
+    }
+
+    /// Reports a committed micro-op to the attached client, if any.
+    fn record_commit(&mut self, seq: u64, commit: Commit) {
+        let cycle = self.cycle();
+        if let Some(lifetime) = self.lifetimes.get(seq as usize) {
+            for observer in self.observers.iter_mut() {
+                observer.on_retire(cycle, lifetime, &commit);
+            }
+        }
+        if let Some(commits) = self.commit_log.as_mut() {
+            commits.push_back(commit);
+        }
+    }
+
+    /// Squashes everything in flight on this thread and redirects fetch to
+    /// `target`, recording `pc` as the exception PC. The active list is then
+    /// rolled back by `commit`.
+    pub(crate) fn take_exception(&mut self, pc: u64, cause: Option<ExceptionCause>, target: u64) {
+        let cycle = self.cycle();
+        for observer in self.observers.iter_mut() {
+            observer.on_exception(cycle, pc, cause);
+        }
+        self.state.exception_pc = pc;
+        self.state.exception_cause = cause;
+        self.state.pc = target;
+        self.state.returns_in_flight = 0;
+        let buffered = self
+            .state
+            .fetch_buffer
+            .iter_mut()
+            .flat_map(|b| b.entries.drain(..));
+        for seq in self
+            .state
+            .decoded_pcs
+            .drain(..)
+            .chain(buffered)
+            .map(|i| i.seq)
+            .collect::<Vec<_>>()
+        {
+            self.record_stage(seq, Stage::Squash);
+        }
+        // Only this thread's micro-ops are squashed; other
+        // threads keep their queue entries and ALU work.
+        let squashed: HashSet<u64> = self.state.active_list.iter().map(|e| e.seq).collect();
+        for &seq in &squashed {
+            self.record_stage(seq, Stage::Squash);
+        }
+        self.state
+            .integer_queue
+            .retain(|e| !squashed.contains(&e.seq));
+        for alu in self.alus.iter_mut() {
+            alu.squash(&squashed);
+        }
+        if let Some(fp) = self.state.fp.as_mut() {
+            fp.queue.clear();
+        }
+        for unit in self.fp_units.iter_mut() {
+            unit.reset();
+        }
+        // Cycles rolling the active list back from the tail takes, for
+        // comparison with snapshot recovery.
+        let entries = self.state.active_list.len();
+        self.stats.rollback_recovery_cycles += match entries {
+            0 => 1,
+            n => n.div_ceil(COMMIT_WIDTH) as u64 + self.exception_cooldown as u64,
+        };
+        self.state.exception = true;
+        if self.state.rob.is_some() {
+            self.recover_rob();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::SimulatorBuilder;
+
+    fn program(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn writes_to_a_hardwired_x0_need_no_free_register() {
+        let config = Config {
+            hardwired_zero: true,
+            ..Config::default()
+        };
+        // The first bundle takes all four free registers.
+        let mut sim = SimulatorBuilder::new()
+            .program(program(&[
+                "addi x1, x0, 1",
+                "addi x2, x0, 2",
+                "addi x3, x0, 3",
+                "addi x4, x0, 4",
+                "nop",
+                "nop",
+                "nop",
+                "nop",
+            ]))
+            .config(&config)
+            .physical_registers(36)
+            .build()
+            .unwrap();
+        while !sim.done() {
+            sim.simulate_cycle();
+            assert!(!sim.state.backpressure.free_list, "cycle {}", sim.cycles());
+        }
+        assert_eq!(sim.committed, 8);
+    }
+
+    /// Divides by zero at PC 1 into a handler that divides by zero once
+    /// more on its first run, counting the runs in x7 and the instructions
+    /// after the division in x9. Returns the registers and the deepest
+    /// exception stack seen.
+    fn run_nested(exception_nesting: usize) -> (Vec<u64>, usize) {
+        let config = Config {
+            exception_nesting,
+            ..Config::default()
+        };
+        let mut sim = SimulatorBuilder::new()
+            .program(program(&[
+                "addi x1, x0, 1",
+                "divu x3, x1, x0",
+                "addi x4, x0, 4",
+            ]))
+            .config(&config)
+            .build()
+            .unwrap();
+        let handler = [
+            "addi x7, x7, 1",
+            "sub x8, x7, x1",
+            "divu x6, x1, x8",
+            "addi x9, x9, 1",
+            "mret",
+        ];
+        sim.set_handler(program(&handler), &config);
+        let mut depth = 0;
+        while !sim.done() {
+            sim.simulate_cycle();
+            depth = depth.max(sim.state.exception_stack.len());
+        }
+        (sim.architectural_registers(), depth)
+    }
+
+    #[test]
+    fn mret_returns_from_a_nested_exception_to_the_one_it_interrupted() {
+        let (registers, depth) = run_nested(1);
+        assert_eq!(depth, 1);
+        // The inner `mret` resumes the handler after its division and the
+        // outer one the program after its own.
+        assert_eq!(registers[7..10], [2, 1, 2]);
+        assert_eq!(registers[3..5], [0, 4]);
+    }
+
+    #[test]
+    fn an_exception_beyond_the_nesting_limit_ends_the_run() {
+        let (registers, depth) = run_nested(0);
+        assert_eq!(depth, 0);
+        assert_eq!(registers[7..10], [1, 0, 0]);
+        assert_eq!(registers[4], 0);
+    }
 }
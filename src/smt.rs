@@ -0,0 +1,303 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+use crate::checkpoint;
+use crate::config::{Config, DispatchMode, FetchPolicy, Termination};
+use crate::json_io::serialize_decoded_pcs;
+use crate::shared::Shared;
+use crate::simulator::{
+    ActiveEntry, Backpressure, DecodedInstruction, DecodedInstructionEntry, NUM_ARCH_REGS,
+    POISON_PATTERN, Simulator,
+};
+
+/// The per-thread part of the pipeline state of a hardware thread other than
+/// thread 0, whose context stays in the top-level `SimulatorState` fields.
+/// The physical register file, free list, busy bits, integer queue and ALUs
+/// are shared by all threads.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ThreadContext {
+    #[serde(rename = "PC")]
+    pub pc: u64,
+    #[serde(rename = "DecodedPCs", serialize_with = "serialize_decoded_pcs")]
+    pub decoded_pcs: Shared<Vec<DecodedInstructionEntry>>,
+    #[serde(rename = "ExceptionPC")]
+    pub exception_pc: u64,
+    #[serde(rename = "Exception")]
+    pub exception: bool,
+    #[serde(rename = "RegisterMapTable")]
+    pub register_map_table: Shared<Vec<u32>>,
+    #[serde(rename = "ActiveList")]
+    pub active_list: Shared<VecDeque<ActiveEntry>>,
+    #[serde(default, skip_serializing_if = "checkpoint::omit")]
+    pub backpressure: Backpressure,
+}
+
+/// The program of a thread other than thread 0.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub(crate) struct ThreadProgram {
+    lines: Vec<String>,
+    machine_code: bool,
+    #[serde(skip)]
+    decoded: Vec<DecodedInstruction>,
+}
+
+/// Simultaneous multithreading: thread scheduling and per-thread counters.
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct Smt {
+    programs: Vec<ThreadProgram>,
+    fetch_policy: FetchPolicy,
+    /// Thread the round-robin search starts from.
+    next_fetch: usize,
+    /// Thread swapped into the top-level state fields.
+    pub current: usize,
+    /// Micro-ops committed by each thread.
+    pub committed: Vec<u64>,
+}
+
+impl Smt {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            programs: Vec::new(),
+            fetch_policy: config.fetch_policy,
+            next_fetch: 0,
+            current: 0,
+            committed: vec![0],
+        }
+    }
+}
+
+impl Simulator {
+    /// Adds a hardware thread running `program`. Its architectural registers
+    /// are mapped to physical registers taken from the free list and start
+    /// with the configured initial values.
+    pub fn add_thread(&mut self, program: Vec<String>, config: &Config) {
+        let mut context = ThreadContext::default();
+        for arch in 0..NUM_ARCH_REGS {
+            let phys = self.state.free_list.pop_front().unwrap();
+            let prf = &mut self.state.physical_register_file;
+            prf[phys as usize] = match config.initial_registers.get(arch) {
+                Some(&value) => value,
+                None if config.hardwired_zero && arch == 0 => 0,
+                None if config.poison_uninitialized => POISON_PATTERN,
+                None => 0,
+            };
+            if config.poison_uninitialized
+                && arch >= config.initial_registers.len()
+                && !(config.hardwired_zero && arch == 0)
+            {
+                self.state.poisoned[phys as usize] = Some(arch as u32);
+            }
+            context.register_map_table.push(phys);
+        }
+        self.state.threads.push(context);
+        self.reclaimer.add_thread();
+        self.smt.programs.push(ThreadProgram {
+            machine_code: Self::is_machine_code(&program, config),
+            lines: program,
+            decoded: Vec::new(),
+        });
+        self.smt.committed.push(0);
+        self.on_thread(self.num_threads() - 1, Self::decode_program);
+    }
+
+    /// Micro-ops committed by each thread, thread 0 first.
+    pub fn smt_committed(&self) -> &[u64] {
+        &self.smt.committed
+    }
+
+    pub fn num_threads(&self) -> usize {
+        1 + self.state.threads.len()
+    }
+
+    /// Runs `f` with thread `t` swapped into the top-level state fields.
+    pub(crate) fn on_thread<R>(&mut self, t: usize, f: impl FnOnce(&mut Self) -> R) -> R {
+        if t == 0 {
+            return f(self);
+        }
+        self.swap_thread(t);
+        self.smt.current = t;
+        let result = f(self);
+        self.smt.current = 0;
+        self.swap_thread(t);
+        result
+    }
+
+    fn swap_thread(&mut self, t: usize) {
+        use std::mem::swap;
+        let state = &mut self.state;
+        let mut context = std::mem::take(&mut state.threads[t - 1]);
+        swap(&mut state.pc, &mut context.pc);
+        swap(&mut state.decoded_pcs, &mut context.decoded_pcs);
+        swap(&mut state.exception_pc, &mut context.exception_pc);
+        swap(&mut state.exception, &mut context.exception);
+        swap(
+            &mut state.register_map_table,
+            &mut context.register_map_table,
+        );
+        swap(&mut state.active_list, &mut context.active_list);
+        swap(&mut state.backpressure, &mut context.backpressure);
+        state.threads[t - 1] = context;
+        let program = &mut self.smt.programs[t - 1];
+        swap(&mut self.program, &mut program.lines);
+        swap(&mut self.machine_code, &mut program.machine_code);
+        swap(&mut self.decoded, &mut program.decoded);
+    }
+
+    /// The instruction at `pc` of thread `t`, in its program or the
+    /// exception handler, outside of a cycle.
+    pub(crate) fn thread_instruction(&self, t: usize, pc: u64) -> Option<&String> {
+        match t {
+            0 => self.instruction(pc),
+            _ if pc >= self.exception_vector => self.instruction(pc),
+            _ => self.smt.programs.get(t - 1)?.lines.get(pc as usize),
+        }
+    }
+
+    /// Returns true if thread `t` has nothing in flight and has reached the
+    /// end of its program.
+    pub(crate) fn thread_done(&self, t: usize) -> bool {
+        if self.termination == Termination::Halt {
+            return false;
+        }
+        let vector = self.exception_vector;
+        if t > 0 {
+            let context = &self.state.threads[t - 1];
+            let len = self.smt.programs[t - 1].lines.len();
+            return finished(context.pc, context.exception, len, vector, 0)
+                && context.decoded_pcs.is_empty()
+                && context.active_list.is_empty();
+        }
+        let handler_len = self.handler.len();
+        finished(
+            self.state.pc,
+            self.state.exception,
+            self.program.len(),
+            vector,
+            handler_len,
+        ) && self.front_end_micro_ops().next().is_none()
+            && self.state.active_list.is_empty()
+    }
+
+    /// Picks the thread that fetches this cycle among those not `stalled`.
+    pub(crate) fn select_fetch_thread(&mut self, stalled: &[bool]) -> Option<usize> {
+        let n = self.num_threads();
+        let order = (0..n).map(|i| (self.smt.next_fetch + i) % n);
+        let mut candidates = order.filter(|&t| !stalled[t] && self.can_fetch(t));
+        let t = match self.smt.fetch_policy {
+            FetchPolicy::RoundRobin => candidates.next(),
+            // min_by_key keeps the first minimum, so ties go round-robin.
+            FetchPolicy::Icount => candidates.min_by_key(|&t| self.icount(t)),
+        }?;
+        self.smt.next_fetch = (t + 1) % n;
+        Some(t)
+    }
+
+    fn can_fetch(&self, t: usize) -> bool {
+        let (exception, backpressure, fetchable) = match t {
+            // A fetch buffer keeps fetching while rename is stalled, and
+            // decode drains it once the program is fetched.
+            0 => match &self.state.fetch_buffer {
+                Some(buffer) => (
+                    self.state.exception,
+                    Backpressure::default(),
+                    self.instruction(self.state.pc).is_some() || !buffer.entries.is_empty(),
+                ),
+                None => (
+                    self.state.exception,
+                    self.state.backpressure,
+                    self.instruction(self.state.pc).is_some(),
+                ),
+            },
+            _ => {
+                let context = &self.state.threads[t - 1];
+                let len = self.smt.programs[t - 1].lines.len();
+                let fetchable = (context.pc as usize) < len;
+                (context.exception, context.backpressure, fetchable)
+            }
+        };
+        // Partial dispatch leaves room in the decode buffer for fetch.
+        let blocked = backpressure.any() && self.dispatch_mode == DispatchMode::AllOrNothing;
+        !exception && !blocked && fetchable
+    }
+
+    /// Micro-ops of thread `t` decoded or renamed but not yet executed.
+    fn icount(&self, t: usize) -> usize {
+        let (decoded, active) = match t {
+            0 => (&self.state.decoded_pcs, &self.state.active_list),
+            _ => {
+                let context = &self.state.threads[t - 1];
+                (&context.decoded_pcs, &context.active_list)
+            }
+        };
+        decoded.len() + active.iter().filter(|e| !e.done).count()
+    }
+}
+
+fn finished(pc: u64, exception: bool, program_len: usize, vector: u64, handler_len: usize) -> bool {
+    if pc >= vector {
+        // Exception scenario: terminate only after the cooldown cycle, once
+        // the handler, if any, has run to its end.
+        !exception && pc >= vector + handler_len as u64
+    } else {
+        // Normal scenario: terminate if PC is past the end of the program.
+        pc as usize >= program_len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::SimulatorBuilder;
+
+    fn program(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    fn two_threads(fetch_policy: FetchPolicy) -> Simulator {
+        let config = Config {
+            fetch_policy,
+            ..Config::default()
+        };
+        SimulatorBuilder::new()
+            .program(program(&["addi x1, x0, 1"; 8]))
+            .thread(program(&["addi x2, x0, 2"; 8]))
+            .config(&config)
+            .physical_registers(96)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn round_robin_takes_turns_and_skips_stalled_threads() {
+        let mut sim = two_threads(FetchPolicy::RoundRobin);
+        sim.on_thread(0, Simulator::fetch_and_decode);
+        let picks: Vec<_> = (0..3)
+            .map(|_| sim.select_fetch_thread(&[false, false]))
+            .collect();
+        assert_eq!(picks, [Some(0), Some(1), Some(0)]);
+        assert_eq!(sim.select_fetch_thread(&[false, true]), Some(0));
+        assert_eq!(sim.select_fetch_thread(&[true, true]), None);
+    }
+
+    #[test]
+    fn icount_picks_the_thread_with_fewer_micro_ops_in_flight() {
+        let mut sim = two_threads(FetchPolicy::Icount);
+        sim.on_thread(0, Simulator::fetch_and_decode);
+        assert!(sim.icount(0) > sim.icount(1));
+        let picks: Vec<_> = (0..3)
+            .map(|_| sim.select_fetch_thread(&[false, false]))
+            .collect();
+        assert_eq!(picks, [Some(1); 3]);
+    }
+
+    #[test]
+    fn every_thread_runs_its_program_to_the_end() {
+        for policy in [FetchPolicy::RoundRobin, FetchPolicy::Icount] {
+            let mut sim = two_threads(policy);
+            while !sim.done() {
+                sim.simulate_cycle();
+            }
+            assert_eq!(sim.smt_committed(), [8, 8], "{:?}", policy);
+        }
+    }
+}
@@ -0,0 +1,395 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::config::Pipeline;
+use crate::energy::Accesses;
+use crate::simulator::{Backpressure, COMMIT_WIDTH, Simulator};
+
+/// Two-sided 95% critical values of Student's t distribution for 1 to 30
+/// degrees of freedom. Larger samples use the normal approximation.
+const T_95: [f64; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+    2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+    2.052, 2.048, 2.045, 2.042,
+];
+
+/// Mean, sample standard deviation and 95% confidence interval half-width of
+/// a metric measured over repeated runs.
+#[derive(Debug, Clone, Copy)]
+pub struct Summary {
+    pub mean: f64,
+    pub stddev: f64,
+    pub ci95: f64,
+}
+
+impl Summary {
+    pub fn of(samples: &[f64]) -> Summary {
+        let n = samples.len();
+        let mean = samples.iter().sum::<f64>() / n as f64;
+        if n < 2 {
+            return Summary {
+                mean,
+                stddev: 0.0,
+                ci95: 0.0,
+            };
+        }
+        let variance = samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1) as f64;
+        let stddev = variance.sqrt();
+        let t = T_95.get(n - 2).copied().unwrap_or(1.960);
+        Summary {
+            mean,
+            stddev,
+            ci95: t * stddev / (n as f64).sqrt(),
+        }
+    }
+}
+
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "mean {:.4}, stddev {:.4}, 95% CI [{:.4}, {:.4}]",
+            self.mean,
+            self.stddev,
+            self.mean - self.ci95,
+            self.mean + self.ci95
+        )
+    }
+}
+
+/// Pipeline counters accumulated at the end of every simulated cycle.
+/// Occupancies and busy cycles are sums over cycles; divide by `cycles` for
+/// averages.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct CycleStats {
+    pub cycles: u64,
+    pub fetched: u64,
+    pub dispatched: u64,
+    pub issued: u64,
+    pub committed: u64,
+    pub integer_queue_occupancy: u64,
+    pub active_list_occupancy: u64,
+    pub fetch_buffer_occupancy: u64,
+    /// Cycles each ALU ended holding an instruction.
+    pub alu_busy_cycles: Vec<u64>,
+    /// Cycles rename stalled a thread on a full queue, active list or free
+    /// list.
+    pub backpressure_cycles: u64,
+    /// Cycles rename stalled a thread on each structure, in the order of
+    /// `Backpressure::NAMES`. A cycle short of several counts for each.
+    pub backpressure_cause_cycles: [u64; Backpressure::NAMES.len()],
+    /// Commit slots offered, `COMMIT_WIDTH` per thread and cycle. Only the
+    /// out-of-order pipeline attributes its commit slots.
+    pub commit_slots: u64,
+    /// Unused commit slots, indexed by `StallCause`.
+    pub lost_commit_slots: [u64; StallCause::ALL.len()],
+    /// Results kept in their ALU for a cycle because every write port was
+    /// taken.
+    pub writeback_stalls: u64,
+    /// Micro-ops left in the queue, with an ALU free, because the register
+    /// file had too few read ports left.
+    pub read_port_stalls: u64,
+    /// Ready micro-ops with a free ALU left in the queue because every
+    /// select port was granted.
+    pub select_port_stalls: u64,
+    /// Cycles spent recovering from exceptions.
+    pub recovery_cycles: u64,
+    /// Cycles recovering from the same exceptions by rolling the active
+    /// list back from the tail would have taken.
+    pub rollback_recovery_cycles: u64,
+    /// Moves renamed onto their source's register instead of executing.
+    pub eliminated_moves: u64,
+    /// Zeroing and constant idioms completed at rename.
+    pub eliminated_idioms: u64,
+    pub accesses: Accesses,
+}
+
+/// Why a commit slot went unused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StallCause {
+    /// Nothing was in flight: fetch and decode did not deliver.
+    FrontEnd,
+    /// Nothing was in flight because rename lacked queue, active list or
+    /// free list space.
+    RenameBackpressure,
+    /// The oldest instruction waits in a queue for a source operand.
+    WaitingOnOperands,
+    /// The oldest instruction is ready but no capable unit was free.
+    NoFreeAlu,
+    /// The oldest instruction is in a functional unit.
+    Executing,
+    /// Rollback or the cooldown cycle after an exception.
+    ExceptionRecovery,
+}
+
+impl StallCause {
+    pub const ALL: [StallCause; 6] = [
+        StallCause::FrontEnd,
+        StallCause::RenameBackpressure,
+        StallCause::WaitingOnOperands,
+        StallCause::NoFreeAlu,
+        StallCause::Executing,
+        StallCause::ExceptionRecovery,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            StallCause::FrontEnd => "FrontEnd",
+            StallCause::RenameBackpressure => "RenameBackpressure",
+            StallCause::WaitingOnOperands => "WaitingOnOperands",
+            StallCause::NoFreeAlu => "NoFreeAlu",
+            StallCause::Executing => "Executing",
+            StallCause::ExceptionRecovery => "ExceptionRecovery",
+        }
+    }
+}
+
+impl CycleStats {
+    pub fn new(num_alus: usize) -> Self {
+        Self {
+            alu_busy_cycles: vec![0; num_alus],
+            ..Self::default()
+        }
+    }
+
+    /// CPI split into the committing base and one component per stall
+    /// cause, in proportion to the commit slots each accounts for. The
+    /// components sum to the overall CPI. None until slots were attributed
+    /// and something committed.
+    pub fn cpi_stack(&self) -> Option<Vec<(&'static str, f64)>> {
+        if self.commit_slots == 0 || self.committed == 0 {
+            return None;
+        }
+        let cpi = self.cycles as f64 / self.committed as f64;
+        let share = |slots: u64| cpi * slots as f64 / self.commit_slots as f64;
+        let mut stack = vec![("Base", share(self.committed))];
+        for cause in StallCause::ALL {
+            stack.push((cause.name(), share(self.lost_commit_slots[cause as usize])));
+        }
+        Some(stack)
+    }
+
+    /// Totals and per-cycle averages, as written to `stats.json`.
+    pub fn to_json(&self) -> Value {
+        let per_cycle = |total: u64| {
+            if self.cycles > 0 {
+                total as f64 / self.cycles as f64
+            } else {
+                0.0
+            }
+        };
+        let utilization: Vec<f64> = self.alu_busy_cycles.iter().map(|&b| per_cycle(b)).collect();
+        let lost: serde_json::Map<String, Value> = StallCause::ALL
+            .iter()
+            .map(|&c| {
+                (
+                    c.name().to_string(),
+                    json!(self.lost_commit_slots[c as usize]),
+                )
+            })
+            .collect();
+        let backpressure: serde_json::Map<String, Value> = Backpressure::NAMES
+            .iter()
+            .zip(self.backpressure_cause_cycles)
+            .map(|(name, cycles)| (name.to_string(), json!(cycles)))
+            .collect();
+        let cpi_stack = self.cpi_stack().map(|stack| {
+            stack
+                .into_iter()
+                .map(|(name, cpi)| (name.to_string(), json!(cpi)))
+                .collect::<serde_json::Map<_, _>>()
+        });
+        json!({
+            "Cycles": self.cycles,
+            "CommitSlots": self.commit_slots,
+            "LostCommitSlots": lost,
+            "CpiStack": cpi_stack,
+            "Totals": {
+                "Fetched": self.fetched,
+                "Dispatched": self.dispatched,
+                "Issued": self.issued,
+                "Committed": self.committed,
+                "BackpressureCycles": self.backpressure_cycles,
+                "BackpressureCauseCycles": backpressure,
+                "AluBusyCycles": self.alu_busy_cycles,
+                "WritebackStalls": self.writeback_stalls,
+                "ReadPortStalls": self.read_port_stalls,
+                "SelectPortStalls": self.select_port_stalls,
+                "RecoveryCycles": self.recovery_cycles,
+                "RollbackRecoveryCycles": self.rollback_recovery_cycles,
+                "EliminatedMoves": self.eliminated_moves,
+                "EliminatedIdioms": self.eliminated_idioms,
+            },
+            "Averages": {
+                "IPC": per_cycle(self.committed),
+                "FetchedPerCycle": per_cycle(self.fetched),
+                "DispatchedPerCycle": per_cycle(self.dispatched),
+                "IssuedPerCycle": per_cycle(self.issued),
+                "IntegerQueueOccupancy": per_cycle(self.integer_queue_occupancy),
+                "ActiveListOccupancy": per_cycle(self.active_list_occupancy),
+                "FetchBufferOccupancy": per_cycle(self.fetch_buffer_occupancy),
+                "AluUtilization": utilization,
+                "BackpressureFraction": per_cycle(self.backpressure_cycles),
+            },
+        })
+    }
+}
+
+impl Simulator {
+    /// Folds the cycle just simulated into `stats`.
+    pub(crate) fn record_stats(&mut self) {
+        let counts = *self.bandwidth.last().unwrap();
+        let busy: Vec<bool> = (0..self.alus.len())
+            .map(|i| match self.pipeline {
+                Pipeline::OutOfOrder => !self.alus[i].is_free(),
+                Pipeline::InOrder => i == 0 && self.in_order.executing(),
+                Pipeline::Scoreboard => self.scoreboard.unit_busy(i),
+                Pipeline::Tomasulo => self.tomasulo.unit_busy(i),
+            })
+            .collect();
+        let threads = &self.state.threads;
+        let active_list = self.state.active_list.len()
+            + threads.iter().map(|t| t.active_list.len()).sum::<usize>();
+        let mut causes = self.state.backpressure.causes();
+        for thread in threads {
+            for (cause, stalled) in causes.iter_mut().zip(thread.backpressure.causes()) {
+                *cause |= stalled;
+            }
+        }
+        let backpressure = causes.contains(&true);
+
+        let stats = &mut self.stats;
+        stats.cycles += 1;
+        stats.fetched += counts.fetched as u64;
+        stats.dispatched += counts.dispatched as u64;
+        stats.issued += counts.issued as u64;
+        stats.committed += counts.committed;
+        stats.integer_queue_occupancy += self.state.integer_queue.len() as u64;
+        stats.active_list_occupancy += active_list as u64;
+        if let Some(buffer) = &self.state.fetch_buffer {
+            stats.fetch_buffer_occupancy += buffer.entries.len() as u64;
+        }
+        for (cycles, busy) in stats.alu_busy_cycles.iter_mut().zip(busy) {
+            *cycles += busy as u64;
+        }
+        stats.backpressure_cycles += backpressure as u64;
+        for (cycles, stalled) in stats.backpressure_cause_cycles.iter_mut().zip(causes) {
+            *cycles += stalled as u64;
+        }
+        let committed = stats.committed;
+
+        // Under reorder-buffer renaming the ROB takes the active list's place.
+        let active_list = match &self.state.rob {
+            Some(rob) => rob.entries.len(),
+            None => active_list,
+        };
+        self.metrics.push(CycleMetrics {
+            pc: self.address(self.state.pc),
+            integer_queue: self.state.integer_queue.len(),
+            active_list,
+            free_list: self.state.free_list.len(),
+            committed,
+            exception: self.state.exception,
+        });
+    }
+}
+
+/// Occupancies and progress at the end of one cycle, a row of the
+/// `--metrics` CSV.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct CycleMetrics {
+    pub pc: u64,
+    pub integer_queue: usize,
+    pub active_list: usize,
+    pub free_list: usize,
+    /// Instructions committed up to and including this cycle.
+    pub committed: u64,
+    pub exception: bool,
+}
+
+/// What the commit stage of one thread saw in a cycle, kept until the end of
+/// the cycle so waiting instructions are judged after issue had its chance.
+pub(crate) struct CommitSlots {
+    thread: usize,
+    committed: u64,
+    recovering: bool,
+    /// The oldest uncommitted instructions, one per unused slot, with their
+    /// PCs and whether each had already completed.
+    pending: Vec<(u64, u64, bool)>,
+    backpressure: bool,
+}
+
+impl Simulator {
+    /// Records the commit outcome of the current thread.
+    pub(crate) fn commit_slots(&self, committed: u64, recovering: bool) -> CommitSlots {
+        CommitSlots {
+            thread: self.smt.current,
+            committed,
+            recovering: recovering || self.state.exception,
+            pending: self
+                .state
+                .active_list
+                .iter()
+                .take(COMMIT_WIDTH - committed as usize)
+                .map(|e| (e.seq, e.pc, e.done))
+                .collect(),
+            backpressure: self.state.backpressure.any(),
+        }
+    }
+
+    /// Charges each commit slot a thread left unused this cycle to the
+    /// instruction that would have taken it: its own stall cause if it was
+    /// still incomplete, the cause of the incomplete head it waited behind
+    /// otherwise, and the front end if there was no instruction at all.
+    /// Queued instructions are judged as of the end of the cycle.
+    pub(crate) fn attribute_commit_slots(&mut self, slots: &CommitSlots) {
+        let lost = COMMIT_WIDTH - slots.committed as usize;
+        // Each lost slot with the PC of the instruction charged for it.
+        let mut causes = Vec::with_capacity(lost);
+        if slots.recovering {
+            causes.resize(lost, (StallCause::ExceptionRecovery, None));
+        } else {
+            let head = slots
+                .pending
+                .first()
+                .map(|&(seq, pc, _)| (self.stall_cause(seq), Some(pc)));
+            for &(seq, pc, done) in &slots.pending {
+                causes.push(if done {
+                    head.unwrap()
+                } else {
+                    (self.stall_cause(seq), Some(pc))
+                });
+            }
+            let empty = match slots.backpressure {
+                true => StallCause::RenameBackpressure,
+                false => StallCause::FrontEnd,
+            };
+            causes.resize(lost, (empty, None));
+        }
+        self.stats.commit_slots += COMMIT_WIDTH as u64;
+        for (cause, pc) in causes {
+            self.stats.lost_commit_slots[cause as usize] += 1;
+            if let Some(profile) = self.profile.as_mut() {
+                profile.record_stall(slots.thread, pc);
+            }
+        }
+    }
+
+    /// Why the incomplete instruction `seq` has not finished.
+    fn stall_cause(&self, seq: u64) -> StallCause {
+        let state = &self.state;
+        let int = state.integer_queue.iter().find(|e| e.seq == seq);
+        let fp = state
+            .fp
+            .as_ref()
+            .and_then(|fp| fp.queue.iter().find(|e| e.seq == seq));
+        let ready = int
+            .map(|e| e.op_a_is_ready && e.op_b_is_ready)
+            .or(fp.map(|e| e.op_a_is_ready && e.op_b_is_ready));
+        match ready {
+            Some(true) => StallCause::NoFreeAlu,
+            Some(false) => StallCause::WaitingOnOperands,
+            None => StallCause::Executing,
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+
+use serde_json::Value;
+
+use crate::batch::{panic_message, parallel_map};
+use crate::builder::SimulatorBuilder;
+use crate::cli::SweepArgs;
+use crate::config::Config;
+use crate::json_io::parse_config;
+use crate::stats::{CycleStats, StallCause};
+
+/// Short parameter names accepted in place of the config keys they stand
+/// for.
+const ALIASES: [(&str, &str); 4] = [
+    ("alus", "num_alus"),
+    ("iq", "integer_queue_capacity"),
+    ("al", "active_list_capacity"),
+    ("regs", "physical_registers"),
+];
+
+/// Builder setters of the parameters that have one, by config key.
+type Setter = fn(SimulatorBuilder, usize) -> SimulatorBuilder;
+const SETTERS: [(&str, Setter); 4] = [
+    ("num_alus", SimulatorBuilder::alus),
+    ("integer_queue_capacity", SimulatorBuilder::iq_capacity),
+    (
+        "active_list_capacity",
+        SimulatorBuilder::active_list_capacity,
+    ),
+    ("physical_registers", SimulatorBuilder::physical_registers),
+];
+
+/// One combination of parameter values and the builder configured with
+/// them, or why the config they yield is invalid.
+pub(crate) struct Point {
+    pub values: Vec<String>,
+    pub builder: Result<SimulatorBuilder, String>,
+}
+
+/// The configurations spanned by a set of parameters over a base config.
+pub(crate) struct Space<'a> {
+    params: &'a [(String, Vec<String>)],
+    base: Value,
+    /// The config key of each parameter, with aliases resolved.
+    keys: Vec<&'a str>,
+}
+
+impl<'a> Space<'a> {
+    pub(crate) fn new(base: &Config, params: &'a [(String, Vec<String>)]) -> Result<Self, String> {
+        let base = serde_json::to_value(base).unwrap();
+        let mut keys = Vec::new();
+        for (name, _) in params {
+            let key = ALIASES
+                .iter()
+                .find(|(alias, _)| alias == name)
+                .map_or(name.as_str(), |&(_, key)| key);
+            if base.pointer(&pointer(key)).is_none() {
+                return Err(format!("Unknown config key: {}", name));
+            }
+            keys.push(key);
+        }
+        Ok(Self { params, base, keys })
+    }
+
+    /// Number of combinations, saturating at `usize::MAX`.
+    pub(crate) fn size(&self) -> usize {
+        self.params
+            .iter()
+            .fold(1, |n: usize, (_, values)| n.saturating_mul(values.len()))
+    }
+
+    /// Every combination, the last parameter varying fastest.
+    pub(crate) fn grid(&self) -> Vec<Point> {
+        (0..self.size()).map(|i| self.point(i)).collect()
+    }
+
+    /// The combination at position `index` of the grid.
+    pub(crate) fn point(&self, mut index: usize) -> Point {
+        let mut values = vec![String::new(); self.params.len()];
+        for (value, (_, choices)) in values.iter_mut().zip(self.params).rev() {
+            *value = choices[index % choices.len()].clone();
+            index /= choices.len();
+        }
+        Point {
+            builder: configure(&self.base, &self.keys, &values),
+            values,
+        }
+    }
+}
+
+/// Simulates the program under the cross-product of the parameter values on
+/// a pool of worker threads and writes one CSV row per configuration, with
+/// its IPC and where the lost commit slots went. An invalid combination or
+/// a failed simulation is reported in its row and does not stop the others.
+pub fn run(args: &SweepArgs) -> Result<(), String> {
+    let base = match &args.config_path {
+        Some(path) => parse_config(path),
+        None => Config::default(),
+    };
+    let space = Space::new(&base, &args.params)?;
+    let program = crate::load_program(&args.input_path);
+    let points = space.grid();
+    let results = parallel_map(&points, args.jobs, |point| simulate(&program, point));
+
+    let failed = results.iter().filter(|r| r.is_err()).count();
+    let csv = sweep_csv(args, &points, &results);
+    match &args.output_path {
+        Some(path) => {
+            fs::write(path, csv).map_err(|err| format!("Failed to write {}: {}", path, err))?;
+            println!("Sweep saved to {}", path);
+        }
+        None => print!("{}", csv),
+    }
+    if failed > 0 {
+        return Err(format!(
+            "{} of {} configurations failed",
+            failed,
+            points.len()
+        ));
+    }
+    Ok(())
+}
+
+/// The JSON pointer to a dotted config key such as `bypass.wakeup_delay`.
+fn pointer(key: &str) -> String {
+    key.split('.').map(|part| format!("/{}", part)).collect()
+}
+
+/// A builder of the base config with each of `keys` set to its value,
+/// through its setter if it has one. A value is taken as JSON if it
+/// parses, such as a number or `true`, and as a string otherwise, such as
+/// `oldest`.
+fn configure(base: &Value, keys: &[&str], values: &[String]) -> Result<SimulatorBuilder, String> {
+    let mut json = base.clone();
+    let mut counts = Vec::new();
+    for (key, value) in keys.iter().zip(values) {
+        match SETTERS.iter().find(|(name, _)| name == key) {
+            Some(&(_, setter)) => {
+                let count = value
+                    .parse()
+                    .map_err(|_| format!("{} must be a count, got '{}'", key, value))?;
+                counts.push((setter, count));
+            }
+            None => {
+                *json.pointer_mut(&pointer(key)).unwrap() =
+                    serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.clone()));
+            }
+        }
+    }
+    let config: Config = serde_json::from_value(json).map_err(|err| err.to_string())?;
+    let builder = counts.into_iter().fold(
+        SimulatorBuilder::new().config(&config),
+        |builder, (setter, count)| setter(builder, count),
+    );
+    builder.configuration().validate()?;
+    Ok(builder)
+}
+
+/// Simulates `program` under the config of `point` and returns the
+/// pipeline counters of the run.
+pub(crate) fn simulate(program: &[String], point: &Point) -> Result<CycleStats, String> {
+    let builder = point.builder.clone()?;
+    let sim = builder.program(program.to_vec()).build()?;
+    let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+        let sim = crate::run(sim, None, false, None, None);
+        sim.stats
+    }));
+    outcome.map_err(panic_message)
+}
+
+fn sweep_csv(args: &SweepArgs, points: &[Point], results: &[Result<CycleStats, String>]) -> String {
+    let mut header: Vec<&str> = args.params.iter().map(|(name, _)| name.as_str()).collect();
+    header.extend(["cycles", "committed", "ipc"]);
+    header.extend(StallCause::ALL.iter().map(|c| c.name()));
+    header.push("error");
+    let mut csv = header.join(",") + "\n";
+    for (point, result) in points.iter().zip(results) {
+        let mut row = point.values.join(",") + ",";
+        row += &match result {
+            Ok(stats) => {
+                let ipc = match stats.cycles {
+                    0 => 0.0,
+                    cycles => stats.committed as f64 / cycles as f64,
+                };
+                let lost: Vec<String> =
+                    stats.lost_commit_slots.iter().map(u64::to_string).collect();
+                format!(
+                    "{},{},{:.3},{},",
+                    stats.cycles,
+                    stats.committed,
+                    ipc,
+                    lost.join(",")
+                )
+            }
+            Err(err) => format!(
+                "{}\"{}\"",
+                ",".repeat(3 + StallCause::ALL.len()),
+                err.replace('"', "\"\"")
+            ),
+        };
+        csv += &row;
+        csv += "\n";
+    }
+    csv
+}
@@ -0,0 +1,60 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::simulator::Simulator;
+
+/// `a7` value of the `ecall` that prints `a0` as a signed integer.
+pub const PRINT_INT: u64 = 1;
+/// `a7` value of the `ecall` that prints the low byte of `a0` as a character.
+pub const PRINT_CHAR: u64 = 11;
+/// `a7` value of the `ecall` that ends the program with exit code `a0`.
+pub const EXIT: u64 = 93;
+
+/// A system instruction, acted upon when it commits.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Environment {
+    /// `ecall` with the syscall number read from `a7`.
+    Call(u64),
+    Break,
+    /// A CSR instruction with the CSR and the value it writes there.
+    Csr(u16, u64),
+}
+
+/// Why a program stopped itself.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Halt {
+    /// The exit `ecall`, with its exit code.
+    Exit(u64),
+    /// An `ebreak` at the given PC.
+    Break(u64),
+}
+
+impl fmt::Display for Halt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Halt::Exit(code) => write!(f, "Program exited with code {}", *code as i64),
+            Halt::Break(pc) => write!(f, "Program stopped at ebreak, instruction {}", pc),
+        }
+    }
+}
+
+/// Whether the ALUs accept `number` as an `ecall`; any other number raises
+/// an illegal-instruction exception.
+pub fn is_syscall(number: u64) -> bool {
+    matches!(number, PRINT_INT | PRINT_CHAR | EXIT)
+}
+
+impl Simulator {
+    /// Performs a committed system instruction of the instruction at `pc`;
+    /// `a0` is the value of `a0` it read. Output is appended to `console`.
+    pub(crate) fn commit_environment(&mut self, environment: Environment, pc: u64, a0: u64) {
+        match environment {
+            Environment::Call(PRINT_INT) => self.console += &(a0 as i64).to_string(),
+            Environment::Call(PRINT_CHAR) => self.console.push(a0 as u8 as char),
+            Environment::Call(EXIT) => self.halt = Some(Halt::Exit(a0)),
+            Environment::Call(number) => unreachable!("unknown syscall {} committed", number),
+            Environment::Break => self.halt = Some(Halt::Break(pc)),
+            Environment::Csr(csr, value) => self.write_csr(csr, value),
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+
+use crate::config::Config;
+use crate::json_io::{self, read_input};
+use crate::simulator::Simulator;
+use crate::{asm, elf};
+
+/// A program file that describes a whole test case: the instructions, read
+/// as a plain program is, the registers they start from, the data memory
+/// they start with and the state they must finish in, such as
+/// `{"program": ["addi x1, x1, 1"], "registers": [0, 41],
+/// "expected": {"registers": {"x1": 42}}}`.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct TestCase {
+    /// Initial values of architectural registers, starting at `x0`.
+    pub registers: Vec<u64>,
+    /// Initial data memory words by address. The simulator has no data
+    /// memory, so they are ignored with a warning.
+    pub memory: BTreeMap<String, u64>,
+    pub expected: Expected,
+}
+
+/// The state a test case must finish in. Registers and counts left out are
+/// not checked.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Expected {
+    /// Final values of architectural registers by name, such as `x1` or
+    /// `a0`.
+    pub registers: BTreeMap<String, u64>,
+    /// Cycles the run takes.
+    pub cycles: Option<usize>,
+    /// Micro-ops the run commits.
+    pub committed: Option<u64>,
+}
+
+/// The test case in the JSON program at `path`, or `None` if it is a plain
+/// array of instructions or not JSON.
+pub fn load(path: &str) -> Result<Option<TestCase>, String> {
+    if elf::is_elf_file(path) || json_io::is_assembly(path) || json_io::is_trace(path) {
+        return Ok(None);
+    }
+    let text = read_input(path).map_err(|err| format!("Failed to read input file: {}", err))?;
+    let Ok(serde_json::Value::Object(mut fields)) = serde_json::from_str(&text) else {
+        return Ok(None);
+    };
+    fields.remove("program");
+    let case: TestCase = serde_json::from_value(fields.into())
+        .map_err(|err| format!("Failed to parse test case {}: {}", path, err))?;
+    if let Some(name) = case
+        .expected
+        .registers
+        .keys()
+        .find(|name| asm::register_index(name).is_none())
+    {
+        return Err(format!(
+            "{}: expected register {} is not an integer register",
+            path, name
+        ));
+    }
+    Ok(Some(case))
+}
+
+impl TestCase {
+    /// Starts the registers of `config` from those of the test case.
+    pub fn apply(&self, config: &mut Config) -> Result<(), String> {
+        if self.registers.is_empty() {
+            return Ok(());
+        }
+        if !config.initial_registers.is_empty() {
+            return Err(
+                "initial_registers is set by both the config and the test case".to_string(),
+            );
+        }
+        config.initial_registers = self.registers.clone();
+        config.validate()
+    }
+
+    /// Every way the finished run `sim` differs from the expected state.
+    pub fn check(&self, sim: &Simulator) -> Vec<String> {
+        let expected = &self.expected;
+        let registers = sim.architectural_registers();
+        let mut mismatches: Vec<String> = expected
+            .registers
+            .iter()
+            .filter_map(|(name, &value)| {
+                let actual = registers[asm::register_index(name).unwrap() as usize];
+                (actual != value).then(|| format!("{} is {}, expected {}", name, actual, value))
+            })
+            .collect();
+        if let Some(cycles) = expected.cycles.filter(|&cycles| cycles != sim.cycles()) {
+            mismatches.push(format!("took {} cycles, expected {}", sim.cycles(), cycles));
+        }
+        if let Some(committed) = expected.committed.filter(|&n| n != sim.committed) {
+            let error = format!(
+                "committed {} micro-ops, expected {}",
+                sim.committed, committed
+            );
+            mismatches.push(error);
+        }
+        mismatches
+    }
+}
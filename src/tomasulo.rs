@@ -0,0 +1,405 @@
+use crate::opcode::Opcode;
+use crate::simulator::{
+    ActiveEntry, CycleCounts, DecodedInstructionEntry, ExceptionCause, IntegerQueueEntry,
+    NUM_ARCH_REGS, Simulator, compute,
+};
+use crate::units;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    Waiting,
+    Executing,
+    /// Result computed and waiting for a common data bus.
+    Completed,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+enum Operand {
+    Value(u64),
+    /// Waiting for the reservation station with this tag to broadcast.
+    Tag(u32),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Station {
+    micro_op: DecodedInstructionEntry,
+    dest: u32,
+    operands: [Operand; 2],
+    stage: Stage,
+    result: Result<u64, ExceptionCause>,
+    stall: u32,
+}
+
+/// Tomasulo's algorithm with reservation stations: each ALU owns
+/// `stations_per_unit` stations, identified by a global tag. Instructions
+/// issue in order into a free station of a capable ALU, capturing ready
+/// operands from the register file and the tags of pending ones. An ALU
+/// executes the oldest ready instruction of its stations and is busy until
+/// the result is broadcast. Completed results compete for the common data
+/// buses, oldest first; a broadcast wakes waiting stations, clears the
+/// register status if the register still maps to that tag, and updates the
+/// register file unless a younger write already reached it.
+///
+/// There is no reorder buffer: an exception is taken when the faulting
+/// instruction is the oldest in flight, so it is imprecise with respect to
+/// younger instructions that already broadcast.
+///
+/// In the log, `IntegerQueue` lists every occupied station, with its own tag
+/// as `DestRegister` and producer tags as register tags, `ActiveList` the instructions holding a station in program
+/// order, and `BusyBitTable` which architectural registers await a tag.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Tomasulo {
+    stations: Vec<Option<Station>>,
+    stations_per_unit: usize,
+    buses: usize,
+    /// Tag of the station producing each architectural register (Qi).
+    register_status: Vec<Option<u32>>,
+    /// Sequence number of the youngest broadcast write to each register.
+    last_written: Vec<Option<u64>>,
+}
+
+impl Tomasulo {
+    pub fn new(num_units: usize, stations_per_unit: usize, buses: usize) -> Self {
+        Self {
+            stations: vec![None; num_units * stations_per_unit],
+            stations_per_unit,
+            buses,
+            register_status: vec![None; NUM_ARCH_REGS],
+            last_written: vec![None; NUM_ARCH_REGS],
+        }
+    }
+
+    pub(crate) fn unit_busy(&self, unit: usize) -> bool {
+        self.unit_stations(unit)
+            .flatten()
+            .any(|s| s.stage != Stage::Waiting)
+    }
+
+    fn unit_stations(&self, unit: usize) -> impl Iterator<Item = &Option<Station>> {
+        let n = self.stations_per_unit;
+        self.stations[unit * n..(unit + 1) * n].iter()
+    }
+}
+
+impl Simulator {
+    /// Advances the Tomasulo pipeline by one cycle, back to front.
+    pub(crate) fn simulate_cycle_tomasulo(&mut self) {
+        let mut counts = CycleCounts::default();
+        if self.state.exception {
+            // Cooldown cycle after the flush.
+            self.state.exception = false;
+            if self.exception_cooldown {
+                self.bandwidth.push(counts);
+                return;
+            }
+        }
+
+        let oldest = self
+            .tomasulo
+            .stations
+            .iter()
+            .flatten()
+            .min_by_key(|s| s.micro_op.seq);
+        if let Some(station) = oldest.filter(|s| s.stage == Stage::Completed && s.result.is_err()) {
+            let (pc, cause) = (station.micro_op.pc, station.result.unwrap_err());
+            self.flush_tomasulo(pc, cause);
+            self.bandwidth.push(counts);
+            return;
+        }
+
+        // Broadcast on the common data buses, oldest result first.
+        let mut completed: Vec<(u64, u32)> = self
+            .tomasulo
+            .stations
+            .iter()
+            .enumerate()
+            .filter_map(|(tag, s)| s.as_ref().map(|s| (s, tag as u32)))
+            .filter(|(s, _)| s.stage == Stage::Completed && s.result.is_ok())
+            .map(|(s, tag)| (s.micro_op.seq, tag))
+            .collect();
+        completed.sort();
+        for &(_, tag) in completed.iter().take(self.tomasulo.buses) {
+            let station = self.tomasulo.stations[tag as usize].take().unwrap();
+            let value = station.result.unwrap();
+            let dest = station.dest as usize;
+            if self.tomasulo.register_status[dest] == Some(tag) {
+                self.tomasulo.register_status[dest] = None;
+            }
+            let seq = station.micro_op.seq;
+            if self.tomasulo.last_written[dest].is_none_or(|last| last < seq)
+                && !(self.hardwired_zero && dest == 0)
+            {
+                self.tomasulo.last_written[dest] = Some(seq);
+                self.state.physical_register_file[dest] = value;
+            }
+            for waiting in self.tomasulo.stations.iter_mut().flatten() {
+                for operand in waiting.operands.iter_mut() {
+                    if matches!(operand, Operand::Tag(t) if *t == tag) {
+                        *operand = Operand::Value(value);
+                    }
+                }
+            }
+            self.committed += 1;
+            counts.committed += 1;
+        }
+
+        // Execute.
+        for station in self.tomasulo.stations.iter_mut().flatten() {
+            if station.stage != Stage::Executing {
+                continue;
+            }
+            if station.stall > 0 {
+                station.stall -= 1;
+                continue;
+            }
+            let [Operand::Value(a), Operand::Value(b)] = station.operands else {
+                unreachable!("executing with a pending operand");
+            };
+            station.result = compute(station.micro_op.op, a, b, self.trap_on_overflow);
+            station.stage = Stage::Completed;
+        }
+
+        // Start the oldest ready station of every idle ALU.
+        let n = self.tomasulo.stations_per_unit;
+        for unit in 0..self.alus.len() {
+            if self.tomasulo.unit_busy(unit) {
+                continue;
+            }
+            let ready = self.tomasulo.stations[unit * n..(unit + 1) * n]
+                .iter_mut()
+                .flatten()
+                .filter(|s| {
+                    s.stage == Stage::Waiting
+                        && s.operands.iter().all(|o| matches!(o, Operand::Value(_)))
+                })
+                .min_by_key(|s| s.micro_op.seq);
+            if let Some(station) = ready {
+                station.stage = Stage::Executing;
+                counts.issued += 1;
+            }
+        }
+
+        // Issue the head of the fetch buffer into a free station.
+        if let Some(instr) = self.state.decoded_pcs.first() {
+            let free = (0..self.alus.len())
+                .filter(|&unit| self.alus[unit].can_execute(instr.op))
+                .flat_map(|unit| unit * n..(unit + 1) * n)
+                .find(|&tag| self.tomasulo.stations[tag].is_none());
+            if let Some(tag) = free {
+                let instr = self.state.decoded_pcs.remove(0);
+                let station = self.issue_to_station(instr);
+                self.tomasulo.register_status[station.dest as usize] = Some(tag as u32);
+                self.tomasulo.stations[tag] = Some(station);
+                counts.dispatched = 1;
+            }
+        }
+
+        // Fetch into an empty buffer.
+        if self.state.decoded_pcs.is_empty() && (self.state.pc as usize) < self.program.len() {
+            let pc = self.state.pc;
+            for mut micro_op in self.decode(pc) {
+                micro_op.seq = self.state.next_seq;
+                self.state.next_seq += 1;
+                self.state.decoded_pcs.push(micro_op);
+                counts.fetched += 1;
+            }
+            self.state.pc += 1;
+        }
+
+        self.sync_tomasulo_state();
+        self.bandwidth.push(counts);
+    }
+
+    fn issue_to_station(&self, micro_op: DecodedInstructionEntry) -> Station {
+        let read = |src: Option<u32>| {
+            let Some(reg) = src else {
+                return Operand::Value(0);
+            };
+            match self.tomasulo.register_status[reg as usize] {
+                Some(tag) => Operand::Tag(tag),
+                None => Operand::Value(self.state.physical_register_file[reg as usize]),
+            }
+        };
+        let a = if micro_op.op == Opcode::Auipc {
+            Operand::Value(self.address(micro_op.pc))
+        } else {
+            read(micro_op.src1)
+        };
+        let b = if micro_op.is_imm {
+            Operand::Value(micro_op.imm)
+        } else {
+            read(micro_op.src2)
+        };
+        let stall = units::extra_cycles(micro_op.op);
+        Station {
+            dest: micro_op.dest,
+            micro_op,
+            operands: [a, b],
+            stage: Stage::Waiting,
+            result: Ok(0),
+            stall,
+        }
+    }
+
+    fn flush_tomasulo(&mut self, pc: u64, cause: ExceptionCause) {
+        let t = &self.tomasulo;
+        self.tomasulo = Tomasulo::new(self.alus.len(), t.stations_per_unit, t.buses);
+        self.state.decoded_pcs.clear();
+        self.state.integer_queue.clear();
+        self.state.active_list.clear();
+        self.state.busy_bit_table.fill(false);
+        self.state.exception = true;
+        self.state.exception_pc = pc;
+        self.state.exception_cause = Some(cause);
+        self.state.pc = self.exception_vector;
+    }
+
+    /// Mirrors the reservation stations and register status into the logged
+    /// structures.
+    fn sync_tomasulo_state(&mut self) {
+        let mut occupied: Vec<(u32, &Station)> = self
+            .tomasulo
+            .stations
+            .iter()
+            .enumerate()
+            .filter_map(|(tag, s)| s.as_ref().map(|s| (tag as u32, s)))
+            .collect();
+        occupied.sort_by_key(|(tag, _)| *tag);
+        self.state.integer_queue = occupied
+            .iter()
+            .map(|&(tag, s)| {
+                let operand = |k: usize| match s.operands[k] {
+                    Operand::Value(v) => (true, 0, v),
+                    Operand::Tag(t) => (false, t, 0),
+                };
+                let (op_a_is_ready, op_a_reg_tag, op_a_value) = operand(0);
+                let (op_b_is_ready, op_b_reg_tag, op_b_value) = operand(1);
+                IntegerQueueEntry {
+                    dest_register: tag,
+                    op_a_is_ready,
+                    op_a_reg_tag,
+                    op_a_value,
+                    op_b_is_ready,
+                    op_b_reg_tag,
+                    op_b_value,
+                    op_b_is_imm: s.micro_op.is_imm,
+                    op_code: s.micro_op.op,
+                    pc: s.micro_op.pc,
+                    op_a_poison: None,
+                    op_b_poison: None,
+                    seq: s.micro_op.seq,
+                    cluster: 0,
+                    bound_alu: None,
+                    register_reads: 0,
+                    csr: None,
+                }
+            })
+            .collect();
+        occupied.sort_by_key(|(_, s)| s.micro_op.seq);
+        self.state.active_list = occupied
+            .iter()
+            .map(|(_, s)| ActiveEntry {
+                done: s.stage == Stage::Completed,
+                exception: s.stage == Stage::Completed && s.result.is_err(),
+                logical_destination: s.dest,
+                old_destination: s.dest,
+                destination: s.dest,
+                pc: s.micro_op.pc,
+                is_fp: false,
+                cause: s.result.err(),
+                poison: None,
+                environment: None,
+                seq: s.micro_op.seq,
+                eliminated: false,
+            })
+            .collect();
+        for (reg, status) in self.tomasulo.register_status.iter().enumerate() {
+            self.state.busy_bit_table[reg] = status.is_some();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::SimulatorBuilder;
+    use crate::config::{Config, Pipeline, TomasuloConfig};
+
+    fn simulator(lines: &[&str], buses: usize) -> Simulator {
+        let config = Config {
+            pipeline: Pipeline::Tomasulo,
+            tomasulo: TomasuloConfig {
+                stations_per_unit: 1,
+                common_data_buses: buses,
+            },
+            ..Config::default()
+        };
+        SimulatorBuilder::new()
+            .program(lines.iter().map(|line| line.to_string()).collect())
+            .config(&config)
+            .build()
+            .unwrap()
+    }
+
+    /// Puts the instruction at `pc` into station `tag` in `stage`, with the
+    /// result an immediate-only instruction computes.
+    fn place(sim: &mut Simulator, pc: u64, tag: usize, stage: Stage) {
+        let mut micro_op = sim.decode(pc).remove(0);
+        micro_op.seq = pc;
+        let mut station = sim.issue_to_station(micro_op);
+        station.stage = stage;
+        station.result = Ok(station.micro_op.imm);
+        sim.tomasulo.register_status[station.dest as usize] = Some(tag as u32);
+        sim.tomasulo.stations[tag] = Some(station);
+        sim.sync_tomasulo_state();
+    }
+
+    fn run(sim: &mut Simulator) {
+        while !sim.done() {
+            sim.simulate_cycle();
+        }
+    }
+
+    #[test]
+    fn one_bus_broadcasts_the_oldest_result_first() {
+        let mut sim = simulator(&["addi x1, x0, 1", "addi x2, x0, 2"], 1);
+        sim.state.pc = 2;
+        place(&mut sim, 1, 0, Stage::Completed);
+        place(&mut sim, 0, 1, Stage::Completed);
+        sim.simulate_cycle();
+        assert_eq!(sim.state.physical_register_file[1..3], [1, 0]);
+        assert!(sim.tomasulo.stations[0].is_some());
+        sim.simulate_cycle();
+        assert_eq!(sim.state.physical_register_file[1..3], [1, 2]);
+    }
+
+    #[test]
+    fn every_bus_broadcasts_in_the_same_cycle() {
+        let mut sim = simulator(&["addi x1, x0, 1", "addi x2, x0, 2"], 2);
+        sim.state.pc = 2;
+        place(&mut sim, 1, 0, Stage::Completed);
+        place(&mut sim, 0, 1, Stage::Completed);
+        sim.simulate_cycle();
+        assert_eq!(sim.state.physical_register_file[1..3], [1, 2]);
+        assert_eq!(sim.committed, 2);
+    }
+
+    #[test]
+    fn a_late_older_write_keeps_the_younger_value() {
+        let mut sim = simulator(&["addi x1, x0, 1", "addi x1, x0, 2"], 1);
+        sim.state.pc = 2;
+        place(&mut sim, 0, 0, Stage::Executing);
+        place(&mut sim, 1, 1, Stage::Completed);
+        run(&mut sim);
+        assert_eq!(sim.state.physical_register_file[1], 2);
+        assert_eq!(sim.committed, 2);
+    }
+
+    #[test]
+    fn a_broadcast_wakes_the_waiting_stations() {
+        let mut sim = simulator(&["addi x1, x0, 5", "add x2, x1, x1", "sub x3, x2, x1"], 1);
+        run(&mut sim);
+        assert_eq!(sim.state.physical_register_file[1..4], [5, 10, 5]);
+    }
+}
@@ -0,0 +1,138 @@
+use std::fs;
+use std::process;
+
+use serde_json::{Value, json};
+
+use crate::lifetime::Lifetime;
+use crate::simulator::Simulator;
+
+/// gem5 ticks per cycle, the default clock period `o3-pipeview.py` assumes.
+const TICKS_PER_CYCLE: u64 = 1000;
+
+/// Pipeline structures shown as tracks, with the stages bounding a micro-op's
+/// stay in each.
+const TRACKS: [&str; 6] = [
+    "DecodedPCs",
+    "IntegerQueue",
+    "FpQueue",
+    "ALUs",
+    "FpUnits",
+    "ActiveList",
+];
+
+/// The track and interval of each structure `l` occupied. Stays cut short
+/// by a squash end at the squash cycle; stays never left (the run stopped
+/// early) are omitted.
+fn stays(l: &Lifetime) -> Vec<(usize, u64, u64)> {
+    let is_fp = l.op.is_fp();
+    let (queue, unit) = if is_fp { (2, 4) } else { (1, 3) };
+    let mut stays = Vec::new();
+    let mut stay = |track: usize, start: Option<u64>, end: Option<u64>| {
+        if let (Some(start), Some(end)) = (start, end.or(l.squash)) {
+            stays.push((track, start, end));
+        }
+    };
+    stay(0, Some(l.fetch), l.dispatch);
+    stay(queue, l.dispatch, l.issue);
+    stay(unit, l.issue, l.complete);
+    stay(5, l.dispatch, l.commit);
+    stays
+}
+
+/// Builds a Chrome `trace_event` document in which every pipeline structure
+/// is a process and every micro-op a complete event for each structure it
+/// passed through, one microsecond per cycle. Overlapping stays are spread
+/// over numbered lanes, the threads of the process, so slices never
+/// overlap within a row.
+pub fn trace_events(lifetimes: &[Lifetime]) -> Value {
+    let mut events = Vec::new();
+    // Cycle at which each lane of each track becomes free.
+    let mut lanes: Vec<Vec<u64>> = vec![Vec::new(); TRACKS.len()];
+    for l in lifetimes {
+        for (track, start, end) in stays(l) {
+            let free = &mut lanes[track];
+            let lane = match free.iter().position(|&f| f <= start) {
+                Some(lane) => lane,
+                None => {
+                    free.push(0);
+                    free.len() - 1
+                }
+            };
+            free[lane] = end.max(start + 1);
+            events.push(json!({
+                "name": format!("{} @{}", l.op, l.pc),
+                "cat": TRACKS[track],
+                "ph": "X",
+                "ts": start,
+                "dur": end - start,
+                "pid": track,
+                "tid": lane,
+                "args": {
+                    "seq": l.seq,
+                    "thread": l.thread,
+                    "pc": l.pc,
+                    "squashed": l.squash.is_some(),
+                },
+            }));
+        }
+    }
+    for (track, name) in TRACKS.iter().enumerate() {
+        if lanes[track].is_empty() {
+            continue;
+        }
+        events.push(json!({
+            "name": "process_name",
+            "ph": "M",
+            "pid": track,
+            "args": { "name": name },
+        }));
+        events.push(json!({
+            "name": "process_sort_index",
+            "ph": "M",
+            "pid": track,
+            "args": { "sort_index": track },
+        }));
+    }
+    json!({ "traceEvents": events })
+}
+
+/// Writes the lifetime of every micro-op in gem5's O3PipeView format, one
+/// `fetch` to `retire` record each, so gem5's `o3-pipeview.py` and viewers
+/// built on it read the run as they would an O3 CPU trace. Fetch and decode
+/// share a cycle, as do rename and dispatch; a stage never reached, such as
+/// the retire of a squashed micro-op, is tick 0.
+pub fn save_o3_pipeview(path: &str, sim: &Simulator) {
+    let tick = |cycle: Option<u64>| cycle.map_or(0, |c| c * TICKS_PER_CYCLE);
+    let mut out = String::new();
+    let mut micro_pc = 0;
+    for (i, l) in sim.lifetimes.iter().enumerate() {
+        // The micro-ops of one instruction are fetched together.
+        micro_pc = match i.checked_sub(1).map(|p| &sim.lifetimes[p]) {
+            Some(prev) if (prev.thread, prev.pc, prev.fetch) == (l.thread, l.pc, l.fetch) => {
+                micro_pc + 1
+            }
+            _ => 0,
+        };
+        let text = sim
+            .thread_instruction(l.thread, l.pc / sim.pc_step)
+            .map_or(l.op.to_string(), Clone::clone);
+        out += &format!(
+            "O3PipeView:fetch:{}:{:#010x}:{}:{}:{}\n",
+            tick(Some(l.fetch)),
+            l.pc,
+            micro_pc,
+            l.seq + 1,
+            text
+        );
+        out += &format!("O3PipeView:decode:{}\n", tick(Some(l.fetch)));
+        out += &format!("O3PipeView:rename:{}\n", tick(l.dispatch));
+        out += &format!("O3PipeView:dispatch:{}\n", tick(l.dispatch));
+        out += &format!("O3PipeView:issue:{}\n", tick(l.issue));
+        out += &format!("O3PipeView:complete:{}\n", tick(l.complete));
+        out += &format!("O3PipeView:retire:{}:store:0\n", tick(l.commit));
+    }
+    fs::write(path, out).unwrap_or_else(|err| {
+        eprintln!("Failed to write O3PipeView file: {}", err);
+        process::exit(1);
+    });
+}
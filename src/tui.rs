@@ -0,0 +1,356 @@
+use serde_json::Value;
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+
+use crate::builder::SimulatorBuilder;
+use crate::checkpoint::Snapshots;
+use crate::cli::TuiArgs;
+use crate::config::{Config, Pipeline};
+use crate::json_io::parse_config;
+use crate::simulator::Simulator;
+
+/// One recorded cycle: the logged state and the ALU stages, which the log
+/// does not carry.
+struct Frame {
+    state: Value,
+    alus: Vec<String>,
+}
+
+const REVERSE: &str = "\x1b[7m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+const HELP: &str = "n/right next  p/b/left back  <count>g go to cycle  home/end first/last  q quit";
+
+/// Cycles between the snapshots that stepping backwards re-executes from.
+const SNAPSHOT_INTERVAL: usize = 64;
+
+/// Simulates the program and lets the user step through the cycles in the
+/// terminal. Entries that changed since the previous cycle are highlighted.
+/// Only periodic snapshots are kept; other cycles are re-executed on demand.
+pub fn run(args: &TuiArgs) -> Result<(), String> {
+    let config = match &args.config_path {
+        Some(path) => parse_config(path),
+        None => Config::default(),
+    };
+    let program = crate::load_program(&args.input_path);
+    let mut sim = SimulatorBuilder::new()
+        .program(program)
+        .config(&config)
+        .build()
+        .map_err(|err| format!("Invalid program: {}", err))?;
+    let show_alus = config.pipeline == Pipeline::OutOfOrder;
+    let mut snapshots = Snapshots::new(SNAPSHOT_INTERVAL);
+    snapshots.record(&sim);
+    while !sim.done() {
+        sim.simulate_cycle();
+        snapshots.record(&sim);
+    }
+    let last = sim.cycles();
+    let mut view = View::new(snapshots.seek(0), show_alus);
+
+    let _raw = RawMode::enter();
+    let mut stdout = io::stdout();
+    let mut cycle = 0;
+    let mut count = String::new();
+    let mut keys = Keys::new();
+    loop {
+        view.seek(&snapshots, cycle);
+        let screen = render(&view.frame, view.previous.as_ref(), cycle, last);
+        print!("\x1b[2J\x1b[H{}", screen.replace('\n', "\r\n"));
+        stdout.flush().map_err(|err| err.to_string())?;
+        let Some(key) = keys.next() else {
+            break;
+        };
+        let n = count.parse::<usize>().ok();
+        match key {
+            Key::Char(c) if c.is_ascii_digit() => {
+                count.push(c);
+                continue;
+            }
+            Key::Char('n') | Key::Char(' ') | Key::Right => {
+                cycle = (cycle + n.unwrap_or(1)).min(last);
+            }
+            Key::Char('p') | Key::Char('b') | Key::Left => {
+                cycle = cycle.saturating_sub(n.unwrap_or(1))
+            }
+            Key::Char('g') => cycle = n.unwrap_or(0).min(last),
+            Key::Home => cycle = 0,
+            Key::End => cycle = last,
+            Key::Char('q') => break,
+            _ => {}
+        }
+        count.clear();
+    }
+    println!();
+    Ok(())
+}
+
+/// The simulator at the displayed cycle, with the frames of that cycle and
+/// the one before it.
+struct View {
+    sim: Simulator,
+    show_alus: bool,
+    frame: Frame,
+    previous: Option<Frame>,
+}
+
+impl View {
+    fn new(sim: Simulator, show_alus: bool) -> View {
+        View {
+            frame: Frame::of(&sim, show_alus),
+            previous: None,
+            sim,
+            show_alus,
+        }
+    }
+
+    /// Moves to `cycle`, simulating forward from the current cycle or, to
+    /// go back, from the nearest snapshot.
+    fn seek(&mut self, snapshots: &Snapshots, cycle: usize) {
+        let current = self.sim.cycles();
+        if cycle == current {
+            return;
+        }
+        if cycle != current + 1 {
+            let before = cycle.saturating_sub(1);
+            if cycle < current {
+                self.sim = snapshots.seek(before);
+            }
+            while self.sim.cycles() < before {
+                self.sim.simulate_cycle();
+            }
+            self.frame = Frame::of(&self.sim, self.show_alus);
+        }
+        if cycle == 0 {
+            self.previous = None;
+            return;
+        }
+        self.sim.simulate_cycle();
+        let frame = Frame::of(&self.sim, self.show_alus);
+        self.previous = Some(std::mem::replace(&mut self.frame, frame));
+    }
+}
+
+impl Frame {
+    fn of(sim: &Simulator, show_alus: bool) -> Frame {
+        Frame {
+            state: sim.state.to_log_value(),
+            alus: match show_alus {
+                true => sim.alus.iter().map(|alu| alu.describe()).collect(),
+                false => Vec::new(),
+            },
+        }
+    }
+}
+
+fn render(frame: &Frame, previous: Option<&Frame>, cycle: usize, last: usize) -> String {
+    let state = &frame.state;
+    let previous = previous.map(|p| &p.state);
+    let changed =
+        |field: &str, i: usize| previous.is_some_and(|p| p[field].get(i) != state[field].get(i));
+    let mut out = String::new();
+    let num = |v: &Value| v.as_u64().unwrap_or(0);
+
+    out += &format!(
+        "{BOLD}Cycle {}/{}{RESET}   PC {}   Exception {}   ExceptionPC {}\n",
+        cycle,
+        last,
+        num(&state["PC"]),
+        state["Exception"].as_bool().unwrap_or(false),
+        num(&state["ExceptionPC"]),
+    );
+    out += &format!("{}\n\n", HELP);
+
+    out += &format!("{BOLD}RegisterMapTable{RESET}\n");
+    let map = state["RegisterMapTable"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    for (row, chunk) in map.chunks(8).enumerate() {
+        for (col, phys) in chunk.iter().enumerate() {
+            let reg = row * 8 + col;
+            let cell = format!("x{:<2}->{:>3}", reg, num(phys));
+            out += &highlight(&cell, changed("RegisterMapTable", reg));
+            out += "  ";
+        }
+        out += "\n";
+    }
+
+    if let Some(free) = state["FreeList"].as_array() {
+        let free: Vec<String> = free.iter().map(|p| num(p).to_string()).collect();
+        out += &format!(
+            "\n{BOLD}FreeList{RESET} ({}): {}\n",
+            free.len(),
+            free.join(" ")
+        );
+    }
+    let busy: Vec<String> = state["BusyBitTable"]
+        .as_array()
+        .map(|bits| {
+            bits.iter()
+                .enumerate()
+                .filter(|(_, b)| b.as_bool() == Some(true))
+                .map(|(p, _)| format!("p{}", p))
+                .collect()
+        })
+        .unwrap_or_default();
+    out += &format!("{BOLD}Busy{RESET}: {}\n", busy.join(" "));
+
+    let queue = state["IntegerQueue"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    out += &format!(
+        "\n{BOLD}IntegerQueue{RESET} ({})\n  {:>4}  {:<6} {:>5}  {:<20} {:<20}\n",
+        queue.len(),
+        "PC",
+        "Op",
+        "Dest",
+        "OpA",
+        "OpB"
+    );
+    for entry in &queue {
+        let operand = |k: &str| {
+            if entry[format!("Op{}IsReady", k)].as_bool() == Some(true) {
+                format!("{:#x}", num(&entry[format!("Op{}Value", k)]))
+            } else {
+                format!("wait p{}", num(&entry[format!("Op{}RegTag", k)]))
+            }
+        };
+        out += &format!(
+            "  {:>4}  {:<6} {:>5}  {:<20} {:<20}\n",
+            num(&entry["PC"]),
+            entry["OpCode"].as_str().unwrap_or(""),
+            format!("p{}", num(&entry["DestRegister"])),
+            operand("A"),
+            operand("B"),
+        );
+    }
+
+    // Under reorder-buffer renaming the ROB takes the active list's place.
+    let (title, entries) = match state.get("ReorderBuffer") {
+        Some(rob) => ("ReorderBuffer", rob.as_array().cloned().unwrap_or_default()),
+        None => (
+            "ActiveList",
+            state["ActiveList"].as_array().cloned().unwrap_or_default(),
+        ),
+    };
+    out += &format!(
+        "\n{BOLD}{}{RESET} ({})\n  {:>4}  {:>4}  {:>4}  Done  Exception\n",
+        title,
+        entries.len(),
+        "PC",
+        "Reg",
+        if title == "ActiveList" { "Old" } else { "Tag" },
+    );
+    for entry in &entries {
+        let second = entry.get("OldDestination").or(entry.get("Tag"));
+        out += &format!(
+            "  {:>4}  x{:<3}  {:>4}  {:<5} {}\n",
+            num(&entry["PC"]),
+            num(&entry["LogicalDestination"]),
+            second.map_or(0, num),
+            entry["Done"].as_bool().unwrap_or(false),
+            entry["Exception"].as_bool().unwrap_or(false),
+        );
+    }
+
+    let alus = &frame.alus;
+    if !alus.is_empty() {
+        out += &format!("\n{BOLD}ALUs{RESET}\n");
+        for (i, alu) in alus.iter().enumerate() {
+            out += &format!("  ALU{} {}\n", i, alu);
+        }
+    }
+    out
+}
+
+fn highlight(text: &str, on: bool) -> String {
+    match on {
+        true => format!("{REVERSE}{}{RESET}", text),
+        false => text.to_string(),
+    }
+}
+
+/// Puts the terminal into unbuffered, non-echoing input mode for as long as
+/// it lives. Without a terminal, keys are read line by line instead.
+struct RawMode {
+    active: bool,
+}
+
+impl RawMode {
+    fn enter() -> RawMode {
+        RawMode {
+            active: stty(&["-icanon", "-echo", "min", "1"]),
+        }
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        if self.active {
+            stty(&["icanon", "echo"]);
+        }
+    }
+}
+
+fn stty(args: &[&str]) -> bool {
+    Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+enum Key {
+    Char(char),
+    Left,
+    Right,
+    Home,
+    End,
+}
+
+/// Decodes key presses, including the escape sequences of the arrow, Home
+/// and End keys, from stdin.
+struct Keys {
+    bytes: io::Bytes<io::StdinLock<'static>>,
+}
+
+impl Keys {
+    fn new() -> Keys {
+        Keys {
+            bytes: io::stdin().lock().bytes(),
+        }
+    }
+
+    fn byte(&mut self) -> Option<u8> {
+        self.bytes.next().and_then(Result::ok)
+    }
+}
+
+impl Iterator for Keys {
+    type Item = Key;
+
+    fn next(&mut self) -> Option<Key> {
+        loop {
+            match self.byte()? {
+                b'\n' | b'\r' => continue,
+                0x1b => {
+                    if self.byte()? != b'[' {
+                        continue;
+                    }
+                    return Some(match self.byte()? {
+                        b'C' => Key::Right,
+                        b'D' => Key::Left,
+                        b'H' => Key::Home,
+                        b'F' => Key::End,
+                        _ => continue,
+                    });
+                }
+                b => return Some(Key::Char(b as char)),
+            }
+        }
+    }
+}
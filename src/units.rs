@@ -0,0 +1,383 @@
+use std::sync::RwLock;
+
+use crate::asm;
+use crate::config::CustomOpcode;
+use crate::opcode::Opcode;
+use crate::simulator::{ExceptionCause, FUSED_LI_EXTRA_CYCLES};
+use crate::syscall;
+
+/// Operands of an integer opcode: raw 64-bit values that signed opcodes
+/// reinterpret as two's complement.
+#[derive(Debug, Clone, Copy)]
+pub struct Operands {
+    pub a: u64,
+    pub b: u64,
+    /// `add`, `sub` and `mul` raise an overflow exception instead of
+    /// wrapping, as MIPS `add` does.
+    pub trap_on_overflow: bool,
+}
+
+impl Operands {
+    fn signed(&self) -> (i64, i64) {
+        (self.a as i64, self.b as i64)
+    }
+
+    /// `f` on the signed operands if overflow traps, else `wrapping`.
+    fn checked(
+        &self,
+        f: fn(i64, i64) -> Option<i64>,
+        wrapping: u64,
+    ) -> Result<u64, ExceptionCause> {
+        let (sa, sb) = self.signed();
+        match self.trap_on_overflow {
+            true => f(sa, sb).map(|v| v as u64).ok_or(ExceptionCause::Overflow),
+            false => Ok(wrapping),
+        }
+    }
+
+    fn signed_division(&self, f: fn(i64, i64) -> Option<i64>) -> Result<u64, ExceptionCause> {
+        let (sa, sb) = self.signed();
+        match f(sa, sb) {
+            Some(v) => Ok(v as u64),
+            None if sb == 0 => Err(ExceptionCause::DivideByZero),
+            None => Err(ExceptionCause::Overflow),
+        }
+    }
+}
+
+type Execute = Box<dyn Fn(Operands) -> Result<u64, ExceptionCause> + Send + Sync>;
+
+/// An opcode registered with `register`.
+struct FunctionalUnit {
+    name: &'static str,
+    /// Cycles the opcode occupies the first execute stage.
+    latency: u32,
+    execute: Execute,
+}
+
+static UNITS: RwLock<Vec<FunctionalUnit>> = RwLock::new(Vec::new());
+
+/// Registers opcode `name` with its execute-stage latency in cycles and
+/// its computation, replacing any unit of that name. Registered opcodes
+/// take the register form `name rd, rs1, rs2` in assembly programs, can be
+/// listed in `alu_capabilities`, and run on every integer pipeline. Names
+/// of mnemonics and opcode classes cannot be registered.
+pub fn register(
+    name: &'static str,
+    latency: u32,
+    execute: impl Fn(Operands) -> Result<u64, ExceptionCause> + Send + Sync + 'static,
+) -> Result<(), String> {
+    if latency == 0 {
+        return Err(format!("opcode '{}' needs a latency of at least 1", name));
+    }
+    let taken = |op: Opcode| op != Opcode::Custom(name);
+    let mnemonic = asm::descriptor_of(name).is_some_and(|(op, _)| taken(op));
+    if mnemonic || Opcode::parse(name).is_some_and(taken) || ["simple", "mul-div"].contains(&name) {
+        return Err(format!("'{}' is already the name of a mnemonic", name));
+    }
+    let mut units = UNITS.write().unwrap();
+    units.retain(|unit| unit.name != name);
+    units.push(FunctionalUnit {
+        name,
+        latency,
+        execute: Box::new(execute),
+    });
+    Ok(())
+}
+
+/// Registers the opcodes defined by the `custom_opcodes` of a config,
+/// replacing those of an earlier config.
+pub(crate) fn register_custom(opcodes: &[CustomOpcode]) -> Result<(), String> {
+    for opcode in opcodes {
+        let result = Expression::parse(&opcode.result)
+            .map_err(|err| format!("custom opcode '{}': {}", opcode.name, err))?;
+        let name =
+            lookup(&opcode.name).unwrap_or_else(|| Box::leak(opcode.name.clone().into_boxed_str()));
+        register(name, opcode.latency, move |o| result.evaluate(o))?;
+    }
+    Ok(())
+}
+
+/// The result of a custom opcode: an expression over the operands `a` and
+/// `b` (`rs1` and `rs2`) with the operators, precedence and parentheses of
+/// C, in 64-bit arithmetic that wraps. `/` and `%` are unsigned and raise
+/// a divide-by-zero exception; shift amounts use the low six bits.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Expression {
+    A,
+    B,
+    Constant(u64),
+    Negate(Box<Expression>),
+    Not(Box<Expression>),
+    Binary(&'static str, Box<Expression>, Box<Expression>),
+}
+
+/// Binary operators from the loosest binding to the tightest.
+const PRECEDENCE: [&[&str]; 6] = [
+    &["|"],
+    &["^"],
+    &["&"],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+impl Expression {
+    pub(crate) fn parse(text: &str) -> Result<Expression, String> {
+        let tokens = tokenize(text)?;
+        let mut parser = Parser { tokens, next: 0 };
+        let expression = parser.binary(0)?;
+        match parser.tokens.get(parser.next) {
+            None => Ok(expression),
+            Some(token) => Err(format!("unexpected '{}'", token)),
+        }
+    }
+
+    pub(crate) fn evaluate(&self, o: Operands) -> Result<u64, ExceptionCause> {
+        Ok(match self {
+            Expression::A => o.a,
+            Expression::B => o.b,
+            Expression::Constant(value) => *value,
+            Expression::Negate(e) => e.evaluate(o)?.wrapping_neg(),
+            Expression::Not(e) => !e.evaluate(o)?,
+            Expression::Binary(op, lhs, rhs) => {
+                let (x, y) = (lhs.evaluate(o)?, rhs.evaluate(o)?);
+                match *op {
+                    "|" => x | y,
+                    "^" => x ^ y,
+                    "&" => x & y,
+                    "<<" => x << (y & 63),
+                    ">>" => x >> (y & 63),
+                    "+" => x.wrapping_add(y),
+                    "-" => x.wrapping_sub(y),
+                    "*" => x.wrapping_mul(y),
+                    "/" => x.checked_div(y).ok_or(ExceptionCause::DivideByZero)?,
+                    _ => x.checked_rem(y).ok_or(ExceptionCause::DivideByZero)?,
+                }
+            }
+        })
+    }
+}
+
+/// Splits an expression into operands, numbers, operators and
+/// parentheses.
+fn tokenize(text: &str) -> Result<Vec<&str>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let len = match c {
+            _ if c.is_ascii_alphanumeric() => rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len()),
+            '<' | '>' if rest[1..].starts_with(c) => 2,
+            '+' | '-' | '*' | '/' | '%' | '&' | '|' | '^' | '~' | '(' | ')' => 1,
+            _ => return Err(format!("unexpected '{}'", c)),
+        };
+        tokens.push(&rest[..len]);
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec<&'a str>,
+    next: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn take(&mut self) -> Result<&'a str, String> {
+        let token = self.tokens.get(self.next).ok_or("unexpected end")?;
+        self.next += 1;
+        Ok(token)
+    }
+
+    /// The operators of `PRECEDENCE[level]` and tighter, left to right.
+    fn binary(&mut self, level: usize) -> Result<Expression, String> {
+        let Some(&operators) = PRECEDENCE.get(level) else {
+            return self.unary();
+        };
+        let mut lhs = self.binary(level + 1)?;
+        while let Some(&op) = self
+            .tokens
+            .get(self.next)
+            .and_then(|token| operators.iter().find(|op| *op == token))
+        {
+            self.next += 1;
+            let rhs = self.binary(level + 1)?;
+            lhs = Expression::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expression, String> {
+        Ok(match self.take()? {
+            "a" => Expression::A,
+            "b" => Expression::B,
+            "-" => Expression::Negate(Box::new(self.unary()?)),
+            "~" => Expression::Not(Box::new(self.unary()?)),
+            "(" => {
+                let inner = self.binary(0)?;
+                match self.take()? {
+                    ")" => inner,
+                    token => return Err(format!("expected ')', found '{}'", token)),
+                }
+            }
+            token => match asm::parse_immediate(token) {
+                Some(value) if token.starts_with(|c: char| c.is_ascii_digit()) => {
+                    Expression::Constant(value)
+                }
+                _ => return Err(format!("unexpected '{}'", token)),
+            },
+        })
+    }
+}
+
+/// The result of opcode `op`, or `None` if no unit executes it.
+pub(crate) fn execute(op: Opcode, o: Operands) -> Option<Result<u64, ExceptionCause>> {
+    let (sa, sb) = o.signed();
+    Some(match op {
+        Opcode::Add => o.checked(i64::checked_add, o.a.wrapping_add(o.b)),
+        Opcode::Sub => o.checked(i64::checked_sub, o.a.wrapping_sub(o.b)),
+        Opcode::Mulu => Ok(o.a.wrapping_mul(o.b)),
+        Opcode::Mul => o.checked(i64::checked_mul, sa.wrapping_mul(sb) as u64),
+        Opcode::Slt => Ok((sa < sb) as u64),
+        Opcode::Sltu => Ok((o.a < o.b) as u64),
+        Opcode::And => Ok(o.a & o.b),
+        Opcode::Or => Ok(o.a | o.b),
+        Opcode::Xor => Ok(o.a ^ o.b),
+        // Shift amounts use the low six bits, as on RV64.
+        Opcode::Sll => Ok(o.a << (o.b & 63)),
+        Opcode::Srl => Ok(o.a >> (o.b & 63)),
+        Opcode::Sra => Ok((sa >> (o.b & 63)) as u64),
+        // The upper immediate is not limited to 20 bits, so `lui` alone can
+        // place constants wider than 32 bits.
+        Opcode::Lui => Ok(o.b << 12),
+        Opcode::Auipc => Ok(o.a.wrapping_add(o.b << 12)),
+        Opcode::Li => Ok(o.b),
+        Opcode::Divu => o.a.checked_div(o.b).ok_or(ExceptionCause::DivideByZero),
+        Opcode::Remu => o.a.checked_rem(o.b).ok_or(ExceptionCause::DivideByZero),
+        Opcode::Div => o.signed_division(i64::checked_div),
+        Opcode::Rem => o.signed_division(i64::checked_rem),
+        Opcode::Unimp => Err(ExceptionCause::IllegalInstruction),
+        // `ecall` reads the syscall number from `a7` and passes `a0` through;
+        // its effect happens at commit.
+        Opcode::Ecall => match syscall::is_syscall(o.a) {
+            true => Ok(o.b),
+            false => Err(ExceptionCause::IllegalInstruction),
+        },
+        Opcode::Ebreak => Ok(o.b),
+        // CSR instructions write the old value of the CSR, read into `b` at
+        // issue; the CSR itself is updated at commit.
+        Opcode::Csrrw | Opcode::Csrrs | Opcode::Csrrc => Ok(o.b),
+        Opcode::Fadd | Opcode::Fsub | Opcode::Fmul | Opcode::Fdiv => return None,
+        Opcode::Custom(name) => {
+            let units = UNITS.read().unwrap();
+            let unit = units.iter().find(|unit| unit.name == name)?;
+            (unit.execute)(o)
+        }
+    })
+}
+
+/// The registered opcode named `op`.
+pub(crate) fn lookup(op: &str) -> Option<&'static str> {
+    let units = UNITS.read().unwrap();
+    units
+        .iter()
+        .find(|unit| unit.name == op)
+        .map(|unit| unit.name)
+}
+
+/// Cycles `op` spends in the first execute stage beyond the first.
+pub(crate) fn extra_cycles(op: Opcode) -> u32 {
+    match op {
+        Opcode::Li => FUSED_LI_EXTRA_CYCLES,
+        Opcode::Custom(name) => {
+            let units = UNITS.read().unwrap();
+            units
+                .iter()
+                .find(|unit| unit.name == name)
+                .map_or(0, |unit| unit.latency - 1)
+        }
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::SimulatorBuilder;
+    use crate::config::Config;
+
+    fn evaluate(text: &str, a: u64, b: u64) -> Result<u64, ExceptionCause> {
+        let operands = Operands {
+            a,
+            b,
+            trap_on_overflow: false,
+        };
+        Expression::parse(text).unwrap().evaluate(operands)
+    }
+
+    #[test]
+    fn expressions_follow_c_precedence() {
+        assert_eq!(evaluate("a + b * 2", 1, 3), Ok(7));
+        assert_eq!(evaluate("(a + b) * 2", 1, 3), Ok(8));
+        assert_eq!(evaluate("a | b & 1 << 1", 1, 3), Ok(3));
+        assert_eq!(evaluate("a - b - 1", 10, 3), Ok(6));
+        assert_eq!(evaluate("-a", 1, 0), Ok(u64::MAX));
+        assert_eq!(evaluate("~0x0f ^ a", 0, 0), Ok(!0x0f));
+        assert_eq!(evaluate("a << b", 1, 65), Ok(2));
+        assert_eq!(evaluate("a % b", 1, 0), Err(ExceptionCause::DivideByZero));
+    }
+
+    #[test]
+    fn malformed_expressions_are_rejected() {
+        for text in ["", "a +", "(a", "a b", "c", "a $ b", "a > b"] {
+            assert!(Expression::parse(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn mnemonics_cannot_be_registered() {
+        for name in ["add", "addi", "mv", "mulu", "simple"] {
+            assert!(register(name, 1, |o| Ok(o.a)).is_err(), "{}", name);
+        }
+        assert!(register("unit_test_zero", 0, |o| Ok(o.a)).is_err());
+    }
+
+    #[test]
+    fn custom_opcodes_run_through_the_pipeline() {
+        let config: Config = serde_json::from_str(
+            r#"{
+                "custom_opcodes": [{"name": "madd", "latency": 3, "result": "a * b + a"}],
+                "alu_capabilities": [["simple"], ["madd"]]
+            }"#,
+        )
+        .unwrap();
+        let program = ["addi x1, x0, 3", "addi x2, x0, 4", "madd x3, x1, x2"];
+        let mut sim = SimulatorBuilder::new()
+            .program(program.iter().map(|line| line.to_string()).collect())
+            .config(&config)
+            .build()
+            .unwrap();
+        while !sim.done() {
+            sim.simulate_cycle();
+        }
+        assert_eq!(sim.architectural_registers()[3], 15);
+    }
+
+    #[test]
+    fn invalid_custom_opcodes_are_config_errors() {
+        let config = |opcode: &str| -> Config {
+            serde_json::from_str(&format!(r#"{{"custom_opcodes": [{}]}}"#, opcode)).unwrap()
+        };
+        let err = config(r#"{"name": "bad_expression", "latency": 1, "result": "a +"}"#)
+            .validate()
+            .unwrap_err();
+        assert_eq!(err, "custom opcode 'bad_expression': unexpected end");
+        assert!(
+            config(r#"{"name": "xor", "latency": 1, "result": "a"}"#)
+                .validate()
+                .is_err()
+        );
+    }
+}
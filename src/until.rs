@@ -0,0 +1,37 @@
+use std::collections::VecDeque;
+
+use crate::cli::Until;
+use crate::interrupt;
+use crate::progress::Progress;
+use crate::simulator::Simulator;
+
+impl Simulator {
+    /// Simulates without logging, hooks or invariant checks until `until`:
+    /// the start of the cycle, or the end of the cycle the instruction at
+    /// the address committed in. The log and an attached debugger then start
+    /// from there. Returns whether the target was reached before the program
+    /// finished or the watchdog or Ctrl-C stopped it.
+    pub fn run_until(&mut self, until: Until, mut progress: Option<&mut Progress>) -> bool {
+        let previous = self.commit_log.replace(VecDeque::new());
+        let mut committed = false;
+        let reached = loop {
+            let reached = match until {
+                Until::Cycle(cycle) => self.cycles() >= cycle,
+                Until::Pc(_) => committed,
+            };
+            if reached || self.done() || self.watchdog_expired() || interrupt::requested() {
+                break reached;
+            }
+            self.simulate_cycle();
+            if let Until::Pc(address) = until {
+                let commits = self.commit_log.replace(VecDeque::new()).unwrap();
+                committed = commits.iter().any(|c| self.address(c.pc) == address);
+            }
+            if let Some(progress) = progress.as_deref_mut() {
+                progress.update(self);
+            }
+        };
+        self.commit_log = previous;
+        reached
+    }
+}
@@ -0,0 +1,286 @@
+use std::fmt;
+
+use crate::asm::{self, Format, Operand, parse_immediate};
+use crate::config::{Config, Pipeline};
+use crate::csr;
+use crate::decoder;
+use crate::opcode::Opcode;
+use crate::simulator::Simulator;
+
+/// A program entry the simulator cannot execute: where it is, the offending
+/// token, what belongs in its place and, when one is likely, a fix.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    /// Index of the instruction in the input.
+    pub index: usize,
+    pub token: String,
+    pub expected: String,
+    pub suggestion: Option<String>,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "instruction {}: found {:?}, expected {}",
+            self.index, self.token, self.expected
+        )?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, "; did you mean {:?}?", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+/// The fields of a diagnostic, without its index.
+struct Found {
+    token: String,
+    expected: String,
+    suggestion: Option<String>,
+}
+
+fn found(token: &str, expected: impl Into<String>, suggestion: Option<String>) -> Found {
+    Found {
+        token: token.to_string(),
+        expected: expected.into(),
+        suggestion,
+    }
+}
+
+/// Checks every instruction before simulation so malformed programs are
+/// rejected with all their problems instead of panicking when first decoded,
+/// and that the program ends below the exception vector.
+pub fn check_program(program: &[String], config: &Config) -> Vec<Diagnostic> {
+    let mut diagnostics = check_handler(program, config);
+    if let Some(line) = program.get(config.vector_index() as usize) {
+        diagnostics.push(Diagnostic {
+            index: config.vector_index() as usize,
+            token: line.clone(),
+            expected: format!(
+                "the end of the program, which must fit below the exception vector {:#x}",
+                config.exception_vector
+            ),
+            suggestion: None,
+        });
+    }
+    diagnostics
+}
+
+/// Checks every instruction of an exception handler, which is mapped at the
+/// exception vector.
+pub fn check_handler(handler: &[String], config: &Config) -> Vec<Diagnostic> {
+    let machine_code = Simulator::is_machine_code(handler, config);
+    handler
+        .iter()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let checked = match machine_code {
+                true => check_word(line, config),
+                false => check_instruction(line, config),
+            };
+            checked.err().map(|found| Diagnostic {
+                index,
+                token: found.token,
+                expected: found.expected,
+                suggestion: found.suggestion,
+            })
+        })
+        .collect()
+}
+
+fn check_word(line: &str, config: &Config) -> Result<(), Found> {
+    let Some(word) = decoder::parse_word(line) else {
+        return Err(found(line, "a hex-encoded instruction word", None));
+    };
+    match decoder::decode_word(0, word) {
+        Some(micro_op) => {
+            check_system(micro_op.op.name(), config).map_err(|expected| found(line, expected, None))
+        }
+        None => Err(found(
+            line,
+            "an RV32IM computational instruction word",
+            None,
+        )),
+    }
+}
+
+fn check_instruction(line: &str, config: &Config) -> Result<(), Found> {
+    let Some((mnemonic, parts)) = asm::split_instruction(line) else {
+        return Err(found(line, "an instruction", None));
+    };
+    check_system(mnemonic, config).map_err(|expected| found(mnemonic, expected, None))?;
+    let descriptor = match asm::descriptor_of(mnemonic) {
+        Some((op, _)) if op.is_fp() && !config.fp.enabled => {
+            return Err(found(
+                mnemonic,
+                "an integer instruction (FP instructions need fp.enabled in the config)",
+                None,
+            ));
+        }
+        Some((_, descriptor)) => descriptor,
+        None => {
+            return Err(found(
+                mnemonic,
+                "a known instruction",
+                closest_mnemonic(mnemonic),
+            ));
+        }
+    };
+    let operands = descriptor.operands;
+    let syntax = format!("{} {}", mnemonic, descriptor.syntax);
+    let syntax = syntax.trim_end();
+
+    // An immediate where a register belongs, or the reverse, means the other
+    // form of the instruction was meant.
+    if let (Some((op, format)), Some(&last)) = (asm::lookup(mnemonic), parts.get(2)) {
+        let other = match format {
+            Format::Register if asm::parse_immediate(last).is_some() => Format::Immediate,
+            Format::Immediate if asm::register_index(last).is_some() => Format::Register,
+            _ => format,
+        };
+        if other != format
+            && let Some(name) = asm::mnemonic_for(op, other)
+        {
+            let fixed = line.replacen(mnemonic, name, 1);
+            return Err(found(last, format!("`{}`", syntax), Some(fixed)));
+        }
+    }
+
+    if parts.len() != operands.len() {
+        return Err(found(
+            line.trim(),
+            format!("{} operands: `{}`", operands.len(), syntax),
+            None,
+        ));
+    }
+    for (&operand, &text) in operands.iter().zip(&parts) {
+        let (valid, expected) = match operand {
+            Operand::Register => (
+                asm::register_index(text).is_some(),
+                "a register (x0-x31 or an ABI name)",
+            ),
+            Operand::FpRegister => (
+                asm::fp_register_index(text).is_some(),
+                "an FP register (f0-f31 or an ABI name)",
+            ),
+            Operand::Immediate => (
+                asm::parse_immediate(text).is_some(),
+                "an integer immediate (decimal, 0x hex or 0b binary)",
+            ),
+            Operand::Csr => (
+                csr::parse(text).is_some(),
+                "a CSR (cycle, instret, mscratch or its number)",
+            ),
+        };
+        if !valid {
+            return Err(found(text, expected, suggest_operand(operand, text)));
+        }
+    }
+
+    // Shifts of a 64-bit register take a 6-bit amount.
+    if let (Some((Opcode::Sll | Opcode::Srl | Opcode::Sra, Format::Immediate)), [_, _, amount]) =
+        (asm::lookup(mnemonic), &parts[..])
+        && asm::parse_immediate(amount).is_some_and(|amount| amount > 63)
+    {
+        return Err(found(amount, "a shift amount from 0 to 63", None));
+    }
+
+    // `csrrs` and `csrrc` from x0 only read, so they may name a read-only
+    // CSR.
+    if let (Some((op, Format::Csr)), [rd, name, rs1]) = (asm::lookup(mnemonic), &parts[..])
+        && csr::parse(name).is_some_and(csr::is_read_only)
+        && (op == Opcode::Csrrw || asm::register_index(rs1) != Some(0))
+    {
+        return Err(found(
+            name,
+            "a writable CSR (cycle and instret are read-only)",
+            Some(format!("csrr {}, {}", rd, name)),
+        ));
+    }
+    Ok(())
+}
+
+/// System and CSR instructions act at commit, which only the out-of-order
+/// pipeline implements.
+fn check_system(op: &str, config: &Config) -> Result<(), String> {
+    let system = matches!(op, "ecall" | "ebreak")
+        || asm::descriptor_of(op).is_some_and(|(op, _)| op.is_csr());
+    match system && config.pipeline != Pipeline::OutOfOrder {
+        true => Err(format!(
+            "an instruction this pipeline executes ({} needs the out-of-order pipeline)",
+            op
+        )),
+        false => Ok(()),
+    }
+}
+
+/// A likely intended spelling of an invalid operand.
+fn suggest_operand(operand: Operand, text: &str) -> Option<String> {
+    let lower = text.to_lowercase();
+    match operand {
+        Operand::Register => {
+            let number = lower.trim_start_matches(['r', '$', 'x']);
+            let candidates = [lower.clone(), format!("x{}", number)];
+            candidates
+                .into_iter()
+                .find(|c| c != text && asm::register_index(c).is_some())
+        }
+        Operand::FpRegister => Some(lower).filter(|c| asm::fp_register_index(c).is_some()),
+        Operand::Csr => Some(lower).filter(|c| csr::parse(c).is_some()),
+        // `$` and `#` prefix immediates in other assemblers.
+        Operand::Immediate => Some(lower.trim_start_matches(['$', '#']).to_string())
+            .filter(|c| parse_immediate(c).is_some()),
+    }
+}
+
+/// The known mnemonic nearest to `mnemonic`, if it is a likely typo.
+fn closest_mnemonic(mnemonic: &str) -> Option<String> {
+    let lower = mnemonic.to_lowercase();
+    asm::mnemonics()
+        .map(|name| (edit_distance(&lower, name), name))
+        .filter(|&(distance, name)| distance <= 1.max(name.len() / 3))
+        .min()
+        .map(|(_, name)| name.to_string())
+}
+
+/// Levenshtein distance between two ASCII strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.as_bytes();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, &ca) in a.as_bytes().iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for j in 0..b.len() {
+            let substitution = diagonal + (ca != b[j]) as usize;
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn errors(lines: &[&str]) -> Vec<String> {
+        let program: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+        check_program(&program, &Config::default())
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn shift_amounts_fit_in_six_bits() {
+        assert!(errors(&["slli x1, x1, 0", "srli x1, x1, 63", "srai x1, x1, 0x3f"]).is_empty());
+        assert_eq!(
+            errors(&["srai x1, x2, 64", "slli x1, x1, 200", "srli x1, x1, -1"]),
+            [
+                "instruction 0: found \"64\", expected a shift amount from 0 to 63",
+                "instruction 1: found \"200\", expected a shift amount from 0 to 63",
+                "instruction 2: found \"-1\", expected a shift amount from 0 to 63"
+            ]
+        );
+    }
+}
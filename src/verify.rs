@@ -0,0 +1,171 @@
+use std::collections::VecDeque;
+use std::fs;
+
+use crate::builder::SimulatorBuilder;
+use crate::cli::VerifyTraceArgs;
+use crate::config::{Config, Pipeline};
+use crate::json_io::parse_config;
+use crate::simulator::Commit;
+
+/// One instruction of a reference log.
+struct Retired {
+    pc: u64,
+    /// The integer register written and its value, if the log records
+    /// register writes at all.
+    write: Option<Option<(usize, u64)>>,
+}
+
+/// Parses a line of a Spike log, with or without `--log-commits`:
+///
+/// ```text
+/// core   0: 3 0x0000000080000000 (0x00000297) x5  0x0000000080000000
+/// core   0: 0x0000000080000000 (0x00000297) auipc   t0, 0x0
+/// ```
+///
+/// Only the commit format, with the privilege level before the PC, records
+/// the registers written.
+fn parse_spike(line: &str) -> Option<Retired> {
+    let rest = line.strip_prefix("core")?;
+    let (_, rest) = rest.split_once(':')?;
+    let mut words = rest.split_whitespace().peekable();
+    let commits = words.peek()?.len() == 1;
+    if commits {
+        words.next();
+    }
+    let pc = parse_hex(words.next()?)?;
+    words.next().filter(|w| w.starts_with("(0x"))?;
+    if !commits {
+        return Some(Retired { pc, write: None });
+    }
+    let mut write = None;
+    while let Some(word) = words.next() {
+        // Integer registers are `x<n>`; FP registers, CSRs and memory
+        // accesses are not compared.
+        if let Some(register) = word.strip_prefix('x').and_then(|r| r.parse().ok()) {
+            write = Some((register, parse_hex(words.next()?)?));
+        }
+    }
+    Some(Retired {
+        pc,
+        write: Some(write),
+    })
+}
+
+/// Parses a line of a QEMU `-d exec` log run with one instruction per
+/// translation block, which records PCs only:
+///
+/// ```text
+/// Trace 0: 0x7f2c4c000100 [00000000/0000000080000000/00000000/00000000]
+/// ```
+fn parse_qemu(line: &str) -> Option<Retired> {
+    let rest = line.strip_prefix("Trace ")?;
+    let (_, fields) = rest.split_once('[')?;
+    let pc = fields.split('/').nth(1)?;
+    Some(Retired {
+        pc: u64::from_str_radix(pc, 16).ok()?,
+        write: None,
+    })
+}
+
+fn parse_hex(word: &str) -> Option<u64> {
+    u64::from_str_radix(word.strip_prefix("0x")?, 16).ok()
+}
+
+/// Simulates the program and checks every instruction it commits against a
+/// Spike or QEMU log of the same program, reporting the first one whose PC
+/// or register write differs. Instruction `i` of the program is at
+/// address `base + 4 * i`; reference instructions before the first one at
+/// `base`, such as a boot ROM, are skipped.
+pub fn run(args: &VerifyTraceArgs) -> Result<(), String> {
+    let config = match &args.config_path {
+        Some(path) => parse_config(path),
+        None => Config::default(),
+    };
+    if config.pipeline != Pipeline::OutOfOrder {
+        return Err("verify-trace is only supported by the out-of-order pipeline".to_string());
+    }
+    let text = fs::read_to_string(&args.reference_path)
+        .map_err(|err| format!("Failed to read {}: {}", args.reference_path, err))?;
+    let reference: Vec<Retired> = text
+        .lines()
+        .filter_map(|line| parse_spike(line).or_else(|| parse_qemu(line)))
+        .skip_while(|r| r.pc != args.base)
+        .collect();
+    if reference.is_empty() {
+        return Err(format!(
+            "{} has no Spike or QEMU instruction at {:#x}",
+            args.reference_path, args.base
+        ));
+    }
+
+    let program = crate::load_program(&args.input_path);
+    let mut sim = SimulatorBuilder::new()
+        .program(program)
+        .config(&config)
+        .build()?;
+    sim.commit_log = Some(VecDeque::new());
+    let sim = crate::run(sim, None, false, None, None);
+    let committed = instructions(sim.commit_log.unwrap());
+
+    for (i, (actual, expected)) in committed.iter().zip(&reference).enumerate() {
+        let pc = args.base + 4 * actual.pc;
+        if pc != expected.pc {
+            return Err(format!(
+                "Mismatch at instruction {}: the reference is at PC {:#x}, the simulator at {:#x}",
+                i, expected.pc, pc
+            ));
+        }
+        let Some(expected_write) = expected.write else {
+            continue;
+        };
+        // Writes to x0 are discarded by the reference whether or not the
+        // simulator hardwires it.
+        let (expected_write, actual_write) = (
+            expected_write.filter(|&(r, _)| r != 0),
+            actual.write.filter(|&(r, _)| r != 0),
+        );
+        if expected_write != actual_write {
+            let describe = |write: Option<(usize, u64)>| match write {
+                Some((register, value)) => format!("x{} = {:#x}", register, value),
+                None => "no register".to_string(),
+            };
+            return Err(format!(
+                "Mismatch at instruction {} (PC {:#x}): the reference wrote {}, the simulator {}",
+                i,
+                pc,
+                describe(expected_write),
+                describe(actual_write)
+            ));
+        }
+    }
+    let compared = committed.len().min(reference.len());
+    println!("Trace matches for {} instructions", compared);
+    if committed.len() != reference.len() {
+        println!(
+            "{} instructions of the {} were not compared",
+            committed.len().abs_diff(reference.len()),
+            match committed.len() > reference.len() {
+                true => "simulator",
+                false => "reference",
+            }
+        );
+    }
+    Ok(())
+}
+
+/// The committed instructions, with their micro-ops merged into the last
+/// register write. Exceptions are dropped, as the reference logs only the
+/// instructions that complete.
+fn instructions(commits: VecDeque<Commit>) -> Vec<Commit> {
+    let mut instructions: Vec<Commit> = Vec::new();
+    for commit in commits {
+        if commit.exception {
+            continue;
+        }
+        match instructions.last_mut() {
+            Some(last) if last.pc == commit.pc => last.write = commit.write.or(last.write),
+            _ => instructions.push(commit),
+        }
+    }
+    instructions
+}
@@ -0,0 +1,120 @@
+use serde::Serialize;
+
+use crate::asm::{self, Field, Format, Operand};
+use crate::config::Config;
+use crate::simulator::PoisonedUse;
+
+/// Width of the immediate field of U-type (`lui`/`auipc`) instructions.
+pub(crate) const UPPER_IMMEDIATE_BITS: u32 = 20;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WarningKind {
+    /// A result is overwritten before any instruction reads it.
+    DeadWrite,
+    /// An immediate does not fit the configured immediate field width.
+    ImmediateTooWide,
+    /// A committed result depended on an uninitialized register.
+    UninitializedRead,
+}
+
+/// A non-fatal problem found in the input program.
+#[derive(Serialize, Debug, Clone)]
+pub struct Warning {
+    #[serde(rename = "Index")]
+    pub index: usize,
+    #[serde(rename = "Kind")]
+    pub kind: WarningKind,
+    #[serde(rename = "Message")]
+    pub message: String,
+}
+
+/// Scans the program for suspicious but legal constructs. Lines that do not
+/// parse are skipped; they are not the linter's concern.
+pub fn check_program(program: &[String], config: &Config) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    // Index of the last unread write to each architectural register.
+    let mut pending_write: [Option<usize>; 32] = [None; 32];
+
+    for (index, line) in program.iter().enumerate() {
+        let Some((mnemonic, operands)) = asm::split_instruction(line) else {
+            continue;
+        };
+        // `ecall` reads its syscall number from `a7` and argument from `a0`.
+        if mnemonic == "ecall" {
+            pending_write[10] = None;
+            pending_write[17] = None;
+            continue;
+        }
+        // Only instructions writing an integer register operand are checked.
+        let Some((_, descriptor)) =
+            asm::descriptor_of(mnemonic).filter(|(_, d)| d.operands.len() == operands.len())
+        else {
+            continue;
+        };
+        let Field::Operand(dest_operand) = descriptor.dest else {
+            continue;
+        };
+        if descriptor.operands[dest_operand] != Operand::Register {
+            continue;
+        }
+        let operand = |field: Field, kind: Operand| match field {
+            Field::Operand(i) if descriptor.operands[i] == kind => Some(operands[i]),
+            _ => None,
+        };
+        let sources = [descriptor.src1, descriptor.src2]
+            .into_iter()
+            .filter_map(|field| operand(field, Operand::Register));
+        // `li` materializes constants of any width.
+        let imm = operand(descriptor.src2, Operand::Immediate).filter(|_| mnemonic != "li");
+
+        let bits = match asm::lookup(mnemonic) {
+            Some((_, Format::UpperImmediate)) => UPPER_IMMEDIATE_BITS,
+            _ => config.immediate_bits,
+        };
+        if let Some(value) = imm.and_then(asm::parse_immediate) {
+            let (min, max) = (-(1i128 << (bits - 1)), 1i128 << (bits - 1));
+            if !(min..max).contains(&(value as i64 as i128)) {
+                warnings.push(Warning {
+                    index,
+                    kind: WarningKind::ImmediateTooWide,
+                    message: format!("immediate {} does not fit in {} bits", imm.unwrap(), bits),
+                });
+            }
+        }
+
+        for src in sources {
+            if let Some(reg) = asm::register_index(src) {
+                pending_write[reg as usize] = None;
+            }
+        }
+        // Writes to a hardwired x0 are meant to be discarded.
+        let dest_name = operands[dest_operand];
+        if let Some(dest) = asm::register_index(dest_name)
+            && !(config.hardwired_zero && dest == 0)
+            && let Some(previous) = pending_write[dest as usize].replace(index)
+        {
+            warnings.push(Warning {
+                index: previous,
+                kind: WarningKind::DeadWrite,
+                message: format!(
+                    "{} is overwritten by instruction {} before being read",
+                    dest_name, index
+                ),
+            });
+        }
+    }
+    warnings.sort_by_key(|w| w.index);
+    warnings
+}
+
+/// Turns the first poisoned commit observed during simulation into a warning.
+pub fn poisoned_use_warning(poisoned: &PoisonedUse) -> Warning {
+    Warning {
+        index: poisoned.pc as usize,
+        kind: WarningKind::UninitializedRead,
+        message: format!(
+            "result depends on uninitialized register x{}",
+            poisoned.register
+        ),
+    }
+}
@@ -0,0 +1,66 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::cli::WatchArgs;
+
+/// Simulates the program, and compares the log against the golden one if
+/// given, every time the program or the config is saved, until interrupted.
+/// Each run is a process of its own, so an invalid program or config is
+/// reported and waited out instead of ending the watch.
+pub fn run(args: &WatchArgs) -> Result<(), String> {
+    let exe = env::current_exe()
+        .map_err(|err| format!("Failed to locate the simulator executable: {}", err))?;
+    let mut watched = vec![args.input_path.as_str()];
+    watched.extend(args.config_path.as_deref());
+    println!("Watching {}; press Ctrl-C to stop", watched.join(" and "));
+
+    let mut seen = None;
+    loop {
+        // A file being replaced may be missing for a moment; it counts as
+        // changed once it is back.
+        let stamps: Vec<Option<SystemTime>> = watched
+            .iter()
+            .map(|path| fs::metadata(path).and_then(|m| m.modified()).ok())
+            .collect();
+        if seen.as_ref() != Some(&stamps) && stamps.iter().all(Option::is_some) {
+            seen = Some(stamps);
+            simulate(&exe, args);
+        }
+        thread::sleep(Duration::from_millis(args.interval));
+    }
+}
+
+/// One run of the program, then the comparison with the golden log.
+fn simulate(exe: &Path, args: &WatchArgs) {
+    let mut command = Command::new(exe);
+    if let Some(path) = &args.config_path {
+        command.args(["--config", path]);
+    }
+    command.args([&args.input_path, &args.output_path]);
+    println!("--- Simulating {}", args.input_path);
+    let passed = match command.status() {
+        Ok(status) => status.success(),
+        Err(err) => {
+            eprintln!("Failed to run the simulator: {}", err);
+            false
+        }
+    };
+    let Some(golden) = &args.golden_path else {
+        return;
+    };
+    if !passed {
+        println!("--- Not compared with {}: the run failed", golden);
+        return;
+    }
+    println!("--- Comparing with {}", golden);
+    if let Err(err) = Command::new(exe)
+        .args(["diff", golden, &args.output_path])
+        .status()
+    {
+        eprintln!("Failed to run the comparison: {}", err);
+    }
+}
@@ -0,0 +1,139 @@
+use std::fmt::Write;
+
+use crate::simulator::{IntegerQueueEntry, Simulator};
+
+/// Cycles without a commit after which the pipeline is considered stuck.
+pub const STALL_CYCLES: usize = 1000;
+
+impl Simulator {
+    /// Stops a run that exceeded `max_cycles` or has not committed for
+    /// `STALL_CYCLES` cycles, keeping a diagnostic report in `watchdog`.
+    /// Returns whether the run should stop.
+    pub(crate) fn watchdog_expired(&mut self) -> bool {
+        let cycles = self.cycles();
+        let reason = if self.max_cycles.is_some_and(|max| cycles >= max) {
+            format!("the limit of {} cycles was reached", cycles)
+        } else if cycles >= STALL_CYCLES
+            && self
+                .bandwidth
+                .iter()
+                .rev()
+                .take(STALL_CYCLES)
+                .all(|counts| counts.committed == 0)
+        {
+            format!("nothing committed for {} cycles", STALL_CYCLES)
+        } else {
+            return false;
+        };
+        self.watchdog = Some(self.stuck_report(&reason));
+        true
+    }
+
+    /// Describes why the pipeline is not making progress: every integer
+    /// queue entry with what it waits for, and the oldest active entry.
+    fn stuck_report(&self, reason: &str) -> String {
+        let state = &self.state;
+        let mut report = format!(
+            "Stopped after cycle {}: {}. {} instructions committed, PC {}.\n",
+            self.cycles(),
+            reason,
+            self.committed,
+            state.pc
+        );
+        match state.active_list.front() {
+            Some(oldest) => writeln!(
+                report,
+                "Oldest active entry: PC {} ({}), {}",
+                oldest.pc,
+                self.instruction_text(oldest.pc),
+                match (oldest.done, oldest.exception) {
+                    (true, true) => "raised an exception",
+                    (true, false) => "done",
+                    (false, _) => "not done",
+                }
+            )
+            .unwrap(),
+            None => report.push_str("The active list is empty.\n"),
+        }
+        if state.integer_queue.is_empty() {
+            report.push_str("The integer queue is empty.\n");
+        } else {
+            writeln!(
+                report,
+                "Integer queue ({} entries):",
+                state.integer_queue.len()
+            )
+            .unwrap();
+        }
+        for entry in &state.integer_queue {
+            writeln!(
+                report,
+                "  PC {} ({}): {}",
+                entry.pc,
+                self.instruction_text(entry.pc),
+                self.blocked_on(entry)
+            )
+            .unwrap();
+        }
+        report
+    }
+
+    /// Why a queued entry has not issued.
+    fn blocked_on(&self, entry: &IntegerQueueEntry) -> String {
+        let operands = [
+            ("A", entry.op_a_is_ready, entry.op_a_reg_tag),
+            ("B", entry.op_b_is_ready, entry.op_b_reg_tag),
+        ];
+        let waiting: Vec<String> = operands
+            .iter()
+            .filter(|(_, ready, _)| !ready)
+            .map(|&(name, _, tag)| format!("operand {} waits on {}", name, self.producer(tag)))
+            .collect();
+        if !waiting.is_empty() {
+            return waiting.join("; ");
+        }
+        if !self.alus.iter().any(|alu| alu.can_execute(entry.op_code)) {
+            format!("ready, but no ALU can execute {}", entry.op_code)
+        } else {
+            "ready, waiting for a free ALU".to_string()
+        }
+    }
+
+    /// The physical register `tag` and the state of the micro-op that is to
+    /// write it.
+    fn producer(&self, tag: u32) -> String {
+        let mut active = self
+            .state
+            .active_list
+            .iter()
+            .chain(self.state.threads.iter().flat_map(|t| &t.active_list));
+        let Some(producer) = active.find(|e| !e.is_fp && e.destination == tag) else {
+            return format!("p{}, which no active entry writes", tag);
+        };
+        let status = if producer.done {
+            "done without writing it"
+        } else if self
+            .state
+            .integer_queue
+            .iter()
+            .any(|e| e.seq == producer.seq)
+        {
+            "still queued"
+        } else if self.alus.iter().any(|alu| alu.holds(producer.seq)) {
+            "executing"
+        } else {
+            "neither queued nor executing"
+        };
+        format!(
+            "p{}, written by PC {} ({}), {}",
+            tag,
+            producer.pc,
+            self.instruction_text(producer.pc),
+            status
+        )
+    }
+
+    fn instruction_text(&self, pc: u64) -> &str {
+        self.instruction(pc).map_or("?", String::as_str)
+    }
+}
@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+
+use crate::config::{WritebackArbitration, WritebackConfig};
+use crate::simulator::Simulator;
+
+/// Arbitration of the ALU results for the write ports.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Writeback {
+    ports: Option<usize>,
+    arbitration: WritebackArbitration,
+    /// ALU that round-robin arbitration considers first.
+    next: usize,
+}
+
+impl Writeback {
+    pub fn new(config: &WritebackConfig) -> Self {
+        Self {
+            ports: config.ports,
+            arbitration: config.arbitration,
+            next: 0,
+        }
+    }
+
+    pub fn is_limited(&self) -> bool {
+        self.ports.is_some()
+    }
+}
+
+impl Simulator {
+    /// Grants the write ports to the ALUs holding a result, returning the
+    /// granted ALUs. The others keep their result and stall.
+    pub(crate) fn arbitrate_writeback(&mut self) -> Vec<usize> {
+        let mut requests: Vec<usize> = (0..self.alus.len())
+            .filter(|&i| self.alus[i].forwarding.is_some())
+            .collect();
+        let Some(ports) = self.writeback.ports else {
+            return requests;
+        };
+        let n = self.alus.len();
+        match self.writeback.arbitration {
+            WritebackArbitration::Oldest => {
+                requests.sort_by_key(|&i| self.alus[i].forwarding.unwrap().seq)
+            }
+            WritebackArbitration::FixedPriority => {}
+            WritebackArbitration::RoundRobin => {
+                let next = self.writeback.next;
+                requests.sort_by_key(|&i| (i + n - next) % n);
+            }
+        }
+        for &i in requests.iter().skip(ports) {
+            self.alus[i].hold();
+            self.stats.writeback_stalls += 1;
+        }
+        requests.truncate(ports);
+        if let Some(&last) = requests.last()
+            && self.writeback.arbitration == WritebackArbitration::RoundRobin
+        {
+            self.writeback.next = (last + 1) % n;
+        }
+        requests
+    }
+}
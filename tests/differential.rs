@@ -0,0 +1,172 @@
+use serde_json::Value;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Random programs checked per pipeline configuration.
+const PROGRAMS: u64 = 40;
+const INSTRUCTIONS: usize = 120;
+
+/// Executes `program` one instruction at a time and returns the architectural
+/// registers when it ends or reaches its first exception, which must leave
+/// the registers as they were before the faulting instruction.
+fn interpret(program: &[String]) -> [u64; 32] {
+    let mut regs = [0u64; 32];
+    for (pc, line) in program.iter().enumerate() {
+        let parts: Vec<&str> = line
+            .split_whitespace()
+            .map(|p| p.trim_end_matches(','))
+            .collect();
+        let reg = |name: &str| name[1..].parse::<usize>().unwrap();
+        let (mnemonic, rd) = (parts[0], reg(parts[1]));
+        let value = |text: &str| match text.starts_with('x') {
+            true => regs[reg(text)],
+            false => text.parse::<i64>().unwrap() as u64,
+        };
+        let (a, b) = match parts.len() {
+            3 => (pc as u64, value(parts[2])),
+            _ => (value(parts[2]), value(parts[3])),
+        };
+        let (sa, sb) = (a as i64, b as i64);
+        let result = match mnemonic {
+            "add" | "addi" => Some(a.wrapping_add(b)),
+            "sub" => Some(a.wrapping_sub(b)),
+            "mulu" => Some(a.wrapping_mul(b)),
+            "mul" => Some(sa.wrapping_mul(sb) as u64),
+            "divu" => a.checked_div(b),
+            "remu" => a.checked_rem(b),
+            "div" => sa.checked_div(sb).map(|v| v as u64),
+            "rem" => sa.checked_rem(sb).map(|v| v as u64),
+            "slt" | "slti" => Some((sa < sb) as u64),
+            "sltu" | "sltiu" => Some((a < b) as u64),
+            "and" | "andi" => Some(a & b),
+            "or" | "ori" => Some(a | b),
+            "xor" | "xori" => Some(a ^ b),
+            "sll" | "slli" => Some(a << (b & 63)),
+            "srl" | "srli" => Some(a >> (b & 63)),
+            "sra" | "srai" => Some((sa >> (b & 63)) as u64),
+            "lui" => Some(b << 12),
+            "auipc" => Some(a.wrapping_add(b << 12)),
+            _ => panic!("interpreter does not know {}", mnemonic),
+        };
+        match result {
+            Some(v) => regs[rd] = v,
+            None => break,
+        }
+    }
+    regs
+}
+
+fn simulator() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_ooo470"))
+}
+
+fn scratch(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "ooo470-differential-{}-{}",
+        std::process::id(),
+        name
+    ))
+}
+
+/// Architectural register values in the last logged state, read through the
+/// register map table (or, under ROB renaming, the retirement map table).
+fn final_registers(log: &[Value]) -> [u64; 32] {
+    let last = log.last().unwrap();
+    let map = last
+        .get("RetirementRegisterMapTable")
+        .unwrap_or(&last["RegisterMapTable"]);
+    let mut regs = [0; 32];
+    for (r, reg) in regs.iter_mut().enumerate() {
+        let phys = map[r].as_u64().unwrap() as usize;
+        *reg = last["PhysicalRegisterFile"][phys].as_u64().unwrap();
+    }
+    regs
+}
+
+/// Checks `PROGRAMS` random programs under `config`. Pipelines without a
+/// reorder buffer take exceptions imprecisely, so they only get programs
+/// free of division by zero.
+fn check_pipeline(name: &str, config: &str, precise: bool) {
+    let div_zero = if precise { "0.02" } else { "0" };
+    let config_path = scratch(&format!("{}-config.json", name));
+    fs::write(&config_path, config).unwrap();
+    for seed in 0..PROGRAMS {
+        let program_path = scratch(&format!("{}-program.json", name));
+        let log_path = scratch(&format!("{}-log.json", name));
+        let status = simulator()
+            .args(["gen", "--instructions", &INSTRUCTIONS.to_string()])
+            .args(["--seed", &seed.to_string(), "--registers", "8"])
+            .args(["--div-zero", div_zero, "--output"])
+            .arg(&program_path)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "gen failed for seed {}", seed);
+        let output = simulator()
+            .arg("--config")
+            .arg(&config_path)
+            .arg(&program_path)
+            .arg(&log_path)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{} seed {}: {}",
+            name,
+            seed,
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        let program: Vec<String> =
+            serde_json::from_str(&fs::read_to_string(&program_path).unwrap()).unwrap();
+        let log: Vec<Value> =
+            serde_json::from_str(&fs::read_to_string(&log_path).unwrap()).unwrap();
+        let expected = interpret(&program);
+        let actual = final_registers(&log);
+        for r in 0..32 {
+            assert_eq!(
+                actual[r], expected[r],
+                "{} seed {}: x{} differs from the sequential interpreter",
+                name, seed, r
+            );
+        }
+        fs::remove_file(program_path).ok();
+        fs::remove_file(log_path).ok();
+    }
+    fs::remove_file(config_path).ok();
+}
+
+#[test]
+fn out_of_order_matches_interpreter() {
+    check_pipeline("out-of-order", "{}", true);
+}
+
+#[test]
+fn reorder_buffer_matches_interpreter() {
+    check_pipeline(
+        "reorder-buffer",
+        r#"{ "renaming": "reorder-buffer" }"#,
+        true,
+    );
+}
+
+#[test]
+fn two_alus_match_interpreter() {
+    check_pipeline("two-alus", r#"{ "num_alus": 2 }"#, true);
+}
+
+#[test]
+fn in_order_matches_interpreter() {
+    check_pipeline("in-order", r#"{ "pipeline": "in-order" }"#, true);
+}
+
+#[test]
+fn scoreboard_matches_interpreter() {
+    check_pipeline("scoreboard", r#"{ "pipeline": "scoreboard" }"#, false);
+}
+
+#[test]
+fn tomasulo_matches_interpreter() {
+    check_pipeline("tomasulo", r#"{ "pipeline": "tomasulo" }"#, false);
+}
@@ -0,0 +1,31 @@
+use std::process::Command;
+
+/// Runs `selftest` over the test cases in `dir`, failing with the first
+/// divergent cycle of any of them.
+fn selftest(dir: &str) {
+    let output = Command::new(env!("CARGO_BIN_EXE_ooo470"))
+        .arg("selftest")
+        .arg(dir)
+        .output()
+        .expect("failed to run the simulator");
+    assert!(
+        output.status.success(),
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+/// Runs the checked-in test cases, so a change to commit, rename or any
+/// other stage that alters a log fails `cargo test`.
+#[test]
+fn golden_traces_match() {
+    selftest(concat!(env!("CARGO_MANIFEST_DIR"), "/testcases"));
+}
+
+/// Runs the reference logs of the course, which were not produced by this
+/// simulator.
+#[test]
+fn reference_traces_match() {
+    selftest(concat!(env!("CARGO_MANIFEST_DIR"), "/given_tests"));
+}